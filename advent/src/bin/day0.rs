@@ -3,69 +3,230 @@ extern crate lazy_static;
 
 use std::io;
 use std::io::prelude::*;
-use std::collections::{BTreeSet, BTreeMap, VecDeque};
+use std::collections::{BTreeMap, BTreeSet};
 
 use regex::Regex;
 
+use advent::cli;
 use advent::coordinate::XY;
+use advent::direction::Dir4;
+use advent::error::{AdventError, exit_on_error};
+use advent::search;
+use advent::timing;
 
 // For this toy day, a puzzle is a rectangular character array such that
 // * the perimeter is marked by | (north-south wall), - (east-west wall) and + (corner);
-// * the interior consists of one D (door), one o (your position), and zero or more X (wall).
+// * the interior consists of one D (door), one or more o (players), and zero or more X (wall).
+// The interior may also hold portals: any other capital letter, each appearing exactly twice.
+// A player standing on a portal may teleport to the other cell with the same letter, at the cost of
+// one step.
 // The challenge is to find the shortest path from o to D through open squares, expressed
-// as a string in the alphabet {N,S,E,W}
+// as a string in the alphabet {N,S,E,W}, with T for a teleport.
+// With several players, they move simultaneously (or wait, written '.') and may never occupy
+// the same cell or swap cells; a player reaching the door leaves the puzzle. We then look for
+// the fewest steps in which every player escapes.
 
 #[derive(Debug, PartialEq, Eq)]
 struct Puzzle {
     width: usize,
     height: usize,
     door: XY,
-    player: XY,
-    walls: BTreeSet<XY>
+    players: Vec<XY>,
+    walls: BTreeSet<XY>,
+    portals: BTreeMap<XY, (char, XY)> // each end of a portal, with its letter and other end
 }
 
 impl Puzzle {
-    fn escape(&self) -> Result<String, IllPosedPuzzle> {
-        // Using BFS, find a shortest path from the player to the door, if one exists.
-        // If this were a real puzzle, it might be worth it to use a proper linked list
-        // with structural sharing, but String is fine for prototyping.
-        let mut visited: BTreeMap<XY, String> = BTreeMap::new();
-        let mut to_visit: VecDeque<(XY, String)> = VecDeque::new();
-
-        to_visit.push_back((self.player.clone(), "".to_owned()));
-
-        while let Some((xy, path)) = to_visit.pop_front() {
-            if xy == self.door {
-                return Ok(path)
-            } else if !visited.contains_key(&xy) {
-                let south = xy.south();
-                if !visited.contains_key(&south) && !self.walls.contains(&south) && south.y < self.height {
-                    to_visit.push_back((south, path.clone() + "S"));
-                }
+    // The open cells adjacent to `xy`, with the direction of each. Searches try them in this
+    // order, which decides between equally short paths.
+    fn neighbors(&self, xy: &XY) -> Vec<(XY, char)> {
+        let mut neighbors: Vec<(XY, char)> = [Dir4::South, Dir4::East, Dir4::North, Dir4::West].iter()
+            .flat_map(|dir| xy.step(*dir).map(|nbr| (nbr, dir.letter())))
+            .filter(|(nbr, _)| nbr.x < self.width && nbr.y < self.height && !self.walls.contains(nbr))
+            .collect();
+        if let Some((_, other_end)) = self.portals.get(xy) {
+            neighbors.push((other_end.clone(), 'T'));
+        }
+        neighbors
+    }
 
-                let east = xy.east();
-                if !visited.contains_key(&east) && !self.walls.contains(&east) && east.x < self.width {
-                    to_visit.push_back((east, path.clone() + "E"));
-                }
+    // A shortest path from `start` to the door, ignoring any other players.
+    fn escape(&self, start: &XY) -> Result<Escape, IllPosedPuzzle> {
+        let path = search::bfs(start.clone(), |xy| self.neighbors(xy), |xy| *xy == self.door)
+            .ok_or(IllPosedPuzzle{})?;
+        Ok(Escape { path: path.states, directions: path.moves.into_iter().collect() })
+    }
 
-                for north in xy.north() {
-                    if !visited.contains_key(&north) && !self.walls.contains(&north) {
-                        to_visit.push_back((north, path.clone() + "N"));
+    // Joint positions of all players after each step, None for those who have escaped.
+    fn joint_moves(&self, positions: &[Option<XY>]) -> Vec<(Vec<Option<XY>>, Vec<Option<char>>)> {
+        // Extend partial joint moves one player at a time, discarding collisions and swaps as we go.
+        let mut partials: Vec<(Vec<Option<XY>>, Vec<Option<char>>)> = vec!((vec!(), vec!()));
+        for position in positions {
+            let xy = match position {
+                None => {
+                    for (next, moves) in partials.iter_mut() {
+                        next.push(None);
+                        moves.push(None);
                     }
-                }
+                    continue
+                },
+                Some(xy) => xy
+            };
+            let mut options = self.neighbors(xy);
+            options.push((xy.clone(), '.'));
 
-                for west in xy.west() {
-                    if !visited.contains_key(&west) && !self.walls.contains(&west) {
-                        to_visit.push_back((west, path.clone() + "W"));
+            let mut extended = vec!();
+            for (next, moves) in &partials {
+                for (target, direction) in &options {
+                    let blocked = next.iter().zip(positions).any(|(other_next, other)| {
+                        match (other_next, other) {
+                            (Some(other_next), Some(other)) => other_next == target || (other_next == xy && other == target),
+                            _ => false
+                        }
+                    });
+                    // only one player can pass through the door in a given step
+                    let door_taken = *target == self.door && next.iter().enumerate()
+                        .any(|(other_idx, other_next)| other_next.is_none() && positions[other_idx].is_some());
+                    if !blocked && !door_taken {
+                        let mut next = next.clone();
+                        let mut moves = moves.clone();
+                        next.push(if *target == self.door { None } else { Some(target.clone()) });
+                        moves.push(Some(*direction));
+                        extended.push((next, moves));
                     }
                 }
+            }
+            partials = extended;
+        }
+        partials
+    }
+
+    // Shortest simultaneous escape of every player, if one exists.
+    fn evacuate(&self) -> Result<Evacuation, IllPosedPuzzle> {
+        let start: Vec<Option<XY>> = self.players.iter().cloned().map(Some).collect();
+        let path = search::bfs(start, |positions| self.joint_moves(positions), |positions| positions.iter().all(Option::is_none))
+            .ok_or(IllPosedPuzzle{})?;
+
+        let escapes = self.players.iter().enumerate().map(|(idx, player)| {
+            let mut cells = vec!(player.clone());
+            let mut directions = String::new();
+            for (next, moves) in path.states[1..].iter().zip(&path.moves) {
+                match moves[idx] {
+                    Some(direction) => {
+                        directions.push(direction);
+                        cells.push(next[idx].clone().unwrap_or_else(|| self.door.clone()));
+                    },
+                    None => break
+                }
+            }
+            Escape { path: cells, directions }
+        }).collect();
+        Ok(Evacuation { steps: path.moves.len(), escapes })
+    }
+
+    // The fewest steps from the nearest player to each cell, as in escape but going on past the door.
+    fn distance_map(&self) -> DistanceMap {
+        let distances = search::distances(self.players.iter().cloned(), |xy| self.neighbors(xy));
+        let rows = (0..self.height).map(|y| {
+            (0..self.width).map(|x| distances.get(&XY::new(x, y)).copied()).collect()
+        }).collect();
+        DistanceMap { rows }
+    }
+
+    // Draws the puzzle in its input format, with walls, and each reachable cell marked with the
+    // last digit of its distance.
+    fn render_distances(&self, map: &DistanceMap) -> String {
+        let boundary = format!("+{}+\n", "-".repeat(self.width));
 
-                visited.insert(xy, path);
+        let mut s = boundary.clone();
+        for (y, row) in map.rows.iter().enumerate() {
+            s.push('|');
+            for (x, distance) in row.iter().enumerate() {
+                s.push(match distance {
+                    _ if self.walls.contains(&XY::new(x, y)) => 'X',
+                    Some(d) => std::char::from_digit((d % 10) as u32, 10).unwrap(),
+                    None => ' '
+                });
             }
+            s.push_str("|\n");
         }
+        s.push_str(&boundary);
+        s
+    }
+
+    // Draws the puzzle in its input format, with each cell of `path` other than the
+    // players and door marked '*'.
+    fn render_path(&self, path: &[XY]) -> String {
+        let path: BTreeSet<&XY> = path.iter().collect();
+        let boundary = format!("+{}+\n", "-".repeat(self.width));
 
-        // if the queue is exhausted but we haven't found a path to the door:
-        Err(IllPosedPuzzle{})
+        let mut s = boundary.clone();
+        for y in 0..self.height {
+            s.push('|');
+            for x in 0..self.width {
+                let xy = XY::new(x, y);
+                let c = if xy == self.door {
+                    'D'
+                } else if self.players.contains(&xy) {
+                    'o'
+                } else if self.walls.contains(&xy) {
+                    'X'
+                } else if let Some((letter, _)) = self.portals.get(&xy) {
+                    *letter
+                } else if path.contains(&xy) {
+                    '*'
+                } else {
+                    ' '
+                };
+                s.push(c);
+            }
+            s.push_str("|\n");
+        }
+        s.push_str(&boundary);
+        s
+    }
+}
+
+// A shortest escape route, both as a sequence of cells (from the player to the door, inclusive)
+// and as a string of moves in the alphabet {N,S,E,W}, or '.' for a step spent waiting.
+#[derive(Debug, PartialEq, Eq)]
+struct Escape {
+    path: Vec<XY>,
+    directions: String
+}
+
+// Non-colliding escape routes for every player, in the order the players appear in the input.
+// `steps` is the number of steps until the last player is out.
+#[derive(Debug, PartialEq, Eq)]
+struct Evacuation {
+    steps: usize,
+    escapes: Vec<Escape>
+}
+
+// The fewest steps to each cell, indexed [y][x]; None for walls and cells nobody can reach.
+#[derive(Debug, PartialEq, Eq)]
+struct DistanceMap {
+    rows: Vec<Vec<Option<usize>>>
+}
+
+impl DistanceMap {
+    fn get(&self, xy: &XY) -> Option<usize> {
+        self.rows.get(xy.y).and_then(|row| row.get(xy.x)).copied().flatten()
+    }
+
+    // A reachable cell as far as possible from the players, the first in reading order, and its distance.
+    fn farthest(&self) -> Option<(XY, usize)> {
+        let mut farthest: Option<(XY, usize)> = None;
+        for (y, row) in self.rows.iter().enumerate() {
+            for (x, distance) in row.iter().enumerate() {
+                match (distance, &farthest) {
+                    (Some(d), Some((_, max))) if d <= max => (),
+                    (Some(d), _) => farthest = Some((XY::new(x, y), *d)),
+                    (None, _) => ()
+                }
+            }
+        }
+        farthest
     }
 }
 
@@ -75,14 +236,14 @@ struct IllPosedPuzzle {}
 #[derive(Debug, PartialEq, Eq)]
 enum PuzzleBuilder {
     Empty,
-    Open{ width: usize, height: usize, door: Option<XY>, player: Option<XY>, walls: BTreeSet<XY> },
-    Closed { width: usize, height: usize, door: XY, player: XY, walls: BTreeSet<XY>},
+    Open{ width: usize, height: usize, door: Option<XY>, players: Vec<XY>, walls: BTreeSet<XY>, portals: BTreeMap<char, Vec<XY>> },
+    Closed { width: usize, height: usize, door: XY, players: Vec<XY>, walls: BTreeSet<XY>, portals: BTreeMap<char, Vec<XY>> },
     Error(String)
 }
 
 impl PuzzleBuilder {
     fn open(width: usize) -> PuzzleBuilder {
-        PuzzleBuilder::Open{ width, height: 0, door: None, player: None, walls: BTreeSet::new() }
+        PuzzleBuilder::Open{ width, height: 0, door: None, players: vec!(), walls: BTreeSet::new(), portals: BTreeMap::new() }
     }
 
 
@@ -93,7 +254,7 @@ impl PuzzleBuilder {
     fn add(self, line: &str) -> PuzzleBuilder {
         lazy_static! {
             static ref PAT_OUTER: Regex = Regex::new(r"\+(-*)\+").unwrap();
-            static ref PAT_INNER: Regex = Regex::new(r"\|([ DoX]*)\|").unwrap();
+            static ref PAT_INNER: Regex = Regex::new(r"\|([ oA-Z]*)\|").unwrap();
         }
         match self {
             PuzzleBuilder::Error(msg) => PuzzleBuilder::Error(msg),
@@ -109,7 +270,7 @@ impl PuzzleBuilder {
                 })
             },
             PuzzleBuilder::Closed { .. } => PuzzleBuilder::err("Cannot add line to closed puzzle."),
-            PuzzleBuilder::Open { width, height, door: Some(door), player: Some(player), walls } if PAT_OUTER.is_match(line) => {
+            PuzzleBuilder::Open { width, height, door: Some(door), players, walls, portals } if !players.is_empty() && PAT_OUTER.is_match(line) => {
                 match PAT_OUTER.captures(line).and_then(|c|{ c.get(1) }) {
                     None => {
                         eprintln!("Pattern reported matched and unmatched on `{}`. This should never happen.", line);
@@ -119,12 +280,12 @@ impl PuzzleBuilder {
                         let error_message = format!("Improper line length {} != {}", m.as_str().len(), width);
                         PuzzleBuilder::err(&error_message)
                     },
-                    Some(_) => PuzzleBuilder::Closed { width, height, door, player, walls }
+                    Some(_) => PuzzleBuilder::Closed { width, height, door, players, walls, portals }
                 }
             },
             PuzzleBuilder::Open { door: None, .. } if PAT_OUTER.is_match(line) => PuzzleBuilder::err("No door in puzzle."),
-            PuzzleBuilder::Open { player: None, .. } if PAT_OUTER.is_match(line) => PuzzleBuilder::err("No player in puzzle."),
-            PuzzleBuilder::Open { width, height, door, player, mut walls } => {
+            PuzzleBuilder::Open { .. } if PAT_OUTER.is_match(line) => PuzzleBuilder::err("No player in puzzle."),
+            PuzzleBuilder::Open { width, height, door, mut players, mut walls, mut portals } => {
                 match PAT_INNER.captures(line).and_then(|c|{ c.get(1) }) {
                     None => {
                         let error_message = format!("Improper line `{}`", line);
@@ -138,33 +299,42 @@ impl PuzzleBuilder {
                         } else {
                             enum B {
                                 Error(String),
-                                Open { door: Option<XY>, player: Option<XY> }
+                                Open { door: Option<XY> }
                             }
                             let b: B = row.chars().enumerate().fold(
-                                B::Open { door, player },
+                                B::Open { door },
                                 |builder, (idx, c)| {
                                     match (builder, c) {
                                         (B::Open { door: Some(_), ..}, 'D') => {
                                             let error_message = format!("Duplicate door detected in row {}.", height);
                                             B::Error(error_message)
                                         },
-                                        (B::Open { player, ..}, 'D') => B::Open{ player, door: Some(XY{ x: idx, y: height })},
-                                        (B::Open { player: Some(_), ..}, 'o') => {
-                                            let error_message = format!("Duplicate player detected in row {}.", height);
-                                            B::Error(error_message)
+                                        (B::Open { .. }, 'D') => B::Open{ door: Some(XY{ x: idx, y: height })},
+                                        (b@B::Open { .. }, 'o') => {
+                                            players.push(XY { x: idx, y: height });
+                                            b
                                         },
-                                        (B::Open { door, ..}, 'o') => B::Open {door, player: Some(XY{ x: idx, y: height })},
                                         (b@B::Open { .. }, 'X') => {
                                             walls.insert(XY { x: idx, y: height });
                                             b
-                                        }
+                                        },
+                                        (b@B::Open { .. }, c) if c.is_ascii_uppercase() => {
+                                            let ends = portals.entry(c).or_insert_with(Vec::new);
+                                            if ends.len() == 2 {
+                                                let error_message = format!("Portal {} appears more than twice (row {}).", c, height);
+                                                B::Error(error_message)
+                                            } else {
+                                                ends.push(XY { x: idx, y: height });
+                                                b
+                                            }
+                                        },
                                         (other, _) => other
                                     }
                                 }
                             );
                             match b {
                                 B::Error(msg) => PuzzleBuilder::Error(msg),
-                                B::Open { door, player } => PuzzleBuilder::Open { width, height: height + 1, door, player, walls }
+                                B::Open { door } => PuzzleBuilder::Open { width, height: height + 1, door, players, walls, portals }
                             }
                         }
                     }
@@ -180,11 +350,20 @@ impl PuzzleBuilder {
             PuzzleBuilder::Closed { door, walls, .. } if walls.contains(&door) => {
                 Err(PuzzleParseError::err("Door and wall at same location."))
             },
-            PuzzleBuilder::Closed { player, walls, .. } if walls.contains(&player) => {
+            PuzzleBuilder::Closed { players, walls, .. } if players.iter().any(|player| walls.contains(player)) => {
                 Err(PuzzleParseError::err("Player and wall at same location."))
             },
-            PuzzleBuilder::Closed { width, height, door, player, walls } => {
-                Ok(Puzzle { width, height, door, player, walls })
+            PuzzleBuilder::Closed { ref portals, .. } if portals.values().any(|ends| ends.len() != 2) => {
+                let (c, _) = portals.iter().find(|(_, ends)| ends.len() != 2).unwrap();
+                Err(PuzzleParseError { msg: format!("Unmatched portal {}.", c) })
+            },
+            PuzzleBuilder::Closed { width, height, door, players, walls, portals } => {
+                let mut ends = BTreeMap::new();
+                for (c, cells) in portals {
+                    ends.insert(cells[0].clone(), (c, cells[1].clone()));
+                    ends.insert(cells[1].clone(), (c, cells[0].clone()));
+                }
+                Ok(Puzzle { width, height, door, players, walls, portals: ends })
             },
             PuzzleBuilder::Open { .. } => Err(PuzzleParseError::err("Incomplete builder")),
         }
@@ -202,14 +381,44 @@ impl PuzzleParseError {
     }
 }
 
-fn main() {
+fn run() -> Result<(), AdventError> {
     let stdin = io::stdin();
     let puzzle: Puzzle = stdin.lock().lines().flatten().fold(PuzzleBuilder::Empty, |builder, line| {
         builder.add(line.as_str())
-    }).build().unwrap();
+    }).build().map_err(|e| AdventError::input(e.msg))?;
+
+    if cli::has_flag("--distances") {
+        let map = timing::time("Distance map", || puzzle.distance_map());
+        print!("{}", puzzle.render_distances(&map));
+        match map.get(&puzzle.door) {
+            Some(d) => println!("The door is {} steps from the nearest player.", d),
+            None => println!("No player can reach the door.")
+        }
+        if let Some((xy, d)) = map.farthest() {
+            println!("The farthest reachable cell is ({}, {}), {} steps from the nearest player.", xy.x, xy.y, d);
+        }
+    }
 
-    let escape_path = puzzle.escape().unwrap();
-    println!("Escape path of length {} found: \n{}", escape_path.len(), escape_path);
+    if let [player] = puzzle.players.as_slice() {
+        let escape = timing::time("Escape", || puzzle.escape(player))
+            .map_err(|_| AdventError::no_solution("the player cannot reach the door"))?;
+        println!("Escape path of length {} found: \n{}", escape.directions.len(), escape.directions);
+        print!("{}", puzzle.render_path(&escape.path));
+    } else {
+        let evacuation = timing::time("Evacuation", || puzzle.evacuate())
+            .map_err(|_| AdventError::no_solution("the players cannot all reach the door"))?;
+        println!("All {} players escape after {} steps:", evacuation.escapes.len(), evacuation.steps);
+        for (player, escape) in puzzle.players.iter().zip(&evacuation.escapes) {
+            println!("Player at ({}, {}): {}", player.x, player.y, escape.directions);
+        }
+        let cells: Vec<XY> = evacuation.escapes.into_iter().flat_map(|escape| escape.path).collect();
+        print!("{}", puzzle.render_path(&cells));
+    }
+    Ok(())
+}
+
+fn main() {
+    exit_on_error(run())
 }
 
 #[cfg(test)]
@@ -223,7 +432,8 @@ mod day_0_spec {
             let empty = PuzzleBuilder::Empty;
             let line = "++";
             match empty.add(line) {
-                PuzzleBuilder::Open { width: 0, height: 0, door: None, player: None, walls } => {
+                PuzzleBuilder::Open { width: 0, height: 0, door: None, players, walls, .. } => {
+                    assert!(players.is_empty());
                     assert!(walls.is_empty())
                 },
                 other => assert!(false, "Unexpected result {:?}", other)
@@ -232,7 +442,8 @@ mod day_0_spec {
             let empty = PuzzleBuilder::Empty;
             let line = "+---+";
             match empty.add(line) {
-                PuzzleBuilder::Open { width: 3, height: 0, door: None, player: None, walls } => {
+                PuzzleBuilder::Open { width: 3, height: 0, door: None, players, walls, .. } => {
+                    assert!(players.is_empty());
                     assert!(walls.is_empty())
                 },
                 other => assert!(false, "Unexpected result {:?}", other)
@@ -279,9 +490,10 @@ mod day_0_spec {
 
         #[test]
         fn open_should_add_empty_row() {
-            let builder = PuzzleBuilder::Open { width: 10, height: 0, door: None, player: None, walls: BTreeSet::new() };
+            let builder = PuzzleBuilder::Open { width: 10, height: 0, door: None, players: vec!(), walls: BTreeSet::new(), portals: BTreeMap::new() };
             match builder.add("|          |") {
-                PuzzleBuilder::Open { width: 10, height: 1, door: None, player: None, walls } => {
+                PuzzleBuilder::Open { width: 10, height: 1, door: None, players, walls, .. } => {
+                    assert!(players.is_empty());
                     assert!(walls.is_empty());
                 },
                 other => assert!(false, "Unexpected result {:?}", other)
@@ -290,9 +502,10 @@ mod day_0_spec {
 
         #[test]
         fn open_should_add_walls() {
-            let builder = PuzzleBuilder::Open { width: 7, height: 0, door: None, player: None, walls: BTreeSet::new() };
+            let builder = PuzzleBuilder::Open { width: 7, height: 0, door: None, players: vec!(), walls: BTreeSet::new(), portals: BTreeMap::new() };
             match builder.add("|  X X  |") {
-                PuzzleBuilder::Open { width: 7, height: 1, door: None, player: None, walls } => {
+                PuzzleBuilder::Open { width: 7, height: 1, door: None, players, walls, .. } => {
+                    assert!(players.is_empty());
                     assert!(walls.contains(&XY::new(2, 0)));
                     assert!(walls.contains(&XY::new(4,0)));
                     assert_eq!(walls.len(), 2);
@@ -300,9 +513,10 @@ mod day_0_spec {
                 other => assert!(false, "Unexpected result {:?}", other)
             };
 
-            let builder = PuzzleBuilder::Open { width: 7, height: 2, door: None, player: None, walls: BTreeSet::new() };
+            let builder = PuzzleBuilder::Open { width: 7, height: 2, door: None, players: vec!(), walls: BTreeSet::new(), portals: BTreeMap::new() };
             match builder.add("|  X X  |") {
-                PuzzleBuilder::Open { width: 7, height: 3, door: None, player: None, walls } => {
+                PuzzleBuilder::Open { width: 7, height: 3, door: None, players, walls, .. } => {
+                    assert!(players.is_empty());
                     assert!(walls.contains(&XY::new(2, 2)));
                     assert!(walls.contains(&XY::new(4,2)));
                     assert_eq!(walls.len(), 2);
@@ -317,9 +531,10 @@ mod day_0_spec {
                 w.insert(XY::new(6,1));
                 w
             };
-            let builder = PuzzleBuilder::Open { width: 7, height: 2, door: None, player: None, walls: walls0.clone() };
+            let builder = PuzzleBuilder::Open { width: 7, height: 2, door: None, players: vec!(), walls: walls0.clone(), portals: BTreeMap::new() };
             match builder.add("|  X X  |") {
-                PuzzleBuilder::Open { width: 7, height: 3, door: None, player: None, walls } => {
+                PuzzleBuilder::Open { width: 7, height: 3, door: None, players, walls, .. } => {
+                    assert!(players.is_empty());
                     assert!(walls.contains(&XY::new(2, 2)));
                     assert!(walls.contains(&XY::new(4,2)));
                     assert_eq!(walls.len(), 5);
@@ -332,15 +547,17 @@ mod day_0_spec {
         fn open_should_add_door() {
             let builder = PuzzleBuilder::open(6);
             match builder.add("|    D |") {
-                PuzzleBuilder::Open { width: 6, height: 1, door: Some(XY { x: 4, y: 0 }), player: None, walls} => {
+                PuzzleBuilder::Open { width: 6, height: 1, door: Some(XY { x: 4, y: 0 }), players, walls, .. } => {
+                    assert!(players.is_empty());
                     assert!(walls.is_empty());
                 },
                 other => assert!(false, "Unexpected result {:?}", other)
             };
 
-            let builder = PuzzleBuilder::Open { width: 6, height: 2, door: None, player: None, walls: BTreeSet::new() };
+            let builder = PuzzleBuilder::Open { width: 6, height: 2, door: None, players: vec!(), walls: BTreeSet::new(), portals: BTreeMap::new() };
             match builder.add("|    D |") {
-                PuzzleBuilder::Open { width: 6, height: 3, door: Some(XY { x: 4, y: 2 }), player: None, walls} => {
+                PuzzleBuilder::Open { width: 6, height: 3, door: Some(XY { x: 4, y: 2 }), players, walls, .. } => {
+                    assert!(players.is_empty());
                     assert!(walls.is_empty());
                 },
                 other => assert!(false, "Unexpected result {:?}", other)
@@ -353,9 +570,10 @@ mod day_0_spec {
                 w.insert(XY::new(6,1));
                 w
             };
-            let builder = PuzzleBuilder::Open { width: 6, height: 2, door: None, player: None, walls: walls0.clone() };
+            let builder = PuzzleBuilder::Open { width: 6, height: 2, door: None, players: vec!(), walls: walls0.clone(), portals: BTreeMap::new() };
             match builder.add("|    D |") {
-                PuzzleBuilder::Open { width: 6, height: 3, door: Some(XY { x: 4, y: 2 }), player: None, walls} => {
+                PuzzleBuilder::Open { width: 6, height: 3, door: Some(XY { x: 4, y: 2 }), players, walls, .. } => {
+                    assert!(players.is_empty());
                     assert_eq!(walls, walls0);
                 },
                 other => assert!(false, "Unexpected result {:?}", other)
@@ -364,7 +582,7 @@ mod day_0_spec {
 
         #[test]
         fn open_should_reject_duplicate_door() {
-            let builder = PuzzleBuilder::Open { width: 6, height: 2, door: Some(XY::new(4,1)), player: None, walls: BTreeSet::new() };
+            let builder = PuzzleBuilder::Open { width: 6, height: 2, door: Some(XY::new(4,1)), players: vec!(), walls: BTreeSet::new(), portals: BTreeMap::new() };
             match builder.add("| D    |") {
                 PuzzleBuilder::Error(msg) => assert_eq!(msg, "Duplicate door detected in row 2."),
                 other => assert!(false, "Unexpected result {:?}", other)
@@ -375,15 +593,17 @@ mod day_0_spec {
         fn open_should_add_player() {
             let builder = PuzzleBuilder::open(6);
             match builder.add("|    o |") {
-                PuzzleBuilder::Open { width: 6, height: 1, door: None, player: Some(XY { x: 4, y: 0 }), walls} => {
+                PuzzleBuilder::Open { width: 6, height: 1, door: None, players, walls, .. } => {
+                    assert_eq!(players, vec!(XY::new(4, 0)));
                     assert!(walls.is_empty());
                 },
                 other => assert!(false, "Unexpected result {:?}", other)
             };
 
-            let builder = PuzzleBuilder::Open { width: 6, height: 2, door: None, player: None, walls: BTreeSet::new() };
+            let builder = PuzzleBuilder::Open { width: 6, height: 2, door: None, players: vec!(), walls: BTreeSet::new(), portals: BTreeMap::new() };
             match builder.add("|    o |") {
-                PuzzleBuilder::Open { width: 6, height: 3, door: None, player: Some(XY { x: 4, y: 2 }), walls} => {
+                PuzzleBuilder::Open { width: 6, height: 3, door: None, players, walls, .. } => {
+                    assert_eq!(players, vec!(XY::new(4, 2)));
                     assert!(walls.is_empty());
                 },
                 other => assert!(false, "Unexpected result {:?}", other)
@@ -396,9 +616,10 @@ mod day_0_spec {
                 w.insert(XY::new(6,1));
                 w
             };
-            let builder = PuzzleBuilder::Open { width: 6, height: 2, door: None, player: None, walls: walls0.clone() };
+            let builder = PuzzleBuilder::Open { width: 6, height: 2, door: None, players: vec!(), walls: walls0.clone(), portals: BTreeMap::new() };
             match builder.add("|    o |") {
-                PuzzleBuilder::Open { width: 6, height: 3, door: None, player: Some(XY { x: 4, y: 2 }), walls} => {
+                PuzzleBuilder::Open { width: 6, height: 3, door: None, players, walls, .. } => {
+                    assert_eq!(players, vec!(XY::new(4, 2)));
                     assert_eq!(walls, walls0);
                 },
                 other => assert!(false, "Unexpected result {:?}", other)
@@ -406,10 +627,13 @@ mod day_0_spec {
         }
 
         #[test]
-        fn open_should_reject_duplicate_player() {
-            let builder = PuzzleBuilder::Open { width: 6, height: 2, door: None, player: Some(XY::new(4,1)), walls: BTreeSet::new() };
-            match builder.add("| o    |") {
-                PuzzleBuilder::Error(msg) => assert_eq!(msg, "Duplicate player detected in row 2."),
+        fn open_should_add_further_players() {
+            let builder = PuzzleBuilder::Open { width: 6, height: 2, door: None, players: vec!(XY::new(4, 1)), walls: BTreeSet::new(), portals: BTreeMap::new() };
+            match builder.add("| o  o |") {
+                PuzzleBuilder::Open { width: 6, height: 3, door: None, players, walls, .. } => {
+                    assert_eq!(players, vec!(XY::new(4, 1), XY::new(1, 2), XY::new(4, 2)));
+                    assert!(walls.is_empty());
+                },
                 other => assert!(false, "Unexpected result {:?}", other)
             }
         }
@@ -423,9 +647,10 @@ mod day_0_spec {
                 w.insert(XY::new(6,1));
                 w
             };
-            let builder = PuzzleBuilder::Open { width: 8, height: 2, door: None, player: None, walls: walls0.clone() };
+            let builder = PuzzleBuilder::Open { width: 8, height: 2, door: None, players: vec!(), walls: walls0.clone(), portals: BTreeMap::new() };
             match builder.add("|oX XD XX|") {
-                PuzzleBuilder::Open { width: 8, height: 3, door: Some(XY { x: 4, y: 2}), player: Some(XY { x: 0, y: 2}), walls } => {
+                PuzzleBuilder::Open { width: 8, height: 3, door: Some(XY { x: 4, y: 2}), players, walls, .. } => {
+                    assert_eq!(players, vec!(XY::new(0, 2)));
                     assert_eq!(walls.len(), 7);
                     assert!(walls.contains(&XY::new(1,2)));
                     assert!(walls.contains(&XY::new(3,2)));
@@ -435,9 +660,10 @@ mod day_0_spec {
                 other => assert!(false, "Unexpected result {:?}", other)
             };
 
-            let builder = PuzzleBuilder::Open { width: 8, height: 2, door: Some(XY::new(5,1)), player: None, walls: walls0.clone() };
+            let builder = PuzzleBuilder::Open { width: 8, height: 2, door: Some(XY::new(5,1)), players: vec!(), walls: walls0.clone(), portals: BTreeMap::new() };
             match builder.add("|oX X  XX|") {
-                PuzzleBuilder::Open { width: 8, height: 3, door: Some(XY { x: 5, y: 1}), player: Some(XY { x: 0, y: 2}), walls } => {
+                PuzzleBuilder::Open { width: 8, height: 3, door: Some(XY { x: 5, y: 1}), players, walls, .. } => {
+                    assert_eq!(players, vec!(XY::new(0, 2)));
                     assert_eq!(walls.len(), 7);
                     assert!(walls.contains(&XY::new(1,2)));
                     assert!(walls.contains(&XY::new(3,2)));
@@ -447,9 +673,10 @@ mod day_0_spec {
                 other => assert!(false, "Unexpected result {:?}", other)
             };
 
-            let builder = PuzzleBuilder::Open { width: 8, height: 2, door: None, player: Some(XY::new(5,1)), walls: walls0.clone() };
+            let builder = PuzzleBuilder::Open { width: 8, height: 2, door: None, players: vec!(XY::new(5, 1)), walls: walls0.clone(), portals: BTreeMap::new() };
             match builder.add("| X XD XX|") {
-                PuzzleBuilder::Open { width: 8, height: 3, door: Some(XY { x: 4, y: 2}), player: Some(XY { x: 5, y: 1}), walls } => {
+                PuzzleBuilder::Open { width: 8, height: 3, door: Some(XY { x: 4, y: 2}), players, walls, .. } => {
+                    assert_eq!(players, vec!(XY::new(5, 1)));
                     assert_eq!(walls.len(), 7);
                     assert!(walls.contains(&XY::new(1,2)));
                     assert!(walls.contains(&XY::new(3,2)));
@@ -460,6 +687,29 @@ mod day_0_spec {
             };
         }
 
+        #[test]
+        fn open_should_add_portals() {
+            let builder = PuzzleBuilder::open(6);
+            match builder.add("|A B A |") {
+                PuzzleBuilder::Open { width: 6, height: 1, door: None, players, walls, portals } => {
+                    assert!(players.is_empty());
+                    assert!(walls.is_empty());
+                    assert_eq!(portals.get(&'A'), Some(&vec!(XY::new(0, 0), XY::new(4, 0))));
+                    assert_eq!(portals.get(&'B'), Some(&vec!(XY::new(2, 0))));
+                },
+                other => assert!(false, "Unexpected result {:?}", other)
+            }
+        }
+
+        #[test]
+        fn open_should_reject_third_portal_end() {
+            let builder = PuzzleBuilder::open(6).add("|A   A |");
+            match builder.add("| o  A |") {
+                PuzzleBuilder::Error(msg) => assert_eq!(msg, "Portal A appears more than twice (row 1)."),
+                other => assert!(false, "Unexpected result {:?}", other)
+            }
+        }
+
         #[test]
         fn open_should_fail_on_bad_line() {
             let builder = PuzzleBuilder::open(6);
@@ -477,9 +727,10 @@ mod day_0_spec {
 
         #[test]
         fn open_should_close_on_boundary_line() {
-            let builder = PuzzleBuilder::Open { width: 6, height: 2, door: Some(XY::new(4,1)), player: Some(XY::new(3,0)), walls: BTreeSet::new() };
+            let builder = PuzzleBuilder::Open { width: 6, height: 2, door: Some(XY::new(4,1)), players: vec!(XY::new(3, 0)), walls: BTreeSet::new(), portals: BTreeMap::new() };
             match builder.add("+------+") {
-                PuzzleBuilder::Closed { width: 6, height: 2, door: XY { x: 4, y: 1}, player: XY{ x: 3, y: 0}, walls } => {
+                PuzzleBuilder::Closed { width: 6, height: 2, door: XY { x: 4, y: 1}, players, walls, .. } => {
+                    assert_eq!(players, vec!(XY::new(3, 0)));
                     assert!(walls.is_empty())
                 },
                 other => assert!(false, "Unexpected result {:?}", other)
@@ -488,13 +739,13 @@ mod day_0_spec {
 
         #[test]
         fn open_should_error_on_improper_line() {
-            let builder = PuzzleBuilder::Open { width: 6, height: 2, door: Some(XY::new(4,1)), player: Some(XY::new(3,0)), walls: BTreeSet::new() };
+            let builder = PuzzleBuilder::Open { width: 6, height: 2, door: Some(XY::new(4,1)), players: vec!(XY::new(3, 0)), walls: BTreeSet::new(), portals: BTreeMap::new() };
             match builder.add("+---+") {
                 PuzzleBuilder::Error(msg) => assert_eq!(msg, "Improper line length 3 != 6"),
                 other => assert!(false, "Unexpected result {:?}", other)
             }
 
-            let builder = PuzzleBuilder::Open { width: 6, height: 2, door: Some(XY::new(4,1)), player: Some(XY::new(3,0)), walls: BTreeSet::new() };
+            let builder = PuzzleBuilder::Open { width: 6, height: 2, door: Some(XY::new(4,1)), players: vec!(XY::new(3, 0)), walls: BTreeSet::new(), portals: BTreeMap::new() };
             match builder.add("+--X--X+") {
                 PuzzleBuilder::Error(msg) => assert_eq!(msg, "Improper line `+--X--X+`"),
                 other => assert!(false, "Unexpected result {:?}", other)
@@ -503,13 +754,13 @@ mod day_0_spec {
 
         #[test]
         fn open_should_error_on_close_if_door_or_player_missing() {
-            let builder = PuzzleBuilder::Open { width: 6, height: 2, door: None, player: Some(XY::new(3,0)), walls: BTreeSet::new() };
+            let builder = PuzzleBuilder::Open { width: 6, height: 2, door: None, players: vec!(XY::new(3, 0)), walls: BTreeSet::new(), portals: BTreeMap::new() };
             match builder.add("+------+") {
                 PuzzleBuilder::Error(msg) => assert_eq!(msg, "No door in puzzle."),
                 other => assert!(false, "Unexpected result {:?}", other)
             };
 
-            let builder = PuzzleBuilder::Open { width: 6, height: 2, door: Some(XY::new(3,0)), player: None, walls: BTreeSet::new() };
+            let builder = PuzzleBuilder::Open { width: 6, height: 2, door: Some(XY::new(3,0)), players: vec!(), walls: BTreeSet::new(), portals: BTreeMap::new() };
             match builder.add("+------+") {
                 PuzzleBuilder::Error(msg) => assert_eq!(msg, "No player in puzzle."),
                 other => assert!(false, "Unexpected result {:?}", other)
@@ -518,13 +769,13 @@ mod day_0_spec {
 
         #[test]
         fn closed_should_error_on_any_line() {
-            let builder = PuzzleBuilder::Closed { width: 6, height: 2, door: XY::new(3,1), player: XY::new(5,0), walls: BTreeSet::new() };
+            let builder = PuzzleBuilder::Closed { width: 6, height: 2, door: XY::new(3,1), players: vec!(XY::new(5, 0)), walls: BTreeSet::new(), portals: BTreeMap::new() };
             match builder.add("+------+") {
                 PuzzleBuilder::Error(msg) => assert_eq!(msg, "Cannot add line to closed puzzle."),
                 other => assert!(false, "Unexpected result {:?}", other)
             };
 
-            let builder = PuzzleBuilder::Closed { width: 6, height: 2, door: XY::new(3,1), player: XY::new(5,0), walls: BTreeSet::new() };
+            let builder = PuzzleBuilder::Closed { width: 6, height: 2, door: XY::new(3,1), players: vec!(XY::new(5, 0)), walls: BTreeSet::new(), portals: BTreeMap::new() };
             match builder.add("| X X  |") {
                 PuzzleBuilder::Error(msg) => assert_eq!(msg, "Cannot add line to closed puzzle."),
                 other => assert!(false, "Unexpected result {:?}", other)
@@ -553,8 +804,8 @@ mod day_0_spec {
                 width: 10,
                 height: 11,
                 door: Some(XY::new(3,5)),
-                player: Some(XY::new(2,7)),
-                walls: BTreeSet::new()
+                players: vec!(XY::new(2, 7)),
+                walls: BTreeSet::new(), portals: BTreeMap::new()
             };
             match builder.build() {
                 Err(PuzzleParseError{msg}) => {
@@ -576,8 +827,8 @@ mod day_0_spec {
                 width: 10,
                 height: 11,
                 door: XY::new(3,5),
-                player: XY::new(2,7),
-                walls: walls.clone()
+                players: vec!(XY::new(2, 7)),
+                walls: walls.clone(), portals: BTreeMap::new()
             };
             let puzzle = builder.build().unwrap();
             assert_eq!(puzzle.width, 10);
@@ -597,12 +848,28 @@ mod day_0_spec {
                 width: 6,
                 height: 5,
                 door: XY::new(1,3),
-                player: XY::new(2,4),
-                walls: walls0.clone()
+                players: vec!(XY::new(2, 4)),
+                walls: walls0.clone(), portals: BTreeMap::new()
             };
             assert_eq!(builder.build(), Err(PuzzleParseError::err("Door and wall at same location.")));
         }
 
+        #[test]
+        fn unmatched_portal_should_error() {
+            let builder = ["+----+", "|oA D|", "|B  B|", "+----+"].iter().fold(PuzzleBuilder::Empty, |builder, line| builder.add(line));
+            assert_eq!(builder.build(), Err(PuzzleParseError::err("Unmatched portal A.")));
+        }
+
+        #[test]
+        fn matched_portals_should_link_both_ends() {
+            let builder = ["+----+", "|oA D|", "|B AB|", "+----+"].iter().fold(PuzzleBuilder::Empty, |builder, line| builder.add(line));
+            let puzzle = builder.build().unwrap();
+            assert_eq!(puzzle.portals.len(), 4);
+            assert_eq!(puzzle.portals.get(&XY::new(1, 0)), Some(&('A', XY::new(2, 1))));
+            assert_eq!(puzzle.portals.get(&XY::new(2, 1)), Some(&('A', XY::new(1, 0))));
+            assert_eq!(puzzle.portals.get(&XY::new(3, 1)), Some(&('B', XY::new(0, 1))));
+        }
+
         #[test]
         fn overlapping_player_and_wall_should_error() {
             let walls0: BTreeSet<XY> = {
@@ -614,10 +881,211 @@ mod day_0_spec {
                 width: 6,
                 height: 5,
                 door: XY::new(2,4),
-                player: XY::new(1,3),
-                walls: walls0.clone()
+                players: vec!(XY::new(1, 3)),
+                walls: walls0.clone(), portals: BTreeMap::new()
             };
             assert_eq!(builder.build(), Err(PuzzleParseError::err("Player and wall at same location.")))
         }
     }
+
+    mod escape {
+        use super::super::*;
+
+        fn parse(lines: &[&str]) -> Puzzle {
+            lines.iter().fold(PuzzleBuilder::Empty, |builder, line| builder.add(line)).build().unwrap()
+        }
+
+        #[test]
+        fn should_find_shortest_path() {
+            let puzzle = parse(&[
+                "+-----+",
+                "|o    |",
+                "| XXX |",
+                "|   XD|",
+                "+-----+"
+            ]);
+            let escape = puzzle.escape(&puzzle.players[0]).unwrap();
+            assert_eq!(escape.directions, "EEEESS");
+            assert_eq!(escape.path.len(), 7);
+            assert_eq!(escape.path.first(), Some(&XY::new(0, 0)));
+            assert_eq!(escape.path.last(), Some(&XY::new(4, 2)));
+            // consecutive cells are adjacent, and agree with the directions
+            for (step, direction) in escape.path.windows(2).zip(escape.directions.chars()) {
+                let expected = match direction {
+                    'N' => step[0].north(),
+                    'S' => Some(step[0].south()),
+                    'E' => Some(step[0].east()),
+                    'W' => step[0].west(),
+                    _ => None
+                };
+                assert_eq!(expected.as_ref(), Some(&step[1]));
+            }
+
+            assert_eq!(puzzle.render_path(&escape.path), "\
+                +-----+\n\
+                |o****|\n\
+                | XXX*|\n\
+                |   XD|\n\
+                +-----+\n");
+        }
+
+        #[test]
+        fn should_fail_when_walled_in() {
+            let puzzle = parse(&[
+                "+---+",
+                "|o X|",
+                "|XX |",
+                "|  D|",
+                "+---+"
+            ]);
+            assert!(puzzle.escape(&puzzle.players[0]).is_err());
+            assert!(puzzle.evacuate().is_err());
+        }
+
+        #[test]
+        fn should_teleport_through_portals() {
+            let puzzle = parse(&[
+                "+-------+",
+                "|oA  X  |",
+                "|XXXXX A|",
+                "|     D |",
+                "+-------+"
+            ]);
+            let escape = puzzle.escape(&puzzle.players[0]).unwrap();
+            assert_eq!(escape.directions, "ETSW");
+            assert_eq!(escape.path, vec!(XY::new(0, 0), XY::new(1, 0), XY::new(6, 1), XY::new(6, 2), XY::new(5, 2)));
+            assert_eq!(puzzle.render_path(&escape.path), "\
+                +-------+\n\
+                |oA  X  |\n\
+                |XXXXX A|\n\
+                |     D*|\n\
+                +-------+\n");
+        }
+
+        #[test]
+        fn should_fail_when_portals_lead_nowhere() {
+            let puzzle = parse(&[
+                "+-----+",
+                "|oA XD|",
+                "|XXXAX|",
+                "+-----+"
+            ]);
+            assert!(puzzle.escape(&puzzle.players[0]).is_err());
+        }
+
+        #[test]
+        fn distance_map_should_reach_every_open_cell() {
+            let puzzle = parse(&[
+                "+-----+",
+                "|o    |",
+                "| XXX |",
+                "|   XD|",
+                "+-----+"
+            ]);
+            let map = puzzle.distance_map();
+            assert_eq!(map.get(&puzzle.door), Some(puzzle.escape(&puzzle.players[0]).unwrap().directions.len()));
+            assert_eq!(map.get(&XY::new(1, 1)), None);
+            assert_eq!(map.get(&XY::new(9, 9)), None);
+            assert_eq!(map.farthest(), Some((XY::new(4, 2), 6)));
+            assert_eq!(puzzle.render_distances(&map), "\
+                +-----+\n\
+                |01234|\n\
+                |1XXX5|\n\
+                |234X6|\n\
+                +-----+\n");
+
+            // taking a portal is a step
+            let puzzle = parse(&[
+                "+-------+",
+                "|oA  X  |",
+                "|XXXXX A|",
+                "|     D |",
+                "+-------+"
+            ]);
+            assert_eq!(puzzle.render_distances(&puzzle.distance_map()), "\
+                +-------+\n\
+                |0123X43|\n\
+                |XXXXX32|\n\
+                |9876543|\n\
+                +-------+\n");
+        }
+
+        #[test]
+        fn distance_map_should_measure_from_the_nearest_player() {
+            let puzzle = parse(&[
+                "+-------+",
+                "|o  D  o|",
+                "+-------+"
+            ]);
+            let map = puzzle.distance_map();
+            assert_eq!(map.rows, vec!(vec!(Some(0), Some(1), Some(2), Some(3), Some(2), Some(1), Some(0))));
+            assert_eq!(map.farthest(), Some((XY::new(3, 0), 3)));
+
+            // cells nobody can reach are left blank
+            let puzzle = parse(&[
+                "+---+",
+                "|o X|",
+                "|XX |",
+                "|  D|",
+                "+---+"
+            ]);
+            let map = puzzle.distance_map();
+            assert_eq!(map.get(&puzzle.door), None);
+            assert_eq!(puzzle.render_distances(&map), "\
+                +---+\n\
+                |01X|\n\
+                |XX |\n\
+                |   |\n\
+                +---+\n");
+        }
+
+        #[test]
+        fn evacuate_single_player_should_match_escape() {
+            let puzzle = parse(&[
+                "+-----+",
+                "|o    |",
+                "| XXX |",
+                "|   XD|",
+                "+-----+"
+            ]);
+            let evacuation = puzzle.evacuate().unwrap();
+            assert_eq!(evacuation.steps, 6);
+            assert_eq!(evacuation.escapes, vec!(puzzle.escape(&puzzle.players[0]).unwrap()));
+        }
+
+        #[test]
+        fn evacuate_should_queue_players_in_a_corridor() {
+            let puzzle = parse(&[
+                "+-----+",
+                "|oo  D|",
+                "+-----+"
+            ]);
+            let evacuation = puzzle.evacuate().unwrap();
+            assert_eq!(evacuation.steps, 4);
+            assert_eq!(evacuation.escapes[0].directions, "EEEE");
+            assert_eq!(evacuation.escapes[1].directions, "EEE");
+        }
+
+        #[test]
+        fn evacuate_should_not_swap_or_share_cells() {
+            // Both players want the single cell next to the door; one has to step aside.
+            let puzzle = parse(&[
+                "+---+",
+                "|o o|",
+                "|XDX|",
+                "+---+"
+            ]);
+            let evacuation = puzzle.evacuate().unwrap();
+            assert_eq!(evacuation.steps, 3);
+            for step in 0..=evacuation.steps {
+                let cells: Vec<&XY> = evacuation.escapes.iter().flat_map(|e| e.path.get(step)).collect();
+                if cells.len() == 2 {
+                    assert_ne!(cells[0], cells[1], "collision at step {}", step);
+                }
+            }
+            let mut lengths: Vec<usize> = evacuation.escapes.iter().map(|e| e.directions.len()).collect();
+            lengths.sort();
+            assert_eq!(lengths, vec!(2, 3));
+        }
+    }
 }
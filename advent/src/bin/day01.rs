@@ -1,13 +1,65 @@
 use std::io;
 use std::io::prelude::*;
 
-use std::collections::BTreeSet;
+use std::collections::BTreeMap;
+use std::iter::FromIterator;
 
-fn decompose_2(summands: &BTreeSet<usize>, target: usize) -> Option<(usize, usize)> {
-    for &s in summands {
+use advent::bitset::BitSet;
+use advent::cli;
+use advent::error::{AdventError, exit_on_error};
+use advent::timing;
+
+// Expense entries may repeat, and a repeated value may legitimately be used once per
+// occurrence, so we track each value's multiplicity rather than collapsing to a set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Multiset(BTreeMap<usize, usize>);
+
+impl Multiset {
+    fn count(&self, x: usize) -> usize {
+        *self.0.get(&x).unwrap_or(&0)
+    }
+
+    fn insert(&mut self, x: usize) {
+        *self.0.entry(x).or_insert(0) += 1;
+    }
+
+    fn remove(&mut self, x: usize) {
+        match self.0.get_mut(&x) {
+            Some(1) => {
+                self.0.remove(&x);
+            },
+            Some(n) => *n -= 1,
+            None => ()
+        }
+    }
+
+    // values which occur more than once, with their multiplicities
+    fn duplicates(&self) -> Vec<(usize, usize)> {
+        self.0.iter().filter(|(_, &n)| n > 1).map(|(&x, &n)| (x, n)).collect()
+    }
+
+    // every entry, in non-decreasing order, as often as it occurs
+    fn sorted(&self) -> Vec<usize> {
+        self.0.iter().flat_map(|(&x, &n)| std::iter::repeat_n(x, n)).collect()
+    }
+}
+
+impl FromIterator<usize> for Multiset {
+    fn from_iter<I: IntoIterator<Item=usize>>(iter: I) -> Multiset {
+        let mut ms = Multiset(BTreeMap::new());
+        for x in iter {
+            ms.insert(x);
+        }
+        ms
+    }
+}
+
+fn decompose_2(summands: &Multiset, target: usize) -> Option<(usize, usize)> {
+    for &s in summands.0.keys() {
         if s <= target {
             let diff = target - s;
-            if s != diff && summands.contains(&diff) {
+            let needed = if s == diff { 2 } else { 1 };
+            if summands.count(diff) >= needed {
                 return Some((s, diff))
             }
         }
@@ -15,11 +67,52 @@ fn decompose_2(summands: &BTreeSet<usize>, target: usize) -> Option<(usize, usiz
     return None
 }
 
-fn decompose_3(summands: &BTreeSet<usize>, target: usize) -> Option<(usize, usize, usize)> {
-    let mut summands_copy: BTreeSet<usize> = summands.clone();
-    for &s in summands {
+// As decompose_2, without searching: mark the entries in a bitset and intersect it with its own
+// reflection about target / 2, leaving exactly those entries s for which target - s is also present.
+// Only entries between target - max and max can pair up, where max is the smaller of target and the
+// largest entry, so the bitset covers just that window, which is symmetric about target / 2.
+fn decompose_2_bitset(summands: &Multiset, target: usize) -> Option<(usize, usize)> {
+    let hi = target.min(*summands.0.keys().next_back()?);
+    let lo = target - hi;
+    if lo > hi {
+        return None
+    }
+    // only a window spanning every usize is too wide to count
+    let width = match (hi - lo).checked_add(1) {
+        Some(width) => width,
+        None => return decompose_2(summands, target)
+    };
+    let mut present = BitSet::new(width);
+    for &s in summands.0.range(lo..=hi).map(|(s, _)| s) {
+        present.set(s - lo);
+    }
+    let mut pairs = present.reversed();
+    pairs.intersect_with(&present);
+    let first = pairs.iter().map(|idx| idx + lo).find(|s| target - s != *s || summands.count(*s) > 1);
+    first.map(|s| (s, target - s))
+}
+
+// Times decompose_2 against decompose_2_bitset, and decompose_3 against decompose_3_sorted,
+// failing if either pair disagrees
+fn bench_decompose(summands: &Multiset, target: usize) -> Result<(), AdventError> {
+    let search = timing::time("Search", || decompose_2(summands, target));
+    let bitset = timing::time("Bitset", || decompose_2_bitset(summands, target));
+    if search != bitset {
+        return Err(AdventError::no_solution(format!("decompositions disagree: {:?} vs {:?}", search, bitset)))
+    }
+    let search = timing::time("Search (3)", || decompose_3(summands, target));
+    let sorted = timing::time("Two pointers (3)", || decompose_3_sorted(summands, target));
+    if search != sorted {
+        return Err(AdventError::no_solution(format!("decompositions disagree: {:?} vs {:?}", search, sorted)))
+    }
+    Ok(())
+}
+
+fn decompose_3(summands: &Multiset, target: usize) -> Option<(usize, usize, usize)> {
+    let mut summands_copy: Multiset = summands.clone();
+    for &s in summands.0.keys() {
         if s <= target {
-            summands_copy.remove(&s);
+            summands_copy.remove(s);
             if let Some((s0, s1)) = decompose_2(&summands_copy, target - s) {
                 return Some((s, s0, s1))
             }
@@ -29,26 +122,160 @@ fn decompose_3(summands: &BTreeSet<usize>, target: usize) -> Option<(usize, usiz
     return None
 }
 
-fn main() {
+// Calls `f` with each triple of entries a <= b <= c summing to `target` (each entry used at most as
+// many times as it occurs), in increasing lexicographic order, until `f` returns false. Fixing a,
+// the pairs b + c are found by closing in on them from both ends of the rest of the sorted entries,
+// which takes O(n²) in all; no triple is possible once a and the next two entries exceed target.
+fn for_each_triple<F>(sorted: &[usize], target: usize, mut f: F) where F: FnMut((usize, usize, usize)) -> bool {
+    for i in 0..sorted.len().saturating_sub(2) {
+        let a = sorted[i];
+        if a + sorted[i + 1] + sorted[i + 2] > target {
+            return
+        }
+        if i > 0 && sorted[i - 1] == a {
+            continue
+        }
+        let (mut lo, mut hi) = (i + 1, sorted.len() - 1);
+        while lo < hi {
+            let (b, c) = (sorted[lo], sorted[hi]);
+            match (a + b + c).cmp(&target) {
+                std::cmp::Ordering::Less => lo += 1,
+                std::cmp::Ordering::Greater => hi -= 1,
+                std::cmp::Ordering::Equal => {
+                    if !f((a, b, c)) {
+                        return
+                    }
+                    while lo < hi && sorted[lo] == b {
+                        lo += 1;
+                    }
+                    while lo < hi && sorted[hi] == c {
+                        hi -= 1;
+                    }
+                }
+            }
+        }
+    }
+}
+
+// As decompose_3, which also finds the lexicographically least triple, in O(n²) rather than O(n² log n)
+fn decompose_3_sorted(summands: &Multiset, target: usize) -> Option<(usize, usize, usize)> {
+    let mut r = None;
+    for_each_triple(&summands.sorted(), target, |triple| {
+        r = Some(triple);
+        false
+    });
+    r
+}
+
+// All distinct triples a <= b <= c of entries summing to `target`, in increasing order
+fn triples(summands: &Multiset, target: usize) -> Vec<(usize, usize, usize)> {
+    let mut r = vec!();
+    for_each_triple(&summands.sorted(), target, |triple| {
+        r.push(triple);
+        true
+    });
+    r
+}
+
+// Finds `k` entries (each used at most as many times as it occurs) summing to `target`.
+fn decompose_k(summands: &Multiset, target: usize, k: usize) -> Option<Vec<usize>> {
+    fn go(summands: &mut Multiset, target: usize, k: usize, min: usize) -> Option<Vec<usize>> {
+        match k {
+            0 if target == 0 => Some(vec!()),
+            0 => None,
+            1 if target >= min && summands.count(target) > 0 => Some(vec!(target)),
+            1 => None,
+            _ if min > target => None,
+            _ => {
+                // Choose summands in non-decreasing order to avoid revisiting permutations
+                let candidates: Vec<usize> = summands.0.range(min..=target).map(|(&s, _)| s).collect();
+                for s in candidates {
+                    summands.remove(s);
+                    let r = go(summands, target - s, k - 1, s);
+                    summands.insert(s);
+                    if let Some(mut r) = r {
+                        r.insert(0, s);
+                        return Some(r)
+                    }
+                }
+                None
+            }
+        }
+    }
+
+    go(&mut summands.clone(), target, k, 0)
+}
+
+fn run() -> Result<(), AdventError> {
+    let target = match cli::option_value("--target") {
+        Some(s) => s.parse::<usize>().map_err(|_| AdventError::input(format!("expected a target sum, got {}", s)))?,
+        None => 2020
+    };
+    let k = match cli::option_value("--k") {
+        Some(s) => Some(s.parse::<usize>().map_err(|_| AdventError::input(format!("expected a number of expenses, got {}", s)))?),
+        None => None
+    };
+
     let stdin = io::stdin();
-    let expenses: BTreeSet<usize> = stdin.lock().lines().flatten()
+    let expenses: Multiset = stdin.lock().lines().flatten()
         .flat_map(|s| usize::from_str_radix(&s, 10)).collect();
+    for (x, n) in expenses.duplicates() {
+        println!("Expense {} appears {} times.", x, n);
+    }
+
+    if let Some(k) = k {
+        match timing::time("Decompose", || decompose_k(&expenses, target, k)) {
+            Some(es) => {
+                let product: usize = es.iter().product();
+                println!("Found expenses {:?} summing to {}. Product: {}", es, target, product);
+            },
+            None => return Err(AdventError::no_solution(format!("no {} expenses sum to {}", k, target)))
+        }
+        return Ok(())
+    }
+
+    if cli::has_flag("--bench") {
+        bench_decompose(&expenses, target)?;
+    }
+
     println!("Part 1:");
-    let (e0, e1) = decompose_2(&expenses, 2020).unwrap();
+    let (e0, e1) = timing::time("Part 1", || {
+        if cli::has_flag("--bitset") {
+            decompose_2_bitset(&expenses, target)
+        } else {
+            decompose_2(&expenses, target)
+        }
+    })
+        .ok_or_else(|| AdventError::no_solution(format!("no 2 expenses sum to {}", target)))?;
     println!("Found expenses {}, {}. Product: {}", e0, e1, e0*e1);
 
     println!("Part 2:");
-    let (e0, e1, e2) = decompose_3(&expenses, 2020).unwrap();
+    let (e0, e1, e2) = timing::time("Part 2", || decompose_3_sorted(&expenses, target))
+        .ok_or_else(|| AdventError::no_solution(format!("no 3 expenses sum to {}", target)))?;
     println!("Found expenses {}, {}, {}. Product: {}", e0, e1, e2, e0*e1*e2);
+    if cli::has_flag("--all") {
+        let all = triples(&expenses, target);
+        println!("{} distinct triples of expenses sum to {}:", all.len(), target);
+        for (e0, e1, e2) in all {
+            println!("{}, {}, {}", e0, e1, e2);
+        }
+    }
+    Ok(())
+}
+
+fn main() {
+    exit_on_error(run())
 }
 
 #[cfg(test)]
 mod day_01_spec {
     use super::*;
+    use std::collections::BTreeSet;
+    use advent::random::Lcg;
 
     #[test]
     fn decompose_2_should_decompose_a_target() {
-        let summands: BTreeSet<usize> = vec!(1,2,3).iter().map(|x| *x).collect();
+        let summands: Multiset = vec!(1,2,3).iter().map(|x| *x).collect();
         let (x0, x1) = decompose_2(&summands, 4).unwrap();
         if x0 < x1 {
             assert_eq!(x0, 1);
@@ -61,13 +288,43 @@ mod day_01_spec {
 
     #[test]
     fn decompose_2_should_not_reuse() {
-        let summands: BTreeSet<usize> = vec!(1,2,3).iter().map(|x| *x).collect();
+        let summands: Multiset = vec!(1,2,3).iter().map(|x| *x).collect();
         assert_eq!(decompose_2(&summands, 6), None);
     }
 
+    #[test]
+    fn decompose_2_should_use_duplicates() {
+        let summands: Multiset = vec!(1010, 5, 1010).iter().map(|x| *x).collect();
+        assert_eq!(summands.duplicates(), vec!((1010, 2)));
+        assert_eq!(decompose_2(&summands, 2020), Some((1010, 1010)));
+    }
+
+    #[test]
+    fn decompose_2_bitset_should_agree() {
+        let summands: Multiset = vec!(1721, 979, 366, 299, 675, 1456, 1010, 5, 2015, 3000).iter().map(|x| *x).collect();
+        for target in 0..4000 {
+            assert_eq!(decompose_2_bitset(&summands, target), decompose_2(&summands, target), "{}", target);
+        }
+        assert_eq!(decompose_2_bitset(&summands, 2020), Some((5, 2015)));
+        assert_eq!(decompose_2_bitset(&summands, 2020 * 2), None);
+
+        let summands: Multiset = vec!(1010, 5, 1010).iter().map(|x| *x).collect();
+        assert_eq!(decompose_2_bitset(&summands, 2020), Some((1010, 1010)));
+        assert_eq!(decompose_2_bitset(&summands, 10), None);
+
+        // the bitset spans only the entries which could pair up, so huge targets stay cheap
+        let half = usize::MAX / 2;
+        let summands: Multiset = [1, 2, half, half, half + 1].iter().copied().collect();
+        assert_eq!(decompose_2_bitset(&summands, usize::MAX), Some((half, half + 1)));
+        assert_eq!(decompose_2_bitset(&summands, usize::MAX - 1), Some((half, half)));
+        let summands: Multiset = [1, 2].iter().copied().collect();
+        assert_eq!(decompose_2_bitset(&summands, usize::MAX), None);
+        assert_eq!(decompose_2_bitset(&Multiset(BTreeMap::new()), 2020), None);
+    }
+
     #[test]
     fn decompose_3_should_decompose_a_target() {
-        let summands: BTreeSet<usize> = vec!(1,2,3,4).iter().map(|x| *x).collect();
+        let summands: Multiset = vec!(1,2,3,4).iter().map(|x| *x).collect();
         let (x0, x1, x2) = decompose_3(&summands, 8).unwrap();
         let mut xs: [usize;3] = [x0, x1, x2];
         xs.sort();
@@ -76,8 +333,73 @@ mod day_01_spec {
 
     #[test]
     fn decompose_3_should_not_reuse() {
-        let summands: BTreeSet<usize> = vec!(1,2,3,4).iter().map(|x| *x).collect();
+        let summands: Multiset = vec!(1,2,3,4).iter().map(|x| *x).collect();
         assert_eq!(decompose_3(&summands, 3), None);
         assert_eq!(decompose_3(&summands, 10), None);
+
+        let summands: Multiset = vec!(2,2,4).iter().map(|x| *x).collect();
+        assert_eq!(decompose_3(&summands, 8), Some((2, 2, 4)));
+        assert_eq!(decompose_3(&summands, 6), None);
+    }
+
+    // Random multisets, from a small linear congruential generator, with entries small enough to
+    // repeat and to make many triples
+    fn sample_multisets() -> Vec<Multiset> {
+        let mut rng = Lcg::new(2020);
+        (0..100).map(|_| {
+            let len = rng.below(20);
+            (0..len).map(|_| rng.below(40) as usize).collect()
+        }).collect()
+    }
+
+    // Every triple of positions, in the slowest way
+    fn brute_force_triples(summands: &Multiset, target: usize) -> Vec<(usize, usize, usize)> {
+        let xs = summands.sorted();
+        let mut r = BTreeSet::new();
+        for i in 0..xs.len() {
+            for j in i + 1..xs.len() {
+                for k in j + 1..xs.len() {
+                    if xs[i] + xs[j] + xs[k] == target {
+                        r.insert((xs[i], xs[j], xs[k]));
+                    }
+                }
+            }
+        }
+        r.into_iter().collect()
+    }
+
+    #[test]
+    fn decompose_3_sorted_should_agree() {
+        let summands: Multiset = [1721, 979, 366, 299, 675, 1456].iter().copied().collect();
+        assert_eq!(decompose_3_sorted(&summands, 2020), Some((366, 675, 979)));
+        assert_eq!(triples(&summands, 2020), vec!((366, 675, 979)));
+
+        let summands: Multiset = [2, 2, 4, 2].iter().copied().collect();
+        assert_eq!(triples(&summands, 6), vec!((2, 2, 2)));
+        assert_eq!(triples(&summands, 8), vec!((2, 2, 4)));
+        assert_eq!(triples(&summands, 10), vec!());
+
+        for summands in sample_multisets() {
+            for target in 0..120 {
+                let all = triples(&summands, target);
+                assert_eq!(all, brute_force_triples(&summands, target), "{:?} {}", summands, target);
+                assert_eq!(decompose_3_sorted(&summands, target), decompose_3(&summands, target), "{:?} {}", summands, target);
+                assert_eq!(all.first().copied(), decompose_3(&summands, target));
+            }
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn decompose_k_test() {
+        let summands: Multiset = vec!(1721, 979, 366, 299, 675, 1456).iter().map(|x| *x).collect();
+        assert_eq!(decompose_k(&summands, 2020, 2), Some(vec!(299, 1721)));
+        assert_eq!(decompose_k(&summands, 2020, 3), Some(vec!(366, 675, 979)));
+        assert_eq!(decompose_k(&summands, 2020, 4), None);
+        assert_eq!(decompose_k(&summands, 0, 0), Some(vec!()));
+
+        let summands: Multiset = vec!(3, 3, 3, 1).iter().map(|x| *x).collect();
+        assert_eq!(decompose_k(&summands, 9, 3), Some(vec!(3, 3, 3)));
+        assert_eq!(decompose_k(&summands, 12, 4), None);
+        assert_eq!(decompose_k(&summands, 10, 4), Some(vec!(1, 3, 3, 3)));
+    }
+}
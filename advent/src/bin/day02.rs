@@ -6,6 +6,10 @@ use std::io::prelude::*;
 
 use regex::Regex;
 
+use advent::cli;
+use advent::error::{AdventError, exit_on_error};
+use advent::timing;
+
 #[derive(Debug, PartialEq, Eq)]
 struct Password {
     c: char,
@@ -43,20 +47,66 @@ impl Password {
     }
 }
 
-fn main() {
+#[derive(Debug, PartialEq, Eq)]
+struct ParseFailure {
+    line_no: usize, // 1-based
+    text: String
+}
+
+// Reads every line, keeping the ones which fail to parse rather than dropping them. Blank lines
+// are ignored.
+fn load<J>(lines: J) -> (Vec<Password>, Vec<ParseFailure>) where J: Iterator<Item=String> {
+    let mut passwords = vec!();
+    let mut failures = vec!();
+    for (idx, line) in lines.enumerate() {
+        if line.trim().is_empty() {
+            continue
+        }
+        match Password::from(&line) {
+            Some(password) => passwords.push(password),
+            None => failures.push(ParseFailure { line_no: idx + 1, text: line })
+        }
+    }
+    (passwords, failures)
+}
+
+// Whether more than `max_fraction` of the lines read failed to parse
+fn too_many_failures(passwords: usize, failures: usize, max_fraction: f64) -> bool {
+    failures > 0 && failures as f64 > max_fraction * (passwords + failures) as f64
+}
+
+fn run() -> Result<(), AdventError> {
     let stdin = io::stdin();
-    let corrupted_passwords: Vec<Password> = {
-        stdin.lock().lines().flatten()
-            .flat_map(|line| Password::from(&line)).collect()
+    let max_fraction = match cli::option_value("--max-failures") {
+        Some(s) => s.parse::<f64>().ok().filter(|f| (0.0..=1.0).contains(f))
+            .ok_or_else(|| AdventError::input(format!("expected a fraction between 0 and 1, got {}", s)))?,
+        None => 0.0
     };
+    let (corrupted_passwords, failures) = load(stdin.lock().lines().flatten());
 
-    println!("Parsed {} corrupted passwords", corrupted_passwords.len());
+    for failure in &failures {
+        eprintln!("Unable to parse line {}: {}", failure.line_no, failure.text);
+    }
+    println!("Parsed {} corrupted passwords; {} lines failed to parse", corrupted_passwords.len(), failures.len());
+    if too_many_failures(corrupted_passwords.len(), failures.len(), max_fraction) {
+        return Err(AdventError::input(format!("{} of {} lines failed to parse, more than the fraction {} allowed by --max-failures",
+            failures.len(), corrupted_passwords.len() + failures.len(), max_fraction)))
+    }
 
-    let valid: usize = corrupted_passwords.iter().fold(0, |acc, cp| acc + (cp.is_valid_1() as usize));
+    let valid: usize = timing::time("Part 1", || {
+        corrupted_passwords.iter().fold(0, |acc, cp| acc + (cp.is_valid_1() as usize))
+    });
     println!("{} passwords are valid.", valid);
 
-    let valid_2: usize = corrupted_passwords.iter().fold(0, |acc, cp| acc + (cp.is_valid_2() as usize));
+    let valid_2: usize = timing::time("Part 2", || {
+        corrupted_passwords.iter().fold(0, |acc, cp| acc + (cp.is_valid_2() as usize))
+    });
     println!("{} passwords are valid in the second sense.", valid_2);
+    Ok(())
+}
+
+fn main() {
+    exit_on_error(run())
 }
 
 #[cfg(test)]
@@ -80,6 +130,35 @@ mod day02_spec {
         }
     }
 
+    mod load {
+        use super::super::*;
+
+        #[test]
+        fn should_report_failures() {
+            let input = "1-3 a: abcde\n\
+                         1-3 b cdefg\n\
+                         \n\
+                         2-9 c: ccccccccc\n\
+                         x-9 c: ccccccccc";
+            let (passwords, failures) = load(input.lines().map(|s| s.to_owned()));
+            assert_eq!(passwords.len(), 2);
+            assert_eq!(failures, vec!(
+                ParseFailure { line_no: 2, text: "1-3 b cdefg".to_owned() },
+                ParseFailure { line_no: 5, text: "x-9 c: ccccccccc".to_owned() }
+            ));
+        }
+
+        #[test]
+        fn should_allow_a_fraction_of_failures() {
+            assert!(!too_many_failures(10, 0, 0.0));
+            assert!(too_many_failures(999, 1, 0.0));
+            assert!(!too_many_failures(8, 2, 0.2));
+            assert!(too_many_failures(7, 3, 0.2));
+            assert!(!too_many_failures(0, 0, 0.0));
+            assert!(!too_many_failures(0, 4, 1.0));
+        }
+    }
+
     mod is_valid_1 {
         use super::super::*;
 
@@ -1,11 +1,19 @@
 use std::io;
 use std::io::prelude::*;
 use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
 
-// We represent a puzzle input as a width > 0, a height >=0, and a set of "trees"
+use advent::cli;
+use advent::error::{AdventError, exit_on_error};
+use advent::stats::{self, Summary};
+use advent::table::Table;
+use advent::timing;
+
+// We represent a puzzle input as a width > 0, a height > 0, and a set of "trees"
 // represented as (x,y) pairs, where 0 <= x < width and 0 <= y < height.
 // Note that conceptually the pairs (locations of trees) repeat periodically to the right:
-// if (x,y) is a tree, then (x + width, y) is a tree as well.
+// if (x,y) is a tree, then (x + width, y) is a tree as well. When the map wraps vertically
+// they repeat downward too.
 #[derive(Debug, PartialEq, Eq)]
 struct Puzzle {
     width: usize,
@@ -14,70 +22,287 @@ struct Puzzle {
 }
 
 impl Puzzle {
-    fn add_line(&mut self, line: &str) {
+    fn add_line(&mut self, line: &str) -> Result<(), String> {
         let y = self.height;
         self.height += 1;
+        let width = line.chars().count();
+        if width != self.width {
+            return Err(format!("line {} has width {}, but the first line has width {}", y + 1, width, self.width))
+        }
         for (x, c) in line.chars().enumerate() {
             if c == '#' {
                 self.trees.insert((x,y));
             }
         }
+        Ok(())
     }
 
-    fn build<J>(lines: &mut J) -> Option<Puzzle>
+    // Every line must be as wide as the first, which must not be empty.
+    fn build<J>(lines: &mut J) -> Result<Puzzle, String>
     where J: Iterator<Item=String> {
-        lines.next().map(|first_line| {
-            let mut puzzle = Puzzle { width: first_line.len(), height: 0, trees: BTreeSet::new() };
-            puzzle.add_line(&first_line);
-            while let Some(line) = lines.next() {
-                puzzle.add_line(&line);
-            }
-            puzzle
-        })
+        let first_line = lines.next().ok_or("expected a nonempty map")?;
+        if first_line.is_empty() {
+            return Err("the first line is empty".to_owned())
+        }
+        let mut puzzle = Puzzle { width: first_line.chars().count(), height: 0, trees: BTreeSet::new() };
+        puzzle.add_line(&first_line)?;
+        for line in lines {
+            puzzle.add_line(&line)?;
+        }
+        Ok(puzzle)
+    }
+
+    fn is_tree(&self, x: usize, y: usize) -> bool {
+        self.trees.contains(&(x % self.width, y % self.height))
+    }
+
+    // The squares visited starting at (0,0) and moving on the specified slope until the run ends
+    // at one of the map's edges (see Edges). The toboggan must move downhill, so the path for dy = 0
+    // is empty.
+    fn path(&self, dx: usize, dy: usize, edges: Edges) -> Path<'_> {
+        Path { puzzle: self, dx, dy, edges, x: 0, y: 0, steps: 0 }
     }
 
     // Count the trees you hit starting at (0,0) and moving on the specified slope.
-    fn traverse(&self, dx: usize, dy: usize) -> usize {
-        let mut x = 0;
-        let mut tree_count = 0;
-        for y in (0..self.height).step_by(dy) {
-            // invariant: x < self.width
-            tree_count += (self.trees.contains(&(x,y))) as usize;
+    fn traverse(&self, dx: usize, dy: usize, edges: Edges) -> usize {
+        self.path(dx, dy, edges).filter(|step| step.tree).count()
+    }
 
-            x = (x + dx) % self.width;
+    // The fraction of squares holding a tree in each column and in each row
+    fn density(&self) -> Density {
+        let tree = |x, y| if self.is_tree(x, y) { 1.0 } else { 0.0 };
+        Density {
+            columns: (0..self.width).map(|x| (0..self.height).map(|y| tree(x, y)).collect()).collect(),
+            rows: (0..self.height).map(|y| (0..self.width).map(|x| tree(x, y)).collect()).collect(),
+            overall: self.trees.len() as f64 / (self.width * self.height) as f64
         }
-        tree_count
+    }
+
+    // The trees actually hit on `slope`, beside the number we would expect to hit if each square
+    // held a tree independently, with the probability given by the density of its column.
+    fn estimate(&self, slope: Slope) -> Estimate {
+        let columns = self.density().columns;
+        let steps: Vec<PathStep> = self.path(slope.dx, slope.dy, Edges::PUZZLE).collect();
+        let exact = steps.iter().filter(|step| step.tree).count();
+        let (expected, variance) = stats::successes(steps.iter().map(|step| {
+            columns[step.x % self.width].mean().unwrap_or(0.0)
+        }));
+        Estimate { exact, expected, std_dev: variance.sqrt() }
+    }
+
+    // Draws the map, repeated rightward as far as the path goes, with visited squares marked
+    // O (open) or X (tree), as in the puzzle statement.
+    fn render_path(&self, dx: usize, dy: usize) -> String {
+        let steps: BTreeSet<(usize, usize)> = self.path(dx, dy, Edges::PUZZLE).map(|step| (step.x, step.y)).collect();
+        let max_x = steps.iter().map(|(x, _)| *x).max().unwrap_or(0);
+        let width = (max_x / self.width + 1) * self.width;
+
+        let mut s = String::new();
+        for y in 0..self.height {
+            for x in 0..width {
+                let c = match (steps.contains(&(x, y)), self.is_tree(x, y)) {
+                    (true, true) => 'X',
+                    (true, false) => 'O',
+                    (false, true) => '#',
+                    (false, false) => '.'
+                };
+                s.push(c);
+            }
+            s.push('\n');
+        }
+        s
     }
 }
 
-fn main() {
+struct Density {
+    columns: Vec<Summary>,
+    rows: Vec<Summary>,
+    overall: f64
+}
+
+#[derive(Debug, PartialEq)]
+struct Estimate {
+    exact: usize,
+    expected: f64,
+    std_dev: f64
+}
+
+// A rational slope dy/dx, in lowest terms: the line through (0,0) passes through the squares
+// (k*dx, k*dy) and no others, so 2/6 visits the same squares as 1/3.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Slope {
+    dy: usize,
+    dx: usize
+}
+
+impl Slope {
+    fn new(dy: usize, dx: usize) -> Option<Slope> {
+        if dy == 0 {
+            // the toboggan must move downhill
+            return None
+        }
+        let d = gcd(dy, dx);
+        Some(Slope { dy: dy / d, dx: dx / d })
+    }
+
+    // Reads "dy/dx", as the slopes are printed
+    fn parse(s: &str) -> Option<Slope> {
+        let (dy, dx) = s.split_once('/')?;
+        Slope::new(usize::from_str_radix(dy.trim(), 10).ok()?, usize::from_str_radix(dx.trim(), 10).ok()?)
+    }
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Horizontal {
+    Periodic, // the map repeats to the right, as in the puzzle
+    Finite // the run ends on leaving the map's right edge
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Vertical {
+    Stop, // the run ends on passing the bottom row, as in the puzzle
+    Wrap { max_steps: usize } // the map repeats downward, and the run ends after max_steps squares
+}
+
+// What becomes of the toboggan at the edges of the map. Periodic and wrapping together make the
+// map a torus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Edges {
+    horizontal: Horizontal,
+    vertical: Vertical
+}
+
+impl Edges {
+    const PUZZLE: Edges = Edges { horizontal: Horizontal::Periodic, vertical: Vertical::Stop };
+}
+
+impl Display for Edges {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self.horizontal {
+            Horizontal::Periodic => write!(f, "repeating rightward")?,
+            Horizontal::Finite => write!(f, "of finite width")?
+        }
+        match self.vertical {
+            Vertical::Stop => Ok(()),
+            Vertical::Wrap { max_steps } => write!(f, ", wrapping vertically for at most {} squares", max_steps)
+        }
+    }
+}
+
+// A visited square. Neither `x` nor `y` is reduced modulo the puzzle's dimensions.
+#[derive(Debug, PartialEq, Eq)]
+struct PathStep {
+    x: usize,
+    y: usize,
+    tree: bool
+}
+
+struct Path<'a> {
+    puzzle: &'a Puzzle,
+    dx: usize,
+    dy: usize,
+    edges: Edges,
+    x: usize,
+    y: usize,
+    steps: usize
+}
+
+impl<'a> Iterator for Path<'a> {
+    type Item = PathStep;
+
+    fn next(&mut self) -> Option<PathStep> {
+        let beyond_bottom = match self.edges.vertical {
+            Vertical::Stop => self.y >= self.puzzle.height,
+            Vertical::Wrap { max_steps } => self.steps >= max_steps
+        };
+        let beyond_right = self.edges.horizontal == Horizontal::Finite && self.x >= self.puzzle.width;
+        if self.dy == 0 || beyond_bottom || beyond_right {
+            return None
+        }
+        let (x, y) = (self.x, self.y);
+        self.x += self.dx;
+        self.y += self.dy;
+        self.steps += 1;
+        Some(PathStep { x, y, tree: self.puzzle.is_tree(x, y) })
+    }
+}
+
+fn run() -> Result<(), AdventError> {
     let stdin = io::stdin();
-    let puzzle = Puzzle::build(&mut stdin.lock().lines().flatten()).unwrap();
+    let puzzle = Puzzle::build(&mut stdin.lock().lines().flatten())
+        .map_err(AdventError::input)?;
     
     println!("Puzzle parsed with width {}, height {}, tree count {}",
         puzzle.width, puzzle.height, puzzle.trees.len());
 
     let (dx, dy) = (1,1);
-    let tree_count_1_1 = puzzle.traverse(dx, dy);
+    let tree_count_1_1 = timing::time("Slope 1/1", || puzzle.traverse(dx, dy, Edges::PUZZLE));
     println!("With dy/dx = {}/{}, I hit {} trees.", dy, dx, tree_count_1_1);
     
     let (dx, dy) = (3,1);
-    let tree_count_3_1 = puzzle.traverse(dx, dy);
+    let tree_count_3_1 = timing::time("Part 1", || puzzle.traverse(dx, dy, Edges::PUZZLE));
     println!("With dy/dx = {}/{}, I hit {} trees.", dy, dx, tree_count_3_1);
+    if cli::has_flag("--render") {
+        print!("{}", puzzle.render_path(dx, dy));
+    }
 
     let (dx, dy) = (5,1);
-    let tree_count_5_1 = puzzle.traverse(dx, dy);
+    let tree_count_5_1 = timing::time("Slope 1/5", || puzzle.traverse(dx, dy, Edges::PUZZLE));
     println!("With dy/dx = {}/{}, I hit {} trees.", dy, dx, tree_count_5_1);
 
     let (dx, dy) = (7,1);
-    let tree_count_7_1 = puzzle.traverse(dx, dy);
+    let tree_count_7_1 = timing::time("Slope 1/7", || puzzle.traverse(dx, dy, Edges::PUZZLE));
     println!("With dy/dx = {}/{}, I hit {} trees.", dy, dx, tree_count_7_1);
 
     let (dx, dy) = (1,2);
-    let tree_count_1_2 = puzzle.traverse(dx, dy);
+    let tree_count_1_2 = timing::time("Slope 2/1", || puzzle.traverse(dx, dy, Edges::PUZZLE));
     println!("With dy/dx = {}/{}, I hit {} trees.", dy, dx, tree_count_1_2);
 
     println!("Product: {}", tree_count_1_1 * tree_count_3_1 * tree_count_5_1 * tree_count_7_1 * tree_count_1_2);
+
+    let horizontal = if cli::has_flag("--finite") { Horizontal::Finite } else { Horizontal::Periodic };
+    let vertical = match cli::option_value("--wrap") {
+        Some(n) => Vertical::Wrap {
+            max_steps: n.parse().map_err(|_| AdventError::input(format!("expected a number of squares to wrap for, got {}", n)))?
+        },
+        None => Vertical::Stop
+    };
+    let edges = Edges { horizontal, vertical };
+    if edges != Edges::PUZZLE {
+        for &(dx, dy) in [(1, 1), (3, 1), (5, 1), (7, 1), (1, 2)].iter() {
+            println!("With dy/dx = {}/{} on a map {}, I hit {} trees.", dy, dx, edges, puzzle.traverse(dx, dy, edges));
+        }
+    }
+
+    if cli::has_flag("--density") {
+        let density = puzzle.density();
+        let mut table = Table::new(&["column", "trees", "density"]);
+        for (x, column) in density.columns.iter().enumerate() {
+            let mean = column.mean().unwrap_or(0.0);
+            table.add_row(vec!(x.to_string(), ((mean * column.count() as f64).round() as usize).to_string(), format!("{:.3}", mean)));
+        }
+        print!("{}", table);
+        let rows: Summary = density.rows.iter().flat_map(|row| row.mean()).collect();
+        println!("Overall density {:.3}; row densities {:.3} ± {:.3}",
+            density.overall, rows.mean().unwrap_or(0.0), rows.std_dev().unwrap_or(0.0));
+    }
+
+    if let Some(slope) = cli::option_value("--slope") {
+        let slope = Slope::parse(&slope)
+            .ok_or_else(|| AdventError::input(format!("expected a downhill slope dy/dx, not {}", slope)))?;
+        let estimate = timing::time("Estimate", || puzzle.estimate(slope));
+        println!("With dy/dx = {}/{}, I hit {} trees; column densities predict {:.1} ± {:.1}.",
+            slope.dy, slope.dx, estimate.exact, estimate.expected, estimate.std_dev);
+    }
+    Ok(())
+}
+
+fn main() {
+    exit_on_error(run())
 }
 
 #[cfg(test)]
@@ -110,6 +335,16 @@ mod day03_spec {
             assert!(puzzle.trees.contains(&(1,2)));
             assert!(puzzle.trees.contains(&(10,10)));
         }
+
+        #[test]
+        fn should_require_one_width() {
+            let lines = |s: &str| s.lines().map(|s| s.to_owned()).collect::<Vec<_>>().into_iter();
+            assert_eq!(Puzzle::build(&mut lines("..#\n#..\n.#")), Err("line 3 has width 2, but the first line has width 3".to_owned()));
+            assert_eq!(Puzzle::build(&mut lines("..#\n#...")), Err("line 2 has width 4, but the first line has width 3".to_owned()));
+            assert_eq!(Puzzle::build(&mut lines("")), Err("expected a nonempty map".to_owned()));
+            assert_eq!(Puzzle::build(&mut lines("\n\n")), Err("the first line is empty".to_owned()));
+            assert!(Puzzle::build(&mut lines("..#\n#..")).is_ok());
+        }
     }
 
     mod traverse {
@@ -119,7 +354,120 @@ mod day03_spec {
         fn should_count_trees() {
             let puzzle = Puzzle::build(&mut PUZZLE_INPUT.lines().map(|s| s.to_owned())).unwrap();
 
-            assert_eq!(puzzle.traverse(3, 1), 7);
+            assert_eq!(puzzle.traverse(3, 1, Edges::PUZZLE), 7);
+            assert_eq!(puzzle.traverse(1, 1, Edges::PUZZLE), 2);
+            assert_eq!(puzzle.traverse(5, 1, Edges::PUZZLE), 3);
+            assert_eq!(puzzle.traverse(7, 1, Edges::PUZZLE), 4);
+            assert_eq!(puzzle.traverse(1, 2, Edges::PUZZLE), 2);
+        }
+
+        #[test]
+        fn should_follow_edges() {
+            let puzzle = Puzzle::build(&mut PUZZLE_INPUT.lines().map(|s| s.to_owned())).unwrap();
+            let finite = Edges { horizontal: Horizontal::Finite, vertical: Vertical::Stop };
+            // the path on 1/3 leaves the right edge after 4 squares: (0,0), (3,1), (6,2), (9,3)
+            assert_eq!(puzzle.path(3, 1, finite).count(), 4);
+            assert_eq!(puzzle.traverse(3, 1, finite), 1);
+            // 1/1 reaches the bottom right corner on a square map
+            assert_eq!(puzzle.traverse(1, 1, finite), puzzle.traverse(1, 1, Edges::PUZZLE));
+
+            // wrapping for as many squares as the puzzle visits changes nothing
+            let wrap = |max_steps| Edges { horizontal: Horizontal::Periodic, vertical: Vertical::Wrap { max_steps } };
+            assert_eq!(puzzle.traverse(3, 1, wrap(11)), 7);
+            assert_eq!(puzzle.traverse(1, 2, wrap(6)), 2);
+            // on an 11 x 11 torus, 1/3 repeats after 11 squares
+            assert_eq!(puzzle.traverse(3, 1, wrap(33)), 21);
+            let steps: Vec<PathStep> = puzzle.path(3, 1, wrap(13)).collect();
+            assert_eq!(steps[11], PathStep { x: 33, y: 11, tree: false });
+            assert_eq!(steps[12].tree, steps[1].tree);
+
+            let both = Edges { horizontal: Horizontal::Finite, vertical: Vertical::Wrap { max_steps: 100 } };
+            assert_eq!(puzzle.path(1, 2, both).count(), 11);
+            assert_eq!(puzzle.path(1, 1, wrap(0)).count(), 0);
+            // a level run goes nowhere, however the edges behave
+            assert_eq!(puzzle.path(3, 0, Edges::PUZZLE).count(), 0);
+            assert_eq!(puzzle.traverse(3, 0, wrap(100)), 0);
+            assert_eq!(both.to_string(), "of finite width, wrapping vertically for at most 100 squares");
+        }
+    }
+
+    mod estimate {
+        use super::*;
+
+        #[test]
+        fn should_reduce_slopes() {
+            assert_eq!(Slope::parse("1/3"), Some(Slope { dy: 1, dx: 3 }));
+            assert_eq!(Slope::parse("4 / 6"), Some(Slope { dy: 2, dx: 3 }));
+            assert_eq!(Slope::parse("5/0"), Some(Slope { dy: 1, dx: 0 }));
+            assert_eq!(Slope::parse("0/3"), None);
+            assert_eq!(Slope::parse("1:3"), None);
+            assert_eq!(Slope::parse("-1/3"), None);
+        }
+
+        #[test]
+        fn should_measure_density() {
+            let puzzle = Puzzle::build(&mut PUZZLE_INPUT.lines().map(|s| s.to_owned())).unwrap();
+            let density = puzzle.density();
+            assert_eq!(density.columns.len(), 11);
+            assert_eq!(density.rows.len(), 11);
+            assert_eq!(density.overall, 37.0 / 121.0);
+            let close = |x: Option<f64>, y: f64| (x.unwrap() - y).abs() < 1e-9;
+            assert!(close(density.columns[0].mean(), 3.0 / 11.0));
+            assert!(close(density.rows[0].mean(), 2.0 / 11.0));
+            let columns: Summary = density.columns.iter().flat_map(|column| column.mean()).collect();
+            assert!(close(columns.mean(), density.overall));
+        }
+
+        #[test]
+        fn should_estimate_collisions() {
+            let puzzle = Puzzle::build(&mut PUZZLE_INPUT.lines().map(|s| s.to_owned())).unwrap();
+            for &(dy, dx) in [(1, 1), (1, 3), (1, 5), (1, 7), (2, 1)].iter() {
+                let estimate = puzzle.estimate(Slope::new(dy, dx).unwrap());
+                assert_eq!(estimate.exact, puzzle.traverse(dx, dy, Edges::PUZZLE));
+                assert!((estimate.exact as f64 - estimate.expected).abs() <= 3.0 * estimate.std_dev, "{:?}", estimate);
+            }
+            assert_eq!(puzzle.estimate(Slope::new(3, 9).unwrap()).exact, 7);
+
+            // straight down column 0 hits every tree in it, as its density predicts
+            let estimate = puzzle.estimate(Slope::new(1, 0).unwrap());
+            assert_eq!(estimate.exact, 3);
+            assert!((estimate.expected - 3.0).abs() < 1e-9);
+        }
+    }
+
+    mod path {
+        use super::*;
+
+        #[test]
+        fn should_yield_visited_squares() {
+            let puzzle = Puzzle::build(&mut PUZZLE_INPUT.lines().map(|s| s.to_owned())).unwrap();
+
+            let steps: Vec<PathStep> = puzzle.path(3, 1, Edges::PUZZLE).collect();
+            assert_eq!(steps.len(), 11);
+            assert_eq!(steps[0], PathStep { x: 0, y: 0, tree: false });
+            assert_eq!(steps[1], PathStep { x: 3, y: 1, tree: false });
+            assert_eq!(steps[2], PathStep { x: 6, y: 2, tree: true });
+            assert_eq!(steps[10], PathStep { x: 30, y: 10, tree: true });
+
+            let collisions: Vec<(usize, usize)> = puzzle.path(1, 2, Edges::PUZZLE).filter(|step| step.tree).map(|step| (step.x, step.y)).collect();
+            assert_eq!(collisions, vec!((1, 2), (3, 6)));
+        }
+
+        #[test]
+        fn should_render_path() {
+            let puzzle = Puzzle::build(&mut PUZZLE_INPUT.lines().map(|s| s.to_owned())).unwrap();
+            let expected = "O.##.........##.........##.......\n\
+                            #..O#...#..#...#...#..#...#...#..\n\
+                            .#....X..#..#....#..#..#....#..#.\n\
+                            ..#.#...#O#..#.#...#.#..#.#...#.#\n\
+                            .#...##..#..X...##..#..#...##..#.\n\
+                            ..#.##.......#.X#.......#.##.....\n\
+                            .#.#.#....#.#.#.#.O..#.#.#.#....#\n\
+                            .#........#.#........X.#........#\n\
+                            #.##...#...#.##...#...#.X#...#...\n\
+                            #...##....##...##....##...#X....#\n\
+                            .#..#...#.#.#..#...#.#.#..#...X.#\n";
+            assert_eq!(puzzle.render_path(3, 1), expected);
         }
     }
 }
\ No newline at end of file
@@ -5,121 +5,239 @@ use std::io;
 use std::io::prelude::*;
 
 use regex::Regex;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use std::ops::RangeInclusive;
+
+use advent::cli;
+use advent::error::{AdventError, exit_on_error};
+use advent::input::{blocks, Blocks, Span};
+use advent::table::Table;
+use advent::timing;
+
+const REQUIRED_KEYS: [&str; 7] = ["byr", "iyr", "eyr", "hgt", "hcl", "ecl", "pid"];
 
 fn validate_passport_keys(p: &BTreeMap<String, String>) -> bool {
-    p.contains_key("byr") &&
-        p.contains_key("iyr") &&
-        p.contains_key("eyr") &&
-        p.contains_key("hgt") &&
-        p.contains_key("hcl") &&
-        p.contains_key("ecl") &&
-        p.contains_key("pid")
+    REQUIRED_KEYS.iter().all(|key| p.contains_key(*key))
+}
+
+// The rules validate_with applies to each required field besides hcl and pid. Years and heights
+// must lie in the given inclusive ranges; heights may be given in any unit listed.
+struct ValidationConfig {
+    birth_years: RangeInclusive<usize>,
+    issue_years: RangeInclusive<usize>,
+    expiration_years: RangeInclusive<usize>,
+    heights: BTreeMap<String, RangeInclusive<f64>>,
+    eye_colors: BTreeSet<String>,
+    height_pat: Regex
 }
 
-fn validate_passport_values(p: &BTreeMap<String, String>) -> bool {
-    
-    let byr_valid = p.get("byr")
-    .and_then(|byr| usize::from_str_radix(byr, 10).ok())
-    .map_or(false, |byr| 1920 <= byr && byr <= 2002);
+impl ValidationConfig {
+    fn new(
+        birth_years: RangeInclusive<usize>,
+        issue_years: RangeInclusive<usize>,
+        expiration_years: RangeInclusive<usize>,
+        heights: &[(&str, RangeInclusive<f64>)],
+        eye_colors: &[&str]
+    ) -> ValidationConfig {
+        let heights: BTreeMap<String, RangeInclusive<f64>> = heights.iter()
+            .map(|(unit, range)| (unit.to_string(), range.clone()))
+            .collect();
+        // longest units first, so that e.g. cm is not read as m
+        let mut units: Vec<String> = heights.keys().map(|unit| regex::escape(unit)).collect();
+        units.sort_by_key(|unit| std::cmp::Reverse(unit.len()));
+        let height_pat = Regex::new(&format!(r"(\d+(?:\.\d+)?)({})", units.join("|"))).unwrap();
+        ValidationConfig {
+            birth_years,
+            issue_years,
+            expiration_years,
+            heights,
+            eye_colors: eye_colors.iter().map(|color| color.to_string()).collect(),
+            height_pat
+        }
+    }
 
-    let iyr_valid: bool = p.get("iyr")
-    .and_then(|iyr| usize::from_str_radix(iyr, 10).ok())
-    .map_or(false, |iyr| 2010 <= iyr && iyr <= 2020);
+    // Checks a single required field; unknown keys (e.g. cid) are never valid.
+    fn validate_field(&self, key: &str, value: &str) -> bool {
+        lazy_static! {
+            static ref HCL_PAT: Regex = Regex::new(r"#[0-9a-f]{6}").unwrap();
+        }
 
-    let eyr_valid: bool = p.get("eyr")
-    .and_then(|eyr| usize::from_str_radix(eyr, 10).ok())
-    .map_or(false, |eyr| 2020 <= eyr && eyr <= 2030);
+        fn year_in(value: &str, years: &RangeInclusive<usize>) -> bool {
+            usize::from_str_radix(value, 10).ok()
+                .is_some_and(|year| years.contains(&year))
+        }
 
-    lazy_static! {
-        static ref HGT_PAT: Regex = Regex::new(r"(\d+)(cm|in)").unwrap();
-        static ref HCL_PAT: Regex = Regex::new(r"#[0-9a-f]{6}").unwrap();
+        match key {
+            "byr" => year_in(value, &self.birth_years),
+            "iyr" => year_in(value, &self.issue_years),
+            "eyr" => year_in(value, &self.expiration_years),
+            "hgt" => !self.heights.is_empty() && self.height_pat.captures(value)
+                .and_then(|caps| caps[1].parse::<f64>().ok().zip(self.heights.get(&caps[2])))
+                .is_some_and(|(h, range)| range.contains(&h)),
+            "hcl" => HCL_PAT.is_match(value),
+            "ecl" => self.eye_colors.contains(value),
+            "pid" => value.len() == 9 && usize::from_str_radix(value, 10).is_ok(),
+            _ => false
+        }
     }
+}
 
-    enum Height {
-        In(usize),
-        Cm(usize)
+impl Default for ValidationConfig {
+    // The puzzle's rules
+    fn default() -> ValidationConfig {
+        ValidationConfig::new(
+            1920..=2002,
+            2010..=2020,
+            2020..=2030,
+            &[("cm", 150.0..=193.0), ("in", 59.0..=76.0)],
+            &["amb", "blu", "brn", "gry", "grn", "hzl", "oth"]
+        )
     }
+}
 
-    impl Height {
-        fn is_valid(&self) -> bool {
-            match self {
-                Height::Cm(h) => 150 <= *h && *h <= 193,
-                Height::In(h) => 59 <= *h && *h <= 76        
-            }
+fn validate_with(p: &BTreeMap<String, String>, config: &ValidationConfig) -> bool {
+    REQUIRED_KEYS.iter().all(|key| p.get(*key).is_some_and(|value| config.validate_field(key, value)))
+}
+
+// A passport which failed validation: where it was, and which required fields were to blame.
+#[derive(Debug, PartialEq, Eq)]
+struct Failure {
+    span: Span,
+    missing: Vec<&'static str>,
+    invalid: Vec<&'static str>
+}
+
+impl Display for Failure {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{}:", self.span)?;
+        if !self.missing.is_empty() {
+            write!(f, " missing {}", self.missing.join(", "))?;
+        }
+        if !self.missing.is_empty() && !self.invalid.is_empty() {
+            write!(f, ";")?;
         }
+        if !self.invalid.is_empty() {
+            write!(f, " invalid {}", self.invalid.join(", "))?;
+        }
+        Ok(())
     }
+}
 
-    let hgt_valid: bool = p.get("hgt")
-    .and_then(|hgt| HGT_PAT.captures(hgt))
-    .and_then(|caps| match &caps[2] {
-        "cm" => usize::from_str_radix(&caps[1], 10).ok().map(|h| Height::Cm(h)),
-        "in" => usize::from_str_radix(&caps[1], 10).ok().map(|h| Height::In(h)),
-        _ => None
-    }).map_or(false, |hgt| hgt.is_valid());
+// Tallies, over a stream of passports, how many were valid and which fields were to blame for those that were not.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct ValidationReport {
+    total: usize,
+    correct_keys: usize,
+    valid_values: usize,
+    missing: BTreeMap<&'static str, usize>,
+    invalid: BTreeMap<&'static str, usize>,
+    // in input order
+    failures: Vec<Failure>
+}
 
-    let hcl_valid: bool = p.get("hcl").map_or(false, |hcl| HCL_PAT.is_match(hcl));
+impl ValidationReport {
+    fn add(&mut self, p: &Passport, config: &ValidationConfig) {
+        self.total += 1;
+        self.correct_keys += validate_passport_keys(&p.fields) as usize;
+        self.valid_values += validate_with(&p.fields, config) as usize;
 
-    let ecl_valid: bool = p.get("ecl").map_or(false, |ecl| match ecl.as_str() {
-        "amb" | "blu" | "brn" | "gry" | "grn" | "hzl" | "oth" => true,
-        _ => false
-    });
+        let mut failure = Failure { span: p.span, missing: vec!(), invalid: vec!() };
+        for key in REQUIRED_KEYS.iter() {
+            match p.fields.get(*key) {
+                None => {
+                    *self.missing.entry(key).or_insert(0) += 1;
+                    failure.missing.push(key);
+                },
+                Some(value) if !config.validate_field(key, value) => {
+                    *self.invalid.entry(key).or_insert(0) += 1;
+                    failure.invalid.push(key);
+                },
+                _ => ()
+            }
+        }
+        if !failure.missing.is_empty() || !failure.invalid.is_empty() {
+            self.failures.push(failure);
+        }
+    }
 
-    let pid_valid: bool = p.get("pid")
-    .filter(|pid| pid.len() == 9)
-    .and_then(|pid| usize::from_str_radix(pid, 10).ok())
-    .is_some();
+    // One row per required field, most frequent failures first
+    fn table(&self) -> Table {
+        let mut rows: Vec<(&str, usize, usize)> = REQUIRED_KEYS.iter().map(|key| {
+            (*key, *self.missing.get(key).unwrap_or(&0), *self.invalid.get(key).unwrap_or(&0))
+        }).collect();
+        rows.sort_by_key(|(key, missing, invalid)| (std::cmp::Reverse(missing + invalid), *key));
 
-    byr_valid && iyr_valid && eyr_valid && hgt_valid && hcl_valid && ecl_valid && pid_valid
+        let mut table = Table::new(&["field", "missing", "invalid"]);
+        for (key, missing, invalid) in rows {
+            table.add_row(vec!(key.to_owned(), missing.to_string(), invalid.to_string()));
+        }
+        table
+    }
 }
 
+#[derive(Debug, PartialEq, Eq)]
+struct Passport {
+    // the lines the passport was read from
+    span: Span,
+    fields: BTreeMap<String, String>
+}
 
+// Reads one passport from each block of lines. Blocks without any fields are skipped.
 struct MapStream<J: Iterator<Item=String>> {
-    lines: J
+    blocks: Blocks<J>
+}
+
+impl<J: Iterator<Item=String>> MapStream<J> {
+    fn new<I>(lines: I) -> MapStream<J> where I: IntoIterator<Item=String, IntoIter=J> {
+        MapStream { blocks: blocks(lines) }
+    }
 }
 
 impl<J: Iterator<Item=String>> Iterator for MapStream<J> {
-    type Item = BTreeMap<String, String>;
+    type Item = Passport;
 
     fn next(&mut self) -> Option<Self::Item> {
         lazy_static! {
             static ref KV_PAT: Regex = Regex::new(r"(\w{3}):([\w#]+)").unwrap();
         }
-        let mut p = BTreeMap::new();
 
-        loop {
-            match self.lines.next() {
-                Some(line) if line.is_empty() && !p.is_empty() => {
-                    return Some(p)
-                },
-                Some(line) => {
-                    KV_PAT.captures_iter(&line).for_each(|cap| {
-                        p.insert(cap[1].to_owned(), cap[2].to_owned());
-                    });
-                },
-                None => {
-                    return Some(p).filter(|m| !m.is_empty())
-                }
+        for block in self.blocks.by_ref() {
+            let fields: BTreeMap<String, String> = block.lines.iter()
+                .flat_map(|line| KV_PAT.captures_iter(line))
+                .map(|cap| (cap[1].to_owned(), cap[2].to_owned()))
+                .collect();
+            if !fields.is_empty() {
+                return Some(Passport { span: block.span, fields })
             }
         }
+        None
     }
 }
 
-fn main() {
+fn run() -> Result<(), AdventError> {
     let stdin = io::stdin();
-    let map_stream = MapStream {
-        lines: stdin.lock().lines().flatten()
-    };
-    let (total_passports, correct_keys, valid_values) = {
-        let (mut t, mut k, mut v) = (0, 0, 0);
+    let map_stream = MapStream::new(stdin.lock().lines().flatten());
+    let config = ValidationConfig::default();
+    let report = timing::time("Validation", || {
+        let mut report = ValidationReport::default();
         for p in map_stream {
-            t += 1;
-            k += validate_passport_keys(&p) as usize;
-            v += validate_passport_values(&p) as usize;
+            report.add(&p, &config);
+        }
+        report
+    });
+    println!("Total passports: {}. Correct keys: {}; valid values: {}", report.total, report.correct_keys, report.valid_values);
+    print!("{}", report.table());
+    if cli::has_flag("--failures") {
+        for failure in &report.failures {
+            println!("{}", failure);
         }
-        (t, k, v)
-    };
-    println!("Total passports: {}. Correct keys: {}; valid values: {}", total_passports, correct_keys, valid_values);
+    }
+    Ok(())
+}
+
+fn main() {
+    exit_on_error(run())
 }
 
 #[cfg(test)]
@@ -130,6 +248,10 @@ mod day04_spec {
         v.iter().map(|p| (p.0.to_owned(), p.1.to_owned())).collect()
     }
 
+    fn passport(first: usize, last: usize, v: Vec<(&str, &str)>) -> Passport {
+        Passport { span: Span { first, last }, fields: vec_to_map(v) }
+    }
+
     #[test]
     fn parse_test_1() {
         let input = "ecl:gry pid:860033327 eyr:2020 hcl:#fffffd\n\
@@ -146,8 +268,8 @@ mod day04_spec {
             ("hgt", "183cm")
         ));
         
-        let mut stream = MapStream { lines: input.lines().map(|s| s.to_owned()) };
-        assert_eq!(stream.next(), Some(expected));
+        let mut stream = MapStream::new(input.lines().map(|s| s.to_owned()));
+        assert_eq!(stream.next(), Some(Passport { span: Span { first: 1, last: 2 }, fields: expected }));
         assert_eq!(stream.next(), None);
     }
 
@@ -192,10 +314,15 @@ mod day04_spec {
             ("hgt","59in"),
         ));
 
-        let mut stream = MapStream { lines: input.lines().map(|s| s.to_owned()) };
-        assert_eq!(stream.next(), Some(expected0));
-        assert_eq!(stream.next(), Some(expected1));
-        assert_eq!(stream.next(), Some(expected2));
+        let mut stream = MapStream::new(input.lines().map(|s| s.to_owned()));
+        assert_eq!(stream.next(), Some(Passport { span: Span { first: 1, last: 2 }, fields: expected0 }));
+        assert_eq!(stream.next(), Some(Passport { span: Span { first: 4, last: 7 }, fields: expected1 }));
+        assert_eq!(stream.next(), Some(Passport { span: Span { first: 10, last: 11 }, fields: expected2 }));
+        assert_eq!(stream.next(), None);
+
+        // a block without fields is no passport, but still counts towards line numbers
+        let mut stream = MapStream::new("no fields here\n\nbyr:1937\n".lines().map(|s| s.to_owned()));
+        assert_eq!(stream.next(), Some(passport(3, 3, vec!(("byr", "1937")))));
         assert_eq!(stream.next(), None);
     }
 
@@ -250,7 +377,7 @@ mod day04_spec {
         }
     }
 
-    mod validate_passport_values {
+    mod validate_with {
         use super::*;
 
         #[test]
@@ -264,7 +391,7 @@ mod day04_spec {
                 ("byr","1980"),
                 ("hcl","#623a2f"),
             ));
-            assert!(validate_passport_values(&p));
+            assert!(validate_with(&p, &ValidationConfig::default()));
 
             let p = vec_to_map(vec!(
                 ("eyr","2029"),
@@ -276,7 +403,7 @@ mod day04_spec {
                 ("hcl","#a97842"),
                 ("hgt","165cm"),
             ));
-            assert!(validate_passport_values(&p));
+            assert!(validate_with(&p, &ValidationConfig::default()));
 
             let p = vec_to_map(vec!(
                 ("hcl","#888785"),
@@ -288,7 +415,7 @@ mod day04_spec {
                 ("ecl","hzl"),
                 ("eyr","2022"),
             ));
-            assert!(validate_passport_values(&p));
+            assert!(validate_with(&p, &ValidationConfig::default()));
 
             let p = vec_to_map(vec!(
                 ("iyr","2010"),
@@ -299,7 +426,7 @@ mod day04_spec {
                 ("eyr","2021"),
                 ("pid","093154719"),
             ));
-            assert!(validate_passport_values(&p));
+            assert!(validate_with(&p, &ValidationConfig::default()));
         }
 
         #[test]
@@ -314,7 +441,7 @@ mod day04_spec {
                 ("iyr","2018"),
                 ("byr","1926"),
             ));
-            assert!(!validate_passport_values(&p));
+            assert!(!validate_with(&p, &ValidationConfig::default()));
 
             let p = vec_to_map(vec!(
                 ("iyr","2019"),
@@ -325,7 +452,7 @@ mod day04_spec {
                 ("pid","012533040"),
                 ("byr","1946"),
             ));
-            assert!(!validate_passport_values(&p));
+            assert!(!validate_with(&p, &ValidationConfig::default()));
 
             let p = vec_to_map(vec!(
                 ("hcl","dab227"),
@@ -337,7 +464,7 @@ mod day04_spec {
                 ("byr","1992"),
                 ("cid","277"),
             ));
-            assert!(!validate_passport_values(&p));
+            assert!(!validate_with(&p, &ValidationConfig::default()));
 
             let p = vec_to_map(vec!(
                 ("hgt","59cm"),
@@ -348,7 +475,78 @@ mod day04_spec {
                 ("pid","3556412378"),
                 ("byr","2007"),
             ));
-            assert!(!validate_passport_values(&p));
+            assert!(!validate_with(&p, &ValidationConfig::default()));
         }
+
+        #[test]
+        fn should_follow_config() {
+            let p = vec_to_map(vec!(
+                ("iyr","2010"),
+                ("hgt","1.58m"),
+                ("hcl","#b6652a"),
+                ("ecl","vio"),
+                ("byr","1944"),
+                ("eyr","2021"),
+                ("pid","093154719"),
+            ));
+            assert!(!validate_with(&p, &ValidationConfig::default()));
+
+            let config = ValidationConfig::new(
+                1900..=2002,
+                2005..=2020,
+                2020..=2030,
+                &[("cm", 150.0..=193.0), ("m", 1.5..=1.93)],
+                &["blu", "vio"]
+            );
+            assert!(validate_with(&p, &config));
+            assert!(config.validate_field("hgt", "158cm"));
+            assert!(!config.validate_field("hgt", "2m"));
+            assert!(!config.validate_field("hgt", "62in"));
+            assert!(!config.validate_field("ecl", "amb"));
+            assert!(config.validate_field("byr", "1901"));
+            assert!(config.validate_field("iyr", "2005"));
+            assert!(!config.validate_field("iyr", "2004"));
+
+            let no_heights = ValidationConfig::new(1920..=2002, 2010..=2020, 2020..=2030, &[], &["blu"]);
+            assert!(!no_heights.validate_field("hgt", "158cm"));
+        }
+    }
+
+    #[test]
+    fn validation_report_test() {
+        let config = ValidationConfig::default();
+        let mut report = ValidationReport::default();
+        report.add(&passport(1, 2, vec!(
+            ("pid","087499704"), ("hgt","74in"), ("ecl","grn"), ("iyr","2012"),
+            ("eyr","2030"), ("byr","1980"), ("hcl","#623a2f"),
+        )), &config);
+        report.add(&passport(4, 4, vec!(
+            ("eyr","1972"), ("cid","100"), ("hcl","#18171d"), ("ecl","amb"),
+            ("hgt","170"), ("pid","186cm"), ("iyr","2018"), ("byr","1926"),
+        )), &config);
+        report.add(&passport(6, 8, vec!(
+            ("hcl","#cfa07d"), ("eyr","2025"), ("pid","166559648"),
+            ("iyr","2011"), ("ecl","brn"), ("hgt","59cm"),
+        )), &config);
+
+        assert_eq!(report.total, 3);
+        assert_eq!(report.correct_keys, 2);
+        assert_eq!(report.valid_values, 1);
+        assert_eq!(report.missing, vec!(("byr", 1)).into_iter().collect());
+        assert_eq!(report.invalid, vec!(("eyr", 1), ("hgt", 2), ("pid", 1)).into_iter().collect());
+
+        let table = report.table().to_string();
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 9);
+        assert_eq!(lines[2], "hgt   | 0       | 2");
+        assert_eq!(lines[3], "byr   | 1       | 0");
+        assert_eq!(lines[4], "eyr   | 0       | 1");
+        assert_eq!(lines[5], "pid   | 0       | 1");
+
+        let failures: Vec<String> = report.failures.iter().map(|failure| failure.to_string()).collect();
+        assert_eq!(failures, vec!(
+            "line 4: invalid eyr, hgt, pid",
+            "lines 6-8: missing byr; invalid hgt"
+        ));
     }
 }
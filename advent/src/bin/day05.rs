@@ -1,42 +1,160 @@
 use std::io;
 use std::io::prelude::*;
 
-use std::collections::{BinaryHeap, BTreeSet};
-
-fn seat_id(k: &str) -> Option<usize> {
-    let b: String = k.chars().flat_map(|c| match c {
-        'F' | 'L' => Some('0'),
-        'B' | 'R' => Some('1'),
-        _ => None
-    }).collect();
-    usize::from_str_radix(b.as_ref(), 2).ok()
+use advent::cli;
+use advent::error::{AdventError, exit_on_error};
+use advent::seats::seat_summary;
+use advent::timing;
+
+// A boarding pass is a 10-bit binary number: seven row bits (F = 0, B = 1)
+// followed by three column bits (L = 0, R = 1).
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+struct BoardingPass(u16);
+
+#[derive(Debug, PartialEq, Eq)]
+enum PassError {
+    InvalidChar { position: usize, c: char },
+    WrongLength(usize)
+}
+
+impl BoardingPass {
+    const LEN: usize = 10;
+    const ROW_LEN: usize = 7;
+
+    fn parse(k: &str) -> Result<BoardingPass, PassError> {
+        seat_id(k).map(|id| BoardingPass(id as u16))
+    }
+
+    fn id(&self) -> usize {
+        self.0 as usize
+    }
+
+    fn row(&self) -> usize {
+        self.id() >> (BoardingPass::LEN - BoardingPass::ROW_LEN)
+    }
+
+    fn col(&self) -> usize {
+        self.id() & 0b111
+    }
+}
+
+fn seat_id(k: &str) -> Result<usize, PassError> {
+    let len = k.chars().count();
+    if len != BoardingPass::LEN {
+        return Err(PassError::WrongLength(len))
+    }
+
+    k.chars().enumerate().try_fold(0, |acc, (position, c)| {
+        let bit = match (position < BoardingPass::ROW_LEN, c) {
+            (true, 'F') | (false, 'L') => 0,
+            (true, 'B') | (false, 'R') => 1,
+            _ => return Err(PassError::InvalidChar { position, c })
+        };
+        Ok((acc << 1) | bit)
+    })
+}
+
+// The plane's occupancy: one byte per row, whose bits (most significant first) are its columns.
+struct SeatMap {
+    rows: Vec<u8>,
+    mine: Option<BoardingPass>
 }
 
-fn open_seat(ids: &BTreeSet<usize>)-> Option<usize> {
-    for &x in ids {
-        let candidate = x + 1;
-        if ids.contains(&(candidate + 1)) && !ids.contains(&candidate) {
-            return Some(candidate)
+impl SeatMap {
+    const ROWS: usize = 1 << BoardingPass::ROW_LEN;
+    const COLS: usize = 1 << (BoardingPass::LEN - BoardingPass::ROW_LEN);
+
+    fn new<'a, I: IntoIterator<Item=&'a BoardingPass>>(passes: I) -> SeatMap {
+        let mut rows = vec!(0; SeatMap::ROWS);
+        for pass in passes {
+            rows[pass.row()] |= 0x80 >> pass.col();
+        }
+        SeatMap { rows, mine: None }
+    }
+
+    fn with_mine(self, id: usize) -> SeatMap {
+        SeatMap { mine: Some(BoardingPass(id as u16)), ..self }
+    }
+
+    fn is_occupied(&self, row: usize, col: usize) -> bool {
+        self.rows[row] & (0x80 >> col) != 0
+    }
+
+    // The number of occupied seats in `row`
+    fn occupancy(&self, row: usize) -> usize {
+        self.rows.get(row).map_or(0, |cols| cols.count_ones() as usize)
+    }
+
+    // The occupancy of every row, front to back
+    fn row_occupancy(&self) -> Vec<usize> {
+        (0..SeatMap::ROWS).map(|row| self.occupancy(row)).collect()
+    }
+
+    // One line per row from the first to the last with anyone (or me) in it, with an aisle down the
+    // middle: '#' for an occupied seat, '.' for an empty one and 'X' for mine.
+    fn render(&self) -> String {
+        let in_use = |row: &usize| self.occupancy(*row) > 0 || self.mine.map(|pass| pass.row()) == Some(*row);
+        let (first, last) = match ((0..SeatMap::ROWS).find(in_use), (0..SeatMap::ROWS).rev().find(in_use)) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return String::new()
+        };
+        let mut map = String::new();
+        for row in first..=last {
+            map.push_str(&format!("{:>3} ", row));
+            for col in 0..SeatMap::COLS {
+                if col == SeatMap::COLS / 2 {
+                    map.push(' ');
+                }
+                map.push(match (self.mine, self.is_occupied(row, col)) {
+                    (Some(pass), _) if pass.row() == row && pass.col() == col => 'X',
+                    (_, true) => '#',
+                    (_, false) => '.'
+                });
+            }
+            map.push('\n');
         }
-    };
-    None
+        map
+    }
 }
 
-fn main() {
+fn run() -> Result<(), AdventError> {
     let stdin = io::stdin();
-    let seat_ids: BinaryHeap<usize> = stdin.lock().lines()
+    let passes: Vec<BoardingPass> = stdin.lock().lines()
     .flatten()
-    .flat_map(|line| seat_id(&line)).collect();
-    let max_seat_id = seat_ids.peek().unwrap();
+    .flat_map(|line| BoardingPass::parse(&line).map_err(|e| eprintln!("Invalid boarding pass {}: {:?}", line, e)))
+    .collect();
+    let summary = timing::time("Part 1", || seat_summary(passes.iter().map(|pass| pass.id())))
+        .ok_or_else(|| AdventError::input("no valid boarding passes"))?;
+    let max_pass = BoardingPass(summary.max as u16);
 
-    println!("Max seat id: {}", max_seat_id);
+    println!("Max seat id: {} (row {}, column {})", max_pass.id(), max_pass.row(), max_pass.col());
 
-    let seat_ids: BTreeSet<usize> = {
-        seat_ids.iter().map(|j| *j).collect()
-    };
+    if cli::has_flag("--all-missing") {
+        let gaps: Vec<String> = summary.gaps().iter().map(|gap| if gap.start() == gap.end() {
+            gap.start().to_string()
+        } else {
+            format!("{}-{}", gap.start(), gap.end())
+        }).collect();
+        println!("{} seat ids missing between {} and {}: {}", summary.missing.len(), summary.min, summary.max, gaps.join(", "));
+    }
 
-    let my_seat = open_seat(&seat_ids).unwrap();
+    let my_seat = timing::time("Part 2", || summary.open_seat())
+        .ok_or_else(|| AdventError::no_solution("no open seat between two occupied seats"))?;
     println!("Open seat found at: {}", my_seat);
+
+    if cli::has_flag("--map") {
+        let map = SeatMap::new(passes.iter()).with_mine(my_seat);
+        print!("{}", map.render());
+        let occupancy = map.row_occupancy();
+        let full_rows = occupancy.iter().filter(|&&n| n == SeatMap::COLS).count();
+        let partial_rows = occupancy.iter().filter(|&&n| 0 < n && n < SeatMap::COLS).count();
+        println!("{} full rows, {} partially occupied rows", full_rows, partial_rows);
+    }
+    Ok(())
+}
+
+fn main() {
+    exit_on_error(run())
 }
 
 #[cfg(test)]
@@ -45,9 +163,81 @@ mod day05_spec {
 
     #[test]
     fn seat_id_test() {
-        assert_eq!(seat_id("FBFBBFFRLR"), Some(357));
-        assert_eq!(seat_id("BFFFBBFRRR"), Some(567));
-        assert_eq!(seat_id("FFFBBBFRRR"), Some(119));
-        assert_eq!(seat_id("BBFFBBFRLL"), Some(820));
+        assert_eq!(seat_id("FBFBBFFRLR"), Ok(357));
+        assert_eq!(seat_id("BFFFBBFRRR"), Ok(567));
+        assert_eq!(seat_id("FFFBBBFRRR"), Ok(119));
+        assert_eq!(seat_id("BBFFBBFRLL"), Ok(820));
+    }
+
+    #[test]
+    fn parse_errors_test() {
+        assert_eq!(seat_id("FBFBBFFRL"), Err(PassError::WrongLength(9)));
+        assert_eq!(seat_id("FBFBBFFRLRR"), Err(PassError::WrongLength(11)));
+        assert_eq!(seat_id(""), Err(PassError::WrongLength(0)));
+        assert_eq!(seat_id("FBFBXFFRLR"), Err(PassError::InvalidChar { position: 4, c: 'X' }));
+        assert_eq!(seat_id("FBFBBFFRFR"), Err(PassError::InvalidChar { position: 8, c: 'F' }));
+        assert_eq!(seat_id("FBFBBFLRLR"), Err(PassError::InvalidChar { position: 6, c: 'L' }));
+    }
+
+    #[test]
+    fn row_col_test() {
+        let pass = BoardingPass::parse("FBFBBFFRLR").unwrap();
+        assert_eq!(pass.row(), 44);
+        assert_eq!(pass.col(), 5);
+
+        let pass = BoardingPass::parse("BBFFBBFRLL").unwrap();
+        assert_eq!(pass.row(), 102);
+        assert_eq!(pass.col(), 4);
+    }
+
+    #[test]
+    fn seat_map_test() {
+        let passes: Vec<BoardingPass> = vec!("FBFBBFFRLR", "FBFBBFFLLL", "FBFBBBFLLR", "FBFBBBFLRL")
+            .iter().map(|pass| BoardingPass::parse(pass).unwrap()).collect();
+        let map = SeatMap::new(passes.iter());
+        assert_eq!(map.occupancy(44), 2);
+        assert_eq!(map.occupancy(45), 0);
+        assert_eq!(map.occupancy(46), 2);
+        assert_eq!(map.occupancy(200), 0);
+        assert_eq!(map.row_occupancy().iter().sum::<usize>(), 4);
+        assert_eq!(map.render(), concat!(
+            " 44 #... .#..\n",
+            " 45 .... ....\n",
+            " 46 .##. ....\n"
+        ));
+
+        let map = map.with_mine(BoardingPass::parse("FBBFFBFRRR").unwrap().id());
+        assert_eq!(map.occupancy(50), 0);
+        assert_eq!(map.render().lines().last(), Some(" 50 .... ...X"));
+        assert_eq!(map.render().lines().count(), 7);
+
+        assert_eq!(SeatMap::new(&[]).render(), "");
+    }
+
+    // The original implementation, which built a binary string and parsed it.
+    fn seat_id_via_string(k: &str) -> Option<usize> {
+        let b: String = k.chars().flat_map(|c| match c {
+            'F' | 'L' => Some('0'),
+            'B' | 'R' => Some('1'),
+            _ => None
+        }).collect();
+        usize::from_str_radix(b.as_ref(), 2).ok()
+    }
+
+    #[test]
+    fn all_passes_test() {
+        for id in 0..1024 {
+            let pass: String = (0..10).map(|bit| {
+                let set = id & (1 << (9 - bit)) != 0;
+                match (bit < 7, set) {
+                    (true, false) => 'F',
+                    (true, true) => 'B',
+                    (false, false) => 'L',
+                    (false, true) => 'R'
+                }
+            }).collect();
+            assert_eq!(seat_id(&pass).ok(), seat_id_via_string(&pass));
+            assert_eq!(seat_id(&pass), Ok(id));
+        }
     }
 }
\ No newline at end of file
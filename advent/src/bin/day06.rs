@@ -1,66 +1,37 @@
 use std::io;
 use std::io::prelude::*;
 
-use std::collections::BTreeSet;
+use advent::cli;
+use advent::customs::{count_group_questions, count_group_questions_bitmask, invalid_answers, overlap_report};
+use advent::error::{AdventError, exit_on_error};
+use advent::timing;
 
-fn count_group_questions<J>(lines: &mut J) -> (usize, usize)
-where J: Iterator<Item=String> {
-    let mut t_any = 0; // total number of questions answered yes by ANY group member
-    let mut t_all = 0;
-    let mut qs_any: BTreeSet<char> = BTreeSet::new();
+fn run() -> Result<(), AdventError> {
+    let stdin = io::stdin();
+    let lines: Vec<String> = stdin.lock().lines().flatten().collect();
 
-    enum QsAll {
-        Nil, // When we finish with a group
-        P(BTreeSet<char>)
+    if cli::has_flag("--stats") {
+        let report = timing::time("Stats", || overlap_report(lines.iter().cloned()));
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
     }
 
-    impl QsAll {
-        fn len(&self) -> usize {
-            match self {
-                QsAll::Nil => 0,
-                QsAll::P(qs) => qs.len()
+    // The bitmask accumulator is faster, but only handles answers 'a'..='z'
+    let (q_any, q_all) = match timing::time("Count (bitmask)", || count_group_questions_bitmask(&mut lines.iter().cloned())) {
+        Ok(counts) => counts,
+        Err(_) => {
+            for invalid in invalid_answers(lines.iter()) {
+                eprintln!("Unexpected answer {:?} on line {}", invalid.c, invalid.line_no);
             }
+            timing::time("Count (sets)", || count_group_questions(&mut lines.into_iter()))
         }
-    }
-
-    let mut qs_all = QsAll::Nil;
-
-    loop {
-        match lines.next() {
-            None => {
-                t_any += qs_any.len();
-                t_all += qs_all.len();
-                break
-            },
-            Some(line) if line.is_empty() => {
-                t_any += qs_any.len();
-                t_all += qs_all.len();
-                qs_any.clear();
-                qs_all = QsAll::Nil;
-            },
-            Some(line) => {
-                qs_any.extend(line.chars());
-                match &mut qs_all {
-                    QsAll::Nil => {
-                        qs_all = QsAll::P(line.chars().collect());
-                    },
-                    QsAll::P(ref mut qs) => {
-                        let next_qs = qs.intersection(&line.chars().collect()).map(|c| *c).collect();
-                        *qs = next_qs;
-                    }
-                }
-            }
-        }
-    }
-
-    (t_any, t_all)
+    };
+    println!("Total questions answered yes by ANY group member: {}", q_any);
+    println!("Total questions answered yes by ALL group members: {}", q_all);
+    Ok(())
 }
 
 fn main() {
-    let stdin = io::stdin();
-    let (q_any, q_all) = count_group_questions(&mut stdin.lock().lines().flatten());
-    println!("Total questions answered yes by ANY group member: {}", q_any);
-    println!("Total questions answered yes by ALL group members: {}", q_all);
+    exit_on_error(run())
 }
 
 #[cfg(test)]
@@ -88,4 +59,4 @@ mod day06_spec {
     fn question_count_test() {
         assert_eq!(count_group_questions(&mut TEST_INPUT.lines().map(|s| s.to_owned())), (11, 6))    ;
     }
-}
\ No newline at end of file
+}
@@ -2,25 +2,45 @@ use std::io;
 use std::io::prelude::*;
 
 use std::collections::{BTreeMap, BTreeSet, VecDeque};
-
-use std::rc::Rc;
+use std::fmt::{Display, Formatter};
 
 #[macro_use]
 extern crate lazy_static;
 use regex::Regex;
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+use advent::cli;
+use advent::error::{AdventError, exit_on_error};
+use advent::intern::{Interner, Symbol};
+use advent::memo::Memo;
+use advent::timing;
+
+// A bag as named in the rules, e.g. "shiny gold"
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash)]
 struct Bag{ adj: String, color: String }
 
 impl Bag {
     fn new(a: &str, c: &str) -> Bag {
         Bag { adj: a.to_owned(), color: c.to_owned() }
     }
+
+    fn name(&self) -> String {
+        format!("{} {}", self.adj, self.color)
+    }
+}
+
+impl Display for Bag {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{} {}", self.adj, self.color)
+    }
 }
 
+// The interned name of a bag. The regulations refer to bags only by id, so each name is stored
+// once, however many rules mention it.
+type BagId = Symbol;
+
 struct BaggageRegulation {
-    must_contain: BTreeMap<Rc<Bag>, usize>,
-    is_contained_by: BTreeSet<Rc<Bag>>
+    must_contain: BTreeMap<BagId, usize>,
+    is_contained_by: BTreeSet<BagId>
 }
 
 impl BaggageRegulation {
@@ -29,63 +49,114 @@ impl BaggageRegulation {
     }
 }
 
+#[derive(Debug, PartialEq, Eq)]
+struct RegulationParseError {
+    line_no: usize, // 1-based
+    line: String,
+    reason: String
+}
+
 struct BaggageRegulations { 
-    regulations: BTreeMap<Rc<Bag>, BaggageRegulation>
+    names: Interner,
+    regulations: BTreeMap<BagId, BaggageRegulation>,
+    leaves: BTreeSet<BagId> // bags explicitly declared to "contain no other bags"
 }
 
 impl BaggageRegulations {
     fn new() -> BaggageRegulations {
-        BaggageRegulations{ regulations: BTreeMap::new() }
+        BaggageRegulations{ names: Interner::new(), regulations: BTreeMap::new(), leaves: BTreeSet::new() }
+    }
+
+    // The id of `bag`, if any rule mentions it
+    fn id(&self, bag: &Bag) -> Option<BagId> {
+        self.names.get(&bag.name())
     }
 
-    fn insert_line(&mut self, line: &str) {
+    // The bag with id `id`, for display
+    fn resolve(&self, id: BagId) -> Bag {
+        let name = self.names.resolve(id);
+        let (adj, color) = name.split_at(name.find(' ').unwrap_or(name.len()));
+        Bag::new(adj, color.trim_start())
+    }
+
+    fn insert_line(&mut self, line: &str) -> Result<(), String> {
         lazy_static! {
-            static ref LINE_PAT: Regex = Regex::new(r"(\w+) (\w+) bags contain (.+).").unwrap();
-            static ref CONTENTS_PAT: Regex = Regex::new(r"(\d+) (\w+) (\w+) bag").unwrap();
+            static ref LINE_PAT: Regex = Regex::new(r"^(\w+) (\w+) bags contain (.+)\.$").unwrap();
+            static ref CONTENTS_PAT: Regex = Regex::new(r"^(\d+) (\w+) (\w+) bags?$").unwrap();
         }
 
-        for caps0 in LINE_PAT.captures(line) {
-            let outer_bag = Bag::new(&caps0[1], &caps0[2]);
-            let outer_bag_boxed = Rc::new(outer_bag);
-
-            let child_refs: Vec<(Rc<Bag>, usize)> = CONTENTS_PAT.captures_iter(&caps0[3]).flat_map(|caps1| {
-                usize::from_str_radix(&caps1[1], 10).map(|n| {
-                    let child_bag = Rc::new(Bag::new(&caps1[2], &caps1[3]));
-                    let child_regulation = self.regulations.entry(child_bag.clone()).or_insert(BaggageRegulation::new());
-                    child_regulation.is_contained_by.insert(outer_bag_boxed.clone());
-                    (child_bag, n)
-                })
-            }).collect();
-
-            // then add all children to outer_bag
-            let outer_regulation = self.regulations.entry(outer_bag_boxed).or_insert(BaggageRegulation::new());
-            for (child_bag, n) in child_refs {
-                outer_regulation.must_contain.insert(child_bag, n);
-            }
+        let caps0 = LINE_PAT.captures(line.trim()).ok_or("Expected `<adj> <color> bags contain <contents>.`")?;
+
+        if &caps0[3] == "no other bags" {
+            let outer_bag = self.names.intern(&Bag::new(&caps0[1], &caps0[2]).name());
+            self.regulations.entry(outer_bag).or_insert_with(BaggageRegulation::new);
+            self.leaves.insert(outer_bag);
+            return Ok(())
+        }
+
+        // check every item before interning anything, so a bad line leaves no trace
+        let mut children: Vec<(Bag, usize)> = vec!();
+        for item in caps0[3].split(", ") {
+            let caps1 = CONTENTS_PAT.captures(item).ok_or(format!("Unrecognized contents `{}`", item))?;
+            let n = caps1[1].parse::<usize>().map_err(|e| format!("Bad count `{}`: {}", &caps1[1], e))?;
+            children.push((Bag::new(&caps1[2], &caps1[3]), n));
         }
+
+        let outer_bag = self.names.intern(&Bag::new(&caps0[1], &caps0[2]).name());
+        let child_refs: Vec<(BagId, usize)> = children.iter()
+            .map(|(child_bag, n)| (self.names.intern(&child_bag.name()), *n))
+            .collect();
+
+        for (child_bag, _) in &child_refs {
+            let child_regulation = self.regulations.entry(*child_bag).or_insert_with(BaggageRegulation::new);
+            child_regulation.is_contained_by.insert(outer_bag);
+        }
+
+        // then add all children to outer_bag
+        let outer_regulation = self.regulations.entry(outer_bag).or_insert_with(BaggageRegulation::new);
+        for (child_bag, n) in child_refs {
+            outer_regulation.must_contain.insert(child_bag, n);
+        }
+
+        Ok(())
     }
 
-    fn build<J>(lines: &mut J) -> BaggageRegulations
+    fn build<J>(lines: &mut J) -> Result<BaggageRegulations, RegulationParseError>
     where J: Iterator<Item=String> {
         let mut regs = BaggageRegulations::new();
-        for line in lines {
-            regs.insert_line(&line);
+        for (idx, line) in lines.enumerate() {
+            if line.trim().is_empty() {
+                continue
+            }
+            if let Err(reason) = regs.insert_line(&line) {
+                return Err(RegulationParseError { line_no: idx + 1, line, reason })
+            }
         }
 
-        regs
+        Ok(regs)
+    }
+
+    // The direct contents of `bag`, or None if no regulation describes its contents. A bag which
+    // only ever appears inside other bags is unknown, whereas one declared to contain
+    // "no other bags" has empty contents.
+    fn contents(&self, bag: &Bag) -> Option<&BTreeMap<BagId, usize>> {
+        let id = self.id(bag)?;
+        self.regulations.get(&id)
+            .filter(|regulation| !regulation.must_contain.is_empty() || self.leaves.contains(&id))
+            .map(|regulation| &regulation.must_contain)
     }
 
-    fn walk_out_from(&self, bag: &Bag) -> BTreeSet<&Bag> {
-        let mut r: BTreeSet<&Bag> = BTreeSet::new();
+    fn walk_out_from(&self, bag: &Bag) -> BTreeSet<BagId> {
+        let mut r: BTreeSet<BagId> = BTreeSet::new();
         let mut q = VecDeque::new();
 
-        q.push_back(bag);
+        q.extend(self.id(bag));
 
         while let Some(outer_bag) = q.pop_front() {
-            for regulation in self.regulations.get(outer_bag) {
+            for regulation in self.regulations.get(&outer_bag) {
                 for parent in &regulation.is_contained_by {
-                    r.insert(parent);
-                    q.push_back(parent);
+                    r.insert(*parent);
+                    q.push_back(*parent);
                 }
             }
         }
@@ -93,53 +164,143 @@ impl BaggageRegulations {
         r
     }
 
-    fn transitive_contents(&self, bag: &Bag) -> BTreeMap<&Bag, usize> {
-        let mut r: BTreeMap<&Bag, usize> = BTreeMap::new();
-        let mut q: VecDeque<(&Bag, usize)> = VecDeque::new();
-
-        for regulation in self.regulations.get(bag) {
-            for (child, &n) in &regulation.must_contain {
-                q.push_back((child, n))
-            }
+    fn transitive_contents(&self, bag: &Bag) -> BTreeMap<BagId, usize> {
+        // The contents of each bag, memoized since a bag type typically appears inside many others
+        fn contents_of(regs: &BaggageRegulations, bag: BagId, memo: &mut Memo<BagId, BTreeMap<BagId, usize>>) -> BTreeMap<BagId, usize> {
+            memo.get_or_compute(bag, |memo| {
+                let mut r: BTreeMap<BagId, usize> = BTreeMap::new();
+                for regulation in regs.regulations.get(&bag) {
+                    for (child, &n) in &regulation.must_contain {
+                        *r.entry(*child).or_insert(0) += n;
+                        for (grandchild, m) in contents_of(regs, *child, memo) {
+                            *r.entry(grandchild).or_insert(0) += n * m;
+                        }
+                    }
+                }
+                r
+            })
         }
 
-        while let Some((child, n0)) = q.pop_front() {
-            *r.entry(child).or_insert(0) += n0;
-            for regulation in self.regulations.get(child) {
-                for (grandchild, n1) in &regulation.must_contain {
-                    q.push_back((grandchild, n0 * n1));
+        self.id(bag).map(|id| contents_of(self, id, &mut Memo::new())).unwrap_or_default()
+    }
+
+    // The bags which contain `bag` at most `depth` levels out; depth 1 gives the bags which hold
+    // it directly.
+    fn bags_containing_within(&self, bag: &Bag, depth: usize) -> BTreeSet<BagId> {
+        let mut r: BTreeSet<BagId> = BTreeSet::new();
+        let mut q = VecDeque::new();
+
+        q.extend(self.id(bag).map(|id| (id, 0)));
+
+        // breadth first, so each bag is first reached at its least depth
+        while let Some((inner_bag, d)) = q.pop_front() {
+            if d == depth {
+                continue
+            }
+            if let Some(regulation) = self.regulations.get(&inner_bag) {
+                for parent in &regulation.is_contained_by {
+                    if r.insert(*parent) {
+                        q.push_back((*parent, d + 1));
+                    }
                 }
             }
         }
 
         r
     }
+
+    // As transitive_contents, counting only the bags nested at most `depth` levels inside `bag`
+    fn contents_within(&self, bag: &Bag, depth: usize) -> BTreeMap<BagId, usize> {
+        fn contents_of(regs: &BaggageRegulations, bag: BagId, depth: usize, memo: &mut Memo<(BagId, usize), BTreeMap<BagId, usize>>) -> BTreeMap<BagId, usize> {
+            memo.get_or_compute((bag, depth), |memo| {
+                let mut r: BTreeMap<BagId, usize> = BTreeMap::new();
+                if depth == 0 {
+                    return r
+                }
+                if let Some(regulation) = regs.regulations.get(&bag) {
+                    for (child, &n) in &regulation.must_contain {
+                        *r.entry(*child).or_insert(0) += n;
+                        for (grandchild, m) in contents_of(regs, *child, depth - 1, memo) {
+                            *r.entry(grandchild).or_insert(0) += n * m;
+                        }
+                    }
+                }
+                r
+            })
+        }
+
+        self.id(bag).map(|id| contents_of(self, id, depth, &mut Memo::new())).unwrap_or_default()
+    }
+
+    // The number of levels of bags nested inside `bag`: 0 for a bag which holds nothing
+    fn max_depth(&self, bag: &Bag) -> usize {
+        fn depth_of(regs: &BaggageRegulations, bag: BagId, memo: &mut Memo<BagId, usize>) -> usize {
+            memo.get_or_compute(bag, |memo| {
+                regs.regulations.get(&bag).into_iter()
+                    .flat_map(|regulation| regulation.must_contain.keys())
+                    .map(|child| 1 + depth_of(regs, *child, memo))
+                    .max()
+                    .unwrap_or(0)
+            })
+        }
+
+        self.id(bag).map(|id| depth_of(self, id, &mut Memo::new())).unwrap_or(0)
+    }
 }
 
-fn main() {
+fn run() -> Result<(), AdventError> {
     let stdin = io::stdin();
-    let baggage_regulations = BaggageRegulations::build(&mut stdin.lock().lines().flatten());
+    let baggage_regulations = BaggageRegulations::build(&mut stdin.lock().lines().flatten()).map_err(|e| {
+        AdventError::input(format!("malformed regulation on line {} ({}): {}", e.line_no, e.reason, e.line))
+    })?;
     println!("Parsed {} baggage regulations.", baggage_regulations.regulations.len());
 
     let my_bag = Bag::new("shiny", "gold");
-    let can_contain_my_bag = baggage_regulations.walk_out_from(&my_bag);
+    if baggage_regulations.contents(&my_bag).is_none() {
+        eprintln!("No regulation describes the contents of my {} bag.", my_bag);
+    }
+    let can_contain_my_bag = timing::time("Part 1", || baggage_regulations.walk_out_from(&my_bag));
     println!("{} bags can contain my shiny gold bag.", can_contain_my_bag.len());
 
-    let my_contents = baggage_regulations.transitive_contents(&my_bag);
+    let my_contents = timing::time("Part 2", || baggage_regulations.transitive_contents(&my_bag));
     let my_contents_total: usize = my_contents.values().sum();
     println!("My bag must contain {} other bags.", my_contents_total);
+    println!("Bags nest {} levels deep inside my bag.", baggage_regulations.max_depth(&my_bag));
+
+    if let Some(depth) = cli::option_value("--depth") {
+        let depth: usize = depth.parse().map_err(|_| AdventError::input(format!("bad depth {}", depth)))?;
+        println!("Within depth {}: {} bags can contain my bag, and it contains {} other bags.", depth,
+            baggage_regulations.bags_containing_within(&my_bag, depth).len(),
+            baggage_regulations.contents_within(&my_bag, depth).values().sum::<usize>()
+        );
+    }
+
+    if cli::has_flag("--contents") {
+        for (bag, n) in &my_contents {
+            println!("{} {}", n, baggage_regulations.resolve(*bag));
+        }
+    }
+    Ok(())
+}
+
+fn main() {
+    exit_on_error(run())
 }
 
 #[cfg(test)]
 mod day07_spec {
     use super::*;
 
+    fn id(regs: &BaggageRegulations, adj: &str, color: &str) -> BagId {
+        regs.id(&Bag::new(adj, color)).unwrap()
+    }
+
     fn get_regulation<'a>(regs: &'a BaggageRegulations, adj: &str, color: &str) -> Option<&'a BaggageRegulation> {
-        regs.regulations.get(&Bag::new(adj, color))
+        regs.id(&Bag::new(adj, color)).and_then(|id| regs.regulations.get(&id))
     }
 
-    fn get_required_contents(outer: &BaggageRegulation, adj: &str, color: &str) -> usize {
-        *outer.must_contain.get(&Bag::new(adj, color)).unwrap_or(&0)
+    fn get_required_contents(regs: &BaggageRegulations, outer: &BaggageRegulation, adj: &str, color: &str) -> usize {
+        *outer.must_contain.get(&id(regs, adj, color)).unwrap_or(&0)
     }
 
     #[test]
@@ -154,23 +315,26 @@ mod day07_spec {
         faded blue bags contain no other bags.\n\
         dotted black bags contain no other bags.\n";
         
-        let regs = BaggageRegulations::build(&mut input.lines().map(|s| s.to_owned()));
+        let regs = BaggageRegulations::build(&mut input.lines().map(|s| s.to_owned())).unwrap();
         let light_red_reg = get_regulation(&regs, "light", "red").unwrap();
         assert!(light_red_reg.is_contained_by.is_empty());
-        assert_eq!(*light_red_reg.must_contain.get(&Bag::new("bright", "white")).unwrap(), 1);
-        assert_eq!(*light_red_reg.must_contain.get(&Bag::new("muted", "yellow")).unwrap(), 2);
+        assert_eq!(*light_red_reg.must_contain.get(&id(&regs, "bright", "white")).unwrap(), 1);
+        assert_eq!(*light_red_reg.must_contain.get(&id(&regs, "muted", "yellow")).unwrap(), 2);
 
         let muted_yellow_reg = get_regulation(&regs, "muted", "yellow").unwrap();
-        assert!(muted_yellow_reg.is_contained_by.contains(&Bag::new("light", "red")));
-        assert!(muted_yellow_reg.is_contained_by.contains(&Bag::new("dark", "orange")));
-        assert_eq!(get_required_contents(&muted_yellow_reg, "shiny", "gold"), 2);
-        assert_eq!(get_required_contents(&muted_yellow_reg, "faded", "blue"), 9);
+        assert!(muted_yellow_reg.is_contained_by.contains(&id(&regs, "light", "red")));
+        assert!(muted_yellow_reg.is_contained_by.contains(&id(&regs, "dark", "orange")));
+        assert_eq!(get_required_contents(&regs, &muted_yellow_reg, "shiny", "gold"), 2);
+        assert_eq!(get_required_contents(&regs, &muted_yellow_reg, "faded", "blue"), 9);
 
-        let faded_blue_reg = regs.regulations.get(&Bag::new("faded", "blue")).unwrap();
-        assert!(faded_blue_reg.is_contained_by.contains(&Bag::new("vibrant", "plum")));
+        let faded_blue_reg = regs.regulations.get(&id(&regs, "faded", "blue")).unwrap();
+        assert!(faded_blue_reg.is_contained_by.contains(&id(&regs, "vibrant", "plum")));
         assert_eq!(faded_blue_reg.is_contained_by.len(), 3);
-        assert_eq!(get_required_contents(&faded_blue_reg, "dotted", "black"), 0);
-        assert_eq!(get_required_contents(&faded_blue_reg, "muted", "yellow"), 0);
+        assert_eq!(get_required_contents(&regs, &faded_blue_reg, "dotted", "black"), 0);
+        assert_eq!(get_required_contents(&regs, &faded_blue_reg, "muted", "yellow"), 0);
+
+        // each of the nine bags is named once
+        assert_eq!(regs.names.len(), 9);
     }
 
     #[test]
@@ -185,7 +349,7 @@ mod day07_spec {
         faded blue bags contain no other bags.\n\
         dotted black bags contain no other bags.\n";
         
-        let regs = BaggageRegulations::build(&mut input.lines().map(|s| s.to_owned()));
+        let regs = BaggageRegulations::build(&mut input.lines().map(|s| s.to_owned())).unwrap();
 
         let shiny_gold_containing_bags = regs.walk_out_from(&Bag::new("shiny", "gold"));
         assert_eq!(shiny_gold_containing_bags.len(), 4);
@@ -201,12 +365,130 @@ mod day07_spec {
         dark blue bags contain 2 dark violet bags.\n\
         dark violet bags contain no other bags.";
         
-        let regs = BaggageRegulations::build(&mut input.lines().map(|s| s.to_owned()));
+        let regs = BaggageRegulations::build(&mut input.lines().map(|s| s.to_owned())).unwrap();
 
         let tc = regs.transitive_contents(&Bag::new("shiny", "gold"));
-        assert_eq!(tc.get(&Bag::new("dark", "red")), Some(&2));
-        assert_eq!(tc.get(&Bag::new("dark", "orange")), Some(&4));
+        assert_eq!(tc.get(&id(&regs, "dark", "red")), Some(&2));
+        assert_eq!(tc.get(&id(&regs, "dark", "orange")), Some(&4));
         let tc_sum: usize = tc.values().sum();
         assert_eq!(tc_sum, 126);
     }
-}
\ No newline at end of file
+
+    fn sample_regulations() -> BaggageRegulations {
+        let input = "light red bags contain 1 bright white bag, 2 muted yellow bags.\n\
+        dark orange bags contain 3 bright white bags, 4 muted yellow bags.\n\
+        bright white bags contain 1 shiny gold bag.\n\
+        muted yellow bags contain 2 shiny gold bags, 9 faded blue bags.\n\
+        shiny gold bags contain 1 dark olive bag, 2 vibrant plum bags.\n\
+        dark olive bags contain 3 faded blue bags, 4 dotted black bags.\n\
+        vibrant plum bags contain 5 faded blue bags, 6 dotted black bags.\n\
+        faded blue bags contain no other bags.\n\
+        dotted black bags contain no other bags.\n";
+        BaggageRegulations::build(&mut input.lines().map(|s| s.to_owned())).unwrap()
+    }
+
+    #[test]
+    fn bags_containing_within_test() {
+        let regs = sample_regulations();
+        let shiny_gold = Bag::new("shiny", "gold");
+        assert!(regs.bags_containing_within(&shiny_gold, 0).is_empty());
+        assert_eq!(regs.bags_containing_within(&shiny_gold, 1).into_iter().map(|id| regs.resolve(id)).collect::<Vec<_>>(),
+            vec!(Bag::new("bright", "white"), Bag::new("muted", "yellow")));
+        assert_eq!(regs.bags_containing_within(&shiny_gold, 2).len(), 4);
+        assert_eq!(regs.bags_containing_within(&shiny_gold, 10), regs.walk_out_from(&shiny_gold));
+
+        // faded blue is directly inside muted yellow, so light red is only two levels out
+        let faded_blue = Bag::new("faded", "blue");
+        assert_eq!(regs.bags_containing_within(&faded_blue, 1).len(), 3);
+        assert!(regs.bags_containing_within(&faded_blue, 2).contains(&id(&regs, "light", "red")));
+        assert_eq!(regs.bags_containing_within(&faded_blue, 2).len(), 6);
+        assert_eq!(regs.bags_containing_within(&faded_blue, 3).len(), 7);
+    }
+
+    #[test]
+    fn contents_within_test() {
+        let regs = sample_regulations();
+        let shiny_gold = Bag::new("shiny", "gold");
+        assert!(regs.contents_within(&shiny_gold, 0).is_empty());
+        let within_one = regs.contents_within(&shiny_gold, 1);
+        assert_eq!(within_one.get(&id(&regs, "vibrant", "plum")), Some(&2));
+        assert_eq!(within_one.get(&id(&regs, "faded", "blue")), None);
+        assert_eq!(within_one.values().sum::<usize>(), 3);
+        assert_eq!(regs.contents_within(&shiny_gold, 2).values().sum::<usize>(), 32);
+        assert_eq!(regs.contents_within(&shiny_gold, 5), regs.transitive_contents(&shiny_gold));
+
+        // light red holds 18 faded blue bags two levels down (in its muted yellow bags), and the
+        // rest four levels down (13 for each of the 5 shiny gold bags it holds)
+        let light_red = Bag::new("light", "red");
+        let faded_blue = id(&regs, "faded", "blue");
+        assert_eq!(regs.contents_within(&light_red, 2).get(&faded_blue), Some(&18));
+        assert_eq!(regs.contents_within(&light_red, 3).get(&faded_blue), Some(&18));
+        assert_eq!(regs.contents_within(&light_red, 4).get(&faded_blue), Some(&(18 + 5 * 13)));
+    }
+
+    #[test]
+    fn max_depth_test() {
+        let regs = sample_regulations();
+        assert_eq!(regs.max_depth(&Bag::new("shiny", "gold")), 2);
+        assert_eq!(regs.max_depth(&Bag::new("light", "red")), 4);
+        assert_eq!(regs.max_depth(&Bag::new("muted", "yellow")), 3);
+        assert_eq!(regs.max_depth(&Bag::new("faded", "blue")), 0);
+        // never mentioned
+        assert_eq!(regs.max_depth(&Bag::new("plaid", "mauve")), 0);
+    }
+
+    #[test]
+    fn strict_parse_test() {
+        let input = "light red bags contain 1 bright white bag, 2 muted yellow bags.\n\
+        faded blue bags contain no other bags.\n\
+        \n\
+        muted yellow bags contain 2 shiny gold bags, lots of faded blue bags.\n";
+        let err = BaggageRegulations::build(&mut input.lines().map(|s| s.to_owned())).err().unwrap();
+        assert_eq!(err.line_no, 4);
+        assert_eq!(err.line, "muted yellow bags contain 2 shiny gold bags, lots of faded blue bags.");
+        assert_eq!(err.reason, "Unrecognized contents `lots of faded blue bags`");
+
+        let input = "light red bags contain 1 bright white bag.\n\
+        faded blue bags hold no other bags.";
+        let err = BaggageRegulations::build(&mut input.lines().map(|s| s.to_owned())).err().unwrap();
+        assert_eq!(err.line_no, 2);
+
+        let input = "light red bags contain no bags.";
+        assert!(BaggageRegulations::build(&mut input.lines().map(|s| s.to_owned())).is_err());
+    }
+
+    #[test]
+    fn contents_test() {
+        let input = "light red bags contain 1 bright white bag, 2 muted yellow bags.\n\
+        bright white bags contain no other bags.";
+        let regs = BaggageRegulations::build(&mut input.lines().map(|s| s.to_owned())).unwrap();
+
+        assert_eq!(regs.contents(&Bag::new("light", "red")).map(|c| c.len()), Some(2));
+        assert_eq!(regs.contents(&Bag::new("bright", "white")).map(|c| c.len()), Some(0));
+        assert!(regs.leaves.contains(&id(&regs, "bright", "white")));
+        // mentioned, but never described
+        assert_eq!(regs.contents(&Bag::new("muted", "yellow")), None);
+        // never mentioned
+        assert_eq!(regs.contents(&Bag::new("shiny", "gold")), None);
+    }
+
+    #[test]
+    fn interning_test() {
+        let regs = sample_regulations();
+        let shiny_gold = id(&regs, "shiny", "gold");
+        assert_eq!(regs.resolve(shiny_gold), Bag::new("shiny", "gold"));
+        assert_eq!(regs.resolve(shiny_gold).to_string(), "shiny gold");
+        assert_eq!(regs.id(&Bag::new("plaid", "mauve")), None);
+        // unknown bags hold nothing and are held by nothing
+        let plaid_mauve = Bag::new("plaid", "mauve");
+        assert!(regs.walk_out_from(&plaid_mauve).is_empty());
+        assert!(regs.transitive_contents(&plaid_mauve).is_empty());
+        assert!(regs.bags_containing_within(&plaid_mauve, 3).is_empty());
+        assert!(regs.contents_within(&plaid_mauve, 3).is_empty());
+
+        // a rejected line interns none of its names
+        let mut regs = BaggageRegulations::new();
+        assert!(regs.insert_line("light red bags contain 1 bright white bag, lots of muted yellow bags.").is_err());
+        assert!(regs.names.is_empty());
+    }
+}
@@ -1,17 +1,23 @@
 use std::io;
 use std::io::prelude::*;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
 
 #[macro_use]
 extern crate lazy_static;
 use regex::Regex;
 
 use advent::bitset::BitSet;
+use advent::cli;
+use advent::error::{AdventError, exit_on_error};
+use advent::input::numbered;
+use advent::timing;
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 enum Instruction {
-    Nop(i32),
-    Acc(i32),
-    Jmp(i32)
+    Nop(i64),
+    Acc(i64),
+    Jmp(i64)
 }
 
 impl Instruction {
@@ -21,49 +27,148 @@ impl Instruction {
         }
 
         INSTRUCTION_PAT.captures(line).and_then(|cap| {
-            i32::from_str_radix(&cap[2], 10).ok().and_then(|n| {
-                match &cap[1] {
-                    "nop" => Some(Instruction::Nop(n)),
-                    "acc" => Some(Instruction::Acc(n)),
-                    "jmp" => Some(Instruction::Jmp(n)),
-                    _ => None
-                }
-            })
+            cap[2].parse::<i64>().ok().and_then(|n| Instruction::new(&cap[1], n))
         })
     }
+
+    fn new(op: &str, n: i64) -> Option<Instruction> {
+        match op {
+            "nop" => Some(Instruction::Nop(n)),
+            "acc" => Some(Instruction::Acc(n)),
+            "jmp" => Some(Instruction::Jmp(n)),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum AssemblyError {
+    // `label` is defined on `line`, and already was on line `first`
+    DuplicateLabel { label: String, line: usize, first: usize },
+    // The operand on `line` names a label defined nowhere in the program
+    UndefinedLabel { label: String, line: usize }
+}
+
+impl Display for AssemblyError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            AssemblyError::DuplicateLabel { label, line, first } =>
+                write!(f, "label {} on line {} was already defined on line {}", label, line, first),
+            AssemblyError::UndefinedLabel { label, line } =>
+                write!(f, "undefined label {} on line {}", label, line)
+        }
+    }
+}
+
+// An instruction as read by the first pass of `assemble`, its operand perhaps still a label
+enum Statement {
+    Resolved(Instruction),
+    Symbolic { op: String, label: String }
+}
+
+// Reads a program in which any line may begin with a label (`loop: jmp loop`), and an operand may
+// name a label in place of an offset; it then stands for the offset to the instruction after the
+// label, or to the end of the program if there is none. A label may also stand alone on its line.
+// Lines which are not instructions are skipped, as in the puzzle format.
+fn assemble<J>(lines: J) -> Result<Vec<Instruction>, AssemblyError>
+where J: IntoIterator<Item=String> {
+    lazy_static! {
+        static ref LABEL_PAT: Regex = Regex::new(r"^\s*([A-Za-z_]\w*):").unwrap();
+        static ref SYMBOLIC_PAT: Regex = Regex::new(r"^\s*(\w{3})\s+([A-Za-z_]\w*)\s*$").unwrap();
+    }
+
+    // First pass: where each label points, and the instructions with any label operands unresolved
+    let mut labels: HashMap<String, (usize, usize)> = HashMap::new(); // label -> (instruction index, line)
+    let mut statements: Vec<(usize, Statement)> = vec!(); // (line, statement)
+    for (line_no, line) in numbered(lines) {
+        let mut rest = line.as_str();
+        if let Some(cap) = LABEL_PAT.captures(&line) {
+            let label = cap[1].to_owned();
+            if let Some((_, first)) = labels.get(&label) {
+                return Err(AssemblyError::DuplicateLabel { label, line: line_no, first: *first })
+            }
+            labels.insert(label, (statements.len(), line_no));
+            rest = &line[cap[0].len()..];
+        }
+        if let Some(instruction) = Instruction::parse(rest) {
+            statements.push((line_no, Statement::Resolved(instruction)));
+        } else if let Some(cap) = SYMBOLIC_PAT.captures(rest) {
+            if Instruction::new(&cap[1], 0).is_some() {
+                statements.push((line_no, Statement::Symbolic { op: cap[1].to_owned(), label: cap[2].to_owned() }));
+            }
+        }
+    }
+
+    // Second pass: labels become offsets
+    statements.into_iter().enumerate().map(|(idx, (line_no, statement))| {
+        match statement {
+            Statement::Resolved(instruction) => Ok(instruction),
+            Statement::Symbolic { op, label } => match labels.get(&label) {
+                Some((target, _)) => Ok(Instruction::new(&op, *target as i64 - idx as i64).expect("op checked in the first pass")),
+                None => Err(AssemblyError::UndefinedLabel { label, line: line_no })
+            }
+        }
+    }).collect()
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum RuntimeError {
+    // The `acc` at `ptr` would take the accumulator beyond the range of an i64
+    Overflow { ptr: usize, accumulator: i64, operand: i64 },
+    // There is no instruction at `ptr` to execute
+    OutOfBounds { ptr: usize }
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            RuntimeError::Overflow { ptr, accumulator, operand } =>
+                write!(f, "accumulator overflow at instruction {}: {} + {}", ptr, accumulator, operand),
+            RuntimeError::OutOfBounds { ptr } =>
+                write!(f, "attempt to execute instruction {}, outside the program", ptr)
+        }
+    }
 }
 
 #[derive(Clone)]
 struct HandheldGameConsole {
     instructions: Vec<Instruction>,
     instruction_ptr: usize,
-    accumulator: i32,
+    accumulator: i64,
 }
 
 impl HandheldGameConsole {
-    fn parse<J>(j: &mut J) -> HandheldGameConsole
+    fn parse<J>(j: &mut J) -> Result<HandheldGameConsole, AssemblyError>
     where J: Iterator<Item=String> {
-        let instructions = j.flat_map(|line| Instruction::parse(&line)).collect();
-        HandheldGameConsole {
+        let instructions = assemble(j)?;
+        Ok(HandheldGameConsole {
             instructions, instruction_ptr: 0, accumulator: 0
+        })
+    }
+
+    // Executes one instruction. On error, the console is left as it was.
+    fn step(&mut self) -> Result<(), RuntimeError> {
+        let ptr = self.instruction_ptr;
+        match self.instructions.get(ptr) {
+            None => return Err(RuntimeError::OutOfBounds { ptr }),
+            Some(Instruction::Acc(x)) => {
+                self.accumulator = self.accumulator.checked_add(*x)
+                    .ok_or(RuntimeError::Overflow { ptr, accumulator: self.accumulator, operand: *x })?;
+            },
+            Some(_) => ()
         }
+        self.instruction_ptr = successor(ptr, &self.instructions[ptr]);
+        Ok(())
     }
 
-    fn step(&mut self) {
-        if let Some(instruction) = self.instructions.get(self.instruction_ptr) {
-            match instruction {
-                Instruction::Nop(_) => self.instruction_ptr += 1,
-                Instruction::Acc(x) => {
-                    self.accumulator += x;
-                    self.instruction_ptr += 1;
-                },
-                Instruction::Jmp(x) => {
-                    self.instruction_ptr = wrapping_add(self.instruction_ptr, *x);
-                }
-            }
-        } else {
-            eprintln!("Attempt to access instruction out of bounds at {}!", self.instruction_ptr);
+    // Runs until the next instruction is one already executed, or lies outside the program
+    fn run(&mut self) -> Result<(), RuntimeError> {
+        let mut executed_instructions = BitSet::new(self.instructions.len());
+        while let Some(false) = executed_instructions.get(self.instruction_ptr) {
+            executed_instructions.set(self.instruction_ptr);
+            self.step()?;
         }
+        Ok(())
     }
 
     fn reset(&mut self) {
@@ -72,111 +177,166 @@ impl HandheldGameConsole {
     }
 }
 
-fn find_infinite_loop(console: &mut HandheldGameConsole) {
-    let mut executed_instructions = BitSet::new(console.instructions.len());
-    while let Some(false) = executed_instructions.get(console.instruction_ptr) {
-        executed_instructions.set(console.instruction_ptr);
-        console.step();
+// The index of the instruction executed after the one at `ptr`. The accumulator never affects it.
+fn successor(ptr: usize, instruction: &Instruction) -> usize {
+    match instruction {
+        Instruction::Jmp(x) => wrapping_add(ptr, *x),
+        _ => ptr + 1
     }
 }
 
-// Returns the index of an instruction which must be changed from NOP to JMP or vice versa
-fn fix_infinite_loop(console: &mut HandheldGameConsole) -> Option<(usize, i32)> {
+// Returns the index of an instruction which must be changed from NOP to JMP or vice versa, and the
+// final accumulator of the fixed program, whose running may fail.
+fn fix_infinite_loop(console: &mut HandheldGameConsole) -> Result<Option<(usize, i64)>, RuntimeError> {
     let mut executed_instructions = BitSet::new(console.instructions.len());
 
     enum ExitStatus {
         InfiniteLoop,
-        Zero(i32),
+        Zero,
         Nonzero(usize)
     }
 
-    fn attempt_fix(mut console: HandheldGameConsole, mut executed_instructions: BitSet) -> ExitStatus {
-        // first, switch the current instruction
-        match console.instructions.get_mut(console.instruction_ptr).map(|j| {
-            match j {
-                Instruction::Nop(x) => *j = Instruction::Jmp(*x),
-                Instruction::Jmp(x) => *j = Instruction::Nop(*x),
-                _ => ()
-            }
-        }) {
-            None => return ExitStatus::Nonzero(console.instruction_ptr),
-            _ => ()
-        };
-
-        // then run the modified instruction set
+    // Follows the program from `ptr` with the instruction there replaced by `fixed`. Only the
+    // instruction pointer is tracked; the fixed program is then run in earnest by run_patched.
+    fn attempt_fix(instructions: &[Instruction], ptr: usize, fixed: &Instruction, mut executed_instructions: BitSet) -> ExitStatus {
+        executed_instructions.set(ptr);
+        let mut ptr = successor(ptr, fixed);
         loop {
-            if console.instruction_ptr == console.instructions.len() {
-                return ExitStatus::Zero(console.accumulator)
-            } else if console.instruction_ptr > console.instructions.len() {
-                return ExitStatus::Nonzero(console.instruction_ptr)
-            } else if let Some(true) = executed_instructions.get(console.instruction_ptr) {
+            if ptr == instructions.len() {
+                return ExitStatus::Zero
+            } else if ptr > instructions.len() {
+                return ExitStatus::Nonzero(ptr)
+            } else if let Some(true) = executed_instructions.set(ptr) {
                 return ExitStatus::InfiniteLoop
             } else {
-                executed_instructions.set(console.instruction_ptr);
-                console.step();
+                ptr = successor(ptr, &instructions[ptr]);
             }
         }
     }
 
     loop {
-        match console.instructions.get(console.instruction_ptr) {
-            None => return None,
-            Some(Instruction::Acc(_)) => {
-                if let Some(true) = executed_instructions.set(console.instruction_ptr) {
-                    return None
-                }
-                console.step()
-            },
-            _ => {
-                match attempt_fix(console.clone(), executed_instructions.clone()) {
-                    ExitStatus::InfiniteLoop => {
-                        if let Some(true) = executed_instructions.set(console.instruction_ptr) {
-                            return None
-                        }
-                        console.step()
-                    },
-                    ExitStatus::Nonzero(_) => {
-                        if let Some(true) = executed_instructions.set(console.instruction_ptr) {
-                            return None
-                        }
-                        console.step()
-                    },
-                    ExitStatus::Zero(acc) => {
-                        return Some((console.instruction_ptr, acc))
-                    }
+        let ptr = console.instruction_ptr;
+        let instruction = match console.instructions.get(ptr) {
+            None => return Ok(None),
+            Some(instruction) => instruction.clone()
+        };
+        let fixed = match instruction {
+            Instruction::Nop(x) => Some(Instruction::Jmp(x)),
+            Instruction::Jmp(x) => Some(Instruction::Nop(x)),
+            Instruction::Acc(_) => None
+        };
+        if let Some(fixed) = fixed {
+            if let ExitStatus::Zero = attempt_fix(&console.instructions, ptr, &fixed, executed_instructions.clone()) {
+                return Ok(run_patched(console, ptr, fixed)?.map(|acc| (ptr, acc)))
+            }
+        }
+        if let Some(true) = executed_instructions.set(ptr) {
+            return Ok(None)
+        }
+        console.instruction_ptr = successor(ptr, &instruction);
+    }
+}
+
+// The same fix as fix_infinite_loop, found in linear time: first mark every instruction from which
+// the unmodified program runs off the end, by walking the control flow graph backwards from the
+// exit; then follow the looping execution until switching an instruction leads into that set.
+fn quick_fix(console: &mut HandheldGameConsole) -> Result<Option<(usize, i64)>, RuntimeError> {
+    let exit = console.instructions.len();
+    let switched = |instruction: &Instruction| match instruction {
+        Instruction::Nop(x) => Some(Instruction::Jmp(*x)),
+        Instruction::Jmp(x) => Some(Instruction::Nop(*x)),
+        Instruction::Acc(_) => None
+    };
+
+    let mut predecessors: Vec<Vec<usize>> = vec!(vec!(); exit + 1);
+    for (ptr, instruction) in console.instructions.iter().enumerate() {
+        let next = successor(ptr, instruction);
+        if next <= exit {
+            predecessors[next].push(ptr);
+        }
+    }
+    let mut can_exit = BitSet::new(exit + 1);
+    let mut to_visit = vec!(exit);
+    can_exit.set(exit);
+    while let Some(ptr) = to_visit.pop() {
+        for prev in &predecessors[ptr] {
+            if let Some(false) = can_exit.set(*prev) {
+                to_visit.push(*prev);
+            }
+        }
+    }
+
+    let mut executed_instructions = BitSet::new(exit);
+    let mut ptr = 0;
+    while let Some(false) = executed_instructions.set(ptr) {
+        let instruction = &console.instructions[ptr];
+        if let Some(fixed) = switched(instruction) {
+            if let Some(true) = can_exit.get(successor(ptr, &fixed)) {
+                // Should the unmodified program also exit, its path out may lead back here
+                if let Some(acc) = run_patched(console, ptr, fixed)? {
+                    return Ok(Some((ptr, acc)))
                 }
             }
         }
+        ptr = successor(ptr, instruction);
     }
+
+    Ok(None)
 }
 
-fn wrapping_add(lhs: usize, rhs: i32) -> usize {
+// The final accumulator, if the program exits after replacing the instruction at `ptr`
+fn run_patched(console: &HandheldGameConsole, ptr: usize, replacement: Instruction) -> Result<Option<i64>, RuntimeError> {
+    let mut patched = console.clone();
+    patched.reset();
+    patched.instructions[ptr] = replacement;
+    patched.run()?;
+    if patched.instruction_ptr == patched.instructions.len() {
+        Ok(Some(patched.accumulator))
+    } else {
+        Ok(None)
+    }
+}
+
+fn wrapping_add(lhs: usize, rhs: i64) -> usize {
     if rhs >= 0 {
-        lhs + (rhs as usize)
+        lhs.wrapping_add(rhs as usize)
     } else {
-        lhs.wrapping_sub(-rhs as usize)
+        lhs.wrapping_sub(rhs.unsigned_abs() as usize)
     }
 }
 
-fn main() {
+fn run() -> Result<(), AdventError> {
     let stdin = io::stdin();
-    let mut console = HandheldGameConsole::parse(&mut stdin.lock().lines().flatten());
-    find_infinite_loop(&mut console);
+    let mut console = HandheldGameConsole::parse(&mut stdin.lock().lines().flatten())
+        .map_err(|e| AdventError::input(e.to_string()))?;
+    let runtime_error = |e: RuntimeError| AdventError::no_solution(e.to_string());
+    timing::time("Part 1", || console.run()).map_err(runtime_error)?;
     println!("Entering infinite loop: accumulator = {}", console.accumulator);
 
     console.reset();
-    match fix_infinite_loop(&mut console) {
+    let fix = if cli::has_flag("--brute-force") {
+        timing::time("Part 2", || fix_infinite_loop(&mut console))
+    } else {
+        timing::time("Part 2", || quick_fix(&mut console))
+    }.map_err(runtime_error)?;
+    match fix {
         None => println!("No fix found."),
         Some((ptr, acc)) => println!(
             "Fix found: corrupted instruction at {}.\n\
              Output of fixed program: {}", ptr, acc
         )
     }
+    Ok(())
+}
+
+fn main() {
+    exit_on_error(run())
 }
 
 #[cfg(test)]
 mod day08_spec {
     use super::*;
+    use advent::random::Lcg;
 
     mod instruction {
         use super::*;
@@ -207,7 +367,7 @@ mod day08_spec {
             jmp -4\n\
             acc +6\n";
             
-            let console = HandheldGameConsole::parse(&mut input.lines().map(|s| s.to_owned()));
+            let console = HandheldGameConsole::parse(&mut input.lines().map(|s| s.to_owned())).unwrap();
             assert_eq!(console.accumulator, 0);
             assert_eq!(console.instruction_ptr, 0);
             assert_eq!(console.instructions, vec!(
@@ -222,6 +382,58 @@ mod day08_spec {
                 Instruction::Acc(6)
             ));
         }
+
+        #[test]
+        fn labels_test() {
+            let input = "nop +0\n\
+            top: acc +1\n\
+            jmp skip\n\
+            back: acc +3\n\
+            jmp top\n\
+            acc -99\n\
+            skip:\n\
+            acc +1\n\
+            jmp back\n\
+            acc +6\n\
+            nop end\n\
+            end:";
+
+            // the puzzle example, then an instruction pointing just past the end
+            let example = HandheldGameConsole::parse(&mut input.lines().map(|s| s.to_owned())).unwrap();
+            assert_eq!(example.instructions, vec!(
+                Instruction::Nop(0),
+                Instruction::Acc(1),
+                Instruction::Jmp(4),
+                Instruction::Acc(3),
+                Instruction::Jmp(-3),
+                Instruction::Acc(-99),
+                Instruction::Acc(1),
+                Instruction::Jmp(-4),
+                Instruction::Acc(6),
+                Instruction::Nop(1)
+            ));
+
+            // a label may point at the instruction using it; run stops on coming back to it
+            let mut countdown = console("acc +3\nloop: acc -1\njmp loop\njmp done\ndone:");
+            assert_eq!(countdown.instructions[2], Instruction::Jmp(-1));
+            assert_eq!(countdown.instructions[3], Instruction::Jmp(1));
+            countdown.run().unwrap();
+            assert_eq!((countdown.instruction_ptr, countdown.accumulator), (1, 2));
+        }
+
+        #[test]
+        fn label_errors_test() {
+            let parse = |program: &str| HandheldGameConsole::parse(&mut program.lines().map(|s| s.to_owned())).map(|c| c.instructions);
+
+            assert_eq!(parse("a: nop +0\nacc +1\na: jmp a").unwrap_err(),
+                AssemblyError::DuplicateLabel { label: "a".to_owned(), line: 3, first: 1 });
+            let undefined = parse("nop +0\n\njmp nowhere").unwrap_err();
+            assert_eq!(undefined, AssemblyError::UndefinedLabel { label: "nowhere".to_owned(), line: 3 });
+            assert_eq!(undefined.to_string(), "undefined label nowhere on line 3");
+
+            // an unknown op is skipped as in the puzzle format, with a label operand or without
+            assert_eq!(parse("xyz here\nhere: acc -2"), Ok(vec!(Instruction::Acc(-2))));
+        }
     }
 
     #[test]
@@ -241,10 +453,38 @@ mod day08_spec {
             )
         };
 
-        find_infinite_loop(&mut console);
+        console.run().unwrap();
         assert_eq!(console.accumulator, 5)
     }
 
+    fn console(program: &str) -> HandheldGameConsole {
+        HandheldGameConsole::parse(&mut program.lines().map(|s| s.to_owned())).unwrap()
+    }
+
+    #[test]
+    fn overflow_test() {
+        // beyond an i32, but well within an i64
+        let mut wide = console("acc +3000000000\nacc +3000000000\nacc -1\n");
+        wide.run().unwrap();
+        assert_eq!(wide.accumulator, 5_999_999_999);
+
+        let mut overflowing = console("acc +9223372036854775807\nnop +0\nacc +1\n");
+        assert_eq!(overflowing.run(), Err(RuntimeError::Overflow { ptr: 2, accumulator: i64::MAX, operand: 1 }));
+        assert_eq!((overflowing.instruction_ptr, overflowing.accumulator), (2, i64::MAX));
+        let mut underflowing = console("acc -9223372036854775808\nacc -1\n");
+        assert_eq!(underflowing.run(), Err(RuntimeError::Overflow { ptr: 1, accumulator: i64::MIN, operand: -1 }));
+
+        let mut past_end = console("jmp +2\n");
+        assert_eq!(past_end.step(), Ok(()));
+        assert_eq!(past_end.step(), Err(RuntimeError::OutOfBounds { ptr: 2 }));
+
+        // only the fixed program overflows: switching the first instruction lets it reach the two adds
+        let mut console = console("jmp +0\nacc +9223372036854775807\nacc +1\n");
+        let error = Err(RuntimeError::Overflow { ptr: 2, accumulator: i64::MAX, operand: 1 });
+        assert_eq!(fix_infinite_loop(&mut console.clone()), error);
+        assert_eq!(quick_fix(&mut console), error);
+    }
+
     #[test]
     fn fix_infinite_loop_test() {
         let mut console = HandheldGameConsole {
@@ -262,6 +502,33 @@ mod day08_spec {
             )
         };
 
-        assert_eq!(fix_infinite_loop(&mut console), Some((7,8)));
+        assert_eq!(fix_infinite_loop(&mut console), Ok(Some((7,8))));
+        console.reset();
+        assert_eq!(quick_fix(&mut console), Ok(Some((7,8))));
+    }
+
+    #[test]
+    fn quick_fix_agrees_with_brute_force() {
+        // Random programs, from a small linear congruential generator
+        let mut rng = Lcg::new(2020);
+        let mut fixes = 0;
+        for _ in 0..2000 {
+            let len = 1 + rng.below(30) as i64;
+            let instructions = (0..len).map(|_| {
+                let x = rng.below(2 * len as u64 + 1) as i64 - len;
+                match rng.below(3) {
+                    0 => Instruction::Nop(x),
+                    1 => Instruction::Acc(x),
+                    _ => Instruction::Jmp(x)
+                }
+            }).collect();
+            let mut console = HandheldGameConsole { instructions, instruction_ptr: 0, accumulator: 0 };
+            let brute_force = fix_infinite_loop(&mut console.clone());
+            assert_eq!(quick_fix(&mut console), brute_force, "{:?}", console.instructions);
+            if let Ok(Some(_)) = brute_force {
+                fixes += 1;
+            }
+        }
+        assert!(fixes > 100);
     }
 }
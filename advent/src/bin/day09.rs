@@ -1,15 +1,86 @@
 use std::io;
 use std::io::prelude::*;
+use std::collections::{HashMap, VecDeque};
+use std::ops::Range;
 
+use advent::cli;
+use advent::error::{AdventError, exit_on_error};
+use advent::timing;
+
+
+// Windows up to this size are cheaper to search pair by pair than to index
+const SMALL_WINDOW: usize = 8;
 
 // Finds the index of the first element of xs which cannot be decomposed as the sum of two different elements in the
 // previous `lookback`
 fn indecomposeable(xs: &Vec<u32>, lookback: usize) -> Option<usize> {
+    if lookback <= SMALL_WINDOW {
+        indecomposeable_naive(xs, lookback)
+    } else {
+        indecomposeable_indexed(xs, lookback)
+    }
+}
+
+// The sums of all pairs of elements in a sliding window, counted with multiplicity so that an
+// element can leave the window without disturbing the sums it shares with no one else.
+struct PairSums {
+    window: VecDeque<u32>,
+    sums: HashMap<u64, u32>
+}
+
+impl PairSums {
+    fn new() -> PairSums {
+        PairSums { window: VecDeque::new(), sums: HashMap::new() }
+    }
+
+    fn push(&mut self, x: u32) {
+        for y in &self.window {
+            *self.sums.entry(x as u64 + *y as u64).or_insert(0) += 1;
+        }
+        self.window.push_back(x);
+    }
+
+    fn pop(&mut self) -> Option<u32> {
+        let x = self.window.pop_front()?;
+        for y in &self.window {
+            let sum = x as u64 + *y as u64;
+            if let Some(count) = self.sums.get_mut(&sum) {
+                *count -= 1;
+                if *count == 0 {
+                    self.sums.remove(&sum);
+                }
+            }
+        }
+        Some(x)
+    }
+
+    fn contains(&self, target: u32) -> bool {
+        self.sums.contains_key(&(target as u64))
+    }
+}
+
+// As indecomposeable, updating the pair sums as the window slides rather than searching every
+// pair afresh: O(lookback) work per element instead of O(lookback²).
+fn indecomposeable_indexed(xs: &[u32], lookback: usize) -> Option<usize> {
+    let mut pair_sums = PairSums::new();
+    for (idx, x) in xs.iter().enumerate() {
+        if idx >= lookback {
+            if !pair_sums.contains(*x) {
+                return Some(idx)
+            }
+            pair_sums.pop();
+        }
+        pair_sums.push(*x);
+    }
+    None
+}
+
+fn indecomposeable_naive(xs: &[u32], lookback: usize) -> Option<usize> {
     fn can_decompose(summands: &[u32], target: &u32) -> bool {
         for idx0 in 0..summands.len() {
             let s0 = &summands[idx0];
             for s1 in &summands[idx0 + 1..] {
-                if (*s0 + *s1) == *target {
+                if (*s0 as u64 + *s1 as u64) == *target as u64 {
                     return true
                 }
             }
@@ -26,19 +97,23 @@ fn indecomposeable(xs: &Vec<u32>, lookback: usize) -> Option<usize> {
 }
 
 
-// decomposes `target` into a sum of consecutive elements of `summands` if possible
-fn decompose<'a>(summands: &'a [u32], target: u32) -> Option<&'a [u32]> {
-    'outer: for idx0 in 0..summands.len() {
-        for idx1 in idx0..summands.len() {
-            let consecutive_sum: u32 = summands[idx0..idx1].iter().sum();
-            if consecutive_sum == target {
-                return Some(&summands[idx0..idx1])
-            } else if consecutive_sum > target {
-                continue 'outer
-            }
+// The first run of at least two consecutive elements of xs summing to `target`, as a range of
+// indices. Since the elements are non-negative, a window whose sum is too large can only shrink
+// from the left.
+fn contiguous_sum(xs: &[u32], target: u32) -> Option<Range<usize>> {
+    let mut start = 0;
+    let mut sum: u64 = 0;
+    for (end, x) in xs.iter().enumerate() {
+        sum += *x as u64;
+        while sum > target as u64 {
+            sum -= xs[start] as u64;
+            start += 1;
+        }
+        if sum == target as u64 && end > start {
+            return Some(start..end + 1)
         }
     }
-    return None
+    None
 }
 
 fn min_max<'a>(slice: &'a [u32]) -> Option<(u32, u32)> {
@@ -52,28 +127,53 @@ fn min_max<'a>(slice: &'a [u32]) -> Option<(u32, u32)> {
     })
 }
 
-fn main() {
+#[derive(Debug, PartialEq, Eq)]
+struct XmasReport {
+    invalid_index: usize,
+    invalid_value: u32,
+    // the encryption weakness: a run of at least two values summing to the invalid one
+    weakness_range: Range<usize>,
+    weakness_min: u32,
+    weakness_max: u32
+}
+
+fn analyze(xs: &Vec<u32>, lookback: usize) -> Result<XmasReport, AdventError> {
+    let invalid_index = indecomposeable(xs, lookback)
+        .ok_or_else(|| AdventError::no_solution(format!("every value is a sum of two of its {} predecessors", lookback)))?;
+    let invalid_value = xs[invalid_index];
+    let weakness_range = contiguous_sum(xs, invalid_value)
+        .ok_or_else(|| AdventError::no_solution(format!("no contiguous run sums to {}", invalid_value)))?;
+    let (weakness_min, weakness_max) = min_max(&xs[weakness_range.clone()])
+        .ok_or_else(|| AdventError::no_solution("empty sum slice"))?;
+    Ok(XmasReport { invalid_index, invalid_value, weakness_range, weakness_min, weakness_max })
+}
+
+fn run() -> Result<(), AdventError> {
     let stdin = io::stdin();
     let input: Vec<u32> = stdin.lock().lines()
         .flatten()
         .flat_map(|line| u32::from_str_radix(&line, 10))
         .collect();
+    let preamble = match cli::option_value("--preamble") {
+        Some(s) => usize::from_str_radix(&s, 10).map_err(|_| AdventError::input(format!("expected a preamble length, got {}", s)))?,
+        None => 25
+    };
 
-    let idx0 = indecomposeable(&input, 25).unwrap();
-    let offending_value = input[idx0];
-    println!("Indecomposable XMAS value: {} at index {}.", offending_value, idx0);
+    let report = timing::time("Analysis", || analyze(&input, preamble))?;
+    println!("Indecomposable XMAS value: {} at index {}.", report.invalid_value, report.invalid_index);
+    println!("Sum slice: indices {}..{}, {:?}", report.weakness_range.start, report.weakness_range.end, &input[report.weakness_range.clone()]);
+    println!("Bounds of sum slice: {}, {}. Min/Max Sum: {}", report.weakness_min, report.weakness_max, report.weakness_min + report.weakness_max);
+    Ok(())
+}
 
-    let xs = decompose(&input[..idx0], offending_value)
-        .or_else(|| decompose(&input[idx0+1..], offending_value))
-        .unwrap();
-    println!("Sum slice: {:?}", xs);
-    let (x0, x1) = min_max(xs).unwrap();
-    println!("Bounds of sum slice: {}, {}. Min/Max Sum: {}", x0, x1, x0 + x1);
+fn main() {
+    exit_on_error(run())
 }
 
 #[cfg(test)]
 mod day09_spec {
     use super::*;
+    use advent::random::Lcg;
 
     #[test]
     fn indecompose_test() {
@@ -89,24 +189,85 @@ mod day09_spec {
             102, 117, 150, 182, 127,
             219, 299, 277, 309, 576);
         assert_eq!(indecomposeable(&input, 5), Some(14));
+        assert_eq!(indecomposeable_indexed(&input, 5), Some(14));
+    }
+
+    #[test]
+    fn pair_sums_test() {
+        let mut pair_sums = PairSums::new();
+        for x in vec!(1, 2, 3, 2) {
+            pair_sums.push(x);
+        }
+        assert!(pair_sums.contains(4));
+        assert!(!pair_sums.contains(6));
+        assert!(!pair_sums.contains(2));
+
+        // 4 = 1 + 3 = 2 + 2 survives the loss of 1
+        assert_eq!(pair_sums.pop(), Some(1));
+        assert!(pair_sums.contains(4));
+        assert_eq!(pair_sums.pop(), Some(2));
+        assert!(!pair_sums.contains(4));
+        assert!(pair_sums.contains(5));
+    }
+
+    #[test]
+    fn indexed_agrees_with_naive() {
+        // Random inputs, from a small linear congruential generator
+        let mut rng = Lcg::new(2020);
+        for lookback in 1..30 {
+            for _ in 0..20 {
+                let len = rng.below(200);
+                let xs: Vec<u32> = (0..len).map(|_| rng.below(50) as u32).collect();
+                assert_eq!(indecomposeable_indexed(&xs, lookback), indecomposeable_naive(&xs, lookback), "{} {:?}", lookback, xs);
+            }
+        }
+        let xs: Vec<u32> = vec!(u32::MAX, u32::MAX, 1, u32::MAX, 0);
+        assert_eq!(indecomposeable_indexed(&xs, 2), Some(2));
+        assert_eq!(indecomposeable_naive(&xs, 2), Some(2));
     }
 
     #[test]
-    fn decompose_test() {
+    fn contiguous_sum_test() {
         let input = vec!(1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11);
-        let xs = decompose(&input[..8], 9).unwrap();
-        assert_eq!(xs, [2,3,4]);
+        assert_eq!(contiguous_sum(&input[..8], 9), Some(1..4));
+        // a single element is not enough
+        assert_eq!(contiguous_sum(&input, 11), Some(4..6));
 
         let input = vec!(1, 3, 5, 7, 9);
-        assert_eq!(decompose(&input[..], 2), None);
+        assert_eq!(contiguous_sum(&input[..], 2), None);
+
+        // runs may end with the last element
+        assert_eq!(contiguous_sum(&[1, 3, 5, 7, 9], 21), Some(2..5));
+        assert_eq!(contiguous_sum(&[5, 0], 5), Some(0..2));
+        assert_eq!(contiguous_sum(&[0, 0], 0), Some(0..2));
+
+        let input = vec!(
+            35, 20, 15, 25, 47,
+            40, 62, 55, 65, 95,
+            102, 117, 150, 182, 127,
+            219, 299, 277, 309, 576
+        );
+        assert_eq!(contiguous_sum(&input[..14], 127), Some(2..6));
+    }
 
+    #[test]
+    fn analyze_test() {
         let input = vec!(
             35, 20, 15, 25, 47,
             40, 62, 55, 65, 95,
             102, 117, 150, 182, 127,
             219, 299, 277, 309, 576
         );
-        let xs = decompose(&input[..14], 127).unwrap();
-        assert_eq!(xs, [15, 25, 47, 40]);
+        assert_eq!(analyze(&input, 5).ok(), Some(XmasReport {
+            invalid_index: 14, invalid_value: 127, weakness_range: 2..6, weakness_min: 15, weakness_max: 47
+        }));
+
+        // the weakness may include the invalid value itself, split across the two slices searched before
+        let input = vec!(1, 2, 3, 0, 0);
+        let report = analyze(&input, 3).unwrap();
+        assert_eq!((report.invalid_index, report.invalid_value), (3, 0));
+        assert_eq!(report.weakness_range, 3..5);
+        assert!(analyze(&vec!(1, 2, 3, 5), 3).is_err());
+        assert!(analyze(&vec!(1, 2, 4, 9), 3).is_err());
     }
 }
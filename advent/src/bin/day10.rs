@@ -3,40 +3,65 @@ use std::io::prelude::*;
 
 use std::collections::BTreeMap;
 
+use advent::cli;
+use advent::counter::Counter;
+use advent::error::{AdventError, exit_on_error};
+use advent::timing;
+
 // for both count_differences and count_paths, xs must be pre-sorted
-fn count_differences(xs: &Vec<u16>) -> BTreeMap<u16, u64> {
-    let mut r = BTreeMap::new();
+fn count_differences(xs: &Vec<u16>) -> Counter<u16> {
+    xs.windows(2).map(|w| w[1] - w[0]).collect()
+}
 
-    for idx in 1..xs.len() {
-        let d = xs[idx] - xs[idx - 1];
-        *r.entry(d).or_insert(0) += 1;
+// The number of paths from each adapter to the sink (the last, and largest, of xs)
+fn path_counts(xs: &[u16]) -> BTreeMap<u16, u64> {
+    let mut counts: BTreeMap<u16, u64> = BTreeMap::new();
+    if let Some(&sink) = xs.last() {
+        counts.insert(sink, 1);
+        for &x in xs.iter().rev().skip_while(|&&x| x == sink) {
+            let paths = (x + 1..=x + 3).flat_map(|y| counts.get(&y)).sum();
+            counts.insert(x, paths);
+        }
     }
-    r
+    counts
 }
 
 fn count_paths(xs: &Vec<u16>) -> u64 {
-    // ps[x] represents the number of paths from x to the sink
-    let mut ps: BTreeMap<u16, u64> = BTreeMap::new();
-    ps.insert(xs[xs.len() - 1], 1);
+    *path_counts(xs).get(&0).unwrap_or(&0)
+}
 
-    fn get_or_zero(ps: &BTreeMap<u16, u64>, k: u16) -> u64 {
-        *ps.get(&k).unwrap_or(&0)
+// The nth (from 0) arrangement of adapters from the outlet to the sink, in lexicographic order,
+// found by skipping over whole blocks of arrangements counted by path_counts. The arrangements
+// number count_paths(xs); past that, there is none.
+fn nth_arrangement(xs: &[u16], mut n: u64) -> Option<Vec<u16>> {
+    let counts = path_counts(xs);
+    let sink = *xs.last()?;
+    if n >= *counts.get(&0)? {
+        return None
     }
 
-    for idx in (0..xs.len()).rev() {
-        let x = xs[idx];
-        if let None = ps.get(&x) {
-            let p1 = get_or_zero(&ps, x + 1);
-            let p2 = get_or_zero(&ps, x + 2);
-            let p3 = get_or_zero(&ps, x + 3);
-            ps.insert(x, p1 + p2 + p3);
+    let mut arrangement = vec!(0);
+    let mut x = 0;
+    while x != sink {
+        for y in x + 1..=x + 3 {
+            let paths = *counts.get(&y).unwrap_or(&0);
+            if n < paths {
+                x = y;
+                break
+            }
+            n -= paths;
         }
+        arrangement.push(x);
     }
+    Some(arrangement)
+}
 
-    get_or_zero(&ps, 0)
+// The first arrangement, which uses every adapter if that is possible at all; empty if there is none
+fn build_chain(xs: &[u16]) -> Vec<u16> {
+    nth_arrangement(xs, 0).unwrap_or_default()
 }
 
-fn main() {
+fn run() -> Result<(), AdventError> {
     let stdin = io::stdin();
     let jolts = {
         let mut m = 0;
@@ -55,11 +80,28 @@ fn main() {
         jolts
     };
 
-    let diffs = count_differences(&jolts);
-    println!("Challenge 1: {} * {} = {}", diffs[&1], diffs[&3], diffs[&1] * (diffs[&3]));
+    let diffs = timing::time("Part 1", || count_differences(&jolts));
+    let (ones, threes) = (diffs.get(&1), diffs.get(&3));
+    println!("Challenge 1: {} * {} = {}", ones, threes, ones * threes);
 
-    let path_count = count_paths(&jolts);
+    let path_count = timing::time("Part 2", || count_paths(&jolts));
     println!("There are {} paths.", path_count);
+
+    let spell_out = |arrangement: &[u16]| arrangement.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(" ");
+    if cli::has_flag("--chain") {
+        println!("Chain: {}", spell_out(&build_chain(&jolts)));
+    }
+    if let Some(n) = cli::option_value("--arrangement") {
+        let n: u64 = n.parse().map_err(|_| AdventError::input(format!("expected an arrangement index, got {}", n)))?;
+        let arrangement = nth_arrangement(&jolts, n)
+            .ok_or_else(|| AdventError::no_solution(format!("there are only {} arrangements", path_count)))?;
+        println!("Arrangement #{}: {}", n, spell_out(&arrangement));
+    }
+    Ok(())
+}
+
+fn main() {
+    exit_on_error(run())
 }
 
 #[cfg(test)]
@@ -69,7 +111,7 @@ mod day10_spec {
     #[test]
     fn count_differences_test() {
         let mut jolts = vec!(0, 1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19, 22);
-        let mut expected: BTreeMap<u16, u64> = vec!((1,7), (3,5)).iter().map(|p| *p).collect();
+        let mut expected: Counter<u16> = [1; 7].iter().chain([3; 5].iter()).cloned().collect();
         assert_eq!(count_differences(&jolts), expected);
 
         jolts = vec!();
@@ -84,7 +126,7 @@ mod day10_spec {
         jolts.push(42);
         jolts.extend(45..50);
         jolts.push(52);
-        expected = vec!((1, 22), (3, 10)).iter().map(|p| *p).collect();
+        expected = [1; 22].iter().chain([3; 10].iter()).cloned().collect();
         assert_eq!(count_differences(&jolts), expected);
     }
 
@@ -107,4 +149,55 @@ mod day10_spec {
         jolts.push(52);
         assert_eq!(count_paths(&jolts), 19208);
     }
+    // Every arrangement, by brute force
+    fn arrangements(xs: &[u16]) -> Vec<Vec<u16>> {
+        fn extend(chain: &mut Vec<u16>, xs: &[u16], all: &mut Vec<Vec<u16>>) {
+            let x = *chain.last().unwrap();
+            if x == *xs.last().unwrap() {
+                all.push(chain.clone());
+            }
+            for &y in xs.iter().filter(|&&y| x < y && y <= x + 3) {
+                chain.push(y);
+                extend(chain, xs, all);
+                chain.pop();
+            }
+        }
+        let mut all = vec!();
+        extend(&mut vec!(0), xs, &mut all);
+        all
+    }
+
+    #[test]
+    fn nth_arrangement_test() {
+        let jolts = vec!(0, 1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19, 22);
+        let expected = arrangements(&jolts);
+        assert_eq!(expected.len(), 8);
+        for (n, arrangement) in expected.iter().enumerate() {
+            assert_eq!(nth_arrangement(&jolts, n as u64).as_ref(), Some(arrangement), "n = {}", n);
+        }
+        assert_eq!(nth_arrangement(&jolts, 8), None);
+        assert_eq!(nth_arrangement(&jolts, 7), Some(vec!(0, 1, 4, 7, 10, 12, 15, 16, 19, 22)));
+
+        let jolts = vec!(0, 2, 3, 4, 6, 9);
+        assert_eq!(nth_arrangement(&jolts, 0), Some(vec!(0, 2, 3, 4, 6, 9)));
+        assert_eq!(nth_arrangement(&jolts, 4), Some(vec!(0, 3, 6, 9)));
+        assert_eq!(nth_arrangement(&jolts, 5), None);
+        assert_eq!(count_paths(&jolts), arrangements(&jolts).len() as u64);
+    }
+
+    #[test]
+    fn build_chain_test() {
+        let jolts = vec!(0, 1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19, 22);
+        assert_eq!(build_chain(&jolts), jolts);
+
+        // 8 is out of reach of every adapter, so no arrangement uses it
+        let jolts = vec!(0, 1, 2, 4, 8, 9);
+        assert_eq!(build_chain(&jolts), vec!());
+        assert_eq!(count_paths(&jolts), 0);
+
+        // 2 and 3 can be skipped, but 4 cannot
+        let jolts = vec!(0, 2, 3, 4, 7);
+        assert_eq!(build_chain(&jolts), jolts);
+        assert!(build_chain(&[0, 1, 5]).is_empty());
+    }
 }
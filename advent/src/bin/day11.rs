@@ -1,30 +1,135 @@
 use std::io::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::DefaultHasher;
+use std::fmt::{Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
 
-use itertools::Itertools;
+use advent::cli;
+use advent::direction::Dir8;
+use advent::error::{AdventError, exit_on_error};
+use advent::table::Table;
+use advent::timing;
 
-#[macro_use]
-extern crate lazy_static;
 
-
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
 enum SeatState {
     Floor,
     Empty,
     Occupied
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+// Two diagrams are equal, and hash alike, when their seats are; the cache is only an accelerator.
+#[derive(Debug, Clone)]
 struct SeatingDiagram {
     width: usize,
-    seats: Vec<Vec<SeatState>>
+    seats: Vec<Vec<SeatState>>,
+    cache: Option<Rc<NeighborCache>>
+}
+
+impl PartialEq for SeatingDiagram {
+    fn eq(&self, other: &SeatingDiagram) -> bool {
+        self.width == other.width && self.seats == other.seats
+    }
+}
+
+impl Eq for SeatingDiagram {}
+
+impl Hash for SeatingDiagram {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.width.hash(state);
+        self.seats.hash(state);
+    }
+}
+
+// The seats in the neighborhood of each cell under both rules, in row-major order. Floor never
+// changes, so these depend only on the layout, and every diagram stepped from the same input can
+// share them.
+#[derive(Debug, PartialEq, Eq)]
+struct NeighborCache {
+    width: usize,
+    adjacent: Vec<Vec<(usize, usize)>>,
+    visible: Vec<Vec<(usize, usize)>>
+}
+
+impl NeighborCache {
+    fn new(diagram: &SeatingDiagram) -> NeighborCache {
+        let mut adjacent = vec!();
+        let mut visible = vec!();
+        for row in 0..diagram.seats.len() {
+            for col in 0..diagram.width {
+                if diagram.seats[row][col] == SeatState::Floor {
+                    // floor has no occupancy to update, so its neighbors are never asked for
+                    adjacent.push(vec!());
+                    visible.push(vec!());
+                } else {
+                    adjacent.push(diagram.adjacent_seats(row, col));
+                    visible.push(diagram.visible_seats(row, col));
+                }
+            }
+        }
+        NeighborCache { width: diagram.width, adjacent, visible }
+    }
+
+    fn neighbors(&self, neighborhood: Neighborhood, row: usize, column: usize) -> &[(usize, usize)] {
+        let cells = match neighborhood {
+            Neighborhood::Adjacent => &self.adjacent,
+            Neighborhood::Visible => &self.visible
+        };
+        &cells[row * self.width + column]
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Neighborhood {
+    Adjacent, // the eight surrounding cells
+    Visible // the first seat seen in each of the eight directions
+}
+
+// An empty seat becomes occupied if it has no occupied neighbors;
+// an occupied seat is vacated if it has at least `tolerance` occupied neighbors.
+#[derive(Debug, Clone, Copy)]
+struct SeatingRule {
+    neighborhood: Neighborhood,
+    tolerance: u8
+}
+
+impl SeatingRule {
+    const ADJACENT: SeatingRule = SeatingRule { neighborhood: Neighborhood::Adjacent, tolerance: 4 };
+    const VISIBLE: SeatingRule = SeatingRule { neighborhood: Neighborhood::Visible, tolerance: 5 };
+
+    fn parse(name: &str) -> Option<SeatingRule> {
+        match name {
+            "adjacent" => Some(SeatingRule::ADJACENT),
+            "visible" => Some(SeatingRule::VISIBLE),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Equilibrium {
+    FixedPoint { iterations: usize },
+    // the diagram after `start` steps recurs every `period` steps thereafter
+    Cycle { start: usize, period: usize }
+}
+
+// One step of run_to_equilibrium: the number of seats occupied after it, and the number that changed
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct StepStats {
+    step: usize,
+    occupied: usize,
+    changes: usize
 }
 
 impl SeatingDiagram {
+    // Reads rows of '.', 'L' and '#', as written by Display; blank lines are skipped. Fails on
+    // any other character, or if the rows are not all the same width.
     fn build<J>(j: &mut J) -> Option<SeatingDiagram>
     where J: Iterator<Item=String> {
 
-        fn parse_line(line: &str) -> Vec<SeatState> {
-            line.chars().flat_map(|c| {
+        fn parse_line(line: &str) -> Option<Vec<SeatState>> {
+            line.trim_end().chars().map(|c| {
                 match c {
                     '.' => Some(SeatState::Floor),
                     'L' => Some(SeatState::Empty),
@@ -34,20 +139,27 @@ impl SeatingDiagram {
             }).collect()
         }
 
-        let mut diagram: SeatingDiagram;
-        if let Some(line) = j.next() {
-            diagram = SeatingDiagram { width: line.len(), seats: vec!(parse_line(&line))};
-        } else {
-            return None
-        }
+        let mut rows = j.filter(|line| !line.trim().is_empty());
+        let first_row = parse_line(&rows.next()?)?;
+        let mut diagram = SeatingDiagram { width: first_row.len(), seats: vec!(first_row), cache: None };
 
-        while let Some(line) = j.next() {
-            diagram.seats.push(parse_line(&line));
+        for line in rows {
+            let row = parse_line(&line)?;
+            if row.len() != diagram.width {
+                return None
+            }
+            diagram.seats.push(row);
         }
 
         Some(diagram)
     }
 
+    // Precomputes both neighborhoods of every seat, for this diagram and all its clones
+    fn with_cache(mut self) -> SeatingDiagram {
+        self.cache = Some(Rc::new(NeighborCache::new(&self)));
+        self
+    }
+
     fn count_occupied_seats(&self) -> usize {
         let mut r = 0;
 
@@ -61,91 +173,50 @@ impl SeatingDiagram {
     }
 
     fn count_adjacent_occupied_seats(&self, row: usize, column: usize) -> u8 {
-        let mut ret = 0;
-
-        fn checked_add(x: usize, dx: i8) -> Option<usize> {
-            if dx < 0 {
-                x.checked_sub(-dx as usize)
-            } else {
-                x.checked_add(dx as usize)
-            }
-        }
-
-        for dr in -1..=1 {
-        for dc in -1..=1 {
-            if (dr, dc) != (0, 0) {
-                let opt_r1 = checked_add(row, dr).filter(|&r1| r1 < self.seats.len());
-                let opt_c1 = checked_add(column, dc).filter(|&c1| c1 < self.width);
-                for r1 in opt_r1 {
-                for c1 in opt_c1 {
-                    if let SeatState::Occupied = self.seats[r1][c1] {
-                        ret += 1;
-                    }
-                }}
-            }
-        }}
+        Dir8::ALL.iter()
+            .flat_map(|dir| self.step_direction((row, column), *dir))
+            .filter(|(r1, c1)| self.seats[*r1][*c1] == SeatState::Occupied)
+            .count() as u8
+    }
 
-        ret
+    // The cell one step from `rc` in direction `dir`, if it is on the diagram
+    fn step_direction(&self, rc: (usize, usize), dir: Dir8) -> Option<(usize, usize)> {
+        let (row, col) = rc;
+        dir.step((col, row)).map(|(c1, r1)| (r1, c1))
+            .filter(|(r1, c1)| r1 < &self.seats.len() && c1 < &self.width)
     }
 
-    fn step(&mut self) -> usize {
-        let mut newly_occupied: Vec<(usize, usize)> = vec!();
-        let mut newly_empty: Vec<(usize, usize)> = vec!();
+    // The seats among the eight cells surrounding (row, column), in row-major order
+    fn adjacent_seats(&self, row: usize, column: usize) -> Vec<(usize, usize)> {
+        let mut seats: Vec<(usize, usize)> = Dir8::ALL.iter()
+            .flat_map(|dir| self.step_direction((row, column), *dir))
+            .filter(|(r1, c1)| self.seats[*r1][*c1] != SeatState::Floor)
+            .collect();
+        seats.sort_unstable();
+        seats
+    }
 
-        for row in 0..self.seats.len() {
-        for col in 0..self.width {
-            match self.seats[row][col] {
-                SeatState::Empty => {
-                    if self.count_adjacent_occupied_seats(row, col) == 0 {
-                        newly_occupied.push((row, col));
-                    }
-                },
-                SeatState::Occupied => {
-                    if self.count_adjacent_occupied_seats(row, col) >= 4 {
-                        newly_empty.push((row, col))
-                    }
-                },
-                _ => ()
+    // The first seat seen in each direction from (row, column), where there is one, in row-major order
+    fn visible_seats(&self, row: usize, column: usize) -> Vec<(usize, usize)> {
+        let mut seats: Vec<(usize, usize)> = Dir8::ALL.iter().flat_map(|dir| {
+            let mut xy = self.step_direction((row, column), *dir);
+            while let Some((x, y)) = xy {
+                if self.seats[x][y] != SeatState::Floor {
+                    break
+                }
+                xy = self.step_direction((x, y), *dir);
             }
-        }}
-
-        for (row, col) in &newly_occupied {
-            self.seats[*row][*col] = SeatState::Occupied;
-        }
-
-        for (row, col) in &newly_empty {
-            self.seats[*row][*col] = SeatState::Empty;
-        }
-
-        newly_occupied.len() + newly_empty.len()
+            xy
+        }).collect();
+        seats.sort_unstable();
+        seats
     }
 
     fn count_visible_occupied_seats(&self, row: usize, column: usize) -> u8 {
-        lazy_static! {
-            static ref DIRECTIONS: Vec<(i8, i8)> = (-1..=1).cartesian_product(-1..=1).filter(|p| *p != (0, 0)).collect();
-        }
-
-        fn step_direction(this: &SeatingDiagram, rc: (usize, usize), v: &(i8, i8)) -> Option<(usize, usize)> {
-            let (row, col) = rc;
-            let (dr, dc) = *v;
-            let opt_r1 = if dr < 0 {
-                row.checked_sub(-dr as usize)
-            } else {
-                row.checked_add(dr as usize)
-            };
-            let opt_c1 = if dc < 0 {
-                col.checked_sub(-dc as usize)
-            } else {
-                col.checked_add(dc as usize)
-            };
-            opt_r1.and_then(|r1| opt_c1.map(|c1| (r1, c1)))
-                .filter(|(r1, c1)| r1 < &this.seats.len() && c1 < &this.width)
-        }
-
         let mut r = 0;
 
-        'vector: for v in DIRECTIONS.iter() {
-            let mut xy = step_direction(self, (row, column), v);
+        'vector: for dir in Dir8::ALL.iter() {
+            let mut xy = self.step_direction((row, column), *dir);
             while let Some((x,y)) = xy {
                 match self.seats[x][y] {
                     SeatState::Occupied => {
@@ -157,30 +228,30 @@ impl SeatingDiagram {
                     },
                     _ => ()
                 };
-                xy = step_direction(self, (x,y), v);
+                xy = self.step_direction((x,y), *dir);
             }
         }
 
         r
     }
 
-    fn step_visible(&mut self) -> usize {
+    fn step_with(&mut self, rule: &SeatingRule) -> usize {
         let mut newly_occupied: Vec<(usize, usize)> = vec!();
         let mut newly_empty: Vec<(usize, usize)> = vec!();
 
+        let count = |this: &SeatingDiagram, row, col| match (&this.cache, rule.neighborhood) {
+            (Some(cache), neighborhood) => cache.neighbors(neighborhood, row, col).iter()
+                .filter(|(r1, c1)| this.seats[*r1][*c1] == SeatState::Occupied)
+                .count() as u8,
+            (None, Neighborhood::Adjacent) => this.count_adjacent_occupied_seats(row, col),
+            (None, Neighborhood::Visible) => this.count_visible_occupied_seats(row, col)
+        };
+
         for row in 0..self.seats.len() {
         for col in 0..self.width {
             match self.seats[row][col] {
-                SeatState::Empty => {
-                    if self.count_visible_occupied_seats(row, col) == 0 {
-                        newly_occupied.push((row, col));
-                    }
-                },
-                SeatState::Occupied => {
-                    if self.count_visible_occupied_seats(row, col) >= 5 {
-                        newly_empty.push((row, col))
-                    }
-                },
+                SeatState::Empty if count(self, row, col) == 0 => newly_occupied.push((row, col)),
+                SeatState::Occupied if count(self, row, col) >= rule.tolerance => newly_empty.push((row, col)),
                 _ => ()
             }
         }}
@@ -195,23 +266,193 @@ impl SeatingDiagram {
 
         newly_occupied.len() + newly_empty.len()
     }
+
+    fn fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    // Steps until the diagram stops changing or revisits an earlier state, recording every step
+    // taken; at a fixed point, the last step is the one which changed nothing.
+    fn run_to_equilibrium(&mut self, rule: &SeatingRule) -> (Equilibrium, Vec<StepStats>) {
+        let mut seen: HashMap<u64, usize> = HashMap::new();
+        let mut stats = vec!();
+        let mut iterations = 0;
+        seen.insert(self.fingerprint(), iterations);
+
+        loop {
+            let changes = self.step_with(rule);
+            stats.push(StepStats { step: stats.len() + 1, occupied: self.count_occupied_seats(), changes });
+            if changes == 0 {
+                break
+            }
+            iterations += 1;
+            if let Some(start) = seen.insert(self.fingerprint(), iterations) {
+                return (Equilibrium::Cycle { start, period: iterations - start }, stats)
+            }
+        }
+
+        (Equilibrium::FixedPoint { iterations }, stats)
+    }
+
+    // The cells (row, column, from, to) whose state differs between `self` and `other`
+    fn diff(&self, other: &SeatingDiagram) -> Vec<(usize, usize, SeatState, SeatState)> {
+        let mut r = vec!();
+        for (row, (xs, ys)) in self.seats.iter().zip(other.seats.iter()).enumerate() {
+            for (col, (x, y)) in xs.iter().zip(ys.iter()).enumerate() {
+                if x != y {
+                    r.push((row, col, *x, *y));
+                }
+            }
+        }
+        r
+    }
+
+    // Displays `self`, with the cells that differ from `previous` shown in reverse video
+    fn highlight_changes<'a>(&'a self, previous: &SeatingDiagram) -> HighlightedDiagram<'a> {
+        let changed = previous.diff(self).into_iter().map(|(row, col, _, _)| (row, col)).collect();
+        HighlightedDiagram { diagram: self, changed }
+    }
 }
 
-fn main() {
+impl SeatState {
+    fn symbol(&self) -> char {
+        match self {
+            SeatState::Floor => '.',
+            SeatState::Empty => 'L',
+            SeatState::Occupied => '#'
+        }
+    }
+}
+
+impl Display for SeatingDiagram {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        for row in &self.seats {
+            let line: String = row.iter().map(|seat| seat.symbol()).collect();
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+struct HighlightedDiagram<'a> {
+    diagram: &'a SeatingDiagram,
+    changed: HashSet<(usize, usize)>
+}
+
+impl Display for HighlightedDiagram<'_> {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        for (row, seats) in self.diagram.seats.iter().enumerate() {
+            let mut line = String::new();
+            for (col, seat) in seats.iter().enumerate() {
+                if self.changed.contains(&(row, col)) {
+                    line.push_str(&format!("\x1b[7m{}\x1b[0m", seat.symbol()));
+                } else {
+                    line.push(seat.symbol());
+                }
+            }
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+// Animates the diagram in the terminal until it stops changing (or until `max_steps` steps have been shown)
+fn watch(diagram: &SeatingDiagram, rule: &SeatingRule, max_steps: usize) {
+    let mut current = diagram.clone();
+    print!("\x1b[2J\x1b[H{}", current);
+    for step in 1..=max_steps {
+        let previous = current.clone();
+        if current.step_with(rule) == 0 {
+            break
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        print!("\x1b[2J\x1b[HStep {}\n{}", step, current.highlight_changes(&previous));
+    }
+}
+
+fn stats_table(stats: &[StepStats]) -> Table {
+    let mut table = Table::new(&["step", "occupied", "changes"]);
+    for step in stats {
+        table.add_row(vec!(step.step.to_string(), step.occupied.to_string(), step.changes.to_string()));
+    }
+    table
+}
+
+// Reports the equilibrium, preceded by the per-step series if --stats is given
+fn report((equilibrium, stats): (Equilibrium, Vec<StepStats>)) {
+    if cli::has_flag("--stats") {
+        print!("{}", stats_table(&stats));
+    }
+    match equilibrium {
+        Equilibrium::FixedPoint { iterations } => println!("Equilibrium reached after {} steps.", iterations),
+        Equilibrium::Cycle { start, period } => println!("No equilibrium: seating cycles with period {} from step {}.", period, start)
+    }
+}
+
+// Continues stepping a diagram saved with --save, under the given rule
+fn resume(path: &str, rule: &SeatingRule) -> Result<(), AdventError> {
+    let text = std::fs::read_to_string(path)?;
+    let mut seating_diagram = SeatingDiagram::build(&mut text.lines().map(|line| line.to_owned()))
+        .map(SeatingDiagram::with_cache)
+        .ok_or_else(|| AdventError::input(format!("{} does not hold a seating diagram", path)))?;
+    println!("Resuming from {}: {} seats are occupied.", path, seating_diagram.count_occupied_seats());
+
+    report(timing::time("Resumed", || seating_diagram.run_to_equilibrium(rule)));
+    println!("{} seats are occupied", seating_diagram.count_occupied_seats());
+    Ok(())
+}
+
+fn run() -> Result<(), AdventError> {
+    let rule = match cli::option_value("--rule") {
+        Some(name) => Some(SeatingRule::parse(&name).ok_or_else(|| AdventError::input(format!("unknown seating rule {}", name)))?),
+        None => None
+    };
+    if let Some(path) = cli::option_value("--resume") {
+        return resume(&path, &rule.unwrap_or(SeatingRule::ADJACENT))
+    }
+
     let stdin = std::io::stdin();
-    let mut seating_diagram = SeatingDiagram::build(&mut stdin.lock().lines().flatten()).unwrap();
+    // part 1 runs on a clone, which shares the cache with the original used for part 2
+    let mut seating_diagram = SeatingDiagram::build(&mut stdin.lock().lines().flatten())
+        .map(SeatingDiagram::with_cache)
+        .ok_or_else(|| AdventError::input("seating diagram rows must be non-empty and of equal width"))?;
 
     println!("Parsed seating diagram of width {} and {} rows.", seating_diagram.width, seating_diagram.seats.len());
 
+    if cli::has_flag("--watch") {
+        let rule = if cli::has_flag("--visible") { SeatingRule::VISIBLE } else { rule.unwrap_or(SeatingRule::ADJACENT) };
+        watch(&seating_diagram, &rule, 1000);
+    }
+
+    if let Some(path) = cli::option_value("--save") {
+        let steps = match cli::option_value("--steps") {
+            Some(s) => usize::from_str_radix(&s, 10).map_err(|_| AdventError::input(format!("expected a number of steps for --steps, got {}", s)))?,
+            None => 1
+        };
+        let mut saved = seating_diagram.clone();
+        for _ in 0..steps {
+            saved.step_with(&rule.unwrap_or(SeatingRule::ADJACENT));
+        }
+        std::fs::write(&path, saved.to_string())?;
+        println!("Saved the diagram after {} steps to {}", steps, path);
+    }
+
     let mut seating_diagram_part1 = seating_diagram.clone();
-    while seating_diagram_part1.step() != 0 {};
+    report(timing::time("Part 1", || seating_diagram_part1.run_to_equilibrium(&SeatingRule::ADJACENT)));
 
     let occupied_count = seating_diagram_part1.count_occupied_seats();
     println!("Part 1: {} seats are occupied", occupied_count);
 
-    while seating_diagram.step_visible() != 0 {};
+    report(timing::time("Part 2", || seating_diagram.run_to_equilibrium(&SeatingRule::VISIBLE)));
     let occupied_count = seating_diagram.count_occupied_seats();
     println!("Part 2: {} seats are occupied", occupied_count);
+    Ok(())
+}
+
+fn main() {
+    exit_on_error(run())
 }
 
 #[cfg(test)]
@@ -257,6 +498,37 @@ mod day11_spec {
         assert_eq!(seating_diagram.seats[9][9], SeatState::Occupied);
     }
 
+    #[test]
+    fn build_invalid_test() {
+        assert_eq!(build_from_str(""), None);
+        assert_eq!(build_from_str("L.L\nL.\n"), None);
+        assert_eq!(build_from_str("L.L\nLxL\n"), None);
+        assert!(build_from_str("L.L\n#.L\n\n").is_some());
+    }
+
+    #[test]
+    fn round_trip_test() {
+        let mut seating_diagram = build_from_str("L.LL.LL.LL\nLLLLLLL.LL\nL.L.L..L..\nLLLL.LL.LL").unwrap();
+        for _ in 0..3 {
+            let text = seating_diagram.to_string();
+            assert_eq!(build_from_str(&text).as_ref(), Some(&seating_diagram), "{}", text);
+            seating_diagram.step_with(&SeatingRule::VISIBLE);
+        }
+    }
+
+    #[test]
+    fn resumed_stepping_test() {
+        // stepping a saved diagram under either rule is the same as stepping the original
+        for rule in [SeatingRule::ADJACENT, SeatingRule::VISIBLE].iter() {
+            let mut original = build_from_str("L.LL.LL.LL\nLLLLLLL.LL\nL.L.L..L..\nLLLL.LL.LL\nL.LL.LL.LL").unwrap();
+            original.step_with(rule);
+            original.step_with(rule);
+            let mut resumed = build_from_str(&original.to_string()).unwrap();
+            assert_eq!(resumed.run_to_equilibrium(rule), original.run_to_equilibrium(rule));
+            assert_eq!(resumed, original);
+        }
+    }
+
     #[test]
     fn step_test() {
         let pre = "L.LL.LL.LL\n\
@@ -282,7 +554,7 @@ mod day11_spec {
                      #.######.#\n\
                      #.#####.##";
         let seating_diagram_post1 = build_from_str(post1).unwrap();
-        assert_eq!(seating_diagram.step(), 71);
+        assert_eq!(seating_diagram.step_with(&SeatingRule::ADJACENT), 71);
         assert_eq!(seating_diagram, seating_diagram_post1);
 
         let post2 = "#.LL.L#.##\n\
@@ -296,7 +568,7 @@ mod day11_spec {
                      #.LLLLLL.L\n\
                      #.#LLLL.##";
         let seating_diagram_post2 = build_from_str(post2).unwrap();
-        assert_eq!(seating_diagram.step(), 51);
+        assert_eq!(seating_diagram.step_with(&SeatingRule::ADJACENT), 51);
         assert_eq!(seating_diagram, seating_diagram_post2);
 
         let post3 = "#.##.L#.##\n\
@@ -310,7 +582,7 @@ mod day11_spec {
                      #.LL###L.L\n\
                      #.#L###.##";
         let seating_diagram_post3 = build_from_str(post3).unwrap();
-        assert_eq!(seating_diagram.step(), 31);
+        assert_eq!(seating_diagram.step_with(&SeatingRule::ADJACENT), 31);
         assert_eq!(seating_diagram, seating_diagram_post3);
 
         let post4 = "#.#L.L#.##\n\
@@ -324,7 +596,7 @@ mod day11_spec {
                      #.LLLLLL.L\n\
                      #.#L#L#.##";
         let seating_diagram_post4 = build_from_str(post4).unwrap();
-        assert_eq!(seating_diagram.step(), 21);
+        assert_eq!(seating_diagram.step_with(&SeatingRule::ADJACENT), 21);
         assert_eq!(seating_diagram, seating_diagram_post4);
 
         let post5 = "#.#L.L#.##\n\
@@ -338,10 +610,10 @@ mod day11_spec {
                      #.LLLLLL.L\n\
                      #.#L#L#.##";
         let seating_diagram_post5 = build_from_str(post5).unwrap();
-        assert_eq!(seating_diagram.step(), 7);
+        assert_eq!(seating_diagram.step_with(&SeatingRule::ADJACENT), 7);
         assert_eq!(seating_diagram, seating_diagram_post5);
 
-        assert_eq!(seating_diagram.step(), 0);
+        assert_eq!(seating_diagram.step_with(&SeatingRule::ADJACENT), 0);
     }
 
     #[test]
@@ -369,7 +641,7 @@ mod day11_spec {
                      #.######.#\n\
                      #.#####.##";
         let seating_diagram_post1 = build_from_str(post1).unwrap();
-        assert_eq!(seating_diagram.step_visible(), 71);
+        assert_eq!(seating_diagram.step_with(&SeatingRule::VISIBLE), 71);
         assert_eq!(seating_diagram, seating_diagram_post1);
 
         let post2 = "#.LL.LL.L#\n\
@@ -383,7 +655,7 @@ mod day11_spec {
                      #.LLLLLL.L\n\
                      #.LLLLL.L#";
         let seating_diagram_post2 = build_from_str(post2).unwrap();
-        assert_eq!(seating_diagram.step_visible(), 64);
+        assert_eq!(seating_diagram.step_with(&SeatingRule::VISIBLE), 64);
         assert_eq!(seating_diagram, seating_diagram_post2);
 
         let post3 = "#.L#.##.L#\n\
@@ -397,7 +669,7 @@ mod day11_spec {
                      #.L#####.L\n\
                      #.L####.L#";
         let seating_diagram_post3 = build_from_str(post3).unwrap();
-        assert_eq!(seating_diagram.step_visible(), 46);
+        assert_eq!(seating_diagram.step_with(&SeatingRule::VISIBLE), 46);
         assert_eq!(seating_diagram, seating_diagram_post3);
 
         let post4 = "#.L#.L#.L#\n\
@@ -411,7 +683,7 @@ mod day11_spec {
                      #.LLLLL#.L\n\
                      #.L#LL#.L#";
         let seating_diagram_post4 = build_from_str(post4).unwrap();
-        assert_eq!(seating_diagram.step_visible(), 35);
+        assert_eq!(seating_diagram.step_with(&SeatingRule::VISIBLE), 35);
         assert_eq!(seating_diagram, seating_diagram_post4);
 
         let post5 = "#.L#.L#.L#\n\
@@ -425,7 +697,7 @@ mod day11_spec {
                      #.LLLLL#.L\n\
                      #.L#LL#.L#";
         let seating_diagram_post5 = build_from_str(post5).unwrap();
-        assert_eq!(seating_diagram.step_visible(), 13);
+        assert_eq!(seating_diagram.step_with(&SeatingRule::VISIBLE), 13);
         assert_eq!(seating_diagram, seating_diagram_post5);
 
         let post6 = "#.L#.L#.L#\n\
@@ -439,9 +711,165 @@ mod day11_spec {
                      #.LLLLL#.L\n\
                      #.L#LL#.L#";
         let seating_diagram_post6 = build_from_str(post6).unwrap();
-        assert_eq!(seating_diagram.step_visible(), 5);
+        assert_eq!(seating_diagram.step_with(&SeatingRule::VISIBLE), 5);
         assert_eq!(seating_diagram, seating_diagram_post6);
 
-        assert_eq!(seating_diagram.step_visible(), 0);
+        assert_eq!(seating_diagram.step_with(&SeatingRule::VISIBLE), 0);
+    }
+
+    #[test]
+    fn run_to_equilibrium_test() {
+        let input = "L.LL.LL.LL\n\
+                     LLLLLLL.LL\n\
+                     L.L.L..L..\n\
+                     LLLL.LL.LL\n\
+                     L.LL.LL.LL\n\
+                     L.LLLLL.LL\n\
+                     ..L.L.....\n\
+                     LLLLLLLLLL\n\
+                     L.LLLLLL.L\n\
+                     L.LLLLL.LL";
+        let mut seating_diagram = build_from_str(input).unwrap();
+        assert_eq!(seating_diagram.run_to_equilibrium(&SeatingRule::ADJACENT).0, Equilibrium::FixedPoint { iterations: 5 });
+        assert_eq!(seating_diagram.count_occupied_seats(), 37);
+
+        let mut seating_diagram = build_from_str(input).unwrap();
+        assert_eq!(seating_diagram.run_to_equilibrium(&SeatingRule::VISIBLE).0, Equilibrium::FixedPoint { iterations: 6 });
+        assert_eq!(seating_diagram.count_occupied_seats(), 26);
+
+        // With zero tolerance for neighbors, a pair of seats flips forever.
+        let intolerant = SeatingRule { neighborhood: Neighborhood::Adjacent, tolerance: 1 };
+        let mut seating_diagram = build_from_str("L.\nL.").unwrap();
+        assert_eq!(seating_diagram.run_to_equilibrium(&intolerant).0, Equilibrium::Cycle { start: 0, period: 2 });
+
+        let mut seating_diagram = build_from_str(".#\n#L\n#L").unwrap();
+        assert_eq!(seating_diagram.run_to_equilibrium(&intolerant).0, Equilibrium::Cycle { start: 1, period: 2 });
+    }
+
+    #[test]
+    fn step_stats_test() {
+        let input = "L.LL.LL.LL\n\
+                     LLLLLLL.LL\n\
+                     L.L.L..L..\n\
+                     LLLL.LL.LL\n\
+                     L.LL.LL.LL\n\
+                     L.LLLLL.LL\n\
+                     ..L.L.....\n\
+                     LLLLLLLLLL\n\
+                     L.LLLLLL.L\n\
+                     L.LLLLL.LL";
+        let (_, stats) = build_from_str(input).unwrap().run_to_equilibrium(&SeatingRule::ADJACENT);
+        // the puzzle's first step fills all 71 seats, and the second empties all but 20
+        assert_eq!(stats.len(), 6);
+        assert_eq!(stats[0], StepStats { step: 1, occupied: 71, changes: 71 });
+        assert_eq!(stats[1], StepStats { step: 2, occupied: 20, changes: 51 });
+        assert_eq!(stats[5], StepStats { step: 6, occupied: 37, changes: 0 });
+        for pair in stats.windows(2) {
+            let (before, after) = (pair[0].occupied, pair[1].occupied);
+            assert!(after.max(before) - after.min(before) <= pair[1].changes);
+        }
+
+        let (_, stats) = build_from_str(input).unwrap().run_to_equilibrium(&SeatingRule::VISIBLE);
+        assert_eq!(stats.iter().map(|step| step.occupied).collect::<Vec<_>>(), vec!(71, 7, 53, 18, 31, 26, 26));
+        assert_eq!(stats_table(&stats).len(), 7);
+
+        // a cycle's stats end with the step which revisits an earlier diagram
+        let intolerant = SeatingRule { neighborhood: Neighborhood::Adjacent, tolerance: 1 };
+        let (_, stats) = build_from_str("L.\nL.").unwrap().run_to_equilibrium(&intolerant);
+        assert_eq!(stats, vec!(StepStats { step: 1, occupied: 2, changes: 2 }, StepStats { step: 2, occupied: 0, changes: 2 }));
+    }
+
+    #[test]
+    fn diff_test() {
+        let gen0 = "L.LL.LL.LL\n\
+                    LLLLLLL.LL\n\
+                    L.L.L..L..\n\
+                    LLLL.LL.LL\n\
+                    L.LL.LL.LL\n\
+                    L.LLLLL.LL\n\
+                    ..L.L.....\n\
+                    LLLLLLLLLL\n\
+                    L.LLLLLL.L\n\
+                    L.LLLLL.LL";
+        let gen2 = "#.LL.L#.##\n\
+                    #LLLLLL.L#\n\
+                    L.L.L..L..\n\
+                    #LLL.LL.L#\n\
+                    #.LL.LL.LL\n\
+                    #.LLLL#.##\n\
+                    ..L.L.....\n\
+                    #LLLLLLLL#\n\
+                    #.LLLLLL.L\n\
+                    #.#LLLL.##";
+        let diagram0 = build_from_str(gen0).unwrap();
+        assert_eq!(diagram0.to_string(), format!("{}\n", gen0));
+
+        let mut diagram1 = diagram0.clone();
+        diagram1.step_with(&SeatingRule::ADJACENT);
+        let changes = diagram0.diff(&diagram1);
+        assert_eq!(changes.len(), 71);
+        assert!(changes.iter().all(|(_, _, from, to)| *from == SeatState::Empty && *to == SeatState::Occupied));
+
+        let mut diagram2 = diagram1.clone();
+        let changed = diagram2.step_with(&SeatingRule::ADJACENT);
+        assert_eq!(diagram2, build_from_str(gen2).unwrap());
+        let changes = diagram1.diff(&diagram2);
+        assert_eq!(changes.len(), changed);
+        assert_eq!(changes[0], (0, 2, SeatState::Occupied, SeatState::Empty));
+        assert!(!changes.iter().any(|(row, col, _, _)| (*row, *col) == (0, 0)));
+
+        assert!(diagram2.diff(&diagram2).is_empty());
+    }
+
+    #[test]
+    fn neighbor_cache_test() {
+        // the example from the statement of part 2, seen from the empty seat in the middle
+        let diagram = build_from_str(".##.##.\n#.#.#.#\n##...##\n...L...\n##...##\n#.#.#.#\n.##.##.").unwrap().with_cache();
+        let cache = diagram.cache.as_ref().unwrap();
+        assert!(cache.neighbors(Neighborhood::Adjacent, 3, 3).is_empty());
+        assert!(cache.neighbors(Neighborhood::Visible, 3, 3).is_empty());
+        assert_eq!(cache.neighbors(Neighborhood::Adjacent, 0, 1), &[(0, 2), (1, 0), (1, 2)]);
+        // looking past the floor at (0, 0) and (1, 1)
+        assert_eq!(cache.neighbors(Neighborhood::Visible, 1, 0), &[(0, 1), (1, 2), (2, 0), (2, 1)]);
+
+        // clones share the cache, which doesn't affect equality
+        let clone = diagram.clone();
+        assert!(Rc::ptr_eq(clone.cache.as_ref().unwrap(), diagram.cache.as_ref().unwrap()));
+        assert_eq!(clone, build_from_str(&diagram.to_string()).unwrap());
+        assert_eq!(clone.fingerprint(), build_from_str(&diagram.to_string()).unwrap().fingerprint());
+    }
+
+    #[test]
+    fn cached_stepping_test() {
+        let input = "L.LL.LL.LL\n\
+                     LLLLLLL.LL\n\
+                     L.L.L..L..\n\
+                     LLLL.LL.LL\n\
+                     L.LL.LL.LL\n\
+                     L.LLLLL.LL\n\
+                     ..L.L.....\n\
+                     LLLLLLLLLL\n\
+                     L.LLLLLL.L\n\
+                     L.LLLLL.LL";
+        let cached = build_from_str(input).unwrap().with_cache();
+        for rule in [SeatingRule::ADJACENT, SeatingRule::VISIBLE].iter() {
+            let mut uncached = build_from_str(input).unwrap();
+            let mut cached = cached.clone();
+            for _ in 0..8 {
+                assert_eq!(cached.step_with(rule), uncached.step_with(rule));
+                assert_eq!(cached, uncached);
+            }
+        }
+        let mut cached = cached;
+        assert_eq!(cached.run_to_equilibrium(&SeatingRule::VISIBLE).0, Equilibrium::FixedPoint { iterations: 6 });
+        assert_eq!(cached.count_occupied_seats(), 26);
+    }
+
+    #[test]
+    fn highlight_changes_test() {
+        let before = build_from_str("L.L\n#L.").unwrap();
+        let after = build_from_str("#.L\n#L.").unwrap();
+        assert_eq!(after.highlight_changes(&before).to_string(), "\x1b[7m#\x1b[0m.L\n#L.\n");
+        assert_eq!(after.highlight_changes(&after).to_string(), after.to_string());
     }
 }
@@ -0,0 +1,634 @@
+use std::io::prelude::*;
+
+#[macro_use]
+extern crate lazy_static;
+
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+
+use advent::cli;
+use advent::error::{AdventError, exit_on_error};
+use advent::timing;
+
+mod optimize;
+mod route;
+use route::RouteFormat;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Cardinal {
+    North,
+    South,
+    East,
+    West
+}
+
+impl Cardinal {
+    fn left(&self, quarter_turns: u8) -> Cardinal {
+        let mut r = *self;
+
+        for _ in 0..quarter_turns {
+            match r {
+                Cardinal::North => r = Cardinal::West,
+                Cardinal::West => r = Cardinal::South,
+                Cardinal::South => r = Cardinal::East,
+                Cardinal::East => r = Cardinal::North
+            }
+        }
+
+        r
+    }
+
+    fn right(&self, quarter_turns: u8) -> Cardinal {
+        self.left(4 - (quarter_turns % 4))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum Instruction {
+    North(usize),
+    South(usize),
+    East(usize),
+    West(usize),
+    Left(u8), // in range 0..4
+    Right(u8), // in range 0..4
+    Forward(usize)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum InstructionError {
+    Unrecognized,
+    // a turn through an angle the interpreter cannot follow
+    Rotation { direction: char, degrees: usize }
+}
+
+// Splits an instruction into its action and its (non-negative) argument.
+fn tokenize(line: &str) -> Result<(char, usize), InstructionError> {
+    lazy_static! {
+        static ref INSTRUCTION_PAT: Regex = Regex::new(r"([NSEWLRF])(\d+)").unwrap();
+    }
+
+    let caps = INSTRUCTION_PAT.captures(line).ok_or(InstructionError::Unrecognized)?;
+    let x = usize::from_str_radix(&caps[2], 10).map_err(|_| InstructionError::Unrecognized)?;
+    caps[1].chars().next().map(|action| (action, x)).ok_or(InstructionError::Unrecognized)
+}
+
+impl Instruction {
+    // Rotations are rounded down to a whole number of quarter turns.
+    fn new(action: char, x: usize) -> Option<Instruction> {
+        match action {
+            'N' => Some(Instruction::North(x)),
+            'S' => Some(Instruction::South(x)),
+            'E' => Some(Instruction::East(x)),
+            'W' => Some(Instruction::West(x)),
+            'L' => Some(Instruction::Left(((x % 360 ) / 90) as u8)),
+            'R' => Some(Instruction::Right(((x % 360 ) / 90) as u8)),
+            'F' => Some(Instruction::Forward(x)),
+            _ => None
+        }
+    }
+
+    // Reports each rotation it rounds, and each line it cannot parse, on stderr.
+    fn parse(line: &str) -> Option<Instruction> {
+        let instruction = tokenize(line).ok().and_then(|(action, x)| Instruction::new(action, x));
+        match (&instruction, tokenize(line)) {
+            (Some(instruction), Ok((_, x))) if instruction.token().1 != x % 360 => {
+                eprintln!("Rounding {} down to {}", line, instruction)
+            },
+            (None, _) => eprintln!("Unable to parse instruction from {}", line),
+            _ => ()
+        }
+        instruction
+    }
+
+    // Like `parse`, but rejects rotations through angles that are not multiples of 90°
+    // rather than rounding them.
+    fn parse_strict(line: &str) -> Result<Instruction, InstructionError> {
+        let (action, x) = tokenize(line)?;
+        if (action == 'L' || action == 'R') && x % 90 != 0 {
+            return Err(InstructionError::Rotation { direction: action, degrees: x })
+        }
+        Instruction::new(action, x).ok_or(InstructionError::Unrecognized)
+    }
+
+    // The action and argument this instruction would be written as, with turns in degrees
+    fn token(&self) -> (char, usize) {
+        match self {
+            Instruction::North(x) => ('N', *x),
+            Instruction::South(x) => ('S', *x),
+            Instruction::East(x) => ('E', *x),
+            Instruction::West(x) => ('W', *x),
+            Instruction::Left(q) => ('L', *q as usize * 90),
+            Instruction::Right(q) => ('R', *q as usize * 90),
+            Instruction::Forward(x) => ('F', *x)
+        }
+    }
+}
+
+impl std::fmt::Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let (action, x) = self.token();
+        write!(f, "{}{}", action, x)
+    }
+}
+
+// In extended mode, rotations may also be through odd multiples of 45°. Only the waypoint
+// can follow those: the ship's heading is always a cardinal direction.
+#[derive(Debug, PartialEq, Eq)]
+enum Maneuver {
+    Standard(Instruction),
+    Diagonal(u8) // counterclockwise eighth turns, odd
+}
+
+impl Maneuver {
+    fn parse(line: &str) -> Result<Maneuver, InstructionError> {
+        match Instruction::parse_strict(line) {
+            Ok(instruction) => Ok(Maneuver::Standard(instruction)),
+            Err(InstructionError::Rotation { direction, degrees }) if degrees % 45 == 0 => {
+                let eighths = ((degrees % 360) / 45) as u8;
+                if direction == 'L' {
+                    Ok(Maneuver::Diagonal(eighths))
+                } else {
+                    Ok(Maneuver::Diagonal(8 - eighths))
+                }
+            },
+            Err(e) => Err(e)
+        }
+    }
+}
+
+// An interpretation of the instructions which moves the ship somehow
+trait Navigator {
+    fn apply(&mut self, instr: &Instruction);
+
+    // The ship's position, east and north of where it started
+    fn position(&self) -> (isize, isize);
+
+    fn manhattan(&self) -> usize {
+        let (x, y) = self.position();
+        (x.abs() + y.abs()) as usize
+    }
+}
+
+fn navigate<N: Navigator + ?Sized>(navigator: &mut N, instructions: &[Instruction]) {
+    for instruction in instructions {
+        navigator.apply(instruction);
+    }
+}
+
+// Both parts' interpretations, from the start
+fn navigators() -> Vec<(&'static str, Box<dyn Navigator>)> {
+    vec!(
+        ("Ship's position", Box::new(ShipsPosition::new())),
+        ("Waypoint method", Box::new(WaypointPosition::new()))
+    )
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct ShipsPosition {
+    x: isize,
+    y: isize,
+    heading: Cardinal
+}
+
+impl ShipsPosition {
+    fn new() -> ShipsPosition {
+        ShipsPosition { x: 0, y: 0, heading: Cardinal::East }
+    }
+}
+
+impl Navigator for ShipsPosition {
+    fn apply(&mut self, instr: &Instruction) {
+        match *instr {
+            Instruction::North(dy) => self.y += dy as isize,
+            Instruction::South(dy) => self.y -= dy as isize,
+            Instruction::East(dx) => self.x += dx as isize,
+            Instruction::West(dx) => self.x -= dx as isize,
+            Instruction::Left(qt) => self.heading = self.heading.left(qt),
+            Instruction::Right(qt) => self.heading = self.heading.right(qt),
+            Instruction::Forward(s) => match self.heading {
+                Cardinal::North => self.y += s as isize,
+                Cardinal::South => self.y -= s as isize,
+                Cardinal::East => self.x += s as isize,
+                Cardinal::West => self.x -= s as isize
+            }
+        }
+    }
+
+    fn position(&self) -> (isize, isize) {
+        (self.x, self.y)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct WaypointPosition {
+    waypoint_dx: isize,
+    waypoint_dy: isize,
+    ship_x: isize,
+    ship_y: isize
+}
+
+impl WaypointPosition {
+    fn new() -> WaypointPosition {
+        WaypointPosition {
+            ship_x: 0, ship_y: 0,
+            waypoint_dx: 10, waypoint_dy: 1
+        }
+    }
+}
+
+impl Navigator for WaypointPosition {
+    fn apply(&mut self, instr: &Instruction) {
+        match *instr {
+            Instruction::North(dy) => self.waypoint_dy += dy as isize,
+            Instruction::South(dy) => self.waypoint_dy -= dy as isize,
+            Instruction::East(dx) => self.waypoint_dx += dx as isize,
+            Instruction::West(dx) => self.waypoint_dx -= dx as isize,
+            Instruction::Left(qt) => {
+                let prev = (self.waypoint_dx, self.waypoint_dy);
+                match qt % 4 {
+                    1 => {
+                        self.waypoint_dx = -prev.1;
+                        self.waypoint_dy = prev.0;
+                    },
+                    2 => {
+                        self.waypoint_dx = -prev.0;
+                        self.waypoint_dy = -prev.1;
+                    },
+                    3 => {
+                        self.waypoint_dx = prev.1;
+                        self.waypoint_dy = -prev.0;
+                    },
+                    _ => () // no rotation
+                }
+            },
+            Instruction::Right(qt) => {
+                self.apply(&Instruction::Left(4 - (qt % 4)));
+            },
+            Instruction::Forward(s) => {
+                self.ship_x += self.waypoint_dx * s as isize;
+                self.ship_y += self.waypoint_dy * s as isize;
+            }
+        }
+    }
+
+    fn position(&self) -> (isize, isize) {
+        (self.ship_x, self.ship_y)
+    }
+}
+
+// An element a + bω + cω² + dω³ of Z[ω], where ω = e^(iπ/4). East is 1 and north is ω², so
+// a turn through 45° is multiplication by ω, which keeps every coefficient an integer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Octagonal([isize; 4]);
+
+impl Octagonal {
+    const EAST: Octagonal = Octagonal([1, 0, 0, 0]);
+    const NORTH: Octagonal = Octagonal([0, 0, 1, 0]);
+
+    fn scale(self, k: isize) -> Octagonal {
+        let [a, b, c, d] = self.0;
+        Octagonal([a * k, b * k, c * k, d * k])
+    }
+
+    fn plus(self, other: Octagonal) -> Octagonal {
+        let [a, b, c, d] = self.0;
+        let [e, f, g, h] = other.0;
+        Octagonal([a + e, b + f, c + g, d + h])
+    }
+
+    fn left(self, eighth_turns: u8) -> Octagonal {
+        let mut r = self.0;
+        for _ in 0..(eighth_turns % 8) {
+            // ω⁴ = -1
+            r = [-r[3], r[0], r[1], r[2]];
+        }
+        Octagonal(r)
+    }
+
+    // Cartesian coordinates; ω and ω³ contribute ±√2/2 along each axis.
+    fn xy(&self) -> (f64, f64) {
+        let [a, b, c, d] = self.0;
+        let h = std::f64::consts::FRAC_1_SQRT_2;
+        (a as f64 + (b - d) as f64 * h, c as f64 + (b + d) as f64 * h)
+    }
+}
+
+// The waypoint interpreter, extended to rotations through multiples of 45°.
+#[derive(Debug, PartialEq, Eq)]
+struct OctagonalWaypointPosition {
+    waypoint: Octagonal,
+    ship: Octagonal
+}
+
+impl OctagonalWaypointPosition {
+    fn new() -> OctagonalWaypointPosition {
+        OctagonalWaypointPosition {
+            waypoint: Octagonal::EAST.scale(10).plus(Octagonal::NORTH),
+            ship: Octagonal([0; 4])
+        }
+    }
+
+    fn apply(&mut self, maneuver: &Maneuver) {
+        match maneuver {
+            Maneuver::Diagonal(eighths) => self.waypoint = self.waypoint.left(*eighths),
+            Maneuver::Standard(instr) => match *instr {
+                Instruction::North(dy) => self.waypoint = self.waypoint.plus(Octagonal::NORTH.scale(dy as isize)),
+                Instruction::South(dy) => self.waypoint = self.waypoint.plus(Octagonal::NORTH.scale(-(dy as isize))),
+                Instruction::East(dx) => self.waypoint = self.waypoint.plus(Octagonal::EAST.scale(dx as isize)),
+                Instruction::West(dx) => self.waypoint = self.waypoint.plus(Octagonal::EAST.scale(-(dx as isize))),
+                Instruction::Left(qt) => self.waypoint = self.waypoint.left(2 * (qt % 4)),
+                Instruction::Right(qt) => self.waypoint = self.waypoint.left(8 - 2 * (qt % 4)),
+                Instruction::Forward(s) => self.ship = self.ship.plus(self.waypoint.scale(s as isize))
+            }
+        }
+    }
+}
+
+fn run() -> Result<(), AdventError> {
+    let stdin = std::io::stdin();
+    let lines: Vec<String> = stdin.lock().lines().flatten().collect();
+    let format = match cli::option_value("--format") {
+        Some(name) => RouteFormat::parse(&name).ok_or_else(|| AdventError::input(format!("unknown route format {}", name)))?,
+        None => RouteFormat::detect(&lines.join("\n"))
+    };
+    if let Some(name) = cli::option_value("--emit") {
+        let emit = RouteFormat::parse(&name).ok_or_else(|| AdventError::input(format!("unknown route format {}", name)))?;
+        let instructions = route::read(&lines.join("\n"), format).map_err(AdventError::input)?;
+        print!("{}", route::write(&instructions, emit));
+        return Ok(())
+    }
+    let malformed = |idx: usize, e: InstructionError| {
+        AdventError::input(format!("malformed instruction on line {} ({:?}): {}", idx + 1, e, lines[idx]))
+    };
+    let instructions: Vec<Instruction> = if format != RouteFormat::Lines {
+        route::read(&lines.join("\n"), format).map_err(AdventError::input)?
+    } else if cli::has_flag("--extended") {
+        let maneuvers = lines.iter().enumerate()
+            .map(|(idx, line)| Maneuver::parse(line).map_err(|e| malformed(idx, e)))
+            .collect::<Result<Vec<Maneuver>, _>>()?;
+        if maneuvers.iter().any(|m| matches!(m, Maneuver::Diagonal(_))) {
+            let mut position = OctagonalWaypointPosition::new();
+            timing::time("Part 2", || maneuvers.iter().for_each(|m| position.apply(m)));
+            let (x, y) = position.ship.xy();
+            println!("Diagonal rotations: only the waypoint method applies.");
+            println!("Waypoint method: x={:.3}, y={:.3}. Manhattan displacement: {:.3}", x, y, x.abs() + y.abs());
+            return Ok(())
+        }
+        maneuvers.into_iter().flat_map(|m| match m {
+            Maneuver::Standard(instruction) => Some(instruction),
+            Maneuver::Diagonal(_) => None
+        }).collect()
+    } else if cli::has_flag("--round") {
+        lines.iter().flat_map(|line| Instruction::parse(line)).collect()
+    } else {
+        lines.iter().enumerate()
+            .map(|(idx, line)| Instruction::parse_strict(line).map_err(|e| malformed(idx, e)))
+            .collect::<Result<Vec<Instruction>, _>>()?
+    };
+
+    let optimized = optimize::optimize(&instructions);
+    if optimized.is_empty() {
+        println!("Optimized route: all {} instructions cancel out.", instructions.len());
+    } else {
+        println!("Optimized route: {} instructions down to {}; compression ratio {:.2}",
+            instructions.len(), optimized.len(), instructions.len() as f64 / optimized.len() as f64);
+    }
+
+    // the answers come from the optimized route; following the original as well checks the optimizer
+    let mut checks = navigators();
+    for (idx, ((description, navigator), (_, check))) in navigators().iter_mut().zip(checks.iter_mut()).enumerate() {
+        timing::time(&format!("Part {}", idx + 1), || navigate(navigator.as_mut(), &optimized));
+        navigate(check.as_mut(), &instructions);
+        if navigator.position() != check.position() {
+            return Err(AdventError::no_solution(format!("{}: the optimized route ends at {:?}, but the original at {:?}",
+                description, navigator.position(), check.position())))
+        }
+        let (x, y) = navigator.position();
+        println!("{}: x={}, y={}. Manhattan displacement: {}", description, x, y, navigator.manhattan());
+    }
+    Ok(())
+}
+
+fn main() {
+    exit_on_error(run())
+}
+
+#[cfg(test)]
+mod day12_spec {
+    use super::*;
+
+    mod cardinal {
+        use super::*;
+
+        #[test]
+        fn left_turn_test() {
+            assert_eq!(Cardinal::East.left(0), Cardinal::East);
+            assert_eq!(Cardinal::East.left(1), Cardinal::North);
+            assert_eq!(Cardinal::North.left(2), Cardinal::South);
+            assert_eq!(Cardinal::South.left(3), Cardinal::West);
+        }
+
+        #[test]
+        fn right_turn_test() {
+            assert_eq!(Cardinal::North.right(0), Cardinal::North);
+            assert_eq!(Cardinal::North.right(1), Cardinal::East);
+            assert_eq!(Cardinal::East.right(2), Cardinal::West);
+            assert_eq!(Cardinal::West.right(3), Cardinal::South);
+        }
+    }
+
+    mod instruction {
+        use super::*;
+
+        #[test]
+        fn parse_test() {
+            assert_eq!(Instruction::parse("F10"), Some(Instruction::Forward(10)));
+            assert_eq!(Instruction::parse("N3"), Some(Instruction::North(3)));
+            assert_eq!(Instruction::parse("R90"), Some(Instruction::Right(1)));
+            assert_eq!(Instruction::parse("L270"), Some(Instruction::Left(3)));
+            assert_eq!(Instruction::parse("S11"), Some(Instruction::South(11)));
+            assert_eq!(Instruction::parse("E13"), Some(Instruction::East(13)));
+            assert_eq!(Instruction::parse("W87"), Some(Instruction::West(87)));
+            
+            // even absurd instructions can be parsed
+            assert_eq!(Instruction::parse("L181"), Some(Instruction::Left(2)));
+    
+            // but negatives cannot
+            assert_eq!(Instruction::parse("N-3"), None);
+        }
+
+        #[test]
+        fn display_test() {
+            for line in vec!("F10", "N3", "R90", "L270", "S11", "E13", "W87", "L0") {
+                assert_eq!(Instruction::parse(line).unwrap().to_string(), line);
+            }
+        }
+
+        #[test]
+        fn parse_strict_test() {
+            assert_eq!(Instruction::parse_strict("R90"), Ok(Instruction::Right(1)));
+            assert_eq!(Instruction::parse_strict("L450"), Ok(Instruction::Left(1)));
+            assert_eq!(Instruction::parse_strict("F7"), Ok(Instruction::Forward(7)));
+            assert_eq!(Instruction::parse_strict("L181"), Err(InstructionError::Rotation { direction: 'L', degrees: 181 }));
+            assert_eq!(Instruction::parse_strict("R45"), Err(InstructionError::Rotation { direction: 'R', degrees: 45 }));
+            assert_eq!(Instruction::parse_strict("N-3"), Err(InstructionError::Unrecognized));
+        }
+    }
+
+    mod maneuver {
+        use super::*;
+
+        #[test]
+        fn parse_test() {
+            assert_eq!(Maneuver::parse("L180"), Ok(Maneuver::Standard(Instruction::Left(2))));
+            assert_eq!(Maneuver::parse("L45"), Ok(Maneuver::Diagonal(1)));
+            assert_eq!(Maneuver::parse("L495"), Ok(Maneuver::Diagonal(3)));
+            assert_eq!(Maneuver::parse("R45"), Ok(Maneuver::Diagonal(7)));
+            assert_eq!(Maneuver::parse("R135"), Ok(Maneuver::Diagonal(5)));
+            assert_eq!(Maneuver::parse("R30"), Err(InstructionError::Rotation { direction: 'R', degrees: 30 }));
+        }
+    }
+
+    mod ships_position {
+        use super::*;
+
+        #[test]
+        fn apply_test() {
+            let mut sp = ShipsPosition::new();
+            sp.apply(&Instruction::Forward(10));
+            assert_eq!(sp, ShipsPosition { heading: Cardinal::East, x: 10, y: 0 });
+
+            sp.apply(&Instruction::North(3));
+            assert_eq!(sp, ShipsPosition { heading: Cardinal::East, x: 10, y: 3 });
+
+            sp.apply(&Instruction::Forward(7));
+            assert_eq!(sp, ShipsPosition { heading: Cardinal::East, x: 17, y: 3 });
+
+            sp.apply(&Instruction::Right(1));
+            assert_eq!(sp, ShipsPosition { heading: Cardinal::South, x: 17, y: 3 });
+
+            sp.apply(&Instruction::Forward(11));
+            assert_eq!(sp, ShipsPosition { heading: Cardinal::South, x: 17, y: -8 });
+        }
+    }
+
+    mod waypoint_position {
+        use super::*;
+
+        #[test]
+        fn apply_test() {
+            let mut wp = WaypointPosition::new();
+
+            wp.apply(&Instruction::Forward(10));
+            assert_eq!(wp, WaypointPosition{
+                ship_x: 100, ship_y: 10,
+                waypoint_dx: 10, waypoint_dy: 1
+            });
+
+            wp.apply(&Instruction::North(3));
+            assert_eq!(wp, WaypointPosition{
+                ship_x: 100, ship_y: 10,
+                waypoint_dx: 10, waypoint_dy: 4
+            });
+
+            wp.apply(&Instruction::Forward(7));
+            assert_eq!(wp, WaypointPosition {
+                ship_x: 170, ship_y: 38,
+                waypoint_dx: 10, waypoint_dy: 4
+            });
+
+            wp.apply(&Instruction::Right(1));
+            assert_eq!(wp, WaypointPosition {
+                ship_x: 170, ship_y: 38,
+                waypoint_dx: 4, waypoint_dy: -10
+            });
+
+            wp.apply(&Instruction::Forward(11));
+            assert_eq!(wp, WaypointPosition {
+                ship_x: 214, ship_y: -72,
+                waypoint_dx: 4, waypoint_dy: -10
+            });
+        }
+    }
+
+    mod navigator {
+        use super::*;
+
+        // Ignores headings and waypoints, and just adds up the distances mentioned
+        struct Odometer(usize);
+
+        impl Navigator for Odometer {
+            fn apply(&mut self, instr: &Instruction) {
+                if let Instruction::North(d) | Instruction::South(d) | Instruction::East(d)
+                    | Instruction::West(d) | Instruction::Forward(d) = instr {
+                    self.0 += d;
+                }
+            }
+
+            fn position(&self) -> (isize, isize) {
+                (self.0 as isize, 0)
+            }
+        }
+
+        #[test]
+        fn navigate_test() {
+            let instructions = vec!(
+                Instruction::Forward(10), Instruction::North(3), Instruction::Forward(7),
+                Instruction::Right(1), Instruction::Forward(11)
+            );
+            let mut navigators: Vec<Box<dyn Navigator>> = vec!(
+                Box::new(ShipsPosition::new()), Box::new(WaypointPosition::new()), Box::new(Odometer(0))
+            );
+            for navigator in navigators.iter_mut() {
+                navigate(navigator.as_mut(), &instructions);
+            }
+            assert_eq!(navigators.iter().map(|n| n.position()).collect::<Vec<_>>(), vec!((17, -8), (214, -72), (31, 0)));
+            assert_eq!(navigators.iter().map(|n| n.manhattan()).collect::<Vec<_>>(), vec!(25, 286, 31));
+        }
+    }
+
+    mod octagonal_waypoint_position {
+        use super::*;
+
+        #[test]
+        fn should_agree_with_waypoint_position_on_right_angles() {
+            let instructions = vec!(
+                Instruction::Forward(10), Instruction::North(3), Instruction::Forward(7),
+                Instruction::Right(1), Instruction::Forward(11), Instruction::Left(3), Instruction::West(4),
+                Instruction::Right(2), Instruction::Forward(5)
+            );
+            let mut wp = WaypointPosition::new();
+            let mut owp = OctagonalWaypointPosition::new();
+            for instruction in instructions {
+                wp.apply(&instruction);
+                owp.apply(&Maneuver::Standard(instruction));
+            }
+            assert_eq!(owp.ship, Octagonal([wp.ship_x, 0, wp.ship_y, 0]));
+            assert_eq!(owp.waypoint, Octagonal([wp.waypoint_dx, 0, wp.waypoint_dy, 0]));
+        }
+
+        #[test]
+        fn diagonal_rotations_test() {
+            let mut owp = OctagonalWaypointPosition::new();
+            owp.apply(&Maneuver::Diagonal(1));
+            let (x, y) = owp.waypoint.xy();
+            assert!((x - 9.0 * std::f64::consts::FRAC_1_SQRT_2).abs() < 1e-9);
+            assert!((y - 11.0 * std::f64::consts::FRAC_1_SQRT_2).abs() < 1e-9);
+
+            // two eighth turns make a quarter turn
+            owp.apply(&Maneuver::Diagonal(1));
+            assert_eq!(owp.waypoint, Octagonal([-1, 0, 10, 0]));
+            // R45, R90, R45: half a turn in all
+            owp.apply(&Maneuver::Diagonal(7));
+            owp.apply(&Maneuver::Standard(Instruction::Right(1)));
+            owp.apply(&Maneuver::Diagonal(7));
+            assert_eq!(owp.waypoint, Octagonal([1, 0, -10, 0]));
+
+            owp.apply(&Maneuver::Diagonal(3));
+            owp.apply(&Maneuver::Standard(Instruction::Forward(2)));
+            let (x, y) = owp.ship.xy();
+            assert!((x - 18.0 * std::f64::consts::FRAC_1_SQRT_2).abs() < 1e-9);
+            assert!((y - 22.0 * std::f64::consts::FRAC_1_SQRT_2).abs() < 1e-9);
+        }
+    }
+}
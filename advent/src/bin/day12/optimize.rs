@@ -0,0 +1,149 @@
+// A peephole pass over routes. Under both interpretations, moves north, south, east and west all
+// shift the same thing (the ship, or the waypoint), so any run of them has the effect of its net
+// displacement; likewise a run of turns is its net turn modulo 360°, and a run of forward moves is
+// their sum. Turns don't commute with moves for the waypoint, so runs of different kinds are never
+// reordered.
+
+use super::Instruction;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Move,
+    Turn,
+    Forward
+}
+
+fn kind(instruction: &Instruction) -> Kind {
+    match instruction {
+        Instruction::North(_) | Instruction::South(_) | Instruction::East(_) | Instruction::West(_) => Kind::Move,
+        Instruction::Left(_) | Instruction::Right(_) => Kind::Turn,
+        Instruction::Forward(_) => Kind::Forward
+    }
+}
+
+// The net effect of a non-empty run of instructions of one kind, in at most two instructions
+fn collapse(run: &[Instruction]) -> Vec<Instruction> {
+    let mut r = vec!();
+    match kind(&run[0]) {
+        Kind::Move => {
+            let (mut north, mut east) = (0, 0);
+            for instruction in run {
+                match *instruction {
+                    Instruction::North(d) => north += d as isize,
+                    Instruction::South(d) => north -= d as isize,
+                    Instruction::East(d) => east += d as isize,
+                    Instruction::West(d) => east -= d as isize,
+                    _ => ()
+                }
+            }
+            if north != 0 {
+                r.push(if north > 0 { Instruction::North(north as usize) } else { Instruction::South(north.unsigned_abs()) });
+            }
+            if east != 0 {
+                r.push(if east > 0 { Instruction::East(east as usize) } else { Instruction::West(east.unsigned_abs()) });
+            }
+        },
+        Kind::Turn => {
+            let quarter_turns_left: usize = run.iter().map(|instruction| match *instruction {
+                Instruction::Left(q) => q as usize % 4,
+                Instruction::Right(q) => 4 - q as usize % 4,
+                _ => 0
+            }).sum();
+            match quarter_turns_left % 4 {
+                0 => (),
+                3 => r.push(Instruction::Right(1)),
+                q => r.push(Instruction::Left(q as u8))
+            }
+        },
+        Kind::Forward => {
+            let distance: usize = run.iter().map(|instruction| match *instruction {
+                Instruction::Forward(s) => s,
+                _ => 0
+            }).sum();
+            if distance > 0 {
+                r.push(Instruction::Forward(distance));
+            }
+        }
+    }
+    r
+}
+
+fn collapse_runs(instructions: &[Instruction]) -> Vec<Instruction> {
+    let mut r = vec!();
+    let mut start = 0;
+    for idx in 1..=instructions.len() {
+        if idx == instructions.len() || kind(&instructions[idx]) != kind(&instructions[start]) {
+            r.extend(collapse(&instructions[start..idx]));
+            start = idx;
+        }
+    }
+    r
+}
+
+// A route with the same effect under both interpretations, and no run which could be shortened.
+// Collapsing repeats until nothing changes, since a run which cancels out brings its neighbors together.
+pub fn optimize(instructions: &[Instruction]) -> Vec<Instruction> {
+    let mut current = collapse_runs(instructions);
+    loop {
+        let next = collapse_runs(&current);
+        if next == current {
+            return next
+        }
+        current = next;
+    }
+}
+
+#[cfg(test)]
+mod optimize_spec {
+    use super::*;
+    use super::super::{navigate, navigators};
+    use advent::random::Lcg;
+
+    fn route(text: &str) -> Vec<Instruction> {
+        text.split_whitespace().map(|token| Instruction::parse_strict(token).unwrap()).collect()
+    }
+
+    #[test]
+    fn optimize_test() {
+        assert_eq!(optimize(&route("N3 N4 E2 S10 W2")), route("S3"));
+        assert_eq!(optimize(&route("L90 L90 R270 L180")), route("L90"));
+        assert_eq!(optimize(&route("L180 L90 R0")), route("R90"));
+        assert_eq!(optimize(&route("R90 L90 F0")), vec!());
+        assert_eq!(optimize(&route("F10 N3 F7 R90 F11")), route("F10 N3 F7 R90 F11"));
+        // dropping the turns brings the forward moves together, and then the moves
+        assert_eq!(optimize(&route("N2 F1 L180 R180 F2 N3")), route("N2 F3 N3"));
+        assert_eq!(optimize(&route("E1 F2 F0 W1 L360 F3")), route("E1 F2 W1 F3"));
+        assert_eq!(optimize(&route("W1 L0 E1")), vec!());
+        assert_eq!(optimize(&[]), vec!());
+    }
+
+    // Random routes, from a small linear congruential generator, with few distinct tokens so that
+    // there is plenty to collapse
+    fn sample_routes() -> Vec<Vec<Instruction>> {
+        let tokens = ["N1", "S1", "E2", "W2", "L90", "R90", "L180", "R270", "F1", "F3"];
+        let mut rng = Lcg::new(2020);
+        (0..300).map(|_| {
+            let len = rng.below(30);
+            (0..len).map(|_| Instruction::parse_strict(tokens[rng.below(tokens.len() as u64) as usize]).unwrap()).collect()
+        }).collect()
+    }
+
+    #[test]
+    fn optimize_preserves_positions() {
+        let mut compressed = 0;
+        for instructions in sample_routes() {
+            let optimized = optimize(&instructions);
+            assert!(optimized.len() <= instructions.len());
+            compressed += instructions.len() - optimized.len();
+            assert_eq!(optimize(&optimized), optimized);
+
+            let mut raw = navigators();
+            for ((_, raw), (_, fast)) in raw.iter_mut().zip(navigators().iter_mut()) {
+                navigate(raw.as_mut(), &instructions);
+                navigate(fast.as_mut(), &optimized);
+                assert_eq!(raw.position(), fast.position(), "{:?} => {:?}", instructions, optimized);
+            }
+        }
+        assert!(compressed > 0);
+    }
+}
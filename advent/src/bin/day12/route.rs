@@ -0,0 +1,128 @@
+// Routes in formats other tools can produce and consume. Besides the puzzle's own one instruction
+// per line ("F10"), a route may be a JSON array of serialized Instructions, in which turns count
+// quarter turns (`[{"Forward":10},{"Right":1}]`), or CSV rows `action,value` in the puzzle's units,
+// optionally under an `action,value` header.
+
+use super::Instruction;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteFormat {
+    Lines,
+    Json,
+    Csv
+}
+
+impl RouteFormat {
+    pub fn parse(name: &str) -> Option<RouteFormat> {
+        match name.to_lowercase().as_str() {
+            "lines" | "text" => Some(RouteFormat::Lines),
+            "json" => Some(RouteFormat::Json),
+            "csv" => Some(RouteFormat::Csv),
+            _ => None
+        }
+    }
+
+    // Guesses the format from the first non-blank line
+    pub fn detect(text: &str) -> RouteFormat {
+        match text.lines().map(|line| line.trim()).find(|line| !line.is_empty()) {
+            Some(line) if line.starts_with('[') || line.starts_with('{') => RouteFormat::Json,
+            Some(line) if line.contains(',') => RouteFormat::Csv,
+            _ => RouteFormat::Lines
+        }
+    }
+}
+
+// Reads a route in the given format. Rotations must be through multiples of 90°; --round applies only
+// to routes given one instruction per line.
+pub fn read(text: &str, format: RouteFormat) -> Result<Vec<Instruction>, String> {
+    let strict = |idx: usize, line: &str| {
+        Instruction::parse_strict(line).map_err(|e| format!("malformed instruction on line {} ({:?}): {}", idx + 1, e, line))
+    };
+    match format {
+        RouteFormat::Lines => text.lines().enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(idx, line)| strict(idx, line.trim()))
+            .collect(),
+        RouteFormat::Csv => text.lines().enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && line.trim() != "action,value")
+            .map(|(idx, line)| {
+                let fields: Vec<&str> = line.split(',').map(|field| field.trim()).collect();
+                match fields.as_slice() {
+                    [action, value] => strict(idx, &format!("{}{}", action, value)),
+                    _ => Err(format!("expected `action,value` on line {}: {}", idx + 1, line))
+                }
+            })
+            .collect(),
+        RouteFormat::Json => {
+            let instructions: Vec<Instruction> = serde_json::from_str(text).map_err(|e| e.to_string())?;
+            for (idx, instruction) in instructions.iter().enumerate() {
+                if let Instruction::Left(q) | Instruction::Right(q) = instruction {
+                    if *q >= 4 {
+                        return Err(format!("instruction {} turns {} quarter turns; at most 3 are allowed", idx + 1, q))
+                    }
+                }
+            }
+            Ok(instructions)
+        }
+    }
+}
+
+pub fn write(instructions: &[Instruction], format: RouteFormat) -> String {
+    match format {
+        RouteFormat::Lines => instructions.iter().map(|instruction| format!("{}\n", instruction)).collect(),
+        RouteFormat::Csv => {
+            let mut csv = "action,value\n".to_owned();
+            for instruction in instructions {
+                let (action, value) = instruction.token();
+                csv.push_str(&format!("{},{}\n", action, value));
+            }
+            csv
+        },
+        RouteFormat::Json => serde_json::to_string_pretty(instructions).unwrap() + "\n"
+    }
+}
+
+#[cfg(test)]
+mod route_spec {
+    use super::*;
+    use super::super::InstructionError;
+
+    fn sample() -> Vec<Instruction> {
+        vec!(
+            Instruction::Forward(10),
+            Instruction::North(3),
+            Instruction::Forward(7),
+            Instruction::Right(1),
+            Instruction::Left(3),
+            Instruction::Forward(11)
+        )
+    }
+
+    #[test]
+    fn detect_test() {
+        assert_eq!(RouteFormat::detect("F10\nN3\n"), RouteFormat::Lines);
+        assert_eq!(RouteFormat::detect("\n  [{\"Forward\": 10}]"), RouteFormat::Json);
+        assert_eq!(RouteFormat::detect("action,value\nF,10\n"), RouteFormat::Csv);
+        assert_eq!(RouteFormat::detect(""), RouteFormat::Lines);
+    }
+
+    #[test]
+    fn round_trip_test() {
+        for format in [RouteFormat::Lines, RouteFormat::Json, RouteFormat::Csv].iter() {
+            let text = write(&sample(), *format);
+            assert_eq!(RouteFormat::detect(&text), *format, "{}", text);
+            assert_eq!(read(&text, *format), Ok(sample()), "{}", text);
+        }
+    }
+
+    #[test]
+    fn read_test() {
+        assert_eq!(read("F,10\nR, 90\n\n", RouteFormat::Csv), Ok(vec!(Instruction::Forward(10), Instruction::Right(1))));
+        assert_eq!(read("[{\"Left\": 2}, {\"East\": 5}]", RouteFormat::Json), Ok(vec!(Instruction::Left(2), Instruction::East(5))));
+        assert_eq!(read("F10\nR45\n", RouteFormat::Lines),
+            Err(format!("malformed instruction on line 2 ({:?}): R45", InstructionError::Rotation { direction: 'R', degrees: 45 })));
+        assert!(read("F,10,3", RouteFormat::Csv).is_err());
+        assert!(read("[{\"Left\": 4}]", RouteFormat::Json).is_err());
+        assert!(read("[{\"Up\": 4}]", RouteFormat::Json).is_err());
+    }
+}
@@ -1,7 +1,11 @@
 use std::io::prelude::*;
 use std::collections::{BinaryHeap};
+use std::fmt::{Display, Formatter};
 
-use modinverse::egcd;
+use advent::cli;
+use advent::error::{AdventError, exit_on_error};
+use advent::numbers::mod_inverse;
+use advent::timing;
 
 // computes the modular additive inverse of x (mod p)
 fn modular_negative(x: usize, p: usize) -> usize {
@@ -32,16 +36,113 @@ where J: Iterator<Item=T>, F: Fn(&T) -> U, U: Ord {
     r.map(|p| p.0)
 }
 
-fn soonest_bus(current_time: usize, bus_ids: &str) -> Option<(usize, usize)> {
-    let mut ids = bus_ids.split(',').flat_map(|id| usize::from_str_radix(id, 10));
+#[derive(Debug, PartialEq, Eq)]
+enum BusIdError {
+    // `position` is the index of the token in the list, i.e. the bus's offset
+    Invalid { position: usize, token: String },
+    Zero { position: usize }
+}
+
+// Reads a comma-separated list of bus ids, with 'x' for a slot no bus is constrained to.
+// Every malformed token is reported.
+fn parse_buses(bus_ids: &str) -> Result<Vec<Option<u64>>, Vec<BusIdError>> {
+    let mut buses = vec!();
+    let mut errors = vec!();
+    for (position, token) in bus_ids.split(',').enumerate() {
+        match token.trim() {
+            "x" => buses.push(None),
+            t if !t.is_empty() && t.chars().all(|c| c.is_ascii_digit()) => match u64::from_str_radix(t, 10) {
+                Ok(0) => errors.push(BusIdError::Zero { position }),
+                Ok(id) => buses.push(Some(id)),
+                Err(_) => errors.push(BusIdError::Invalid { position, token: token.to_owned() })
+            },
+            _ => errors.push(BusIdError::Invalid { position, token: token.to_owned() })
+        }
+    }
+    if errors.is_empty() { Ok(buses) } else { Err(errors) }
+}
+
+fn soonest_bus(current_time: usize, buses: &[Option<u64>]) -> Option<(usize, usize)> {
+    let mut ids = buses.iter().flatten().map(|id| *id as usize);
     min_by(&mut ids, |t| modular_negative(current_time, *t)).map(|bus_id| (bus_id, bus_id - (current_time % bus_id)))
 }
 
-fn bus_constraints(bus_ids: &str) -> BinaryHeap<(usize, usize)> {
+// The buses in service, each leaving the station every `id` minutes from time 0.
+#[derive(Debug, PartialEq, Eq)]
+struct Schedule {
+    buses: Vec<usize>
+}
+
+// A round trip out and back on the same bus. `total_wait` counts the minutes spent waiting
+// for the bus at either end.
+#[derive(Debug, PartialEq, Eq)]
+struct RoundTrip {
+    bus_id: usize,
+    departure: usize,
+    return_departure: usize,
+    total_wait: usize
+}
+
+impl Schedule {
+    fn new(buses: &[Option<u64>]) -> Schedule {
+        Schedule { buses: buses.iter().flatten().map(|id| *id as usize).collect() }
+    }
+
+    // Among the buses leaving within `window` minutes of `now`, the one minimizing the total
+    // wait for a trip that spends `stay` minutes away before catching the same bus back.
+    // Since every departure of a bus is a multiple of its id, the wait on the return leg
+    // depends only on `stay`. Ties go to the earlier departure.
+    fn best_round_trip(&self, now: usize, window: usize, stay: usize) -> Option<RoundTrip> {
+        let mut trips = self.buses.iter().flat_map(|&bus_id| {
+            let departure = now + modular_negative(now, bus_id);
+            let return_wait = modular_negative(stay, bus_id);
+            if departure - now <= window {
+                Some(RoundTrip {
+                    bus_id, departure,
+                    return_departure: departure + stay + return_wait,
+                    total_wait: departure - now + return_wait
+                })
+            } else {
+                None
+            }
+        });
+        min_by(&mut trips, |trip| (trip.total_wait, trip.departure))
+    }
+
+    // The table of departures from the puzzle statement: one row for each of the `rows` minutes from
+    // `start`, with a D in a bus's column if it leaves that minute and a dot otherwise
+    fn render_timetable(&self, start: usize, rows: usize) -> String {
+        let time_width = "time".len().max((start + rows).saturating_sub(1).to_string().len()) + 3;
+        let headers: Vec<String> = self.buses.iter().map(|bus_id| format!("bus {}", bus_id)).collect();
+        let widths: Vec<usize> = headers.iter().map(|header| (header.len() + 2).max(8)).collect();
+
+        let mut lines = vec!();
+        let mut header = format!("{:<width$}", "time", width = time_width);
+        for (h, width) in headers.iter().zip(&widths) {
+            header.push_str(&format!("{:<width$}", h, width = width));
+        }
+        lines.push(header);
+        for time in start..start + rows {
+            let mut line = format!("{:<width$}", time, width = time_width);
+            for (bus_id, width) in self.buses.iter().zip(&widths) {
+                let mark = if time % bus_id == 0 { 'D' } else { '.' };
+                line.push_str(&format!("  {:<width$}", mark, width = width - 2));
+            }
+            lines.push(line);
+        }
+
+        lines.iter().map(|line| format!("{}\n", line.trim_end())).collect()
+    }
+}
+
+// The constraints (id, t mod id) on a timestamp t at which each bus leaves as many minutes after t
+// as its offset in the list
+fn bus_constraints(buses: &[Option<u64>]) -> BinaryHeap<(usize, usize)> {
     let mut cs = BinaryHeap::new();
 
-    for (idx, bus_id) in bus_ids.split(',').enumerate() {
-        for id in usize::from_str_radix(bus_id, 10) {
+    for (idx, bus_id) in buses.iter().enumerate() {
+        if let Some(id) = bus_id {
+            let id = *id as usize;
             cs.push((id, modular_negative(idx, id)));
         }
     }
@@ -49,44 +150,145 @@ fn bus_constraints(bus_ids: &str) -> BinaryHeap<(usize, usize)> {
     cs
 }
 
-// We need to use signed int here because the egcd crate author does unsafe multiplication
-fn chinese_remainder(mut constraints: BinaryHeap<(usize, usize)>) -> Option<i128> {
-    let mut p = 1;
-    let mut s: i128 = 0;
+#[derive(Debug, PartialEq, Eq)]
+enum CrtError {
+    // Buses `a` and `b` share a factor, so that their constraints may conflict
+    NonCoprime { a: u64, b: u64 },
+    // The product of the bus ids exceeds u128::MAX
+    Overflow
+}
+
+impl Display for CrtError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            CrtError::NonCoprime { a, b } => write!(f, "bus ids {} and {} are not coprime", a, b),
+            CrtError::Overflow => write!(f, "the product of the bus ids overflows a u128")
+        }
+    }
+}
+
+// The least timestamp satisfying every constraint. Each constraint (n, rem) in turn is added to a
+// solution s modulo the product p of the moduli so far, as s + k * p for the k < n which satisfies
+// it; since k * p < p * n, nothing overflows unless the product of all the moduli does.
+fn chinese_remainder(mut constraints: BinaryHeap<(usize, usize)>) -> Result<u128, CrtError> {
+    let mut moduli: Vec<u64> = vec!();
+    let mut p: u128 = 1;
+    let mut s: u128 = 0;
+
+    while let Some((n, rem)) = constraints.pop() {
+        let n = n as u64;
+        let inverse = mod_inverse((p % n as u128) as u64, n).ok_or_else(|| {
+            let a = moduli.iter().copied().find(|m| gcd(*m as u128, n as u128) > 1).unwrap_or(n);
+            CrtError::NonCoprime { a, b: n }
+        })?;
+        let next_p = p.checked_mul(n as u128).ok_or(CrtError::Overflow)?;
+        let n = n as u128;
+        let difference = (rem as u128 % n + n - s % n) % n;
+        s += difference * inverse as u128 % n * p;
+        p = next_p;
+        moduli.push(n as u64);
+    }
+
+    Ok(s)
+}
+
+fn gcd(a: u128, b: u128) -> u128 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+// A CRT-free cross-check for chinese_remainder: start at the residue for the largest modulus,
+// then for each subsequent constraint step by the lcm of the moduli seen so far until the
+// constraint is satisfied. Slower, but uses nothing beyond addition and remainders.
+fn earliest_departure_sieve(mut constraints: BinaryHeap<(usize, usize)>) -> Option<u128> {
+    let (n, rem) = constraints.pop()?;
+    let mut t = rem as u128;
+    let mut step = n as u128;
 
     while let Some((n, rem)) = constraints.pop() {
-        let rem: i128 = rem as i128;
-        let n: i128 = n as i128;
-        if let (1, cp, cn) = egcd(p, n) {
-            let next_p = p * n;
-            s = (cp * rem * p + cn * s * n) % next_p;
-            if s < 0 {
-                s += next_p;
+        let (n, rem) = (n as u128, rem as u128);
+        // t mod n cycles with period n / gcd(step, n), so if we haven't hit `rem` by then we never will
+        let mut tries = 0;
+        while t % n != rem % n {
+            if tries >= n {
+                eprintln!("Constraint {} mod {} is unsatisfiable!", rem, n);
+                return None
             }
-            p = next_p;
-        } else {
-            eprintln!("Non-relatively-prime inputs discovered!");
-            return None
+            t += step;
+            tries += 1;
         }
+        step = step / gcd(step, n) * n;
     }
 
-    return Some(s)
+    Some(t)
 }
 
-
-fn main() {
+fn run() -> Result<(), AdventError> {
     let stdin = std::io::stdin();
     let mut stdin_lines = stdin.lock().lines().flatten();
-    let current_time = stdin_lines.next().and_then(|s| usize::from_str_radix(&s, 10).ok()).unwrap();
-    let bus_ids_line = stdin_lines.next().unwrap();
+    let current_time = stdin_lines.next().and_then(|s| usize::from_str_radix(&s, 10).ok())
+        .ok_or_else(|| AdventError::input("expected a timestamp on the first line"))?;
+    let bus_ids_line = stdin_lines.next()
+        .ok_or_else(|| AdventError::input("expected bus ids on the second line"))?;
+    let buses = parse_buses(&bus_ids_line).map_err(|errors| {
+        for e in &errors {
+            match e {
+                BusIdError::Invalid { position, token } => eprintln!("Malformed bus id at position {}: {:?}", position, token),
+                BusIdError::Zero { position } => eprintln!("Bus id 0 at position {}", position)
+            }
+        }
+        AdventError::input(format!("{} malformed bus ids", errors.len()))
+    })?;
+    if let Some(rows) = cli::option_value("--timetable") {
+        let rows = rows.parse::<usize>().map_err(|_| AdventError::input(format!("expected a number of minutes, got {}", rows)))?;
+        print!("{}", Schedule::new(&buses).render_timetable(current_time, rows));
+    }
+
     println!("Part 1:");
-    let (bus_id, wait_time) = soonest_bus(current_time, &bus_ids_line).unwrap();
+    let (bus_id, wait_time) = timing::time("Part 1", || soonest_bus(current_time, &buses))
+        .ok_or_else(|| AdventError::input("no buses in service"))?;
     println!("The first bus to leave will be #{} in {} minutes. Part 1: {}", bus_id, wait_time, bus_id * wait_time);
 
     println!("Part 2:");
-    let constraints = bus_constraints(&bus_ids_line);
-    let departure_time = chinese_remainder(constraints).unwrap();
-    println!("Earliest timestamp: {}", departure_time);
+    let constraints = bus_constraints(&buses);
+    if cli::has_flag("--bigint") {
+        #[cfg(feature = "bigint")]
+        {
+            let constraints: Vec<(u64, u64)> = constraints.into_iter().map(|(n, rem)| (n as u64, rem as u64)).collect();
+            let departure_time = timing::time("Part 2 (bigint)", || advent::numbers::chinese_remainder_big(&constraints))
+                .ok_or_else(|| AdventError::no_solution("no timestamp satisfies the bus constraints"))?;
+            println!("Earliest timestamp (bigint): {}", departure_time);
+        }
+        #[cfg(not(feature = "bigint"))]
+        return Err(AdventError::input("--bigint needs a build with the bigint feature"))
+    } else if cli::has_flag("--sieve") {
+        let departure_time = timing::time("Part 2 (sieve)", || earliest_departure_sieve(constraints))
+            .ok_or_else(|| AdventError::no_solution("no timestamp satisfies the bus constraints"))?;
+        println!("Earliest timestamp (sieve): {}", departure_time);
+    } else {
+        let departure_time = timing::time("Part 2", || chinese_remainder(constraints)).map_err(|e| match e {
+            CrtError::Overflow if !cfg!(feature = "bigint") => AdventError::no_solution(format!("{}; a build with the bigint feature can solve it with --bigint", e)),
+            CrtError::Overflow => AdventError::no_solution(format!("{}; try --bigint", e)),
+            e => AdventError::no_solution(e.to_string())
+        })?;
+        println!("Earliest timestamp: {}", departure_time);
+    }
+
+    if let (Some(window), Some(stay)) = (cli::option_value("--window"), cli::option_value("--stay")) {
+        let parse = |s: &str| usize::from_str_radix(s, 10).map_err(|_| AdventError::input(format!("expected a number of minutes, got {}", s)));
+        let (window, stay) = (parse(&window)?, parse(&stay)?);
+        match Schedule::new(&buses).best_round_trip(current_time, window, stay) {
+            Some(trip) => println!(
+                "Best round trip: bus #{}, leaving at {} and returning at {}; {} minutes spent waiting.",
+                trip.bus_id, trip.departure, trip.return_departure, trip.total_wait
+            ),
+            None => println!("No bus leaves within {} minutes.", window)
+        }
+    }
+    Ok(())
+}
+
+fn main() {
+    exit_on_error(run())
 }
 
 #[cfg(test)]
@@ -100,19 +302,115 @@ mod day13_spec {
         assert_eq!(m, &15);
     }
 
+    fn buses(bus_ids: &str) -> Vec<Option<u64>> {
+        parse_buses(bus_ids).unwrap()
+    }
+
+    #[test]
+    fn parse_buses_test() {
+        assert_eq!(buses("7,13,x,x,59,x,31,19"), vec!(Some(7), Some(13), None, None, Some(59), None, Some(31), Some(19)));
+        assert_eq!(buses("x, 17"), vec!(None, Some(17)));
+        assert_eq!(buses("x"), vec!(None));
+    }
+
+    #[test]
+    fn parse_buses_negative_test() {
+        assert_eq!(parse_buses("7,y,13"), Err(vec!(BusIdError::Invalid { position: 1, token: "y".to_owned() })));
+        assert_eq!(parse_buses("7,,0,x1,-3,99999999999999999999"), Err(vec!(
+            BusIdError::Invalid { position: 1, token: "".to_owned() },
+            BusIdError::Zero { position: 2 },
+            BusIdError::Invalid { position: 3, token: "x1".to_owned() },
+            BusIdError::Invalid { position: 4, token: "-3".to_owned() },
+            BusIdError::Invalid { position: 5, token: "99999999999999999999".to_owned() }
+        )));
+        assert!(parse_buses("").is_err());
+        assert!(parse_buses("X").is_err());
+    }
+
     #[test]
     fn soonest_bus_test() {
-        let bus_id = soonest_bus(939, "7,13,59,31,19").unwrap();
+        let bus_id = soonest_bus(939, &buses("7,13,59,31,19")).unwrap();
         assert_eq!(bus_id, (59, 5));
 
-        let bus_id = soonest_bus(939, "7,13,x,x,59,x,31,19").unwrap();
+        let bus_id = soonest_bus(939, &buses("7,13,x,x,59,x,31,19")).unwrap();
         assert_eq!(bus_id, (59, 5));
+
+        assert_eq!(soonest_bus(939, &buses("x,x")), None);
+    }
+
+    #[test]
+    fn schedule_new_test() {
+        assert_eq!(Schedule::new(&buses("7,13,x,x,59,x,31,19")), Schedule { buses: vec!(7, 13, 59, 31, 19) });
+    }
+
+    #[test]
+    fn render_timetable_test() {
+        let schedule = Schedule::new(&buses("7,13,x,x,59,x,31,19"));
+        // as in the puzzle statement
+        let expected = [
+            "time   bus 7   bus 13  bus 59  bus 31  bus 19",
+            "929      .       .       .       .       .",
+            "930      .       .       .       D       .",
+            "931      D       .       .       .       D",
+            "932      .       .       .       .       .",
+            "933      .       .       .       .       .",
+            "934      .       .       .       .       .",
+            "935      .       .       .       .       .",
+            "936      .       D       .       .       .",
+            "937      .       .       .       .       .",
+            "938      D       .       .       .       .",
+            "939      .       .       .       .       .",
+            "940      .       .       .       .       .",
+            "941      .       .       .       .       .",
+            "942      .       .       .       .       .",
+            "943      .       .       .       .       .",
+            "944      .       .       D       .       .",
+            "945      D       .       .       .       .",
+            "946      .       .       .       .       .",
+            "947      .       .       .       .       .",
+            "948      .       .       .       .       .",
+            "949      .       D       .       .       .",
+            ""
+        ].join("\n");
+        assert_eq!(schedule.render_timetable(929, 21), expected);
+
+        // columns widen for long ids and times
+        let schedule = Schedule::new(&buses("x,1789,2"));
+        assert_eq!(schedule.render_timetable(1788, 2), [
+            "time   bus 1789  bus 2",
+            "1788     .         D",
+            "1789     D         .",
+            ""
+        ].join("\n"));
+        assert_eq!(Schedule::new(&buses("x")).render_timetable(0, 1), "time\n0\n");
+    }
+
+    #[test]
+    fn best_round_trip_test() {
+        let schedule = Schedule::new(&buses("7,13,x,x,59,x,31,19"));
+        // nothing leaves in the next 4 minutes
+        assert_eq!(schedule.best_round_trip(939, 4, 30), None);
+
+        // the soonest bus, #59, would leave us waiting 29 minutes on the way back
+        assert_eq!(schedule.best_round_trip(939, 10, 30), Some(RoundTrip {
+            bus_id: 7, departure: 945, return_departure: 980, total_wait: 11
+        }));
+        assert_eq!(schedule.best_round_trip(939, 30, 100), Some(RoundTrip {
+            bus_id: 7, departure: 945, return_departure: 1050, total_wait: 11
+        }));
+        assert_eq!(schedule.best_round_trip(939, 10, 65), Some(RoundTrip {
+            bus_id: 13, departure: 949, return_departure: 1014, total_wait: 10
+        }));
+        // with a narrower window, #59 is the only option
+        assert_eq!(schedule.best_round_trip(939, 5, 65), Some(RoundTrip {
+            bus_id: 59, departure: 944, return_departure: 1062, total_wait: 58
+        }));
     }
 
     #[test]
     fn bus_constraints_test() {
         let bus_id_line = "7,13,x,x,59,x,31,19";
-        let mut constraints = bus_constraints(bus_id_line);
+        let mut constraints = bus_constraints(&buses(bus_id_line));
         assert_eq!(constraints.pop(), Some((59, 55)));
         assert_eq!(constraints.pop(), Some((31, 25)));
         assert_eq!(constraints.pop(), Some((19, 12)));
@@ -121,7 +419,7 @@ mod day13_spec {
         assert_eq!(constraints.pop(), None);
 
         let bus_id_line = "5,x,x,7,x,x,3,11";
-        let mut constraints = bus_constraints(bus_id_line);
+        let mut constraints = bus_constraints(&buses(bus_id_line));
         assert_eq!(constraints.pop(), Some((11, 4)));
         assert_eq!(constraints.pop(), Some((7, 4)));
         assert_eq!(constraints.pop(), Some((5, 0)));
@@ -167,4 +465,60 @@ mod day13_spec {
         assert_eq!(cr, 779210);
     }
 
+    #[test]
+    fn chinese_remainder_error_test() {
+        let constraints: BinaryHeap<(usize, usize)> = vec!((4, 0), (7, 1), (6, 1)).into_iter().collect();
+        // the largest moduli are taken first
+        assert_eq!(chinese_remainder(constraints), Err(CrtError::NonCoprime { a: 6, b: 4 }));
+        let constraints: BinaryHeap<(usize, usize)> = vec!((7, 0), (7, 0)).into_iter().collect();
+        assert_eq!(chinese_remainder(constraints), Err(CrtError::NonCoprime { a: 7, b: 7 }));
+        assert_eq!(chinese_remainder(BinaryHeap::new()), Ok(0));
+    }
+
+    #[test]
+    fn chinese_remainder_large_test() {
+        // The four largest 32-bit primes, whose product lies between i128::MAX and u128::MAX
+        let primes: [usize; 4] = [4294967291, 4294967279, 4294967231, 4294967197];
+        let product = primes.iter().fold(1u128, |p, n| p * *n as u128);
+        assert!(product > i128::MAX as u128);
+        let constraints: BinaryHeap<(usize, usize)> = primes.iter().enumerate().map(|(idx, n)| (*n, n - 1 - idx)).collect();
+        let cr = chinese_remainder(constraints).unwrap();
+        assert!(cr < product);
+        for (idx, n) in primes.iter().enumerate() {
+            assert_eq!(cr % *n as u128, (n - 1 - idx) as u128);
+        }
+        // and all but 1 below the product
+        let constraints: BinaryHeap<(usize, usize)> = primes.iter().map(|n| (*n, n - 1)).collect();
+        assert_eq!(chinese_remainder(constraints), Ok(product - 1));
+
+        // 64-bit moduli
+        let constraints: BinaryHeap<(usize, usize)> = vec!((u64::MAX as usize, 3), ((1 << 61) - 1, 5)).into_iter().collect();
+        let cr = chinese_remainder(constraints).unwrap();
+        assert_eq!((cr % u64::MAX as u128, cr % ((1 << 61) - 1)), (3, 5));
+
+        let constraints: BinaryHeap<(usize, usize)> = primes.iter().map(|n| (*n, 0)).chain(vec!((4294967189, 0))).collect();
+        assert_eq!(chinese_remainder(constraints), Err(CrtError::Overflow));
+    }
+
+    #[test]
+    fn earliest_departure_sieve_test() {
+        for bus_id_line in vec!(
+            "7,13,x,x,59,x,31,19",
+            "17,x,13,19",
+            "67,7,59,61",
+            "67,x,7,59,61",
+            "67,7,x,59,61",
+            "1789,37,47,1889"
+        ) {
+            let sieve = earliest_departure_sieve(bus_constraints(&buses(bus_id_line))).unwrap();
+            let cr = chinese_remainder(bus_constraints(&buses(bus_id_line))).unwrap();
+            assert_eq!(sieve, cr);
+        }
+        assert_eq!(earliest_departure_sieve(bus_constraints(&buses("1789,37,47,1889"))), Some(1202161486));
+
+        assert_eq!(earliest_departure_sieve(bus_constraints(&buses("x"))), None);
+        assert_eq!(earliest_departure_sieve(bus_constraints(&buses("4,6"))), None);
+        assert_eq!(earliest_departure_sieve(bus_constraints(&buses("4,x,6"))), Some(4));
+    }
+
 }
\ No newline at end of file
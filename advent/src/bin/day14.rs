@@ -1,12 +1,17 @@
 use std::io::prelude::*;
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::{Display, Formatter};
 
 #[macro_use]
 extern crate lazy_static;
 
 use regex::Regex;
 
+use advent::cli;
+use advent::error::{AdventError, exit_on_error};
+use advent::timing;
+
 
 #[derive(Debug, PartialEq, Eq)]
 struct Mask {
@@ -15,6 +20,9 @@ struct Mask {
 }
 
 impl Mask {
+    // All X: leaves every value unchanged, and is in effect before the first mask instruction
+    const IDENTITY: Mask = Mask { zeros: 0, ones: 0 };
+
     // parameter `m` is just the masking string, e.g. "XXXXXXXXXXXXXXXXXXXXXXXXXXXXX1XXXX0X"
     fn parse(m: &str) -> Mask {
         let mut zeros = 0;
@@ -31,69 +39,124 @@ impl Mask {
         Mask { zeros, ones }
     }
 
+    // Unlike `parse`, insists on exactly 36 characters, each one of 'X', '0', '1'
+    fn parse_strict(m: &str) -> Result<Mask, MaskError> {
+        let len = m.chars().count();
+        if len != 36 {
+            return Err(MaskError::WrongLength(len))
+        }
+        if let Some((position, c)) = m.chars().enumerate().find(|(_, c)| !"X01".contains(*c)) {
+            return Err(MaskError::InvalidChar { position, c })
+        }
+        Ok(Mask::parse(m))
+    }
+
     const fn floating_bits(&self) -> u64 {
         !(self.zeros | self.ones) & ((1 << 36) - 1)
     }
-}
 
-fn set_mem(memory: &mut BTreeMap<u64, u64>, mask: &Mask, address: u64, value: u64) {
-    let masked_value = (value | mask.ones) & !mask.zeros;
-    memory.insert(address, masked_value);
-}
-
-struct Floater<'a> {
-    base: u64,
-    mask: &'a Mask,
-    pos: u64 // the next item to be yielded, expressed as a  uint in the range 0..2^(number of floating bits)
-}
-
-impl<'a> Floater<'a> {
-    fn new(base: u64, mask: &'a Mask) -> Floater<'a> {
-        Floater { base, mask, pos: 0 }
+    #[cfg(test)]
+    fn floating_count(&self) -> u32 {
+        self.floating_bits().count_ones()
     }
 
-    fn explode(&self) -> u64 {
-        let mut r = self.base & !self.mask.floating_bits() | self.mask.ones;
-        let mut f = self.mask.floating_bits();
-        let mut p = self.pos;
+    // The part 1 decoding: forced bits replace those of `value`, and X bits pass it through
+    fn apply_value(&self, value: u64) -> u64 {
+        (value | self.ones) & !self.zeros
+    }
 
-        while f > 0 && p > 0 {
-            let f_bit_idx = 63 - f.leading_zeros();
-            let f_bit: u64 = 1 << f_bit_idx;
-            let p_bit_idx = f.count_ones() - 1;
-            let p_bit: u64 = 1 << p_bit_idx;
+    // The part 2 decoding: every address which agrees with `base | ones` off the floating bits, in
+    // increasing order; there are 2^floating_count of them
+    fn iter_addresses(&self, base: u64) -> Addresses {
+        let floating = self.floating_bits();
+        Addresses { fixed: (base | self.ones) & !floating, floating, next: Some(0) }
+    }
 
-            r |= (p & p_bit) << (f_bit_idx - p_bit_idx);
-            f &= !f_bit;
-            p &= !p_bit;
+    // The single mask with the effect of applying `self` and then `other` to a value: `other`'s
+    // forced bits win, and its X bits let `self`'s through. Composition is associative, with
+    // IDENTITY on either side leaving a mask unchanged.
+    fn compose(&self, other: &Mask) -> Mask {
+        Mask {
+            zeros: other.zeros | (self.zeros & !other.ones),
+            ones: other.ones | (self.ones & !other.zeros)
         }
+    }
+}
 
-        r
+impl Display for Mask {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        let m: String = (0..36).rev().map(|idx| {
+            let bit = 1 << idx;
+            if self.zeros & bit != 0 {
+                '0'
+            } else if self.ones & bit != 0 {
+                '1'
+            } else {
+                'X'
+            }
+        }).collect();
+        f.write_str(&m)
     }
 }
 
-impl Iterator for Floater<'_> {
+// The addresses a floating mask writes to; see Mask::iter_addresses
+struct Addresses {
+    fixed: u64,
+    floating: u64,
+    next: Option<u64> // the floating bits of the next address, None once every subset has been yielded
+}
+
+impl Iterator for Addresses {
     type Item = u64;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let floating_bits = self.mask.floating_bits();
-        if self.pos < (1 << floating_bits.count_ones()) {
-            let item = self.explode();
-            self.pos += 1;
-            Some(item)
-        } else {
-            None
-        }
+    fn next(&mut self) -> Option<u64> {
+        let bits = self.next?;
+        // the next subset of the floating bits in increasing order, wrapping back to 0 after the last
+        let successor = bits.wrapping_sub(self.floating) & self.floating;
+        self.next = if successor == 0 { None } else { Some(successor) };
+        Some(self.fixed | bits)
     }
 }
 
+fn set_mem(memory: &mut BTreeMap<u64, u64>, mask: &Mask, address: u64, value: u64) {
+    memory.insert(address, mask.apply_value(value));
+}
+
 fn set_mem_2(memory: &mut BTreeMap<u64, u64>, mask: &Mask, address: u64, value: u64) {
-    let floater = Floater::new(address, &mask);
-    for a in floater {
+    for a in mask.iter_addresses(address) {
         memory.insert(a, value);
     }
 }
 
+#[derive(Debug, PartialEq, Eq)]
+enum MaskError {
+    WrongLength(usize),
+    InvalidChar { position: usize, c: char }
+}
+
+const ADDRESS_LIMIT: u64 = 1 << 36;
+
+#[derive(Debug, PartialEq, Eq)]
+enum InstructionError {
+    Mask(MaskError),
+    AddressOutOfRange(String),
+    ValueOutOfRange(String),
+    Unrecognized
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct InstructionParseError {
+    line_no: usize,
+    line: String,
+    error: InstructionError
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParseMode {
+    Strict, // any malformed line is an error
+    Lenient // malformed lines are silently dropped
+}
+
 #[derive(Debug, PartialEq, Eq)]
 enum Instruction {
     SetMask(Mask),
@@ -117,33 +180,185 @@ impl Instruction {
             }) })
         }))
     }
+
+    fn parse_strict(line: &str) -> Result<Instruction, InstructionError> {
+        lazy_static! {
+            static ref SET_MASK_PAT: Regex = Regex::new(r"^mask = (.*)$").unwrap();
+            static ref SET_MEM_PAT: Regex = Regex::new(r"^mem\[(\d+)\] = (\d+)$").unwrap();
+        }
+
+        // Both addresses and values live in a 36-bit space
+        fn parse_36(digits: &str) -> Option<u64> {
+            u64::from_str_radix(digits, 10).ok().filter(|x| *x < ADDRESS_LIMIT)
+        }
+
+        if let Some(caps) = SET_MASK_PAT.captures(line) {
+            Mask::parse_strict(&caps[1]).map(Instruction::SetMask).map_err(InstructionError::Mask)
+        } else if let Some(caps) = SET_MEM_PAT.captures(line) {
+            let address = parse_36(&caps[1]).ok_or_else(|| InstructionError::AddressOutOfRange(caps[1].to_owned()))?;
+            let value = parse_36(&caps[2]).ok_or_else(|| InstructionError::ValueOutOfRange(caps[2].to_owned()))?;
+            Ok(Instruction::SetMem { address, value })
+        } else {
+            Err(InstructionError::Unrecognized)
+        }
+    }
 }
 
-fn main() {
-    let mut mask = Mask { zeros: 0, ones: 0 };
-    let mut memory = BTreeMap::new();
-    let mut memory_2 = BTreeMap::new();
-    let stdin = std::io::stdin();
-    for instruction in stdin.lock().lines().flatten().flat_map(|line| Instruction::parse(&line)) {
-        match instruction {
-            Instruction::SetMask(next_mask) => mask = next_mask,
-            Instruction::SetMem { address, value } => {
-                set_mem(&mut memory, &mask, address, value);
-                set_mem_2(&mut memory_2, &mask, address, value);
+// The addresses which a part 2 write may reach: those agreeing with `bits` wherever `fixed` has a 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct AddressFamily {
+    fixed: u64,
+    bits: u64
+}
+
+impl AddressFamily {
+    fn new(mask: &Mask, address: u64) -> AddressFamily {
+        let fixed = !mask.floating_bits() & (ADDRESS_LIMIT - 1);
+        AddressFamily { fixed, bits: (address | mask.ones) & fixed }
+    }
+
+    fn size(&self) -> u128 {
+        1 << (36 - self.fixed.count_ones())
+    }
+
+    fn intersect(&self, other: &AddressFamily) -> Option<AddressFamily> {
+        if (self.bits ^ other.bits) & self.fixed & other.fixed != 0 {
+            None
+        } else {
+            Some(AddressFamily { fixed: self.fixed | other.fixed, bits: self.bits | other.bits })
+        }
+    }
+}
+
+struct Program(Vec<Instruction>);
+
+impl Program {
+    // Runs the program, storing each value with `set` (set_mem or set_mem_2)
+    fn execute<F>(&self, set: F) -> BTreeMap<u64, u64>
+    where F: Fn(&mut BTreeMap<u64, u64>, &Mask, u64, u64) {
+        let mut mask = &Mask::IDENTITY;
+        let mut memory = BTreeMap::new();
+        for instruction in &self.0 {
+            match instruction {
+                Instruction::SetMask(next_mask) => mask = next_mask,
+                Instruction::SetMem { address, value } => set(&mut memory, mask, *address, *value)
+            }
+        }
+        memory
+    }
+
+    // Each write (mask, address, value), with the mask in effect for it
+    fn writes(&self) -> Vec<(&Mask, u64, u64)> {
+        let mut mask = &Mask::IDENTITY;
+        let mut writes = vec!();
+        for instruction in &self.0 {
+            match instruction {
+                Instruction::SetMask(next_mask) => mask = next_mask,
+                Instruction::SetMem { address, value } => writes.push((mask, *address, *value))
             }
         }
+        writes
     }
+
+    // The part 1 sum, counting only the last write to each address
+    fn final_memory_sum(&self) -> u64 {
+        let mut written = HashSet::new();
+        self.writes().into_iter().rev()
+            .filter(|(_, address, _)| written.insert(*address))
+            .map(|(mask, _, value)| mask.apply_value(value))
+            .sum()
+    }
+
+    // The one mask with the effect of every mask instruction in turn, starting from IDENTITY
+    fn net_mask(&self) -> Mask {
+        self.0.iter().fold(Mask::IDENTITY, |net, instruction| match instruction {
+            Instruction::SetMask(mask) => net.compose(mask),
+            Instruction::SetMem { .. } => net
+        })
+    }
+
+    // The part 2 sum without exploding any addresses. Walking backwards, each write contributes its
+    // value once for each address in its family which no later write reaches. The later families are
+    // kept as a signed sum of families (by inclusion-exclusion) whose total size is that of their union.
+    fn final_floating_sum(&self) -> u128 {
+        let mut later: BTreeMap<AddressFamily, i64> = BTreeMap::new();
+        let mut sum = 0;
+        for (mask, address, value) in self.writes().into_iter().rev() {
+            let family = AddressFamily::new(mask, address);
+            let overlaps: Vec<(AddressFamily, i64)> = later.iter()
+                .filter_map(|(other, sign)| family.intersect(other).map(|overlap| (overlap, *sign)))
+                .collect();
+            let shadowed: i128 = overlaps.iter().map(|(overlap, sign)| *sign as i128 * overlap.size() as i128).sum();
+            let fresh = (family.size() as i128 - shadowed) as u128;
+            if fresh == 0 {
+                continue
+            }
+            sum += fresh * value as u128;
+            for (overlap, sign) in overlaps.into_iter().chain(std::iter::once((family, -1))) {
+                let count = later.entry(overlap).or_insert(0);
+                *count -= sign;
+                if *count == 0 {
+                    later.remove(&overlap);
+                }
+            }
+        }
+        sum
+    }
+}
+
+fn parse_program<J>(lines: J, mode: ParseMode) -> Result<Vec<Instruction>, InstructionParseError> where J: Iterator<Item=String> {
+    let mut instructions = vec!();
+    for (idx, line) in lines.enumerate() {
+        match mode {
+            ParseMode::Lenient => instructions.extend(Instruction::parse(&line)),
+            ParseMode::Strict => match Instruction::parse_strict(&line) {
+                Ok(instruction) => instructions.push(instruction),
+                Err(error) => return Err(InstructionParseError { line_no: idx + 1, line, error })
+            }
+        }
+    }
+    Ok(instructions)
+}
+
+fn run() -> Result<(), AdventError> {
+    let stdin = std::io::stdin();
+    let mode = if cli::has_flag("--strict") { ParseMode::Strict } else { ParseMode::Lenient };
+    let program = parse_program(stdin.lock().lines().flatten(), mode).map(Program).map_err(|e|
+        AdventError::input(format!("malformed instruction on line {} ({:?}): {}", e.line_no, e.error, e.line))
+    )?;
+
+    if cli::has_flag("--net-mask") {
+        println!("Net mask: {}", program.net_mask());
+    }
+
+    // without a memory to fill, this works even on programs with many floating bits
+    if cli::has_flag("--optimize") {
+        let memory_sum = timing::time("Part 1", || program.final_memory_sum());
+        let memory_sum_2 = timing::time("Part 2", || program.final_floating_sum());
+        println!("Part 1: Sum of set values = {}", memory_sum);
+        println!("Part 2: Sum of set values = {}", memory_sum_2);
+        return Ok(())
+    }
+
+    let memory = timing::time("Part 1", || program.execute(set_mem));
+    let memory_2 = timing::time("Part 2", || program.execute(set_mem_2));
     println!("Part 1: Memory: {} addresses are set.", memory.len());
     println!("Part 2: Memory: {} addresses are set.", memory_2.len());
     let memory_sum = memory.values().fold(0, |acc, v| acc + v);
     let memory_sum_2 = memory_2.values().fold(0, |acc, v| acc + v);
     println!("Part 1: Sum of set values = {}", memory_sum);
     println!("Part 2: Sum of set values = {}", memory_sum_2);
+    Ok(())
+}
+
+fn main() {
+    exit_on_error(run())
 }
 
 #[cfg(test)]
 mod day14_spec {
     use super::*;
+    use advent::random::Lcg;
 
     #[test]
     fn mask_parse_test() {
@@ -178,18 +393,187 @@ mod day14_spec {
     }
 
     #[test]
-    fn floater_test() {
+    fn mask_parse_strict_test() {
+        assert_eq!(Mask::parse_strict("XXXXXXXXXXXXXXXXXXXXXXXXXXXXX1XXXX0X"), Ok(Mask { zeros: 2, ones: 64 }));
+        assert_eq!(Mask::parse_strict("XXXXXXXXXXXXXXXXXXXXXXXXXXXX1XXXX0X"), Err(MaskError::WrongLength(35)));
+        assert_eq!(Mask::parse_strict("XXXXXXXXXXXXXXXXXXXXXXXXXXXXX1XXXX0XX"), Err(MaskError::WrongLength(37)));
+        assert_eq!(Mask::parse_strict("XXXXXXXXXXXXXXXXXXXXXXXXXXXXX1XXXX2X"), Err(MaskError::InvalidChar { position: 34, c: '2' }));
+    }
+
+    #[test]
+    fn instruction_parse_strict_test() {
+        assert_eq!(Instruction::parse_strict("mem[8] = 11"), Ok(Instruction::SetMem { address: 8, value: 11 }));
+        assert_eq!(Instruction::parse_strict("mem[68719476735] = 68719476735"),
+            Ok(Instruction::SetMem { address: 68719476735, value: 68719476735 }));
+        assert_eq!(Instruction::parse_strict("mem[68719476736] = 1"),
+            Err(InstructionError::AddressOutOfRange("68719476736".to_owned())));
+        assert_eq!(Instruction::parse_strict("mem[1] = 99999999999999999999999"),
+            Err(InstructionError::ValueOutOfRange("99999999999999999999999".to_owned())));
+        assert_eq!(Instruction::parse_strict("mask = X"), Err(InstructionError::Mask(MaskError::WrongLength(1))));
+        assert_eq!(Instruction::parse_strict("mem[8] = 11 "), Err(InstructionError::Unrecognized));
+    }
+
+    #[test]
+    fn parse_program_test() {
+        let lines = || vec!(
+            "mask = XXXXXXXXXXXXXXXXXXXXXXXXXXXXX1XXXX0X",
+            "mem[8] = 11",
+            "mask = XXXX",
+            "mem[7] = 101"
+        ).into_iter().map(|s| s.to_owned());
+
+        let lenient = parse_program(lines(), ParseMode::Lenient).unwrap();
+        assert_eq!(lenient.len(), 3);
+
+        let strict = parse_program(lines(), ParseMode::Strict);
+        assert_eq!(strict, Err(InstructionParseError {
+            line_no: 3, line: "mask = XXXX".to_owned(), error: InstructionError::Mask(MaskError::WrongLength(4))
+        }));
+    }
+
+    #[test]
+    fn address_family_test() {
+        let mask = Mask::parse("000000000000000000000000000000X1001X");
+        let family = AddressFamily::new(&mask, 42);
+        assert_eq!(family.size(), 4);
+        let addresses: Vec<u64> = mask.iter_addresses(42).collect();
+        assert!(addresses.iter().all(|a| a & family.fixed == family.bits));
+
+        let other = AddressFamily::new(&Mask::parse("00000000000000000000000000000000X0XX"), 26);
+        assert_eq!(family.intersect(&other).map(|f| f.size()), Some(2));
+        assert_eq!(family.intersect(&AddressFamily::new(&mask, 0)), None);
+    }
+
+    fn example_program() -> Program {
+        Program(parse_program(vec!(
+            "mask = 000000000000000000000000000000X1001X",
+            "mem[42] = 100",
+            "mask = 00000000000000000000000000000000X0XX",
+            "mem[26] = 1"
+        ).into_iter().map(|s| s.to_owned()), ParseMode::Strict).unwrap())
+    }
+
+    #[test]
+    fn final_sum_test() {
+        let program = example_program();
+        assert_eq!(program.final_floating_sum(), 208);
+        assert_eq!(program.final_memory_sum(), program.execute(set_mem).values().sum::<u64>());
+
+        // 34 floating bits, far too many to explode
+        let program = Program(parse_program(vec!(
+            "mask = XXXXXXXXXXXXXXXXXXXXXXXXXXXXX1XXXX0X",
+            "mem[8] = 11",
+            "mem[7] = 101",
+            "mem[8] = 0"
+        ).into_iter().map(|s| s.to_owned()), ParseMode::Strict).unwrap());
+        assert_eq!(program.final_memory_sum(), 165);
+        assert_eq!(program.final_floating_sum(), 101 << 34);
+    }
+
+    // Random programs over few floating bits, from a small linear congruential generator
+    fn sample_programs() -> Vec<Program> {
+        let mut rng = Lcg::new(2020);
+        (0..100).map(|_| {
+            let mut instructions = vec!();
+            for idx in 0..rng.below(30) {
+                // the initial mask floats every bit
+                if idx == 0 || rng.below(4) == 0 {
+                    // one of the low 8 bits floats with probability 1/4, and the rest are fixed
+                    let mask: String = (0..36).rev().map(|bit| match (bit < 8, rng.below(4)) {
+                        (true, 0) => 'X',
+                        (_, 1) => '1',
+                        _ => '0'
+                    }).collect();
+                    instructions.push(Instruction::SetMask(Mask::parse(&mask)));
+                } else {
+                    instructions.push(Instruction::SetMem { address: rng.below(64), value: rng.below(1000) });
+                }
+            }
+            Program(instructions)
+        }).collect()
+    }
+
+    #[test]
+    fn final_sums_agree_with_execution() {
+        for program in sample_programs() {
+            assert_eq!(program.final_memory_sum(), program.execute(set_mem).values().sum::<u64>());
+            assert_eq!(program.final_floating_sum(), program.execute(set_mem_2).values().map(|v| *v as u128).sum::<u128>());
+        }
+    }
+
+    #[test]
+    fn iter_addresses_test() {
         let mask = Mask::parse("000000000000000000000000000000X1001X");
         assert_eq!(mask.floating_bits(), 33);
-        let mut floater = Floater::new(42, &mask);
-        assert_eq!(floater.next(), Some(26));
-        assert_eq!(floater.next(), Some(27));
-        assert_eq!(floater.next(), Some(58));
-        assert_eq!(floater.next(), Some(59));
-        assert_eq!(floater.next(), None);
+        assert_eq!(mask.floating_count(), 2);
+        let mut addresses = mask.iter_addresses(42);
+        assert_eq!(addresses.next(), Some(26));
+        assert_eq!(addresses.next(), Some(27));
+        assert_eq!(addresses.next(), Some(58));
+        assert_eq!(addresses.next(), Some(59));
+        assert_eq!(addresses.next(), None);
 
         let mask = Mask::parse("00000000000000000000000000000000X0XX");
-        let addresses: Vec<u64> = Floater::new(26, &mask).collect();
+        let addresses: Vec<u64> = mask.iter_addresses(26).collect();
         assert_eq!(addresses, vec!(16, 17, 18, 19, 24, 25, 26, 27));
+
+        // in address decoding a 0 bit leaves the address bit alone
+        assert_eq!(Mask::parse(&"0".repeat(36)).iter_addresses(12345).collect::<Vec<u64>>(), vec!(12345));
+        let all_floating = Mask::IDENTITY;
+        assert_eq!(all_floating.floating_count(), 36);
+        assert_eq!(all_floating.iter_addresses(7).take(3).collect::<Vec<u64>>(), vec!(0, 1, 2));
+    }
+
+    #[test]
+    fn apply_value_test() {
+        let mask = Mask::parse("XXXXXXXXXXXXXXXXXXXXXXXXXXXXX1XXXX0X");
+        assert_eq!(mask.apply_value(11), 73);
+        assert_eq!(mask.apply_value(101), 101);
+        assert_eq!(mask.apply_value(0), 64);
+        assert_eq!(Mask::IDENTITY.apply_value(11), 11);
+        assert_eq!(mask.to_string(), "XXXXXXXXXXXXXXXXXXXXXXXXXXXXX1XXXX0X");
+    }
+
+    // Masks over the low 8 bits (the rest X), each bit 0, 1 or X
+    fn sample_masks() -> Vec<Mask> {
+        let mut rng = Lcg::new(2020);
+        (0..30).map(|_| {
+            let low: String = (0..8).map(|_| match rng.below(3) {
+                0 => '0',
+                1 => '1',
+                _ => 'X'
+            }).collect();
+            Mask::parse(&format!("{}{}", "X".repeat(28), low))
+        }).collect()
+    }
+
+    #[test]
+    fn compose_test() {
+        let a = Mask::parse("XXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXX01X1");
+        let b = Mask::parse("XXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXX1X0X");
+        assert_eq!(a.compose(&b).to_string(), "XXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXX1101");
+        assert_eq!(b.compose(&a).to_string(), "XXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXX0101");
+
+        let masks = sample_masks();
+        for a in &masks {
+            assert_eq!(Mask::IDENTITY.compose(a), *a);
+            assert_eq!(a.compose(&Mask::IDENTITY), *a);
+            assert_eq!(a.compose(a), *a);
+            for b in &masks {
+                let ab = a.compose(b);
+                for value in 0..256 {
+                    assert_eq!(ab.apply_value(value), b.apply_value(a.apply_value(value)));
+                }
+                for c in &masks {
+                    assert_eq!(ab.compose(c), a.compose(&b.compose(c)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn net_mask_test() {
+        assert_eq!(example_program().net_mask().to_string(), "00000000000000000000000000000000001X");
+        assert_eq!(Program(vec!()).net_mask(), Mask::IDENTITY);
     }
 }
\ No newline at end of file
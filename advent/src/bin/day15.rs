@@ -1,39 +1,303 @@
 use std::io::prelude::*;
-use std::collections::HashMap;
-
-fn elf_memory_game(inits: &Vec<usize>, turns: usize) -> usize {
-    let mut last_occurrence: HashMap<usize, usize> = HashMap::new();
-    let mut current: usize = match inits.first() {
-        None => {
-            eprintln!("Elf memory game cannot be played without seed numbers!");
-            return 0;
-        },
-        Some(s) => {
-            let mut c = *s;
-            let mut prev = None;
-            for (idx, seed) in inits.iter().enumerate() {
-                if let Some(p) = prev {
-                    last_occurrence.insert(p, idx - 1);
-                }
-                c = *seed;
-                prev = Some(c);
-            }
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::mem::size_of;
+
+use serde::Serialize;
+
+use advent::cli;
+use advent::error::{AdventError, exit_on_error};
+use advent::timing;
+
+// Where each number was last spoken, as a 0-indexed turn.
+trait LastOccurrence {
+    // Records that `number` was spoken on turn `idx`, returning the turn it was last spoken before that.
+    fn replace(&mut self, number: usize, idx: usize) -> Option<usize>;
+
+    // Roughly how many bytes the table holds on to, counting what it has reserved as well as used
+    fn approximate_bytes(&self) -> usize;
+}
+
+// Lets a table be inspected after the game, which otherwise takes ownership of it
+impl<T: LastOccurrence> LastOccurrence for &mut T {
+    fn replace(&mut self, number: usize, idx: usize) -> Option<usize> {
+        (**self).replace(number, idx)
+    }
+
+    fn approximate_bytes(&self) -> usize {
+        (**self).approximate_bytes()
+    }
+}
+
+// A HashMap keeps one control byte per bucket alongside each (key, value)
+fn hash_map_bytes<K, V>(map: &HashMap<K, V>) -> usize {
+    map.capacity() * (size_of::<(K, V)>() + 1)
+}
+
+impl LastOccurrence for HashMap<usize, usize> {
+    fn replace(&mut self, number: usize, idx: usize) -> Option<usize> {
+        self.insert(number, idx)
+    }
+
+    fn approximate_bytes(&self) -> usize {
+        hash_map_bytes(self)
+    }
+}
 
-            c
+// The hash table with u32 numbers and turns, at half the size of the usize one. Numbers and turns
+// are checked to fit, and a game which outgrows them panics rather than wrapping.
+struct CompactTable {
+    turns: HashMap<u32, u32>
+}
+
+impl CompactTable {
+    fn new() -> CompactTable {
+        CompactTable { turns: HashMap::new() }
+    }
+}
+
+impl LastOccurrence for CompactTable {
+    fn replace(&mut self, number: usize, idx: usize) -> Option<usize> {
+        let number = u32::try_from(number).expect("number too large for the compact table");
+        let idx = u32::try_from(idx).expect("turn too large for the compact table");
+        self.turns.insert(number, idx).map(|prev| prev as usize)
+    }
+
+    fn approximate_bytes(&self) -> usize {
+        hash_map_bytes(&self.turns)
+    }
+}
+
+const DENSE_CHUNK: usize = 1 << 20;
+
+// Every number spoken after the seeds is smaller than the number of turns played, so a flat
+// table indexed by number suffices, at 4 bytes per entry rather than a HashMap's ~20.
+// The table grows a chunk at a time, faulting in its pages in bulk as the game reaches them.
+// Turns must fit in a u32; u32::MAX marks a number never spoken.
+struct DenseTable {
+    turns: Vec<u32>
+}
+
+impl DenseTable {
+    fn new() -> DenseTable {
+        DenseTable { turns: vec!() }
+    }
+}
+
+impl LastOccurrence for DenseTable {
+    fn replace(&mut self, number: usize, idx: usize) -> Option<usize> {
+        if number >= self.turns.len() {
+            self.turns.resize((number / DENSE_CHUNK + 1) * DENSE_CHUNK, u32::MAX);
         }
-    };
+        match std::mem::replace(&mut self.turns[number], idx as u32) {
+            u32::MAX => None,
+            prev => Some(prev as usize)
+        }
+    }
 
-    for idx in (inits.len() - 1)..(turns - 1) {
-        match last_occurrence.insert(current, idx) {
-            None => current = 0,
-            Some(prev_idx) => current = idx - prev_idx
-        }        
+    fn approximate_bytes(&self) -> usize {
+        self.turns.capacity() * size_of::<u32>()
     }
-    
-    current
 }
 
-fn main() {
+// A table in an anonymous memory map sized for the whole game up front. The kernel hands out
+// zeroed pages only as they are touched, so turns are stored one-based, with 0 for "never spoken".
+#[cfg(feature = "mmap")]
+struct MappedTable {
+    map: memmap2::MmapMut
+}
+
+#[cfg(feature = "mmap")]
+impl MappedTable {
+    fn new(capacity: usize) -> std::io::Result<MappedTable> {
+        memmap2::MmapMut::map_anon(capacity.max(1) * 4).map(|map| MappedTable { map })
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl LastOccurrence for MappedTable {
+    fn replace(&mut self, number: usize, idx: usize) -> Option<usize> {
+        let cell = &mut self.map[4 * number..4 * number + 4];
+        let prev = u32::from_ne_bytes([cell[0], cell[1], cell[2], cell[3]]);
+        cell.copy_from_slice(&(idx as u32 + 1).to_ne_bytes());
+        match prev {
+            0 => None,
+            prev => Some(prev as usize - 1)
+        }
+    }
+
+    // the whole map, though only the pages touched are backed by memory
+    fn approximate_bytes(&self) -> usize {
+        self.map.len()
+    }
+}
+
+// Watches the game as it is played, e.g. to gather statistics.
+trait Observer {
+    // Called once for each turn (1-indexed) in order; `first` tells whether `number` is spoken for
+    // the first time.
+    fn spoken(&mut self, turn: usize, number: usize, first: bool);
+}
+
+// Not watching at all
+impl Observer for () {
+    fn spoken(&mut self, _turn: usize, _number: usize, _first: bool) {}
+}
+
+// The period and first turn of a repeating stretch at the end of the game
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Cycle {
+    start: usize,
+    period: usize
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct AnalysisReport {
+    turns: usize,
+    max_spoken: usize,
+    new_numbers: usize,
+    new_number_rate: f64,
+    cycle: Option<Cycle>
+}
+
+// Experimental: statistics on the spoken sequence, and a search for a cycle among its last
+// `window` numbers. A cycle only counts once it has repeated three times, and one that began
+// before the window is reported as starting at the window's first turn.
+struct Analysis {
+    turns: usize,
+    max_spoken: usize,
+    new_numbers: usize,
+    window: usize,
+    recent: VecDeque<usize>
+}
+
+impl Analysis {
+    fn new(window: usize) -> Analysis {
+        Analysis { turns: 0, max_spoken: 0, new_numbers: 0, window, recent: VecDeque::new() }
+    }
+
+    fn cycle(&self) -> Option<Cycle> {
+        let n = self.recent.len();
+        let period = (1..=n / 3).find(|&p| (n - 2 * p..n).all(|i| self.recent[i] == self.recent[i - p]))?;
+        let mut start = n - 3 * period;
+        while start > 0 && self.recent[start - 1] == self.recent[start - 1 + period] {
+            start -= 1;
+        }
+        Some(Cycle { start: self.turns - n + start + 1, period })
+    }
+
+    fn report(&self) -> AnalysisReport {
+        AnalysisReport {
+            turns: self.turns,
+            max_spoken: self.max_spoken,
+            new_numbers: self.new_numbers,
+            new_number_rate: if self.turns == 0 { 0.0 } else { self.new_numbers as f64 / self.turns as f64 },
+            cycle: self.cycle()
+        }
+    }
+}
+
+impl Observer for Analysis {
+    fn spoken(&mut self, turn: usize, number: usize, first: bool) {
+        self.turns = turn;
+        self.max_spoken = self.max_spoken.max(number);
+        self.new_numbers += first as usize;
+        if self.recent.len() == self.window {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(number);
+    }
+}
+
+// The memory held by a game's last-occurrence table once it is over, as printed by --stats
+#[derive(Debug, PartialEq, Eq, Serialize)]
+struct MemoryReport {
+    table: String,
+    turns: usize,
+    approximate_bytes: usize
+}
+
+// Plays the game once, up to the largest requested turn, and returns the number spoken on
+// each of `turns` (1-indexed) in the order requested.
+fn elf_memory_game(inits: &Vec<usize>, turns: &[usize]) -> Vec<usize> {
+    play(inits, turns, HashMap::new())
+}
+
+fn play<T: LastOccurrence>(inits: &[usize], turns: &[usize], last_occurrence: T) -> Vec<usize> {
+    play_observed(inits, turns, last_occurrence, &mut ())
+}
+
+// As play, telling `observer` about every number spoken up to the last turn requested (or the last
+// seed, if that is later)
+fn play_observed<T, O>(inits: &[usize], turns: &[usize], mut last_occurrence: T, observer: &mut O) -> Vec<usize>
+where T: LastOccurrence, O: Observer {
+    let mut results = vec![0; turns.len()];
+    if inits.is_empty() {
+        eprintln!("Elf memory game cannot be played without seed numbers!");
+        return results;
+    }
+
+    // (turn, position in `turns`) in the order we will encounter them
+    let mut queries: Vec<(usize, usize)> = turns.iter().enumerate().map(|(pos, turn)| (*turn, pos)).collect();
+    queries.sort();
+    let mut queries = queries.into_iter().peekable();
+
+    while let Some((turn, pos)) = queries.peek().copied() {
+        if turn == 0 {
+            eprintln!("Turns are numbered from 1; ignoring turn 0.");
+        } else if turn <= inits.len() {
+            results[pos] = inits[turn - 1];
+        } else {
+            break
+        }
+        queries.next();
+    }
+
+    for (idx, seed) in inits[..inits.len() - 1].iter().enumerate() {
+        let prev = last_occurrence.replace(*seed, idx);
+        observer.spoken(idx + 1, *seed, prev.is_none());
+    }
+    let mut current = inits[inits.len() - 1];
+
+    // at the top of each iteration, `current` is the number spoken on turn idx + 1
+    let mut idx = inits.len() - 1;
+    for (turn, pos) in queries {
+        while idx + 1 < turn {
+            let prev = last_occurrence.replace(current, idx);
+            observer.spoken(idx + 1, current, prev.is_none());
+            match prev {
+                None => current = 0,
+                Some(prev_idx) => current = idx - prev_idx
+            }
+            idx += 1;
+        }
+        results[pos] = current;
+    }
+    // the last number spoken has not been recorded yet
+    let prev = last_occurrence.replace(current, idx);
+    observer.spoken(idx + 1, current, prev.is_none());
+
+    results
+}
+
+// As play, also reporting the memory `last_occurrence` ends up holding
+fn play_measured<T: LastOccurrence>(inits: &[usize], turns: &[usize], mut last_occurrence: T, table: &str) -> (Vec<usize>, MemoryReport) {
+    let results = timing::time("Memory game", || play(inits, turns, &mut last_occurrence));
+    let report = MemoryReport {
+        table: table.to_owned(),
+        turns: turns.iter().copied().max().unwrap_or(0),
+        approximate_bytes: last_occurrence.approximate_bytes()
+    };
+    (results, report)
+}
+
+fn parse_turns(s: &str) -> Result<Vec<usize>, AdventError> {
+    s.split(',').map(|w| {
+        usize::from_str_radix(w.trim(), 10).map_err(|_| AdventError::input(format!("expected a turn number in --turns, got {}", w.trim())))
+    }).collect()
+}
+
+fn run() -> Result<(), AdventError> {
     let stdin = std::io::stdin();
     let seeds: Vec<usize> = stdin.lock().lines().flatten()
         .flat_map(|line| {
@@ -42,13 +306,64 @@ fn main() {
             us
         })
         .collect();
-    let turns = 2020;
-    let result = elf_memory_game(&seeds, turns);
-    println!("{}th number in the game: {}", turns, result);
+    if seeds.is_empty() {
+        return Err(AdventError::input("expected comma-separated starting numbers"))
+    }
+    let turns = match cli::option_value("--turns") {
+        Some(s) => parse_turns(&s)?,
+        None => vec!(2020, 30_000_000)
+    };
+    let table = cli::option_value("--table").unwrap_or_else(|| "hash".to_owned());
+    let max_turn = turns.iter().copied().max().unwrap_or(0);
+    if table != "hash" && max_turn >= u32::MAX as usize {
+        return Err(AdventError::input(format!("the {} table only supports games of fewer than {} turns", table, u32::MAX)))
+    }
+    if table == "compact" && seeds.iter().any(|seed| u32::try_from(*seed).is_err()) {
+        return Err(AdventError::input(format!("the compact table only supports starting numbers up to {}", u32::MAX)))
+    }
+    // the dense and mapped tables are indexed by number, and sized for the largest
+    let max_seed = seeds.iter().copied().max().unwrap_or(0);
+    let max_indexed = max_turn.max(DENSE_CHUNK);
+    if (table == "dense" || table == "mmap") && max_seed > max_indexed {
+        return Err(AdventError::input(format!("the {} table only supports starting numbers up to {} for these turns; try --table hash",
+            table, max_indexed)))
+    }
+    let (results, memory) = match table.as_str() {
+        "hash" => play_measured(&seeds, &turns, HashMap::new(), &table),
+        "compact" => play_measured(&seeds, &turns, CompactTable::new(), &table),
+        "dense" => play_measured(&seeds, &turns, DenseTable::new(), &table),
+        #[cfg(feature = "mmap")]
+        "mmap" => {
+            let capacity = max_seed.max(max_turn).checked_add(1)
+                .ok_or_else(|| AdventError::input("the game is too large for the mmap table"))?;
+            play_measured(&seeds, &turns, MappedTable::new(capacity)?, &table)
+        },
+        other => return Err(AdventError::input(format!("unknown table `{}`; expected hash, compact, dense{}",
+            other, if cfg!(feature = "mmap") { " or mmap" } else { "" })))
+    };
+    for (turn, result) in turns.iter().zip(results) {
+        println!("{}th number in the game: {}", turn, result);
+    }
+    if cli::has_flag("--stats") {
+        println!("{}", serde_json::to_string_pretty(&memory).unwrap());
+    }
 
-    let turns = 30_000_000;
-    let result = elf_memory_game(&seeds, turns);
-    println!("{}th number in the game: {}", turns, result);
+    if cli::has_flag("--analyze") {
+        let mut analysis = Analysis::new(1 << 16);
+        timing::time("Analysis", || play_observed(&seeds, &turns, HashMap::new(), &mut analysis));
+        let report = analysis.report();
+        println!("Over {} turns, the largest number spoken was {}; {} numbers were new ({:.2}% of turns).",
+            report.turns, report.max_spoken, report.new_numbers, 100.0 * report.new_number_rate);
+        match report.cycle {
+            Some(Cycle { start, period }) => println!("From turn {} on, the numbers repeat every {} turns.", start, period),
+            None => println!("No cycle found in the last {} turns.", report.turns.min(analysis.window))
+        }
+    }
+    Ok(())
+}
+
+fn main() {
+    exit_on_error(run())
 }
 
 #[cfg(test)]
@@ -57,11 +372,134 @@ mod day15_spec {
 
     #[test]
     fn elf_game_test() {
-        assert_eq!(elf_memory_game(&vec!(1,3,2), 2020), 1);
-        assert_eq!(elf_memory_game(&vec!(2,1,3), 2020), 10);
-        assert_eq!(elf_memory_game(&vec!(1,2,3), 2020), 27);
-        assert_eq!(elf_memory_game(&vec!(2,3,1), 2020), 78);
-        assert_eq!(elf_memory_game(&vec!(3,2,1), 2020), 438);
-        assert_eq!(elf_memory_game(&vec!(3,1,2), 2020), 1836);
+        assert_eq!(elf_memory_game(&vec!(1,3,2), &[2020]), vec!(1));
+        assert_eq!(elf_memory_game(&vec!(2,1,3), &[2020]), vec!(10));
+        assert_eq!(elf_memory_game(&vec!(1,2,3), &[2020]), vec!(27));
+        assert_eq!(elf_memory_game(&vec!(2,3,1), &[2020]), vec!(78));
+        assert_eq!(elf_memory_game(&vec!(3,2,1), &[2020]), vec!(438));
+        assert_eq!(elf_memory_game(&vec!(3,1,2), &[2020]), vec!(1836));
+    }
+
+    #[test]
+    fn elf_game_multi_query_test() {
+        // 0, 3, 6, 0, 3, 3, 1, 0, 4, 0, ...
+        let seeds = vec!(0, 3, 6);
+        assert_eq!(elf_memory_game(&seeds, &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]), vec!(0, 3, 6, 0, 3, 3, 1, 0, 4, 0));
+        assert_eq!(elf_memory_game(&seeds, &[10, 2020, 4]), vec!(0, 436, 0));
+        assert_eq!(elf_memory_game(&seeds, &[2020, 2020]), vec!(436, 436));
+        assert_eq!(elf_memory_game(&seeds, &[]), Vec::<usize>::new());
+        assert_eq!(elf_memory_game(&vec!(), &[1, 2]), vec!(0, 0));
+    }
+
+    #[test]
+    fn dense_table_test() {
+        let seeds = vec!(0, 3, 6);
+        let turns = [1, 4, 9, 2020, 30_000];
+        assert_eq!(play(&seeds, &turns, DenseTable::new()), elf_memory_game(&seeds, &turns));
+        // seeds beyond the first chunk
+        let seeds = vec!(DENSE_CHUNK + 5, 1, DENSE_CHUNK + 5);
+        assert_eq!(play(&seeds, &[4, 5, 2020], DenseTable::new()), elf_memory_game(&seeds, &[4, 5, 2020]));
+    }
+
+    #[test]
+    fn compact_table_test() {
+        let seeds = vec!(0, 3, 6);
+        let turns = [1, 4, 9, 2020, 30_000];
+        assert_eq!(play(&seeds, &turns, CompactTable::new()), elf_memory_game(&seeds, &turns));
+        let seeds = vec!(u32::MAX as usize, 1, u32::MAX as usize);
+        assert_eq!(play(&seeds, &[4, 5, 2020], CompactTable::new()), elf_memory_game(&seeds, &[4, 5, 2020]));
+    }
+
+    #[test]
+    #[should_panic(expected = "number too large for the compact table")]
+    fn compact_table_overflow_test() {
+        play(&[u32::MAX as usize + 1, 1], &[3], CompactTable::new());
+    }
+
+    #[test]
+    fn play_measured_test() {
+        let seeds = vec!(0, 3, 6);
+        let turns = [2020, 30_000];
+        let (hash, hash_report) = play_measured(&seeds, &turns, HashMap::new(), "hash");
+        let (compact, compact_report) = play_measured(&seeds, &turns, CompactTable::new(), "compact");
+        assert_eq!(hash, compact);
+        assert_eq!((hash_report.turns, compact_report.turns), (30_000, 30_000));
+        // the same buckets, with (u32, u32) entries in place of (usize, usize)
+        assert_eq!(compact_report.approximate_bytes * 17, hash_report.approximate_bytes * 9);
+
+        let (_, dense_report) = play_measured(&seeds, &turns, DenseTable::new(), "dense");
+        assert_eq!(dense_report.approximate_bytes, 4 * DENSE_CHUNK);
+        assert_eq!(serde_json::to_string(&dense_report).unwrap(),
+            format!(r#"{{"table":"dense","turns":30000,"approximate_bytes":{}}}"#, 4 * DENSE_CHUNK));
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn mapped_table_test() {
+        let seeds = vec!(0, 3, 6);
+        let turns = [1, 4, 9, 2020, 30_000];
+        assert_eq!(play(&seeds, &turns, MappedTable::new(30_000).unwrap()), elf_memory_game(&seeds, &turns));
+    }
+
+    // Records the whole game
+    impl Observer for Vec<(usize, usize, bool)> {
+        fn spoken(&mut self, turn: usize, number: usize, first: bool) {
+            self.push((turn, number, first));
+        }
+    }
+
+    #[test]
+    fn observer_test() {
+        let mut spoken = vec!();
+        assert_eq!(play_observed(&[0, 3, 6], &[7, 4], HashMap::new(), &mut spoken), vec!(1, 0));
+        assert_eq!(spoken, vec!(
+            (1, 0, true), (2, 3, true), (3, 6, true), (4, 0, false), (5, 3, false), (6, 3, false), (7, 1, true)
+        ));
+
+        // every seed is seen, even if no turn asked for comes after them
+        let mut spoken = vec!();
+        play_observed(&[2, 1, 2], &[1], DenseTable::new(), &mut spoken);
+        assert_eq!(spoken, vec!((1, 2, true), (2, 1, true), (3, 2, false)));
+    }
+
+    #[test]
+    fn analysis_test() {
+        let mut analysis = Analysis::new(1000);
+        assert_eq!(play_observed(&[0, 3, 6], &[2020], HashMap::new(), &mut analysis), vec!(436));
+        let report = analysis.report();
+        assert_eq!(report.turns, 2020);
+        let sequence = {
+            let mut spoken = vec!();
+            play_observed(&[0, 3, 6], &[2020], HashMap::new(), &mut spoken);
+            spoken
+        };
+        assert_eq!(report.max_spoken, sequence.iter().map(|(_, number, _)| *number).max().unwrap());
+        assert_eq!(report.new_numbers, sequence.iter().filter(|(_, _, first)| *first).count());
+        assert_eq!(report.cycle, None);
+        assert_eq!(analysis.recent.len(), 1000);
+
+        // once 1 follows itself, it is spoken forever
+        let mut analysis = Analysis::new(10);
+        play_observed(&[4, 1, 1], &[30], HashMap::new(), &mut analysis);
+        assert_eq!(analysis.report().cycle, Some(Cycle { start: 21, period: 1 }));
+        let mut analysis = Analysis::new(100);
+        play_observed(&[4, 1, 1], &[30], HashMap::new(), &mut analysis);
+        let report = analysis.report();
+        assert_eq!(report.cycle, Some(Cycle { start: 2, period: 1 }));
+        assert_eq!((report.new_numbers, report.max_spoken), (2, 4));
+
+        let mut analysis = Analysis::new(10);
+        for (turn, number) in vec!(5, 1, 2, 3, 1, 2, 3, 1, 2, 3).into_iter().enumerate() {
+            analysis.spoken(turn + 1, number, turn < 4);
+        }
+        assert_eq!(analysis.cycle(), Some(Cycle { start: 2, period: 3 }));
+        assert_eq!(Analysis::new(10).report().cycle, None);
+    }
+
+    #[test]
+    fn parse_turns_test() {
+        assert_eq!(parse_turns("2020,30000000,1000000000").unwrap(), vec!(2020, 30_000_000, 1_000_000_000));
+        assert_eq!(parse_turns("2020, 10").unwrap(), vec!(2020, 10));
+        assert_eq!(parse_turns("2020, x,10").unwrap_err().to_string(), "Invalid input: expected a turn number in --turns, got x");
     }
 }
\ No newline at end of file
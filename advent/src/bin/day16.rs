@@ -1,36 +1,97 @@
 use std::io::prelude::*;
 use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
 
 #[macro_use]
 extern crate lazy_static;
 
 use regex::Regex;
 
+use advent::cli;
+use advent::error::{AdventError, exit_on_error};
+use advent::table::Table;
+use advent::timing;
+
+// The name of a ticket field, e.g. "departure station". Related fields share a leading word,
+// which prefix queries match a whole word at a time.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct FieldName(String);
+
+impl FieldName {
+    fn new(name: &str) -> FieldName {
+        FieldName(name.to_owned())
+    }
+
+    fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    // True if the name begins with the words of `prefix`: "departure" matches "departure time"
+    // and "departure", but not "departures".
+    fn has_prefix(&self, prefix: &str) -> bool {
+        match self.as_str().strip_prefix(prefix) {
+            Some(rest) => rest.is_empty() || prefix.is_empty() || rest.starts_with(' '),
+            None => false
+        }
+    }
+
+    fn is_departure(&self) -> bool {
+        self.has_prefix("departure")
+    }
+}
+
+impl Display for FieldName {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 struct FieldRule {
-    field_name: String,
+    field_name: FieldName,
     r0_min: usize,
     r0_max: usize,
     r1_min: usize,
     r1_max: usize
 }
 
+#[derive(Debug, PartialEq, Eq)]
+struct FieldRuleParseError {
+    line: String,
+    reason: String
+}
+
 impl FieldRule {
-    fn parse(line: &str) -> Option<FieldRule> {
+    // Accepts e.g. "class: 1-3 or 5-7", with optional whitespace around the separators.
+    // A range may also be written half-open as "a..b", i.e. a <= x < b.
+    fn parse(line: &str) -> Result<FieldRule, FieldRuleParseError> {
         lazy_static! {
-            static ref RULE_PAT: Regex = Regex::new(r"(.+): (\d+)-(\d+) or (\d+)-(\d+)").unwrap();
+            static ref RULE_PAT: Regex = Regex::new(
+                r"^\s*([^:]+?)\s*:\s*(\d+)\s*(-|\.\.)\s*(\d+)\s+or\s+(\d+)\s*(-|\.\.)\s*(\d+)\s*$"
+            ).unwrap();
         }
-        
-        let r: Option<FieldRule> = RULE_PAT.captures(line).and_then(|caps| {
-            let field_name = caps[1].to_owned();
-            usize::from_str_radix(&caps[2], 10).ok().and_then(|r0_min| {
-            usize::from_str_radix(&caps[3], 10).ok().and_then(|r0_max| {
-            usize::from_str_radix(&caps[4], 10).ok().and_then(|r1_min| {
-            usize::from_str_radix(&caps[5], 10).ok().map(|r1_max| {
-                FieldRule { field_name, r0_min, r0_max, r1_min, r1_max }
-            }) }) }) })
-        });
 
-        r
+        let err = |reason: String| FieldRuleParseError { line: line.to_owned(), reason };
+
+        // Converts the bounds as written to an inclusive (min, max) pair
+        fn range(lo: &str, sep: &str, hi: &str) -> Result<(usize, usize), String> {
+            let lo_n = usize::from_str_radix(lo, 10).map_err(|e| format!("Bad bound {}: {}", lo, e))?;
+            let hi_n = usize::from_str_radix(hi, 10).map_err(|e| format!("Bad bound {}: {}", hi, e))?;
+            let max = if sep == ".." {
+                hi_n.checked_sub(1).filter(|max| *max >= lo_n).ok_or_else(|| format!("Empty range {}..{}", lo, hi))?
+            } else {
+                hi_n
+            };
+            if lo_n > max {
+                return Err(format!("Reversed range {}-{}", lo, hi))
+            }
+            Ok((lo_n, max))
+        }
+
+        let caps = RULE_PAT.captures(line).ok_or_else(|| err("Expected `<field>: <min>-<max> or <min>-<max>`".to_owned()))?;
+        let field_name = FieldName::new(&caps[1]);
+        let (r0_min, r0_max) = range(&caps[2], &caps[3], &caps[4]).map_err(err)?;
+        let (r1_min, r1_max) = range(&caps[5], &caps[6], &caps[7]).map_err(err)?;
+        Ok(FieldRule { field_name, r0_min, r0_max, r1_min, r1_max })
     }
 
     fn valid(&self, x: usize) -> bool {
@@ -72,183 +133,215 @@ impl Ticket {
     }
 }
 
-fn identify_fields<'a>(field_rules: &'a Vec<FieldRule>, valid_tickets: &Vec<Ticket>) -> Option<Vec<&'a FieldRule>> {
-    #[derive(Clone, Copy)]
-    enum Candidate {
-        Eliminated,
-        Possible,
-        Committed
+// The result of field identification: entry `i` is the rule governing field `i` of every ticket.
+struct FieldAssignment<'a>(Vec<&'a FieldRule>);
+
+impl<'a> FieldAssignment<'a> {
+    fn mapping_table(&self, ticket: &Ticket) -> Table {
+        let mut table = Table::new(&["field", "rule", "value"]);
+        for (field_idx, rule) in self.0.iter().enumerate() {
+            let value = ticket.0.get(field_idx).map(|v| v.to_string()).unwrap_or_default();
+            table.add_row(vec!(field_idx.to_string(), rule.field_name.to_string(), value));
+        }
+        table
     }
 
-    enum Instruction {
-        Eliminate(usize, usize), // field_idx, rule_idx
-        Commit(usize, usize)
+    // The (index, value on `ticket`) of every field whose name satisfies `predicate`
+    fn fields_where<F>(&self, ticket: &Ticket, predicate: F) -> Vec<(usize, usize)> where F: Fn(&FieldName) -> bool {
+        self.0.iter().zip(&ticket.0).enumerate()
+            .filter(|(_, (rule, _))| predicate(&rule.field_name))
+            .map(|(field_idx, (_, value))| (field_idx, *value))
+            .collect()
     }
 
-    struct Candidates{
-        cs: Vec<Vec<Candidate>>,
-        queue: VecDeque<Instruction>
+    fn fields_with_prefix(&self, ticket: &Ticket, prefix: &str) -> Vec<(usize, usize)> {
+        self.fields_where(ticket, |name| name.has_prefix(prefix))
     }
+}
 
-    impl std::fmt::Display for Candidates {
-        fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-            let mut s = String::new();
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Candidate {
+    Eliminated,
+    Possible,
+    Committed
+}
 
-            for row in &self.cs {
-                for j in (0..=row.len()).step_by(8) {
-                    for c in &row[j..(j+8).min(row.len())] {
-                        match c {
-                            Candidate::Eliminated => s.push('.'),
-                            Candidate::Possible => s.push('*'),
-                            Candidate::Committed => s.push('C')
-                        }    
-                    }
-                    s.push(' ');
-                }
-                s.push('\n');
-            }
+enum Instruction {
+    Eliminate(usize, usize), // field_idx, rule_idx
+    Commit(usize, usize)
+}
 
-            write!(formatter, "{}", s)
-        }
-    }
+// Which rules may still govern which fields: entry (i, j) is for field i and rule j.
+struct Candidates {
+    cs: Vec<Vec<Candidate>>,
+    queue: VecDeque<Instruction>
+}
 
-    impl Candidates {
-        fn new(n: usize) -> Candidates {
-            let cs = vec![vec![Candidate::Possible; n]; n];
-            let queue = VecDeque::new();
-            Candidates { cs, queue }
-        }
+impl Candidates {
+    fn new(n: usize) -> Candidates {
+        let cs = vec![vec![Candidate::Possible; n]; n];
+        let queue = VecDeque::new();
+        Candidates { cs, queue }
+    }
 
-        fn eliminate(&mut self, field_idx: usize, rule_idx: usize) -> Result<(), String> {
-            match self.cs[field_idx].get_mut(rule_idx) {
-                Some(Candidate::Committed) => return Err("Inconsistency".to_owned()),
-                Some(Candidate::Eliminated) => return Ok(()),
-                Some(p@Candidate::Possible) => {
-                    *p = Candidate::Eliminated;
-                    if let SearchResult::Single(other_rule_idx) = self.search_row(field_idx) {
-                        self.queue.push_back(Instruction::Commit(field_idx, other_rule_idx));
+    // Eliminates each rule violated by some field of some ticket, committing fields to rules as
+    // the eliminations force them. On an inconsistency, the matrix is left as it was when found.
+    fn eliminate_invalid(&mut self, field_rules: &[FieldRule], valid_tickets: &[Ticket]) -> Result<(), String> {
+        for ticket in valid_tickets {
+            for (field_idx, field) in ticket.0.iter().enumerate() {
+                for (rule_idx, rule) in field_rules.iter().enumerate() {
+                    if !rule.valid(*field) {
+                        self.eliminate(field_idx, rule_idx)?;
                     }
-                    if let SearchResult::Single(other_field_idx) = self.search_column(rule_idx) {
-                        self.queue.push_back(Instruction::Commit(other_field_idx, rule_idx));
-                    }
-                },
-                None => return Err("Out of bounds?".to_owned())
+                }
             }
-
-            self.clear_queue()
         }
+        Ok(())
+    }
 
-        fn commit(&mut self, field_idx: usize, rule_idx: usize) -> Result<(), String> {
-            match self.cs[field_idx].get_mut(rule_idx) {
-                Some(Candidate::Eliminated) => return Err("Inconsistency".to_owned()),
-                Some(Candidate::Committed) => return Ok(()),
-                Some(p@Candidate::Possible) => {
-                    *p = Candidate::Committed;
-                    for idx in 0..self.cs.len() {
-                        if idx != field_idx {
-                            self.queue.push_back(Instruction::Eliminate(idx, rule_idx));
-                        }
-                        if idx != rule_idx {
-                            self.queue.push_back(Instruction::Eliminate(field_idx, idx));
-                        }
-                    }
-                },
-                None => return Err("Out of bounds?".to_owned())
-            }
+    // The committed rule for every field, if each has one
+    fn assignment<'a>(&self, field_rules: &'a [FieldRule]) -> Option<FieldAssignment<'a>> {
+        self.cs.iter().map(|row| {
+            row.iter().position(|c| *c == Candidate::Committed).map(|rule_idx| &field_rules[rule_idx])
+        }).collect::<Option<Vec<_>>>().map(FieldAssignment)
+    }
 
-            self.clear_queue()
+    fn symbol(c: Candidate) -> char {
+        match c {
+            Candidate::Eliminated => '.',
+            Candidate::Possible => '*',
+            Candidate::Committed => 'C'
         }
+    }
 
-        fn clear_queue(&mut self) -> Result<(), String> {
-            while let Some(instruction) = self.queue.pop_front() {
-                match instruction {
-                    Instruction::Eliminate(field_idx, rule_idx) => {
-                        if let Err(msg) = self.eliminate(field_idx, rule_idx) {
-                            return Err(msg)
-                        }
-                    },
-                    Instruction::Commit(field_idx, rule_idx) => {
-                        if let Err(msg) = self.commit(field_idx, rule_idx) {
-                            return Err(msg)
-                        }
-                    }
-                }
-            }
+    // One line per field, with '.' for an eliminated rule, '*' for a possible one and 'C' for the
+    // committed one, in groups of eight rules.
+    fn to_string_grid(&self) -> String {
+        let mut s = String::new();
+        for row in &self.cs {
+            let groups: Vec<String> = row.chunks(8).map(|group| group.iter().map(|c| Candidates::symbol(*c)).collect()).collect();
+            s.push_str(&groups.join(" "));
+            s.push('\n');
+        }
+        s
+    }
 
-            Ok(())
+    // As to_string_grid, with a header row of rule indices and a leading column of field indices
+    fn to_csv<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        let header: Vec<String> = (0..self.cs.len()).map(|rule_idx| rule_idx.to_string()).collect();
+        writeln!(writer, "field,{}", header.join(","))?;
+        for (field_idx, row) in self.cs.iter().enumerate() {
+            let cells: Vec<String> = row.iter().map(|c| Candidates::symbol(*c).to_string()).collect();
+            writeln!(writer, "{},{}", field_idx, cells.join(","))?;
         }
+        Ok(())
+    }
 
-        fn search_row(&self, field_idx: usize) -> SearchResult {
-            let mut r = SearchResult::Empty;
-
-            for (rule_idx, c) in self.cs[field_idx].iter().enumerate() {
-                match (&r, c) {
-                    (_, Candidate::Committed) => return SearchResult::Committed(rule_idx),
-                    (SearchResult::Empty, Candidate::Possible) =>
-                        r = SearchResult::Single(rule_idx),
-                    (SearchResult::Single(_), Candidate::Possible) =>
-                        // Gotcha alert! Because possibles may not be cleared at the same time as commits are made,
-                        // we might end up returning SearchResult::Committed
-                        r = SearchResult::Multiple,
-                    _ => ()
+    fn eliminate(&mut self, field_idx: usize, rule_idx: usize) -> Result<(), String> {
+        match self.cs[field_idx].get_mut(rule_idx) {
+            Some(Candidate::Committed) => return Err("Inconsistency".to_owned()),
+            Some(Candidate::Eliminated) => return Ok(()),
+            Some(p@Candidate::Possible) => {
+                *p = Candidate::Eliminated;
+                if let SearchResult::Single(other_rule_idx) = self.search_row(field_idx) {
+                    self.queue.push_back(Instruction::Commit(field_idx, other_rule_idx));
                 }
-            }
-
-            r
+                if let SearchResult::Single(other_field_idx) = self.search_column(rule_idx) {
+                    self.queue.push_back(Instruction::Commit(other_field_idx, rule_idx));
+                }
+            },
+            None => return Err("Out of bounds?".to_owned())
         }
 
-        fn search_column(&self, rule_idx: usize) -> SearchResult {
-            let mut r = SearchResult::Empty;
+        self.clear_queue()
+    }
 
-            for field_idx in 0..self.cs.len() {
-                match (&r, self.cs[field_idx][rule_idx]) {
-                    (_, Candidate::Committed) => return SearchResult::Committed(field_idx),
-                    (SearchResult::Empty, Candidate::Possible) => r = SearchResult::Single(field_idx),
-                    (SearchResult::Single(_), Candidate::Possible) => r = SearchResult::Multiple,
-                    _ => ()
+    fn commit(&mut self, field_idx: usize, rule_idx: usize) -> Result<(), String> {
+        match self.cs[field_idx].get_mut(rule_idx) {
+            Some(Candidate::Eliminated) => return Err("Inconsistency".to_owned()),
+            Some(Candidate::Committed) => return Ok(()),
+            Some(p@Candidate::Possible) => {
+                *p = Candidate::Committed;
+                for idx in 0..self.cs.len() {
+                    if idx != field_idx {
+                        self.queue.push_back(Instruction::Eliminate(idx, rule_idx));
+                    }
+                    if idx != rule_idx {
+                        self.queue.push_back(Instruction::Eliminate(field_idx, idx));
+                    }
                 }
-            }
-
-            r
+            },
+            None => return Err("Out of bounds?".to_owned())
         }
-    }
 
-    enum SearchResult {
-        Empty,
-        Single(usize), // used when we have not yet realized that we have eliminated all but one in the row/column
-        Committed(usize),
-        Multiple
+        self.clear_queue()
     }
 
-    let mut candidates = Candidates::new(field_rules.len());
-
-    for ticket in valid_tickets {
-        for (field_idx, field) in ticket.0.iter().enumerate() {
-            for (rule_idx, rule) in field_rules.iter().enumerate() {
-                if !rule.valid(*field) {
-                    if let Err(_) = candidates.eliminate(field_idx, rule_idx) {
-                        return None
+    fn clear_queue(&mut self) -> Result<(), String> {
+        while let Some(instruction) = self.queue.pop_front() {
+            match instruction {
+                Instruction::Eliminate(field_idx, rule_idx) => {
+                    if let Err(msg) = self.eliminate(field_idx, rule_idx) {
+                        return Err(msg)
+                    }
+                },
+                Instruction::Commit(field_idx, rule_idx) => {
+                    if let Err(msg) = self.commit(field_idx, rule_idx) {
+                        return Err(msg)
                     }
                 }
             }
         }
+
+        Ok(())
     }
 
-    println!("Candidates:\n{}", candidates);
+    fn search_row(&self, field_idx: usize) -> SearchResult {
+        let mut r = SearchResult::Empty;
+
+        for (rule_idx, c) in self.cs[field_idx].iter().enumerate() {
+            match (&r, c) {
+                (_, Candidate::Committed) => return SearchResult::Committed(rule_idx),
+                (SearchResult::Empty, Candidate::Possible) =>
+                    r = SearchResult::Single(rule_idx),
+                (SearchResult::Single(_), Candidate::Possible) =>
+                    // Gotcha alert! Because possibles may not be cleared at the same time as commits are made,
+                    // we might end up returning SearchResult::Committed
+                    r = SearchResult::Multiple,
+                _ => ()
+            }
+        }
 
-    let mut ret: Vec<&'a FieldRule> = vec!();
+        r
+    }
 
-    'r: for row in candidates.cs {
-        for (rule_idx, c) in row.iter().enumerate() {
-            if let Candidate::Committed = c {
-                ret.push(&field_rules[rule_idx]);
-                continue 'r
+    fn search_column(&self, rule_idx: usize) -> SearchResult {
+        let mut r = SearchResult::Empty;
+
+        for field_idx in 0..self.cs.len() {
+            match (&r, self.cs[field_idx][rule_idx]) {
+                (_, Candidate::Committed) => return SearchResult::Committed(field_idx),
+                (SearchResult::Empty, Candidate::Possible) => r = SearchResult::Single(field_idx),
+                (SearchResult::Single(_), Candidate::Possible) => r = SearchResult::Multiple,
+                _ => ()
             }
         }
-        return None
+
+        r
     }
+}
 
-    Some(ret)
+enum SearchResult {
+    Empty,
+    Single(usize), // used when we have not yet realized that we have eliminated all but one in the row/column
+    Committed(usize),
+    Multiple
+}
+
+fn identify_fields<'a>(field_rules: &'a [FieldRule], valid_tickets: &[Ticket]) -> Option<FieldAssignment<'a>> {
+    let mut candidates = Candidates::new(field_rules.len());
+    candidates.eliminate_invalid(field_rules, valid_tickets).ok()?;
+    candidates.assignment(field_rules)
 }
 
 fn eat_line<J>(j: &mut J, expected: &str) where J: Iterator<Item=String> {
@@ -261,61 +354,182 @@ fn eat_line<J>(j: &mut J, expected: &str) where J: Iterator<Item=String> {
     }
 }
 
-fn main() {
-    let stdin = std::io::stdin();
-    let mut stdin_lines = stdin.lock().lines().flatten();
+// One section of notes, laid out as in the puzzle input: the field rules, my ticket, and the
+// nearby tickets, of which only the valid ones are kept.
+struct Notes {
+    field_rules: Vec<FieldRule>,
+    my_ticket: Ticket,
+    valid_tickets: Vec<Ticket>,
+    scanning_error_rate: usize
+}
 
-    println!("Getting field rules:");
-    let mut field_rules: Vec<FieldRule> = vec!();
-    while let Some(line) = stdin_lines.next() {
-        if line.is_empty() {
-            break
-        } else {
-            for field_rule in FieldRule::parse(&line) {
-                field_rules.push(field_rule);
+impl Notes {
+    fn parse<J>(mut lines: J) -> Result<Notes, AdventError> where J: Iterator<Item=String> {
+        let mut field_rules: Vec<FieldRule> = vec!();
+        let mut rule_errors: Vec<FieldRuleParseError> = vec!();
+        while let Some(line) = lines.next() {
+            if line.is_empty() {
+                break
+            } else {
+                match FieldRule::parse(&line) {
+                    Ok(field_rule) => field_rules.push(field_rule),
+                    Err(e) => rule_errors.push(e)
+                }
             }
         }
-    }
-    println!("{} field rules parsed.", field_rules.len());
+        if !rule_errors.is_empty() {
+            for e in &rule_errors {
+                eprintln!("Malformed field rule `{}`: {}", e.line, e.reason);
+            }
+            return Err(AdventError::input(format!("{} malformed field rules", rule_errors.len())))
+        }
 
-    eat_line(&mut stdin_lines, "your ticket:");
-    let my_ticket = stdin_lines.next().map(|line| Ticket::parse(&line)).unwrap();
-    if my_ticket.len() != field_rules.len() {
-        eprintln!("Length mismatch: {} field rules but {} fields.", field_rules.len(), my_ticket.len());
-    }
+        eat_line(&mut lines, "your ticket:");
+        let my_ticket = lines.next().map(|line| Ticket::parse(&line))
+            .ok_or_else(|| AdventError::input("missing your ticket"))?;
+        if my_ticket.len() != field_rules.len() {
+            eprintln!("Length mismatch: {} field rules but {} fields.", field_rules.len(), my_ticket.len());
+        }
 
-    eat_line(&mut stdin_lines, "");
-    eat_line(&mut stdin_lines, "nearby tickets:");
+        eat_line(&mut lines, "");
+        eat_line(&mut lines, "nearby tickets:");
 
-    let mut nearby_tickets = vec!();
-    let mut scanning_error_rate = 0;
-    for line in stdin_lines {
-        let ticket = Ticket::parse(&line);
-        if ticket.len() != field_rules.len() {
-            eprintln!("Length mismatch: {} field rules but {} fields.", field_rules.len(), ticket.len());
-        }
+        let mut valid_tickets = vec!();
+        let mut scanning_error_rate = 0;
+        for line in lines.filter(|line| !line.is_empty()) {
+            let ticket = Ticket::parse(&line);
+            if ticket.len() != field_rules.len() {
+                eprintln!("Length mismatch: {} field rules but {} fields.", field_rules.len(), ticket.len());
+            }
 
-        if let Some(bad_indices) = ticket.is_corrupt(&field_rules) {
-            for idx in bad_indices {
-                scanning_error_rate += ticket.0[idx]
+            if let Some(bad_indices) = ticket.is_corrupt(&field_rules) {
+                for idx in bad_indices {
+                    scanning_error_rate += ticket.0[idx]
+                }
+            } else {
+                valid_tickets.push(ticket)
             }
+        }
+
+        Ok(Notes { field_rules, my_ticket, valid_tickets, scanning_error_rate })
+    }
+
+    fn departure_product(&self, assignment: &FieldAssignment) -> usize {
+        assignment.fields_where(&self.my_ticket, FieldName::is_departure).iter().map(|(_, value)| value).product()
+    }
+}
+
+// Splits the input into sections, each introduced by a header line `[name]`. Any lines before the
+// first header form an unnamed section, so input without headers is a single unnamed section.
+fn split_sections<J>(lines: J) -> Vec<(Option<String>, Vec<String>)> where J: Iterator<Item=String> {
+    lazy_static! {
+        static ref HEADER_PAT: Regex = Regex::new(r"^\[(.*)\]$").unwrap();
+    }
+
+    let mut sections: Vec<(Option<String>, Vec<String>)> = vec!();
+    for line in lines {
+        if let Some(caps) = HEADER_PAT.captures(line.trim()) {
+            sections.push((Some(caps[1].trim().to_owned()), vec!()));
         } else {
-            nearby_tickets.push(ticket)
+            match sections.last_mut() {
+                Some((_, section_lines)) => section_lines.push(line),
+                None if line.trim().is_empty() => (),
+                None => sections.push((None, vec!(line)))
+            }
         }
     }
-    println!("{} valid nearby tickets parsed; scanning error rate: {}", nearby_tickets.len(), scanning_error_rate);
+    sections
+}
 
-    let ordered_fields = identify_fields(&field_rules, &nearby_tickets).unwrap();
+// The candidate matrix, as CSV if `path` ends in .csv and as the text grid otherwise
+fn write_matrix(candidates: &Candidates, path: &str) -> Result<(), AdventError> {
+    let mut file = std::fs::File::create(path)?;
+    if path.ends_with(".csv") {
+        candidates.to_csv(&mut file)?;
+    } else {
+        file.write_all(candidates.to_string_grid().as_bytes())?;
+    }
+    Ok(())
+}
 
-    let mut prod = 1;
+fn solve_single<J>(lines: J) -> Result<(), AdventError> where J: Iterator<Item=String> {
+    println!("Getting field rules:");
+    let notes = Notes::parse(lines)?;
+    println!("{} field rules parsed.", notes.field_rules.len());
+    println!("{} valid nearby tickets parsed; scanning error rate: {}", notes.valid_tickets.len(), notes.scanning_error_rate);
+
+    let mut candidates = Candidates::new(notes.field_rules.len());
+    let eliminated = timing::time("Part 2", || candidates.eliminate_invalid(&notes.field_rules, &notes.valid_tickets));
+    // written before reporting any inconsistency, which is when it is most wanted
+    if let Some(path) = cli::option_value("--debug-matrix") {
+        write_matrix(&candidates, &path)?;
+    }
+    let assignment = eliminated.ok().and_then(|_| candidates.assignment(&notes.field_rules))
+        .ok_or_else(|| AdventError::no_solution("unable to identify ticket fields"))?;
+
+    if cli::has_flag("--show-mapping") {
+        print!("{}", assignment.mapping_table(&notes.my_ticket));
+    }
 
-    for (field, rule) in my_ticket.0.iter().zip(ordered_fields) {
-        if rule.field_name.starts_with("departure") {
-            prod *= field
+    if let Some(prefix) = cli::option_value("--prefix") {
+        for (field_idx, value) in assignment.fields_with_prefix(&notes.my_ticket, &prefix) {
+            println!("Field {} ({}): {}", field_idx, assignment.0[field_idx].field_name, value);
         }
     }
 
-    println!("Departure fields product: {}", prod);
+    println!("Departure fields product: {}", notes.departure_product(&assignment));
+    Ok(())
+}
+
+// Solves each section independently; a section which fails to parse or solve is reported in the
+// summary rather than stopping the batch.
+fn solve_batch(sections: Vec<(Option<String>, Vec<String>)>) -> Result<(), AdventError> {
+    let mut summary = Table::new(&["section", "rules", "valid tickets", "error rate", "departure product"]);
+
+    for (idx, (name, lines)) in sections.into_iter().enumerate() {
+        let name = name.unwrap_or_else(|| format!("#{}", idx + 1));
+        println!("[{}]", name);
+        let row = match Notes::parse(lines.into_iter()) {
+            Ok(notes) => {
+                let product = match timing::time(&name, || identify_fields(&notes.field_rules, &notes.valid_tickets)) {
+                    Some(assignment) => {
+                        if cli::has_flag("--show-mapping") {
+                            print!("{}", assignment.mapping_table(&notes.my_ticket));
+                        }
+                        notes.departure_product(&assignment).to_string()
+                    },
+                    None => "unidentified".to_owned()
+                };
+                vec!(
+                    name,
+                    notes.field_rules.len().to_string(),
+                    notes.valid_tickets.len().to_string(),
+                    notes.scanning_error_rate.to_string(),
+                    product
+                )
+            },
+            Err(e) => vec!(name, "-".to_owned(), "-".to_owned(), "-".to_owned(), e.to_string())
+        };
+        summary.add_row(row);
+    }
+
+    print!("{}", summary);
+    Ok(())
+}
+
+fn run() -> Result<(), AdventError> {
+    let stdin = std::io::stdin();
+    let mut sections = split_sections(stdin.lock().lines().flatten());
+
+    match sections.as_slice() {
+        [] => Err(AdventError::input("no notes")),
+        [(None, _)] => solve_single(sections.remove(0).1.into_iter()),
+        _ => solve_batch(sections)
+    }
+}
+
+fn main() {
+    exit_on_error(run())
 }
 
 #[cfg(test)]
@@ -328,14 +542,37 @@ mod day16_spec {
         #[test]
         fn parse_test() {
             let field_rule = FieldRule::parse("class: 1-3 or 5-7").unwrap();
-            assert_eq!(field_rule.field_name, "class");
+            assert_eq!(field_rule.field_name.as_str(), "class");
             assert_eq!(field_rule.r0_min, 1);
             assert_eq!(field_rule.r0_max, 3);
             assert_eq!(field_rule.r1_min, 5);
             assert_eq!(field_rule.r1_max, 7);
 
             let field_rule = FieldRule::parse("departure time: 29-483 or 491-963").unwrap();
-            assert_eq!(field_rule.field_name, "departure time");
+            assert_eq!(field_rule.field_name.as_str(), "departure time");
+
+            let field_rule = FieldRule::parse("  arrival track :  1 - 3   or 5-7 ").unwrap();
+            assert_eq!(field_rule.field_name.as_str(), "arrival track");
+            assert_eq!((field_rule.r0_min, field_rule.r0_max, field_rule.r1_min, field_rule.r1_max), (1, 3, 5, 7));
+
+            // half-open ranges
+            let field_rule = FieldRule::parse("class: 1..4 or 5-7").unwrap();
+            assert_eq!((field_rule.r0_min, field_rule.r0_max), (1, 3));
+            assert!(field_rule.valid(3));
+            assert!(!field_rule.valid(4));
+        }
+
+        #[test]
+        fn parse_negative_test() {
+            let e = FieldRule::parse("class: 3-1 or 5-7").err().unwrap();
+            assert_eq!(e, FieldRuleParseError { line: "class: 3-1 or 5-7".to_owned(), reason: "Reversed range 3-1".to_owned() });
+
+            let e = FieldRule::parse("class: 1-3 or 5..5").err().unwrap();
+            assert_eq!(e.reason, "Empty range 5..5");
+
+            assert!(FieldRule::parse("class: 1-3").is_err());
+            assert!(FieldRule::parse("class 1-3 or 5-7").is_err());
+            assert!(FieldRule::parse("class: 1-3 or 5-99999999999999999999999").is_err());
         }
 
         #[test]
@@ -347,5 +584,126 @@ mod day16_spec {
         }
     }
 
+    fn lines(text: &str) -> impl Iterator<Item=String> + '_ {
+        text.lines().map(|line| line.to_owned())
+    }
+
+    #[test]
+    fn split_sections_test() {
+        let sections = split_sections(lines("a: 1-2 or 3-4\n\nyour ticket:\n1"));
+        assert_eq!(sections, vec!((None, vec!("a: 1-2 or 3-4".to_owned(), "".to_owned(), "your ticket:".to_owned(), "1".to_owned()))));
+
+        let sections = split_sections(lines("\n[first]\nx\n\n[ second ]\ny\n[]"));
+        let names: Vec<Option<&str>> = sections.iter().map(|(name, _)| name.as_deref()).collect();
+        assert_eq!(names, vec!(Some("first"), Some("second"), Some("")));
+        assert_eq!(sections[0].1, vec!("x".to_owned(), "".to_owned()));
+        assert!(sections[2].1.is_empty());
+
+        assert!(split_sections(lines("")).is_empty());
+    }
+
+    #[test]
+    fn notes_parse_test() {
+        let text = concat!(
+            "class: 1-3 or 5-7\n",
+            "row: 6-11 or 33-44\n",
+            "seat: 13-40 or 45-50\n",
+            "\n",
+            "your ticket:\n",
+            "7,1,14\n",
+            "\n",
+            "nearby tickets:\n",
+            "7,3,47\n",
+            "40,4,50\n",
+            "55,2,20\n",
+            "38,6,12\n",
+            "\n"
+        );
+        let notes = Notes::parse(lines(text)).unwrap();
+        assert_eq!(notes.field_rules.len(), 3);
+        assert_eq!(notes.my_ticket.0, vec!(7, 1, 14));
+        assert_eq!(notes.valid_tickets.len(), 1);
+        assert_eq!(notes.scanning_error_rate, 71);
+
+        assert!(Notes::parse(lines("class: 3-1 or 5-7\n\nyour ticket:\n1")).is_err());
+        assert!(Notes::parse(lines("class: 1-3 or 5-7\n\nyour ticket:")).is_err());
+    }
+
+    #[test]
+    fn departure_product_test() {
+        let text = "departure a: 0-5 or 8-19\nb: 0-1 or 4-19\ndeparture c: 0-13 or 16-19\n\nyour ticket:\n11,12,13\n\nnearby tickets:\n3,9,18\n15,1,5\n5,14,9";
+        let notes = Notes::parse(lines(text)).unwrap();
+        let assignment = identify_fields(&notes.field_rules, &notes.valid_tickets).unwrap();
+        assert_eq!(notes.departure_product(&assignment), 11 * 13);
+        assert_eq!(assignment.fields_with_prefix(&notes.my_ticket, "departure"), vec!((0, 11), (2, 13)));
+        assert_eq!(assignment.fields_with_prefix(&notes.my_ticket, "b"), vec!((1, 12)));
+        assert!(assignment.fields_with_prefix(&notes.my_ticket, "depart").is_empty());
+    }
+
+    #[test]
+    fn field_name_test() {
+        let name = FieldName::new("departure station");
+        assert!(name.is_departure());
+        assert!(name.has_prefix("departure station"));
+        assert!(name.has_prefix(""));
+        assert!(!name.has_prefix("depart"));
+        assert!(!name.has_prefix("arrival"));
+        assert!(FieldName::new("departure").is_departure());
+        assert!(!FieldName::new("departures").is_departure());
+        assert!(!FieldName::new("arrival departure").is_departure());
+        assert_eq!(name.to_string(), "departure station");
+    }
+
+    #[test]
+    fn identify_fields_test() {
+        let field_rules: Vec<FieldRule> = vec!(
+            "class: 0-1 or 4-19",
+            "row: 0-5 or 8-19",
+            "seat: 0-13 or 16-19"
+        ).into_iter().map(|line| FieldRule::parse(line).unwrap()).collect();
+        let tickets: Vec<Ticket> = vec!("3,9,18", "15,1,5", "5,14,9").into_iter().map(Ticket::parse).collect();
+
+        let assignment = identify_fields(&field_rules, &tickets).unwrap();
+        let names: Vec<&str> = assignment.0.iter().map(|rule| rule.field_name.as_str()).collect();
+        assert_eq!(names, vec!("row", "class", "seat"));
+
+        let my_ticket = Ticket::parse("11,12,13");
+        let expected = concat!(
+            "field | rule  | value\n",
+            "------+-------+------\n",
+            "0     | row   | 11\n",
+            "1     | class | 12\n",
+            "2     | seat  | 13\n"
+        );
+        assert_eq!(assignment.mapping_table(&my_ticket).to_string(), expected);
+    }
+
+    #[test]
+    fn candidates_test() {
+        let field_rules: Vec<FieldRule> = vec!(
+            "class: 0-1 or 4-19",
+            "row: 0-5 or 8-19",
+            "seat: 0-13 or 16-19"
+        ).into_iter().map(|line| FieldRule::parse(line).unwrap()).collect();
+
+        // 15 rules out seat for field 1 only, leaving the rest undecided
+        let mut candidates = Candidates::new(3);
+        candidates.eliminate_invalid(&field_rules, &[Ticket::parse("4,4,4")]).unwrap();
+        assert_eq!(candidates.to_string_grid(), "***\n***\n***\n");
+        candidates.eliminate_invalid(&field_rules, &[Ticket::parse("4,15,4")]).unwrap();
+        assert_eq!(candidates.to_string_grid(), "***\n**.\n***\n");
+        assert!(candidates.assignment(&field_rules).is_none());
+
+        candidates.eliminate_invalid(&field_rules, &vec!("3,9,18", "15,1,5", "5,14,9").into_iter().map(Ticket::parse).collect::<Vec<_>>()).unwrap();
+        assert_eq!(candidates.to_string_grid(), ".C.\nC..\n..C\n");
+        let mut csv = vec!();
+        candidates.to_csv(&mut csv).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap(), "field,0,1,2\n0,.,C,.\n1,C,.,.\n2,.,.,C\n");
+
+        let mut wide = Candidates::new(10);
+        wide.eliminate(0, 9).unwrap();
+        assert_eq!(wide.to_string_grid().lines().next(), Some("******** *."));
+    }
+
 
 }
\ No newline at end of file
@@ -1,9 +1,18 @@
 use std::io::prelude::*;
 use std::collections::{HashSet, HashMap};
+use std::convert::TryFrom;
+use std::fmt::{Display, Formatter};
 use std::hash::Hash;
+use std::mem::size_of;
+use std::num::NonZeroUsize;
 
+use advent::cli;
+use advent::error::{AdventError, exit_on_error};
+use advent::table::Table;
+use advent::timing;
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 struct Point3 {
     x: isize, y: isize, z: isize
 }
@@ -29,7 +38,7 @@ impl Point3 {
 }
 
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 struct Point4 {
     w: isize, x: isize, y: isize, z: isize
 }
@@ -54,85 +63,405 @@ impl Point4 {
     }
 }
 
+// Cells which can report their position, for bounding boxes
+trait Coordinates {
+    fn coordinates(&self) -> Vec<isize>;
+}
+
+impl Coordinates for Point3 {
+    fn coordinates(&self) -> Vec<isize> {
+        vec!(self.x, self.y, self.z)
+    }
+}
+
+impl Coordinates for Point4 {
+    fn coordinates(&self) -> Vec<isize> {
+        vec!(self.x, self.y, self.z, self.w)
+    }
+}
+
+// Cells which can be moved by a displacement of the same type
+trait Translate {
+    fn translate(&self, delta: &Self) -> Self;
+}
+
+impl Translate for Point3 {
+    fn translate(&self, delta: &Point3) -> Point3 {
+        Point3::new(self.x + delta.x, self.y + delta.y, self.z + delta.z)
+    }
+}
+
+impl Translate for Point4 {
+    fn translate(&self, delta: &Point4) -> Point4 {
+        Point4::new(self.w + delta.w, self.x + delta.x, self.y + delta.y, self.z + delta.z)
+    }
+}
+
+// A birth/survival rule in Golly notation, e.g. "B3/S23": a dead cell with exactly 3 active
+// neighbors becomes active, and a live cell with 2 or 3 active neighbors stays active.
+// Bit n of each mask is set if n active neighbors qualify.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct Rule {
+    birth: u16,
+    survival: u16
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct RuleParseError {
+    msg: String
+}
+
+impl RuleParseError {
+    fn err(msg: &str) -> RuleParseError {
+        RuleParseError { msg: msg.to_owned() }
+    }
+}
+
+impl Rule {
+    const PUZZLE: Rule = Rule { birth: 1 << 3, survival: (1 << 2) | (1 << 3) };
+
+    fn parse(s: &str) -> Result<Rule, RuleParseError> {
+        fn counts(digits: &str) -> Result<u16, RuleParseError> {
+            let mut mask = 0;
+            for c in digits.chars() {
+                match c.to_digit(10) {
+                    Some(d) => mask |= 1 << d,
+                    None => return Err(RuleParseError::err(&format!("Unexpected character `{}` in rule", c)))
+                }
+            }
+            Ok(mask)
+        }
+
+        let mut birth = None;
+        let mut survival = None;
+        for part in s.trim().split('/') {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some('B') | Some('b') if birth.is_none() => birth = Some(counts(chars.as_str())?),
+                Some('S') | Some('s') if survival.is_none() => survival = Some(counts(chars.as_str())?),
+                _ => return Err(RuleParseError::err(&format!("Malformed rule `{}`; expected e.g. B3/S23", s)))
+            }
+        }
+
+        match (birth, survival) {
+            (Some(birth), _) if birth & 1 == 1 =>
+                // every cell in infinite space would be born on the first generation
+                Err(RuleParseError::err("B0 rules are not supported")),
+            (Some(birth), Some(survival)) => Ok(Rule { birth, survival }),
+            _ => Err(RuleParseError::err(&format!("Rule `{}` must specify both B and S counts", s)))
+        }
+    }
+
+    fn next_state(&self, active: bool, active_neighbors: u8) -> bool {
+        let mask = if active { self.survival } else { self.birth };
+        active_neighbors < 16 && mask & (1 << active_neighbors) != 0
+    }
+}
+
 struct Conway<T> {
-    cells: HashSet<T> // only record active cells
+    cells: HashSet<T>, // only record active cells
+    // bookkeeping for `evolve`: for each cell, its active neighbor count and whether it is itself
+    // active. Entries are kept between generations, so that a cell counted again reuses its slot;
+    // those the population has left behind stay until pruned.
+    counts: HashMap<T, (u8, bool)>
+}
+
+// The size and extent of a population: `bounds` holds the least and greatest coordinate of
+// any active cell along each axis, and is empty when no cells are active.
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct Census {
+    active: usize,
+    bounds: Vec<(isize, isize)>
+}
+
+impl Census {
+    fn bounding_box(&self) -> String {
+        if self.bounds.is_empty() {
+            "-".to_owned()
+        } else {
+            self.bounds.iter().map(|(lo, hi)| format!("[{}, {}]", lo, hi)).collect::<Vec<_>>().join(" x ")
+        }
+    }
+}
+
+// Caps on a run, so that an experiment whose population grows without bound stops with an error
+// rather than exhausting memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Limits {
+    generations: usize,
+    active: usize
+}
+
+impl Limits {
+    const NONE: Limits = Limits { generations: usize::MAX, active: usize::MAX };
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum LimitError {
+    // More generations were asked for than allowed; checked before running any
+    Generations { requested: usize, limit: usize },
+    // The population outgrew the limit in this generation
+    Active { generation: usize, active: usize, limit: usize }
+}
+
+impl Display for LimitError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            LimitError::Generations { requested, limit } => {
+                write!(f, "{} generations requested, but at most {} are allowed", requested, limit)
+            },
+            LimitError::Active { generation, active, limit } => {
+                write!(f, "{} cells active after generation {}, over the limit of {}", active, generation, limit)
+            }
+        }
+    }
+}
+
+// Every `every` generations, drop the bookkeeping for cells outside the bounding box of the active
+// cells widened by `margin` on each side. Every candidate for the next generation neighbors an active
+// cell, so a margin of 1 keeps every entry the next generation would use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Pruning {
+    every: NonZeroUsize,
+    margin: usize
 }
 
-impl<T: Hash + Eq> Conway<T> {
+impl<T: Hash + Eq + Clone> Conway<T> {
+    fn new(cells: HashSet<T>) -> Conway<T> {
+        Conway { cells, counts: HashMap::new() }
+    }
+
+    // Collects active cells, ignoring repeats
+    fn from_points<I: IntoIterator<Item=T>>(points: I) -> Conway<T> {
+        Conway::new(points.into_iter().collect())
+    }
 
-    fn parse<L, F>(lines: L, f: F) -> Conway<T>
+    // Reads a grid of '#' (active) and '.' cells. The cell in column x of line y is placed by
+    // `f(x + dx, y + dy)`, where `offset` = (dx, dy) is where the top left corner of the grid goes.
+    fn parse<L, F>(lines: L, offset: (isize, isize), f: F) -> Conway<T>
     where L: Iterator<Item=String>, F: Fn(isize, isize) -> T {
+        let (dx, dy) = offset;
         let mut cells = HashSet::new();
 
         for (y, line) in lines.enumerate() {
             for (x, c) in line.chars().enumerate() {
                 if c == '#' {
-                    cells.insert(f(x as isize, y as isize));
+                    cells.insert(f(x as isize + dx, y as isize + dy));
                 }
             }
         }
 
-        Conway { cells }
+        Conway::new(cells)
     }
 
-    fn evolve<J, F>(&self, neighbors: F) -> Conway<T>
+    // Advances one generation in place, returning whether any cell changed state. Each active cell
+    // adds one to the count of each of its neighbors, so every candidate's count is complete after
+    // a single pass over the active cells.
+    fn evolve<J, F>(&mut self, rule: &Rule, neighbors: F) -> bool
     where J: Iterator<Item=T>, F: Fn(&T) -> J {
-        let mut visited: HashMap<T, bool> = HashMap::new();
+        // On the first generation, at least every active cell will be counted; thereafter the
+        // map retains the entries of the previous generations.
+        self.counts.reserve(self.cells.len());
+        for count in self.counts.values_mut() {
+            *count = (0, false);
+        }
 
-        // iterate over all neighbors of self's cells
-        for cell in &self.cells {
-            for candidate in neighbors(cell) {
-                if !visited.contains_key(&candidate) {
-                    
-                    let mut active_neighbors = 0;
-                    for nbr in neighbors(&candidate) {
-                        active_neighbors += self.cells.contains(&nbr) as u8;
-                        if active_neighbors > 3 {
-                            break
-                        }
-                    }
-                    match active_neighbors {
-                        2 if self.cells.contains(&candidate) => {
-                            visited.insert(candidate, true);
-                        },
-                        3 => {
-                            visited.insert(candidate, true);
-                        },
-                        _ => {
-                            visited.insert(candidate, false);
-                        }
-                    }
+        for cell in self.cells.drain() {
+            for nbr in neighbors(&cell) {
+                self.counts.entry(nbr).or_insert((0, false)).0 += 1;
+            }
+            self.counts.entry(cell).or_insert((0, false)).1 = true;
+        }
+
+        let mut changed = false;
+        // a cell with no active neighbors is never born, so entries left behind change nothing
+        for (cell, (active_neighbors, active)) in &self.counts {
+            let next = rule.next_state(*active, *active_neighbors);
+            changed |= next != *active;
+            if next {
+                self.cells.insert(cell.clone());
+            }
+        }
+        changed
+    }
+
+    // Evolves up to `n` generations, passing each generation's number (from 1), census and memory
+    // estimate to `callback`, and pruning as `pruning` directs. Stops early once a generation changes
+    // nothing (as when the population has died out), since every later one would be the same; returns
+    // the number of generations run, or an error once the generations or the population pass `limits`.
+    fn run<J, F, C>(&mut self, n: usize, rule: &Rule, neighbors: F, limits: &Limits, pruning: Option<Pruning>,
+        mut callback: C) -> Result<usize, LimitError>
+    where J: Iterator<Item=T>, F: Fn(&T) -> J, C: FnMut(usize, &Census, usize), T: Coordinates {
+        if n > limits.generations {
+            return Err(LimitError::Generations { requested: n, limit: limits.generations })
+        }
+        for generation in 1..=n {
+            // the bookkeeping grows with the population, so check it before counting the next generation
+            self.check_active(generation - 1, limits)?;
+            let changed = self.evolve(rule, &neighbors);
+            if let Some(Pruning { every, margin }) = pruning {
+                if generation % every.get() == 0 {
+                    self.prune(margin);
                 }
             }
+            callback(generation, &self.census(), self.approximate_bytes());
+            if !changed {
+                return Ok(generation)
+            }
         }
+        self.check_active(n, limits)?;
+        Ok(n)
+    }
+
+    fn check_active(&self, generation: usize, limits: &Limits) -> Result<(), LimitError> {
+        if self.cells.len() > limits.active {
+            Err(LimitError::Active { generation, active: self.cells.len(), limit: limits.active })
+        } else {
+            Ok(())
+        }
+    }
+
+    // The heap held by the active cells and by the bookkeeping, estimated from their capacities
+    fn approximate_bytes(&self) -> usize {
+        self.cells.capacity() * (size_of::<T>() + 1) + self.counts.capacity() * (size_of::<(T, (u8, bool))>() + 1)
+    }
+}
+
+impl<T: Hash + Eq + Translate> Conway<T> {
+    // Moves every active cell by `delta`
+    fn translate(&mut self, delta: &T) {
+        self.cells = self.cells.drain().map(|cell| cell.translate(delta)).collect();
+    }
+}
 
-        let cells = visited.into_iter().flat_map(|p| {
-            match p {
-                (x, true) => Some(x),
-                _ => None
+impl<T: Hash + Eq + Coordinates> Conway<T> {
+    fn census(&self) -> Census {
+        let mut bounds: Vec<(isize, isize)> = vec!();
+        for cell in &self.cells {
+            let coordinates = cell.coordinates();
+            if bounds.is_empty() {
+                bounds = coordinates.iter().map(|c| (*c, *c)).collect();
+            }
+            for ((lo, hi), c) in bounds.iter_mut().zip(coordinates) {
+                *lo = (*lo).min(c);
+                *hi = (*hi).max(c);
             }
-        }).collect();
-        Conway{ cells }
+        }
+        Census { active: self.cells.len(), bounds }
+    }
+
+    // Drops the bookkeeping for cells outside the bounding box of the active cells, widened by
+    // `margin` on each side (all of it, if no cell is active), and the memory it held
+    fn prune(&mut self, margin: usize) {
+        let margin = isize::try_from(margin).unwrap_or(isize::MAX);
+        let bounds: Vec<(isize, isize)> = self.census().bounds.iter()
+            .map(|(lo, hi)| (lo.saturating_sub(margin), hi.saturating_add(margin)))
+            .collect();
+        self.counts.retain(|cell, _| {
+            !bounds.is_empty() && bounds.iter().zip(cell.coordinates()).all(|((lo, hi), c)| *lo <= c && c <= *hi)
+        });
+        self.counts.shrink_to_fit();
     }
 }
 
-fn main() {
+// How long to run, and within what bounds
+struct Settings {
+    generations: usize,
+    limits: Limits,
+    pruning: Option<Pruning>
+}
+
+// Runs the configured number of generations (six for the puzzle), printing a row per generation if
+// `table` is given; returns the final active count.
+fn boot<T, J, F>(conway: &mut Conway<T>, rule: &Rule, neighbors: F, settings: &Settings, mut table: Option<&mut Table>) -> Result<usize, LimitError>
+where T: Hash + Eq + Clone + Coordinates, J: Iterator<Item=T>, F: Fn(&T) -> J {
+    if let Some(table) = table.as_mut() {
+        let census = conway.census();
+        table.add_row(vec!("0".to_owned(), census.active.to_string(), census.bounding_box(), conway.approximate_bytes().to_string()));
+    }
+    let generations = conway.run(settings.generations, rule, neighbors, &settings.limits, settings.pruning, |generation, census, bytes| {
+        if let Some(table) = table.as_mut() {
+            table.add_row(vec!(generation.to_string(), census.active.to_string(), census.bounding_box(), bytes.to_string()));
+        }
+    })?;
+    if generations < settings.generations {
+        println!("The population is stable after {} generations.", generations);
+    }
+    Ok(conway.cells.len())
+}
+
+// The value of a numeric option, if given
+fn count_option(name: &str) -> Result<Option<usize>, AdventError> {
+    cli::option_value(name).map(|s| {
+        s.parse().map_err(|_| AdventError::input(format!("expected a number for {}, got {}", name, s)))
+    }).transpose()
+}
+
+fn run() -> Result<(), AdventError> {
+    let rule = match cli::option_value("--rule").map(|s| Rule::parse(&s)) {
+        None => Rule::PUZZLE,
+        Some(Ok(rule)) => rule,
+        Some(Err(e)) => return Err(AdventError::input(e.msg))
+    };
+
+    // where to put the top left corner of the input; the answers don't depend on it, but the
+    // bounding boxes in the census do
+    let (dx, dy) = match cli::option_value("--offset") {
+        None => (0, 0),
+        Some(s) => {
+            let parts: Vec<Option<isize>> = s.split(',').map(|part| part.trim().parse().ok()).collect();
+            match parts.as_slice() {
+                [Some(dx), Some(dy)] => (*dx, *dy),
+                _ => return Err(AdventError::input(format!("expected --offset x,y; got {}", s)))
+            }
+        }
+    };
+
     let stdin = std::io::stdin();
-    let conway3_0: Conway<Point3> = Conway::parse(stdin.lock().lines().flatten(), |x,y| Point3::new(x,y,0));
-    let conway4_0: Conway<Point4> = {
-        let cells: HashSet<Point4> = (&conway3_0.cells).iter().map(|c|
-            Point4::new(0, c.x, c.y, 0)
-        ).collect();
-        Conway { cells }
+    let mut conway3_0: Conway<Point3> = Conway::parse(stdin.lock().lines().flatten(), (0, 0), |x,y| Point3::new(x,y,0));
+    let mut conway4_0: Conway<Point4> = Conway::from_points(conway3_0.cells.iter().map(|c| Point4::new(0, c.x, c.y, 0)));
+    conway3_0.translate(&Point3::new(dx, dy, 0));
+    conway4_0.translate(&Point4::new(0, dx, dy, 0));
+
+    let settings = Settings {
+        generations: count_option("--generations")?.unwrap_or(6),
+        limits: Limits {
+            generations: count_option("--max-generations")?.unwrap_or(Limits::NONE.generations),
+            active: count_option("--max-active")?.unwrap_or(Limits::NONE.active)
+        },
+        pruning: match count_option("--prune")? {
+            None => None,
+            Some(every) => Some(Pruning {
+                every: NonZeroUsize::new(every).ok_or_else(|| AdventError::input("expected a positive number of generations for --prune"))?,
+                margin: count_option("--prune-margin")?.unwrap_or(1)
+            })
+        }
     };
+    let limit_error = |e: LimitError| AdventError::no_solution(e.to_string());
 
-    let conway3_6 = (0..6).fold(conway3_0, |c, _| c.evolve(|p| p.neighbors()));
+    let census = cli::has_flag("--census");
+    let new_table = || Table::new(&["Generation", "Active", "Bounding box", "Approx. bytes"]);
 
-    println!("3D active cells after 6 generations: {}", conway3_6.cells.len());
+    let mut conway3 = conway3_0;
+    let mut table = new_table();
+    let active = timing::time("Part 1", || boot(&mut conway3, &rule, |p| p.neighbors(), &settings, Some(&mut table).filter(|_| census)));
+    if census {
+        print!("{}", table);
+    }
+    println!("3D active cells after {} generations: {}", settings.generations, active.map_err(limit_error)?);
 
-    let conway4_6 = (0..6).fold(conway4_0, |c, _| c.evolve(|p| p.neighbors()));
+    let mut conway4 = conway4_0;
+    let mut table = new_table();
+    let active = timing::time("Part 2", || boot(&mut conway4, &rule, |p| p.neighbors(), &settings, Some(&mut table).filter(|_| census)));
+    if census {
+        print!("{}", table);
+    }
+    println!("4D Active cells after {} generations: {}", settings.generations, active.map_err(limit_error)?);
+    Ok(())
+}
 
-    println!("4D Active cells after 6 generations: {}", conway4_6.cells.len());
+fn main() {
+    exit_on_error(run())
 }
 
 #[cfg(test)]
@@ -144,7 +473,7 @@ mod day17_spec {
         let input = ".#.\n\
                      ..#\n\
                      ###";
-        let conway: Conway<Point3> = Conway::parse(input.lines().map(|s| s.to_owned()), |x, y| Point3::new(x, y, 0));
+        let conway: Conway<Point3> = Conway::parse(input.lines().map(|s| s.to_owned()), (0, 0), |x, y| Point3::new(x, y, 0));
         assert_eq!(conway.cells.len(), 5);
         assert!(conway.cells.contains(&Point3::new(0, 2, 0)))
     }
@@ -154,17 +483,263 @@ mod day17_spec {
         let input = ".#.\n\
                      ..#\n\
                      ###";
-        let conway: Conway<Point3> = Conway::parse(input.lines().map(|s| s.to_owned()),
+        let conway: Conway<Point3> = Conway::parse(input.lines().map(|s| s.to_owned()), (0, 0),
             |x, y| Point3::new(x, y, 0));
-        let conway1 = conway.evolve(|p| p.neighbors());
+        let mut conway = conway;
+        conway.evolve(&Rule::PUZZLE, |p| p.neighbors());
+
+        assert_eq!(conway.cells.len(), 11);
+        assert!(conway.cells.contains(&Point3::new(0, 1, -1)));
+        assert!(conway.cells.contains(&Point3::new(1, 3, 1)));
+        assert!(conway.cells.contains(&Point3::new(0, 1, 0)));
+        assert!(conway.cells.contains(&Point3::new(1, 2, 0)));
+
+        conway.evolve(&Rule::PUZZLE, |p| p.neighbors());
+        assert_eq!(conway.cells.len(), 21);
+        // every cell now active was counted, and its entry is kept for the next generation
+        assert!(conway.cells.iter().all(|cell| conway.counts.contains_key(cell)));
+
+        for _ in 2..6 {
+            conway.evolve(&Rule::PUZZLE, |p| p.neighbors());
+        }
+        assert_eq!(conway.cells.len(), 112);
+    }
+
+    #[test]
+    fn rule_parse_test() {
+        assert_eq!(Rule::parse("B3/S23"), Ok(Rule::PUZZLE));
+        assert_eq!(Rule::parse("s23/b3"), Ok(Rule::PUZZLE));
+        assert_eq!(Rule::parse("B36/S"), Ok(Rule { birth: (1 << 3) | (1 << 6), survival: 0 }));
+        assert!(Rule::parse("B3").is_err());
+        assert!(Rule::parse("B3/S2x").is_err());
+        assert!(Rule::parse("B3/B2").is_err());
+        assert!(Rule::parse("23/3").is_err());
+        assert!(Rule::parse("B03/S23").is_err());
+
+        assert!(Rule::PUZZLE.next_state(false, 3));
+        assert!(!Rule::PUZZLE.next_state(false, 2));
+        assert!(Rule::PUZZLE.next_state(true, 2));
+        assert!(!Rule::PUZZLE.next_state(true, 26));
+    }
+
+    #[test]
+    fn alternate_rule_test() {
+        let input = ".#.\n\
+                     ..#\n\
+                     ###";
+        let conway: Conway<Point3> = Conway::parse(input.lines().map(|s| s.to_owned()), (0, 0),
+            |x, y| Point3::new(x, y, 0));
+
+        // With no survival, the live cells all die, and only cells with exactly one neighbor are born
+        let rule = Rule::parse("B1/S").unwrap();
+        let mut conway1 = Conway::new(conway.cells.clone());
+        conway1.evolve(&rule, |p| p.neighbors());
+        assert!(conway1.cells.iter().all(|p| !conway.cells.contains(p)));
+        assert!(conway1.cells.contains(&Point3::new(0, -1, 0)));
+        assert!(conway1.cells.contains(&Point3::new(0, 0, 0)));
+        assert!(!conway1.cells.contains(&Point3::new(1, 1, 0)));
+
+        // "B/S012345678" (no births, everything below 9 neighbors survives) is static in 3D for the sample
+        let rule = Rule::parse("B/S012345678").unwrap();
+        let mut conway1 = Conway::new(conway.cells.clone());
+        conway1.evolve(&rule, |p| p.neighbors());
+        assert_eq!(conway1.cells, conway.cells);
+    }
+
+    #[test]
+    fn conway_4d_test() {
+        let input = ".#.\n\
+                     ..#\n\
+                     ###";
+        let mut conway: Conway<Point4> = Conway::parse(input.lines().map(|s| s.to_owned()), (0, 0),
+            |x, y| Point4::new(0, x, y, 0));
+        for _ in 0..6 {
+            conway.evolve(&Rule::PUZZLE, |p| p.neighbors());
+        }
+        assert_eq!(conway.cells.len(), 848);
+    }
+
+    #[test]
+    fn census_test() {
+        let input = ".#.\n\
+                     ..#\n\
+                     ###";
+        let mut conway: Conway<Point3> = Conway::parse(input.lines().map(|s| s.to_owned()), (0, 0),
+            |x, y| Point3::new(x, y, 0));
+        assert_eq!(conway.census(), Census { active: 5, bounds: vec!((0, 2), (0, 2), (0, 0)) });
+
+        let mut censuses = vec!();
+        let generations = conway.run(6, &Rule::PUZZLE, |p| p.neighbors(), &Limits::NONE, None, |generation, census, _| censuses.push((generation, census.clone()))).unwrap();
+        assert_eq!(generations, 6);
+        assert_eq!(censuses.iter().map(|(generation, _)| *generation).collect::<Vec<_>>(), vec!(1, 2, 3, 4, 5, 6));
+        let counts: Vec<usize> = censuses.iter().map(|(_, census)| census.active).collect();
+        assert_eq!((counts[0], counts[1], counts[2], counts[5]), (11, 21, 38, 112));
+        assert_eq!(censuses[0].1.bounds, vec!((0, 2), (1, 3), (-1, 1)));
+        assert_eq!(censuses[0].1.bounding_box(), "[0, 2] x [1, 3] x [-1, 1]");
+        assert_eq!(censuses[5].1, conway.census());
+
+        let mut conway: Conway<Point4> = Conway::parse(input.lines().map(|s| s.to_owned()), (0, 0),
+            |x, y| Point4::new(0, x, y, 0));
+        let mut last = None;
+        conway.run(6, &Rule::PUZZLE, |p| p.neighbors(), &Limits::NONE, None, |_, census, _| last = Some(census.active)).unwrap();
+        assert_eq!(last, Some(848));
+    }
+
+    #[test]
+    fn run_should_stop_when_stable() {
+        // a lone cell dies on the first generation, after which nothing changes
+        let mut conway = Conway::new(vec!(Point3::new(0, 0, 0)).into_iter().collect());
+        let mut censuses = vec!();
+        assert_eq!(conway.run(6, &Rule::PUZZLE, |p| p.neighbors(), &Limits::NONE, None, |_, census, _| censuses.push(census.clone())), Ok(2));
+        assert_eq!(censuses, vec!(Census { active: 0, bounds: vec!() }, Census { active: 0, bounds: vec!() }));
+        assert_eq!(censuses[0].bounding_box(), "-");
+
+        // with no births and universal survival, the first generation changes nothing
+        let mut conway = Conway::new(vec!(Point3::new(0, 0, 0), Point3::new(1, 0, 0)).into_iter().collect());
+        assert_eq!(conway.run(6, &Rule::parse("B/S012345678").unwrap(), |p| p.neighbors(), &Limits::NONE, None, |_, _, _| ()), Ok(1));
+        assert_eq!(conway.census(), Census { active: 2, bounds: vec!((0, 1), (0, 0), (0, 0)) });
+    }
+
+    #[test]
+    fn offset_test() {
+        let input = ".#.\n\
+                     ..#\n\
+                     ###";
+        let shifted: Conway<Point3> = Conway::parse(input.lines().map(|s| s.to_owned()), (-4, 7), |x, y| Point3::new(x, y, 0));
+        assert_eq!(shifted.census().bounds, vec!((-4, -2), (7, 9), (0, 0)));
+
+        let mut conway: Conway<Point3> = Conway::parse(input.lines().map(|s| s.to_owned()), (0, 0), |x, y| Point3::new(x, y, 0));
+        conway.translate(&Point3::new(-4, 7, 0));
+        assert_eq!(conway.cells, shifted.cells);
 
-        assert_eq!(conway1.cells.len(), 11);
-        assert!(conway1.cells.contains(&Point3::new(0, 1, -1)));
-        assert!(conway1.cells.contains(&Point3::new(1, 3, 1)));
-        assert!(conway1.cells.contains(&Point3::new(0, 1, 0)));
-        assert!(conway1.cells.contains(&Point3::new(1, 2, 0)));
+        assert_eq!(Point4::new(1, 2, 3, 4).translate(&Point4::new(-1, 0, 1, 2)), Point4::new(0, 2, 4, 6));
+    }
 
-        let conway2 = conway1.evolve(|p| p.neighbors());
-        assert_eq!(conway2.cells.len(), 21);
+    #[test]
+    fn translate_commutes_with_evolve() {
+        let input = ".#.\n\
+                     ..#\n\
+                     ###";
+        let delta = Point3::new(3, -5, 11);
+        let mut moved_first: Conway<Point3> = Conway::parse(input.lines().map(|s| s.to_owned()), (0, 0), |x, y| Point3::new(x, y, 0));
+        let mut moved_last = Conway::from_points(moved_first.cells.iter().cloned());
+        moved_first.translate(&delta);
+        for _ in 0..4 {
+            moved_first.evolve(&Rule::PUZZLE, |p| p.neighbors());
+            moved_last.evolve(&Rule::PUZZLE, |p| p.neighbors());
+        }
+        moved_last.translate(&delta);
+        assert_eq!(moved_first.cells, moved_last.cells);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn composite_seed_test() {
+        let input = ".#.\n\
+                     ..#\n\
+                     ###";
+        let seed: Conway<Point4> = Conway::parse(input.lines().map(|s| s.to_owned()), (0, 0), |x, y| Point4::new(0, x, y, 0));
+        // each copy grows by at most one cell along each axis per generation, so copies 20 apart
+        // never interact in six generations
+        let far = Point4::new(0, 20, 0, 0);
+        let mut pair = Conway::from_points(seed.cells.iter().cloned().chain(seed.cells.iter().map(|p| p.translate(&far))));
+        assert_eq!(pair.cells.len(), 10);
+        for _ in 0..6 {
+            pair.evolve(&Rule::PUZZLE, |p| p.neighbors());
+        }
+        assert_eq!(pair.cells.len(), 2 * 848);
+
+        // overlapping points are counted once
+        let doubled = Conway::from_points(seed.cells.iter().cloned().chain(seed.cells.iter().cloned()));
+        assert_eq!(doubled.cells, seed.cells);
+    }
+
+    #[test]
+    fn isolated_survival_test() {
+        // S0: a cell with no active neighbors survives, even though it is nobody's neighbor
+        let mut conway = Conway::new(vec!(Point3::new(0, 0, 0), Point3::new(5, 5, 5)).into_iter().collect());
+        conway.evolve(&Rule::parse("B/S0").unwrap(), |p| p.neighbors());
+        assert_eq!(conway.cells.len(), 2);
+    }
+
+    #[test]
+    fn limits_test() {
+        let input = ".#.\n\
+                     ..#\n\
+                     ###";
+        let parse = || -> Conway<Point3> { Conway::parse(input.lines().map(|s| s.to_owned()), (0, 0), |x, y| Point3::new(x, y, 0)) };
+
+        let limits = Limits { generations: 5, active: usize::MAX };
+        let mut conway = parse();
+        assert_eq!(conway.run(6, &Rule::PUZZLE, |p| p.neighbors(), &limits, None, |_, _, _| ()),
+            Err(LimitError::Generations { requested: 6, limit: 5 }));
+        assert_eq!(conway.census().active, 5);
+
+        // the populations run 5, 11, 21, 38, ...
+        let limits = Limits { generations: 6, active: 30 };
+        let mut conway = parse();
+        let mut generations = vec!();
+        let result = conway.run(6, &Rule::PUZZLE, |p| p.neighbors(), &limits, None, |generation, _, _| generations.push(generation));
+        assert_eq!(result, Err(LimitError::Active { generation: 3, active: 38, limit: 30 }));
+        // the check comes before counting generation 4, not after
+        assert_eq!(generations, vec!(1, 2, 3));
+        assert_eq!(result.unwrap_err().to_string(), "38 cells active after generation 3, over the limit of 30");
+
+        // the seed and the last generation are checked too
+        let limits = Limits { generations: 6, active: 4 };
+        assert_eq!(parse().run(6, &Rule::PUZZLE, |p| p.neighbors(), &limits, None, |_, _, _| ()),
+            Err(LimitError::Active { generation: 0, active: 5, limit: 4 }));
+        let limits = Limits { generations: 6, active: 30 };
+        assert_eq!(parse().run(3, &Rule::PUZZLE, |p| p.neighbors(), &limits, None, |_, _, _| ()),
+            Err(LimitError::Active { generation: 3, active: 38, limit: 30 }));
+    }
+
+    #[test]
+    fn pruning_test() {
+        let input = ".#.\n\
+                     ..#\n\
+                     ###";
+        let parse = || -> Conway<Point3> { Conway::parse(input.lines().map(|s| s.to_owned()), (0, 0), |x, y| Point3::new(x, y, 0)) };
+        let pruning = Some(Pruning { every: NonZeroUsize::new(2).unwrap(), margin: 1 });
+
+        // pruning changes nothing but the memory held
+        let mut pruned = parse();
+        let mut censuses = vec!();
+        assert_eq!(pruned.run(6, &Rule::PUZZLE, |p| p.neighbors(), &Limits::NONE, pruning, |_, census, bytes| {
+            assert!(bytes > 0);
+            censuses.push(census.clone());
+        }), Ok(6));
+        let mut unpruned = parse();
+        let mut expected = vec!();
+        unpruned.run(6, &Rule::PUZZLE, |p| p.neighbors(), &Limits::NONE, None, |_, census, _| expected.push(census.clone())).unwrap();
+        assert_eq!(censuses, expected);
+        assert_eq!(pruned.cells, unpruned.cells);
+        assert!(pruned.counts.len() <= unpruned.counts.len());
+
+        // a far-off cell dies at once, and its neighborhood's entries go with the next pruning
+        let mut conway = parse();
+        conway.cells.insert(Point3::new(100, 0, 0));
+        conway.run(1, &Rule::PUZZLE, |p| p.neighbors(), &Limits::NONE, None, |_, _, _| ()).unwrap();
+        assert!(conway.counts.contains_key(&Point3::new(101, 1, 1)));
+        let bounds = conway.census().bounds;
+        conway.prune(1);
+        assert!(!conway.counts.contains_key(&Point3::new(101, 1, 1)));
+        assert!(conway.counts.keys().all(|cell| {
+            bounds.iter().zip(cell.coordinates()).all(|((lo, hi), c)| lo - 1 <= c && c <= hi + 1)
+        }));
+        // and the next generation comes out the same as from a fresh start
+        let cells = conway.cells.clone();
+        conway.evolve(&Rule::PUZZLE, |p| p.neighbors());
+        let mut fresh = Conway::new(cells);
+        fresh.evolve(&Rule::PUZZLE, |p| p.neighbors());
+        assert_eq!(conway.cells, fresh.cells);
+
+        // once the population dies out, pruning drops everything, whatever the margin
+        let mut conway = Conway::from_points((0..8).flat_map(|x| (0..8).map(move |y| Point3::new(x, y, 0))));
+        let mut bytes = vec!();
+        conway.run(1, &Rule::parse("B/S").unwrap(), |p| p.neighbors(), &Limits::NONE, None, |_, _, b| bytes.push(b)).unwrap();
+        conway.prune(usize::MAX);
+        assert_eq!(conway.census().active, 0);
+        assert!(conway.counts.is_empty());
+        assert!(conway.approximate_bytes() < bytes[0]);
+    }
+}
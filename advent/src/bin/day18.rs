@@ -1,9 +1,25 @@
 use std::io::prelude::*;
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::marker::PhantomData;
+use std::str::FromStr;
 
 #[macro_use]
 extern crate lazy_static;
 use regex::Regex;
 
+use advent::cli;
+use advent::error::{AdventError, exit_on_error};
+use advent::timing;
+
+// x op y, reporting overflow on stderr
+fn checked(x: u64, op: char, y: u64) -> Option<u64> {
+    u64::apply(op, x, y).or_else(|| {
+        eprintln!("Overflow: {} {} {}", x, op, y);
+        None
+    })
+}
+
 fn left_to_right(line: &str) -> Option<u64> {
     #[derive(Clone, Copy, Debug)]
     enum Acc {
@@ -30,7 +46,7 @@ fn left_to_right(line: &str) -> Option<u64> {
             current = Acc::Empty;
             rest = caps[1].to_owned();
         } else if let Some(caps) = NUMBER_PAT.captures(&rest) {
-            let x = u64::from_str_radix(&caps[1], 10).unwrap();
+            let x = u64::from_str_radix(&caps[1], 10).map_err(|_| eprintln!("Literal {} overflows", &caps[1])).ok()?;
             rest = caps[2].to_owned();
             match current {
                 Acc::Empty => current = Acc::Infix(x),
@@ -39,8 +55,8 @@ fn left_to_right(line: &str) -> Option<u64> {
                         stack.len(), current, rest);
                     return None
                 },
-                Acc::Add(x0) => current = Acc::Infix(x + x0),
-                Acc::Mul(x0) => current = Acc::Infix(x * x0)
+                Acc::Add(x0) => current = Acc::Infix(checked(x0, '+', x)?),
+                Acc::Mul(x0) => current = Acc::Infix(checked(x0, '*', x)?)
             }
         } else if let Some(caps) = OP_PAT.captures(&rest) {
             match (current, &caps[1]) {
@@ -61,8 +77,8 @@ fn left_to_right(line: &str) -> Option<u64> {
                     return None
                 },
                 (Some(Acc::Empty), Acc::Infix(c)) => current = Acc::Infix(c),
-                (Some(Acc::Add(x0)), Acc::Infix(c)) => current = Acc::Infix(x0 + c),
-                (Some(Acc::Mul(x0)), Acc::Infix(c)) => current = Acc::Infix(x0 * c),
+                (Some(Acc::Add(x0)), Acc::Infix(c)) => current = Acc::Infix(checked(x0, '+', c)?),
+                (Some(Acc::Mul(x0)), Acc::Infix(c)) => current = Acc::Infix(checked(x0, '*', c)?),
                 _ => {
                     eprintln!("Missing operand! Stack depth {}, current acc {:?}, rest {}",
                         stack.len() + 1, current, rest);
@@ -133,7 +149,7 @@ fn add_before_mult(line: &str) -> Option<u64> {
             }
             rest = caps[1].to_owned();
         } else if let Some(caps) = NUMBER_PAT.captures(&rest) {
-            let x = u64::from_str_radix(&caps[1], 10).unwrap();
+            let x = u64::from_str_radix(&caps[1], 10).map_err(|_| eprintln!("Literal {} overflows", &caps[1])).ok()?;
             match current {
                 Current::Empty => current = Current::Value(x),
                 _ => {
@@ -144,9 +160,9 @@ fn add_before_mult(line: &str) -> Option<u64> {
             }
             rest = caps[2].to_owned();
         } else if let Some(caps) = PLUS_NUMBER_PAT.captures(&rest) {
-            let x = u64::from_str_radix(&caps[1], 10).unwrap();
+            let x = u64::from_str_radix(&caps[1], 10).map_err(|_| eprintln!("Literal {} overflows", &caps[1])).ok()?;
             match current {
-                Current::Value(x0) => current = Current::Value(x0 + x),
+                Current::Value(x0) => current = Current::Value(checked(x0, '+', x)?),
                 _ => {
                     eprintln!("Unexpected token '+'.\nStack depth {}\ncurrent: {:?}\nrest: {}",
                         stack.len(), current, rest);
@@ -168,7 +184,7 @@ fn add_before_mult(line: &str) -> Option<u64> {
             }
             rest = caps[1].to_owned();
         } else if let Some(caps) = TIMES_NUMBER_PAT.captures(&rest) {
-            let x = u64::from_str_radix(&caps[1], 10).unwrap();
+            let x = u64::from_str_radix(&caps[1], 10).map_err(|_| eprintln!("Literal {} overflows", &caps[1])).ok()?;
             match current {
                 Current::Value(x0) => {
                     stack.push(StackFrame::Mult(x0));
@@ -207,13 +223,13 @@ fn add_before_mult(line: &str) -> Option<u64> {
                     let mut open_paren_found = false;
                     while let Some(frame) = stack.pop() {
                         match frame {
-                            StackFrame::Mult(x0) => s *= x0,
+                            StackFrame::Mult(x0) => s = checked(x0, '*', s)?,
                             StackFrame::OpenP => {
                                 open_paren_found = true; break
                             },
                             StackFrame::PAdd(x0) => {
                                 open_paren_found = true;
-                                s += x0; break
+                                s = checked(x0, '+', s)?; break
                             },
                             StackFrame::PMult(x0) => {
                                 // demote PMult to Mult but don't multiply yet
@@ -241,7 +257,7 @@ fn add_before_mult(line: &str) -> Option<u64> {
                     let mut s = x;
                     while let Some(acc) = stack.pop() {
                         match acc {
-                            StackFrame::Mult(x0) => s *= x0,
+                            StackFrame::Mult(x0) => s = checked(x0, '*', s)?,
                             _ => {
                                 eprintln!("Unmatched opening parenthesis encountered!");
                                 return None
@@ -258,17 +274,278 @@ fn add_before_mult(line: &str) -> Option<u64> {
     }
 }
 
-fn main() {
-    let stdin = std::io::stdin();
-    let mut ltr = 0;
-    let mut abm = 0;
-    for line in stdin.lock().lines().flatten() {
-        ltr += left_to_right(&line).unwrap();
-        abm += add_before_mult(&line).unwrap();
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Assoc {
+    Left,
+    Right
+}
+
+// The integers expressions are evaluated over: u64 by default, or i128 for inputs whose values
+// (or differences) leave the range of u64.
+trait Number: Copy + PartialEq + Debug + Display + FromStr {
+    const ZERO: Self;
+
+    // None when the result is not representable, or the operator is not one of + - * / ^
+    fn apply(op: char, x: Self, y: Self) -> Option<Self>;
+}
+
+impl Number for u64 {
+    const ZERO: u64 = 0;
+
+    fn apply(op: char, x: u64, y: u64) -> Option<u64> {
+        match op {
+            '+' => x.checked_add(y),
+            '-' => x.checked_sub(y),
+            '*' => x.checked_mul(y),
+            '/' => x.checked_div(y),
+            '^' => (y <= u32::MAX as u64).then(|| x.checked_pow(y as u32)).flatten(),
+            _ => None
+        }
+    }
+}
+
+impl Number for i128 {
+    const ZERO: i128 = 0;
+
+    fn apply(op: char, x: i128, y: i128) -> Option<i128> {
+        match op {
+            '+' => x.checked_add(y),
+            '-' => x.checked_sub(y),
+            '*' => x.checked_mul(y),
+            '/' => x.checked_div(y),
+            '^' => (0..=u32::MAX as i128).contains(&y).then(|| x.checked_pow(y as u32)).flatten(),
+            _ => None
+        }
+    }
+}
+
+const OPERATORS: &str = "+-*/^";
+
+#[derive(Debug, PartialEq, Eq)]
+enum EvalError {
+    // The expression does not parse, or uses an operator missing from the table
+    Syntax(String),
+    // A literal, an intermediate result or a sum of results which does not fit the number type
+    Overflow(String),
+    DivisionByZero(String)
+}
+
+impl Display for EvalError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            EvalError::Syntax(msg) => write!(f, "{}", msg),
+            EvalError::Overflow(what) => write!(f, "{} overflows", what),
+            EvalError::DivisionByZero(what) => write!(f, "{} divides by zero", what)
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Token<N> {
+    Number(N),
+    Op(char),
+    Open,
+    Close
+}
+
+fn tokenize<N: Number>(line: &str) -> Result<Vec<Token<N>>, EvalError> {
+    let mut tokens = vec!();
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            c if c.is_whitespace() => (),
+            c if c.is_ascii_digit() => {
+                let mut digits = c.to_string();
+                while let Some(d) = chars.peek().filter(|c| c.is_ascii_digit()) {
+                    digits.push(*d);
+                    chars.next();
+                }
+                let x = digits.parse().map_err(|_| EvalError::Overflow(format!("literal {}", digits)))?;
+                tokens.push(Token::Number(x));
+            },
+            op => tokens.push(Token::Op(op))
+        }
+    }
+    Ok(tokens)
+}
+
+// Evaluates expressions by precedence climbing over a table of binary operators, each with
+// a precedence (higher binds tighter) and an associativity. The supported operators are
+// + - * / and ^; arithmetic that would overflow N (or, for u64, leave the naturals) is an error.
+struct Evaluator<N = u64> {
+    ops: HashMap<char, (u8, Assoc)>,
+    number: PhantomData<N>
+}
+
+impl<N: Number> Evaluator<N> {
+    fn new(table: Vec<(char, u8, Assoc)>) -> Evaluator<N> {
+        let ops = table.into_iter().map(|(op, precedence, assoc)| (op, (precedence, assoc))).collect();
+        Evaluator { ops, number: PhantomData }
+    }
+
+    // The preset for Part 1
+    fn left_to_right() -> Evaluator<N> {
+        Evaluator::new(vec!(('+', 1, Assoc::Left), ('*', 1, Assoc::Left)))
+    }
+
+    // The preset for Part 2
+    fn add_before_mult() -> Evaluator<N> {
+        Evaluator::new(vec!(('+', 2, Assoc::Left), ('*', 1, Assoc::Left)))
+    }
+
+    // Either the name of a preset, or a comma-separated table of `op:precedence[:left|right]`,
+    // e.g. `+:1,*:2` for conventional precedence.
+    fn parse(spec: &str) -> Result<Evaluator<N>, String> {
+        match spec {
+            "left-to-right" => return Ok(Evaluator::left_to_right()),
+            "add-before-mult" => return Ok(Evaluator::add_before_mult()),
+            _ => ()
+        }
+        let mut table = vec!();
+        for entry in spec.split(',') {
+            let fields: Vec<&str> = entry.trim().split(':').collect();
+            let op = match fields[0].chars().collect::<Vec<char>>().as_slice() {
+                [op] if OPERATORS.contains(*op) => *op,
+                _ => return Err(format!("unsupported operator `{}`", fields[0]))
+            };
+            let precedence = fields.get(1).and_then(|p| u8::from_str_radix(p, 10).ok())
+                .ok_or_else(|| format!("missing or invalid precedence for `{}`", op))?;
+            let assoc = match fields.get(2) {
+                None | Some(&"left") => Assoc::Left,
+                Some(&"right") => Assoc::Right,
+                Some(other) => return Err(format!("associativity must be left or right, not `{}`", other))
+            };
+            if fields.len() > 3 {
+                return Err(format!("unexpected text in `{}`", entry))
+            }
+            table.push((op, precedence, assoc));
+        }
+        Ok(Evaluator::new(table))
+    }
+
+    // As try_evaluate, reporting any error on stderr
+    fn evaluate(&self, line: &str) -> Option<N> {
+        self.try_evaluate(line).map_err(|e| eprintln!("Cannot evaluate {}: {}", line, e)).ok()
+    }
+
+    fn try_evaluate(&self, line: &str) -> Result<N, EvalError> {
+        let tokens = tokenize(line)?;
+        let mut pos = 0;
+        let x = self.expression(&tokens, &mut pos, 0)?;
+        match tokens.get(pos) {
+            None => Ok(x),
+            Some(token) => Err(EvalError::Syntax(format!("unexpected {:?} at token {}", token, pos)))
+        }
     }
 
+    // The sum of the values of `lines`, failing on the first which cannot be evaluated
+    fn sum<'a, J>(&self, mut lines: J) -> Result<N, (EvalError, &'a str)> where J: Iterator<Item=&'a String> {
+        lines.try_fold(N::ZERO, |sum, line| {
+            let x = self.try_evaluate(line).map_err(|e| (e, line.as_str()))?;
+            N::apply('+', sum, x).ok_or_else(|| (EvalError::Overflow("the sum".to_owned()), line.as_str()))
+        })
+    }
+
+    // Evaluates the longest expression starting at `pos` whose operators all have
+    // precedence at least `min_precedence`, leaving `pos` just past it.
+    fn expression(&self, tokens: &[Token<N>], pos: &mut usize, min_precedence: u16) -> Result<N, EvalError> {
+        let mut x = self.operand(tokens, pos)?;
+        while let Some(Token::Op(op)) = tokens.get(*pos) {
+            let (precedence, assoc) = match self.ops.get(op) {
+                Some((precedence, assoc)) => (*precedence as u16, *assoc),
+                None => return Err(EvalError::Syntax(format!("operator {} has no precedence", op)))
+            };
+            if precedence < min_precedence {
+                break
+            }
+            *pos += 1;
+            let y = match assoc {
+                Assoc::Left => self.expression(tokens, pos, precedence + 1)?,
+                Assoc::Right => self.expression(tokens, pos, precedence)?
+            };
+            x = match N::apply(*op, x, y) {
+                Some(z) => z,
+                None if *op == '/' && y == N::ZERO => return Err(EvalError::DivisionByZero(format!("{} {} {}", x, op, y))),
+                None => return Err(EvalError::Overflow(format!("{} {} {}", x, op, y)))
+            };
+        }
+        Ok(x)
+    }
+
+    fn operand(&self, tokens: &[Token<N>], pos: &mut usize) -> Result<N, EvalError> {
+        let token = tokens.get(*pos);
+        *pos += 1;
+        match token {
+            Some(Token::Number(x)) => Ok(*x),
+            Some(Token::Open) => {
+                let x = self.expression(tokens, pos, 0)?;
+                match tokens.get(*pos) {
+                    Some(Token::Close) => {
+                        *pos += 1;
+                        Ok(x)
+                    },
+                    _ => Err(EvalError::Syntax("unmatched opening parenthesis".to_owned()))
+                }
+            },
+            other => Err(EvalError::Syntax(format!("expected a number or sub-expression, found {:?}", other)))
+        }
+    }
+}
+
+// Times the hand-written Part 1 and 2 evaluators against the presets, failing if any line's value differs
+fn bench_evaluators(lines: &[String]) -> Result<(), AdventError> {
+    let parts = [
+        ("left-to-right", left_to_right as fn(&str) -> Option<u64>, Evaluator::left_to_right()),
+        ("add-before-mult", add_before_mult, Evaluator::add_before_mult())
+    ];
+    for (name, hand_written, evaluator) in parts.iter() {
+        let expected: Vec<Option<u64>> = timing::time(&format!("{} (hand-written)", name), || lines.iter().map(|line| hand_written(line)).collect());
+        let actual: Vec<Option<u64>> = timing::time(&format!("{} (preset)", name), || lines.iter().map(|line| evaluator.evaluate(line)).collect());
+        if let Some(idx) = (0..lines.len()).find(|idx| expected[*idx] != actual[*idx]) {
+            return Err(AdventError::no_solution(format!("{} evaluators disagree on `{}`: {:?} vs {:?}", name, lines[idx], expected[idx], actual[idx])))
+        }
+    }
+    Ok(())
+}
+
+fn solve<N: Number>(lines: &[String]) -> Result<(), AdventError> {
+    let failure = |(e, line): (EvalError, &str)| match e {
+        EvalError::Syntax(_) => AdventError::input(format!("cannot evaluate `{}`: {}", line, e)),
+        _ => AdventError::no_solution(format!("while evaluating `{}`, {}", line, e))
+    };
+    let ltr: N = timing::time("Part 1", || Evaluator::left_to_right().sum(lines.iter())).map_err(failure)?;
+    let abm: N = timing::time("Part 2", || Evaluator::add_before_mult().sum(lines.iter())).map_err(failure)?;
+
     println!("Left-to-right sum of provided expressions: {}", ltr);
     println!("Add-before-multiply sum of expressions: {}", abm);
+
+    if let Some(spec) = cli::option_value("--precedence") {
+        let evaluator: Evaluator<N> = Evaluator::parse(&spec).map_err(|e| AdventError::input(format!("bad precedence table: {}", e)))?;
+        let custom: N = timing::time("Custom precedence", || evaluator.sum(lines.iter())).map_err(failure)?;
+        println!("Sum of expressions with precedence {}: {}", spec, custom);
+    }
+    Ok(())
+}
+
+fn run() -> Result<(), AdventError> {
+    let stdin = std::io::stdin();
+    let lines: Vec<String> = stdin.lock().lines().flatten().collect();
+
+    if cli::has_flag("--bench") {
+        bench_evaluators(&lines)?;
+    }
+
+    if cli::has_flag("--i128") {
+        solve::<i128>(&lines)
+    } else {
+        solve::<u64>(&lines)
+    }
+}
+
+fn main() {
+    exit_on_error(run())
 }
 
 #[cfg(test)]
@@ -353,6 +630,96 @@ mod day18_spec {
         assert_eq!(add_before_mult(expr), Some(23340));
     }
 
+    #[test]
+    fn evaluator_presets_test() {
+        let exprs = [
+            "1 + 3", "3 * 2 + 4", "3 + 2 * 4", "(3 * 2) + 4", "1 + 2 * 3 + 4 * 5 + 6",
+            "1 + (2 * 3) + (4 * (5 + 6))", "5 * 9 * (7 * 3 * 3 + 9 * 3 + (8 + 6 * 4))",
+            "((2 + 4 * 9) * (6 + 9 * 8 + 6) + 6) + 2 + 4 * 2"
+        ];
+        for expr in exprs.iter() {
+            assert_eq!(Evaluator::left_to_right().evaluate(expr), left_to_right(expr), "{}", expr);
+            assert_eq!(Evaluator::add_before_mult().evaluate(expr), add_before_mult(expr), "{}", expr);
+        }
+    }
+
+    #[test]
+    fn evaluator_custom_table_test() {
+        let conventional: Evaluator = Evaluator::parse("+:1, -:1, *:2, /:2, ^:3:right").unwrap();
+        assert_eq!(conventional.evaluate("2 + 3 * 4"), Some(14));
+        assert_eq!(conventional.evaluate("10 - 4 - 3"), Some(3));
+        assert_eq!(conventional.evaluate("2 ^ 3 ^ 2"), Some(512));
+        assert_eq!(conventional.evaluate("100 / (2 + 3) / 2"), Some(10));
+
+        let right: Evaluator = Evaluator::new(vec!(('-', 1, Assoc::Right)));
+        assert_eq!(right.evaluate("10 - 4 - 3"), Some(9));
+
+        // underflow, division by zero, and operators missing from the table
+        assert_eq!(conventional.evaluate("3 - 5"), None);
+        assert_eq!(conventional.evaluate("3 / (2 - 2)"), None);
+        assert_eq!(Evaluator::<u64>::left_to_right().evaluate("3 - 5"), None);
+    }
+
+    #[test]
+    fn evaluator_malformed_test() {
+        let evaluator: Evaluator = Evaluator::add_before_mult();
+        assert_eq!(evaluator.evaluate("1 +"), None);
+        assert_eq!(evaluator.evaluate("(1 + 2"), None);
+        assert_eq!(evaluator.evaluate("1 + 2)"), None);
+        assert_eq!(evaluator.evaluate("1 2"), None);
+        assert_eq!(evaluator.evaluate(""), None);
+    }
+
+    #[test]
+    fn evaluator_parse_test() {
+        assert!(Evaluator::<u64>::parse("left-to-right").is_ok());
+        assert!(Evaluator::<u64>::parse("%:1").is_err());
+        assert!(Evaluator::<u64>::parse("+").is_err());
+        assert!(Evaluator::<u64>::parse("+:1:up").is_err());
+        assert!(Evaluator::<u64>::parse("+:1:left:x").is_err());
+    }
+
+    #[test]
+    fn overflow_test() {
+        let big = format!("{} * 2", u64::MAX / 2 + 1);
+        let evaluator: Evaluator = Evaluator::left_to_right();
+        assert_eq!(evaluator.try_evaluate(&big), Err(EvalError::Overflow(format!("{} * 2", u64::MAX / 2 + 1))));
+        assert_eq!(left_to_right(&big), None);
+        assert_eq!(add_before_mult(&big), None);
+        assert_eq!(evaluator.try_evaluate("18446744073709551616 + 1"), Err(EvalError::Overflow("literal 18446744073709551616".to_owned())));
+        assert_eq!(evaluator.try_evaluate("(1 +"), Err(EvalError::Syntax("expected a number or sub-expression, found None".to_owned())));
+
+        let conventional: Evaluator = Evaluator::parse("+:1, -:1, *:2, /:2").unwrap();
+        assert_eq!(conventional.try_evaluate("3 / (2 - 2)"), Err(EvalError::DivisionByZero("3 / 0".to_owned())));
+        assert_eq!(conventional.try_evaluate("3 - 5"), Err(EvalError::Overflow("3 - 5".to_owned())));
+
+        // each line fits, but their sum does not
+        let lines = [format!("{} + 0", u64::MAX), "1".to_owned()];
+        assert_eq!(evaluator.sum(lines.iter()), Err((EvalError::Overflow("the sum".to_owned()), "1")));
+    }
+
+    #[test]
+    fn i128_test() {
+        let big = format!("{} * 2", u64::MAX / 2 + 1);
+        let evaluator: Evaluator<i128> = Evaluator::left_to_right();
+        assert_eq!(evaluator.try_evaluate(&big), Ok(u64::MAX as i128 + 1));
+        assert_eq!(evaluator.try_evaluate("18446744073709551616 + 1"), Ok(18446744073709551617));
+
+        let conventional: Evaluator<i128> = Evaluator::parse("+:1, -:1, *:2, /:2, ^:3:right").unwrap();
+        assert_eq!(conventional.try_evaluate("3 - 5 * 2"), Ok(-7));
+        assert_eq!(conventional.try_evaluate("(0 - 2) ^ 3"), Ok(-8));
+        assert_eq!(conventional.try_evaluate("2 ^ (0 - 1)"), Err(EvalError::Overflow("2 ^ -1".to_owned())));
+        assert_eq!(conventional.try_evaluate("2 ^ 127"), Err(EvalError::Overflow("2 ^ 127".to_owned())));
+
+        let lines = [format!("{} + 0", u64::MAX), "1".to_owned()];
+        assert_eq!(evaluator.sum(lines.iter()), Ok(u64::MAX as i128 + 1));
+
+        // the presets agree with u64 evaluation wherever that succeeds
+        for expr in ["1 + 2 * 3 + 4 * 5 + 6", "5 * 9 * (7 * 3 * 3 + 9 * 3 + (8 + 6 * 4))"].iter() {
+            assert_eq!(Evaluator::<i128>::add_before_mult().evaluate(expr), add_before_mult(expr).map(|x| x as i128));
+        }
+    }
+
     #[test]
     fn test_51() {
         let expr = "1 + (2 * 3) + (4 * (5 + 6))";
@@ -0,0 +1,156 @@
+// Static analysis of a rule graph: the lengths of the messages each rule can match, and which
+// rules can never take part in matching rule 0. Intended for debugging rule sets whose messages
+// fail to match unexpectedly.
+//
+// Reachability is decided by pointer identity within the graph, so the analysis should be run on
+// rules as built by RulesBuilder, before `Rules::simplify` merges identical nodes.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::rc::Rc;
+
+use super::{Rule, Rules};
+
+// The lengths of the strings a rule can match, from `min_len` up to `max_len`
+// (`None` if the rule matches arbitrarily long strings)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub min_len: usize,
+    pub max_len: Option<usize>
+}
+
+impl Span {
+    const EMPTY: Span = Span { min_len: 0, max_len: Some(0) };
+
+    // True if the rule can match the empty string
+    pub fn nullable(&self) -> bool {
+        self.min_len == 0
+    }
+
+    // The span of a rule matching a string of `self` followed by a string of `other`
+    fn then(&self, other: &Span) -> Span {
+        let min_len = self.min_len + other.min_len;
+        let max_len = self.max_len.and_then(|m| other.max_len.map(|n| m + n));
+        Span { min_len, max_len }
+    }
+
+    // The span of a rule matching either a string of `self` or a string of `other`
+    fn or(&self, other: &Span) -> Span {
+        let min_len = self.min_len.min(other.min_len);
+        let max_len = self.max_len.and_then(|m| other.max_len.map(|n| m.max(n)));
+        Span { min_len, max_len }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct RuleReport {
+    pub index: u8,
+    pub span: Span,
+    pub reachable: bool
+}
+
+// One report per rule, in index order
+pub fn analyze(rules: &Rules) -> Vec<RuleReport> {
+    let mut spans = HashMap::new();
+    let reachable = reachable_from(rules, 0);
+    rules.0.iter().map(|(index, rule)| RuleReport {
+        index: *index,
+        span: span(rule, &mut spans),
+        reachable: reachable.contains(index)
+    }).collect()
+}
+
+// The indices of rules which do not appear in the graph of rule 0
+pub fn unreachable(rules: &Rules) -> BTreeSet<u8> {
+    let reachable = reachable_from(rules, 0);
+    rules.0.keys().filter(|index| !reachable.contains(index)).cloned().collect()
+}
+
+fn reachable_from(rules: &Rules, root: u8) -> BTreeSet<u8> {
+    fn visit(rule: &Rc<Rule>, seen: &mut HashSet<*const Rule>) {
+        if !seen.insert(Rc::as_ptr(rule)) {
+            return
+        }
+        match rule.as_ref() {
+            Rule::Literal(_) => (),
+            Rule::Just(rs) => rs.iter().for_each(|r| visit(r, seen)),
+            Rule::Or(alt0, alt1) => alt0.iter().chain(alt1.iter()).for_each(|r| visit(r, seen)),
+            Rule::Nest(left, right) => left.iter().chain(right.iter()).for_each(|r| visit(r, seen))
+        }
+    }
+
+    let mut seen = HashSet::new();
+    if let Some(rule) = rules.0.get(&root) {
+        visit(rule, &mut seen);
+    }
+    rules.0.iter().filter(|(_, rule)| seen.contains(&Rc::as_ptr(rule))).map(|(index, _)| *index).collect()
+}
+
+// Memoized by node, since the rule graph shares subrules heavily
+pub fn span(rule: &Rc<Rule>, memo: &mut HashMap<*const Rule, Span>) -> Span {
+    if let Some(s) = memo.get(&Rc::as_ptr(rule)) {
+        return *s
+    }
+
+    let seq_span = |rs: &Vec<Rc<Rule>>, memo: &mut HashMap<*const Rule, Span>| {
+        rs.iter().fold(Span::EMPTY, |acc, r| acc.then(&span(r, memo)))
+    };
+
+    let s = match rule.as_ref() {
+        Rule::Literal(_) => Span { min_len: 1, max_len: Some(1) },
+        Rule::Just(rs) => seq_span(rs, memo),
+        Rule::Or(alt0, alt1) => seq_span(alt0, memo).or(&seq_span(alt1, memo)),
+        Rule::Nest(left, right) => {
+            // L^k R^k with k >= 1; the shortest match is L R
+            let once = seq_span(left, memo).then(&seq_span(right, memo));
+            Span { min_len: once.min_len, max_len: if once.max_len == Some(0) { Some(0) } else { None } }
+        }
+    };
+    memo.insert(Rc::as_ptr(rule), s);
+    s
+}
+
+#[cfg(test)]
+mod analysis_spec {
+    use super::*;
+    use super::super::RulesBuilder;
+
+    fn build(lines: &[&str]) -> Rules {
+        let mut builder = RulesBuilder::new();
+        for line in lines {
+            builder.add_line(line);
+        }
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn analyze_test() {
+        let rules = build(&[
+            "0: 4 1 5",
+            "1: 2 3 | 3 2",
+            "2: 4 4 | 5 5",
+            "3: 4 5 | 5 4",
+            "4: \"a\"",
+            "5: \"b\"",
+            "6: 4 | 4 1",
+            "7: 5"
+        ]);
+        let reports = analyze(&rules);
+        assert_eq!(reports.len(), 8);
+        assert_eq!(reports[0], RuleReport { index: 0, span: Span { min_len: 6, max_len: Some(6) }, reachable: true });
+        assert_eq!(reports[6], RuleReport { index: 6, span: Span { min_len: 1, max_len: Some(5) }, reachable: false });
+        assert!(reports.iter().all(|r| !r.span.nullable()));
+
+        let expected: BTreeSet<u8> = vec!(6, 7).into_iter().collect();
+        assert_eq!(unreachable(&rules), expected);
+    }
+
+    #[test]
+    fn nest_span_test() {
+        let rules = build(&["4: \"a\"", "5: \"b\"", "42: 4 4 | 5", "31: 5 5", "8: 42 | 42 8", "11: 42 31 | 42 11 31", "0: 8 11"]);
+        let mut memo = HashMap::new();
+        assert_eq!(span(&rules.0[&8], &mut memo), Span { min_len: 1, max_len: None });
+        assert_eq!(span(&rules.0[&11], &mut memo), Span { min_len: 3, max_len: None });
+        assert_eq!(span(&rules.0[&0], &mut memo), Span { min_len: 4, max_len: None });
+        assert!(unreachable(&rules).is_empty());
+    }
+}
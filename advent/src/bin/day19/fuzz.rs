@@ -0,0 +1,192 @@
+// Random testing of the matcher: messages sampled from a rule's grammar must match it, and
+// messages mutated so that they cannot belong to the grammar must not.
+//
+// Nest rules match arbitrarily long messages, so sampling bounds how many times each one repeats.
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+
+use advent::random::Lcg;
+
+use super::analysis;
+use super::{Rule, Rules};
+
+// Never the letter of a literal rule, which RulesBuilder only accepts in a..z
+const FOREIGN: char = '#';
+
+impl Rule {
+    fn sample(&self, rng: &mut Lcg, max_depth: usize, msg: &mut String) {
+        fn sequence(rules: &[Rc<Rule>], rng: &mut Lcg, max_depth: usize, msg: &mut String) {
+            for rule in rules {
+                rule.sample(rng, max_depth, msg);
+            }
+        }
+
+        match self {
+            Rule::Literal(c) => msg.push(*c),
+            Rule::Just(rules) => sequence(rules, rng, max_depth, msg),
+            Rule::Or(alt0, alt1) => {
+                let alt = if rng.below(2) == 0 { alt0 } else { alt1 };
+                sequence(alt, rng, max_depth, msg)
+            },
+            Rule::Nest(left, right) => {
+                let k = 1 + rng.below(max_depth.max(1) as u64);
+                // L^k R^k, each copy sampled afresh
+                for _ in 0..k {
+                    sequence(left, rng, max_depth, msg);
+                }
+                for _ in 0..k {
+                    sequence(right, rng, max_depth, msg);
+                }
+            }
+        }
+    }
+}
+
+impl Rules {
+    // A random message matching rule `rule_idx`, with each Nest rule repeated at most `max_depth`
+    // times (but at least once), or None if there is no such rule
+    pub fn sample(&self, rule_idx: u8, rng: &mut Lcg, max_depth: usize) -> Option<String> {
+        let mut msg = String::new();
+        self.0.get(&rule_idx)?.sample(rng, max_depth, &mut msg);
+        Some(msg)
+    }
+}
+
+// A message on which the matcher disagreed with the grammar it came from
+#[derive(Debug, PartialEq, Eq)]
+pub enum FuzzFailure {
+    Rejected(String),
+    Accepted { sampled: String, mutated: String }
+}
+
+impl Display for FuzzFailure {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            FuzzFailure::Rejected(msg) => write!(f, "sampled message {:?} does not match", msg),
+            FuzzFailure::Accepted { sampled, mutated } => write!(f, "{:?}, mutated from {:?}, matches", mutated, sampled)
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct FuzzReport {
+    pub samples: usize,
+    pub mutations: usize
+}
+
+// Each mutation of `msg` which no grammar of the given fixed length (if any) can match: a foreign
+// letter inserted anywhere and, for a fixed length, a letter dropped or repeated
+fn mutations(msg: &str, rng: &mut Lcg, fixed_len: Option<usize>) -> Vec<String> {
+    let chars: Vec<char> = msg.chars().collect();
+    let at = |rng: &mut Lcg, n: usize| rng.below(n as u64) as usize;
+    let mut mutated = vec!();
+
+    let pos = at(rng, chars.len() + 1);
+    mutated.push(chars[..pos].iter().chain(&[FOREIGN]).chain(&chars[pos..]).collect());
+    if fixed_len.is_some() && !chars.is_empty() {
+        let pos = at(rng, chars.len());
+        mutated.push(chars[..pos].iter().chain(&chars[pos + 1..]).collect());
+        mutated.push(chars[..=pos].iter().chain(&chars[pos..]).collect());
+    }
+    mutated
+}
+
+// Checks total_match against `samples` messages sampled from rule `rule_idx` and their mutations
+pub fn fuzz(rules: &Rules, rule_idx: u8, rng: &mut Lcg, samples: usize, max_depth: usize) -> Result<FuzzReport, FuzzFailure> {
+    let rule = match rules.0.get(&rule_idx) {
+        Some(rule) => rule,
+        None => return Ok(FuzzReport { samples: 0, mutations: 0 })
+    };
+    let span = analysis::span(rule, &mut HashMap::new());
+    let fixed_len = span.max_len.filter(|max_len| *max_len == span.min_len);
+
+    let mut report = FuzzReport { samples: 0, mutations: 0 };
+    for _ in 0..samples {
+        let msg = rules.sample(rule_idx, rng, max_depth).unwrap();
+        if !rule.total_match(&msg) {
+            return Err(FuzzFailure::Rejected(msg))
+        }
+        report.samples += 1;
+        for mutated in mutations(&msg, rng, fixed_len) {
+            if rule.total_match(&mutated) {
+                return Err(FuzzFailure::Accepted { sampled: msg, mutated })
+            }
+            report.mutations += 1;
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod fuzz_spec {
+    use super::*;
+    use super::super::RulesBuilder;
+
+    fn build(lines: &[&str]) -> Rules {
+        let mut builder = RulesBuilder::new();
+        for line in lines {
+            builder.add_line(line);
+        }
+        builder.build().unwrap()
+    }
+
+    const EXAMPLE: [&str; 6] = ["0: 4 1 5", "1: 2 3 | 3 2", "2: 4 4 | 5 5", "3: 4 5 | 5 4", "4: \"a\"", "5: \"b\""];
+
+    #[test]
+    fn sample_test() {
+        let rules = build(&EXAMPLE);
+        let mut rng = Lcg::new(2020);
+        for _ in 0..50 {
+            let msg = rules.sample(0, &mut rng, 3).unwrap();
+            assert_eq!(msg.len(), 6);
+            assert!(rules.0[&0].total_match(&msg), "{}", msg);
+        }
+        assert_eq!(rules.sample(9, &mut rng, 3), None);
+
+        // the sampler reaches every one of rule 0's eight messages
+        let sampled: std::collections::BTreeSet<String> = (0..200).map(|_| rules.sample(0, &mut rng, 3).unwrap()).collect();
+        assert_eq!(sampled.len(), 8);
+
+        // Nest rules repeat between 1 and max_depth times
+        let rules = build(&["4: \"a\"", "5: \"b\"", "8: 4 | 4 8", "11: 4 5 | 4 11 5"]);
+        for _ in 0..50 {
+            let msg = rules.sample(8, &mut rng, 3).unwrap();
+            assert!((1..=3).contains(&msg.len()) && msg.chars().all(|c| c == 'a'), "{}", msg);
+            let msg = rules.sample(11, &mut rng, 0).unwrap();
+            assert_eq!(msg, "ab");
+        }
+    }
+
+    #[test]
+    fn mutations_test() {
+        let mut rng = Lcg::new(2020);
+        let mutated = mutations("ab", &mut rng, None);
+        assert_eq!(mutated.len(), 1);
+        assert!(mutated[0].len() == 3 && mutated[0].contains(FOREIGN));
+        assert_eq!(mutated[0].replace(FOREIGN, ""), "ab");
+
+        let mutated = mutations("abc", &mut rng, Some(3));
+        assert_eq!(mutated.iter().map(|m| m.len()).collect::<Vec<usize>>(), vec!(4, 2, 4));
+        assert_eq!(mutations("", &mut rng, Some(0)), vec!(FOREIGN.to_string()));
+    }
+
+    #[test]
+    fn fuzz_test() {
+        let mut rng = Lcg::new(2020);
+        let rules = build(&EXAMPLE);
+        assert_eq!(fuzz(&rules, 0, &mut rng, 100, 4), Ok(FuzzReport { samples: 100, mutations: 300 }));
+
+        // of unbounded length, so only foreign letters are inserted
+        let mut lines = EXAMPLE.to_vec();
+        lines.extend(&["8: 1 | 1 8", "11: 2 3 | 2 11 3", "6: 8 11"]);
+        let rules = build(&lines);
+        assert_eq!(fuzz(&rules, 6, &mut rng, 100, 4), Ok(FuzzReport { samples: 100, mutations: 100 }));
+        assert_eq!(fuzz(&rules, 7, &mut rng, 100, 4), Ok(FuzzReport { samples: 0, mutations: 0 }));
+
+        assert_eq!(FuzzFailure::Rejected("ab".to_owned()).to_string(), "sampled message \"ab\" does not match");
+        assert_eq!(FuzzFailure::Accepted { sampled: "ab".to_owned(), mutated: "a#b".to_owned() }.to_string(),
+            "\"a#b\", mutated from \"ab\", matches");
+    }
+}
@@ -0,0 +1,861 @@
+use std::io::prelude::*;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::rc::Rc;
+
+#[macro_use]
+extern crate lazy_static;
+use regex::Regex;
+
+use advent::cli;
+use advent::error::{AdventError, exit_on_error};
+use advent::random::Lcg;
+use advent::table::Table;
+use advent::timing;
+
+mod analysis;
+mod fuzz;
+
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+enum Rule {
+    Literal(char),
+    Just(Vec<Rc<Rule>>),
+    Or(Vec<Rc<Rule>>, Vec<Rc<Rule>>),
+
+    // A self-referencing rule `n: L R | L n R`, which matches L^k R^k for k >= 1. With R empty,
+    // as in `n: L | L n`, it matches L repeated. Other self-references are not supported.
+    Nest(Vec<Rc<Rule>>, Vec<Rc<Rule>>)
+}
+
+impl Rule {
+    // Every suffix of `msg` which can remain after this rule matches a prefix of it, shortest
+    // (i.e. longest match) first. Both alternatives of each Or are followed, so an ambiguous
+    // grammar, such as `15: 1 | 14` inside a longer sequence, is matched however the ambiguity must
+    // be resolved; likewise a Nest rule takes any number of copies of L.
+    fn partial_match<'a>(&self, msg: &'a str) -> Vec<&'a str> {
+        fn sequence<'a>(rules: &[Rc<Rule>], msgs: Vec<&'a str>) -> Vec<&'a str> {
+            rules.iter().fold(msgs, |tails, rule| {
+                let mut next: Vec<&'a str> = tails.iter().flat_map(|tail| rule.partial_match(tail)).collect();
+                next.sort_by_key(|tail| tail.len());
+                next.dedup();
+                next
+            })
+        }
+
+        let mut tails = match self {
+            Rule::Literal(c) => match msg.chars().next() {
+                Some(h) if h == *c => vec!(&msg[c.len_utf8()..]),
+                _ => vec!()
+            },
+            Rule::Just(subrules) => sequence(subrules, vec!(msg)),
+            Rule::Or(alt0, alt1) => {
+                let mut tails = sequence(alt0, vec!(msg));
+                tails.extend(sequence(alt1, vec!(msg)));
+                tails
+            },
+            Rule::Nest(left, right) => {
+                let mut tails = vec!();
+                for tail in sequence(left, vec!(msg)) {
+                    tails.extend(sequence(right, vec!(tail)));
+                    if tail.len() < msg.len() {
+                        tails.extend(sequence(right, self.partial_match(tail)));
+                    }
+                }
+                tails
+            }
+        };
+        tails.sort_by_key(|tail| tail.len());
+        tails.dedup();
+        tails
+    }
+
+    // Whether any way of matching this rule consumes the whole message
+    fn total_match(&self, msg: &str) -> bool {
+        self.partial_match(msg).contains(&"")
+    }
+}
+
+impl std::fmt::Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn write_seq(f: &mut std::fmt::Formatter<'_>, rules: &[Rc<Rule>]) -> std::fmt::Result {
+            for (idx, rule) in rules.iter().enumerate() {
+                if idx > 0 {
+                    write!(f, " ")?;
+                }
+                write!(f, "{}", rule)?;
+            }
+            Ok(())
+        }
+
+        match self {
+            Rule::Literal(c) => write!(f, "\"{}\"", c),
+            Rule::Just(rules) => {
+                write!(f, "(")?;
+                write_seq(f, rules)?;
+                write!(f, ")")
+            },
+            Rule::Or(alt0, alt1) => {
+                write!(f, "(")?;
+                write_seq(f, alt0)?;
+                write!(f, " | ")?;
+                write_seq(f, alt1)?;
+                write!(f, ")")
+            },
+            Rule::Nest(left, right) => {
+                write!(f, "(")?;
+                write_seq(f, left)?;
+                if right.is_empty() {
+                    write!(f, ")+")
+                } else {
+                    write!(f, ")^k (")?;
+                    write_seq(f, right)?;
+                    write!(f, ")^k")
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Rules(BTreeMap<u8, Rc<Rule>>);
+
+#[derive(Debug, PartialEq, Eq, Default)]
+struct SimplifyStats {
+    nodes_before: usize,
+    nodes_after: usize,
+    inlined: usize, // single-element Just wrappers replaced by their contents
+    flattened: usize, // Just sequences spliced into an enclosing sequence
+    deduplicated: usize // structurally identical nodes merged into a shared Rc
+}
+
+impl Rules {
+    // Rewrites every rule into an equivalent, smaller graph:
+    // * `Just` rules of a single subrule (e.g. "8: 42") are replaced by the subrule itself;
+    // * `Just` rules appearing inside a sequence are spliced into that sequence;
+    // * an `Or` whose alternatives are identical becomes a `Just`;
+    // * structurally identical subtrees are hash-consed so that they share a single Rc.
+    // Matching semantics are unchanged.
+    fn simplify(&mut self) -> SimplifyStats {
+        struct Simplifier {
+            stats: SimplifyStats,
+            interned: HashMap<Rule, Rc<Rule>>,
+            visited: HashMap<*const Rule, Rc<Rule>>
+        }
+
+        impl Simplifier {
+            fn intern(&mut self, rule: Rule) -> Rc<Rule> {
+                if let Some(existing) = self.interned.get(&rule) {
+                    self.stats.deduplicated += 1;
+                    Rc::clone(existing)
+                } else {
+                    let rc = Rc::new(rule.clone());
+                    self.interned.insert(rule, Rc::clone(&rc));
+                    rc
+                }
+            }
+
+            fn sequence(&mut self, rules: &Vec<Rc<Rule>>) -> Vec<Rc<Rule>> {
+                let mut seq = vec!();
+                for rule in rules {
+                    let simplified = self.rule(rule);
+                    match simplified.as_ref() {
+                        Rule::Just(inner) => {
+                            self.stats.flattened += 1;
+                            seq.extend(inner.iter().cloned());
+                        },
+                        _ => seq.push(simplified)
+                    }
+                }
+                seq
+            }
+
+            fn rule(&mut self, rule: &Rc<Rule>) -> Rc<Rule> {
+                let key: *const Rule = rule.as_ref();
+                if let Some(simplified) = self.visited.get(&key) {
+                    return Rc::clone(simplified)
+                }
+
+                let simplified = match rule.as_ref() {
+                    Rule::Literal(c) => self.intern(Rule::Literal(*c)),
+                    Rule::Just(subrules) => {
+                        let mut seq = self.sequence(subrules);
+                        if seq.len() == 1 {
+                            self.stats.inlined += 1;
+                            seq.pop().unwrap()
+                        } else {
+                            self.intern(Rule::Just(seq))
+                        }
+                    },
+                    Rule::Or(alt0, alt1) => {
+                        let alt0 = self.sequence(alt0);
+                        let alt1 = self.sequence(alt1);
+                        if alt0 == alt1 {
+                            if alt0.len() == 1 {
+                                self.stats.inlined += 1;
+                                Rc::clone(&alt0[0])
+                            } else {
+                                self.intern(Rule::Just(alt0))
+                            }
+                        } else {
+                            self.intern(Rule::Or(alt0, alt1))
+                        }
+                    },
+                    Rule::Nest(left, right) => {
+                        let left = self.sequence(left);
+                        let right = self.sequence(right);
+                        self.intern(Rule::Nest(left, right))
+                    }
+                };
+
+                self.visited.insert(key, Rc::clone(&simplified));
+                simplified
+            }
+        }
+
+        let mut simplifier = Simplifier {
+            stats: SimplifyStats::default(),
+            interned: HashMap::new(),
+            visited: HashMap::new()
+        };
+        simplifier.stats.nodes_before = self.node_count();
+
+        let simplified: BTreeMap<u8, Rc<Rule>> = self.0.iter().map(|(idx, rule)| {
+            (*idx, simplifier.rule(rule))
+        }).collect();
+        let mut stats = simplifier.stats;
+        self.0 = simplified;
+
+        stats.nodes_after = self.node_count();
+        stats
+    }
+
+    // Number of distinct rule nodes reachable from any numbered rule.
+    fn node_count(&self) -> usize {
+        fn visit(rule: &Rc<Rule>, seen: &mut HashSet<*const Rule>) {
+            if seen.insert(rule.as_ref()) {
+                match rule.as_ref() {
+                    Rule::Literal(_) => (),
+                    Rule::Just(rules) => rules.iter().for_each(|r| visit(r, seen)),
+                    Rule::Or(alt0, alt1) => {
+                        alt0.iter().chain(alt1.iter()).for_each(|r| visit(r, seen))
+                    },
+                    Rule::Nest(left, right) => {
+                        left.iter().chain(right.iter()).for_each(|r| visit(r, seen))
+                    }
+                }
+            }
+        }
+
+        let mut seen = HashSet::new();
+        for rule in self.0.values() {
+            visit(rule, &mut seen);
+        }
+        seen.len()
+    }
+}
+
+// Rule definitions, which may be added or overridden at any time; each build resolves the current
+// definitions afresh.
+#[derive(Clone)]
+struct RulesBuilder {
+    just_rules: BTreeMap<u8, Vec<u8>>,
+    or_rules: BTreeMap<u8, (Vec<u8>, Vec<u8>)>,
+    rules: BTreeMap<u8, Rc<Rule>> // will always contain all the literal rules we know about
+}
+
+impl RulesBuilder {
+    fn new() -> RulesBuilder {
+        let just_rules = BTreeMap::new();
+        let or_rules = BTreeMap::new();
+        let rules = BTreeMap::new();
+        RulesBuilder { just_rules, or_rules, rules }
+    }
+
+    fn add_line(&mut self, line: &str) {
+        lazy_static! {
+            static ref LITERAL_PAT: Regex = Regex::new(r#"(\d+):\s*"([a-z])""#).unwrap();
+            static ref JUST_PAT: Regex = Regex::new(r"(\d+): ([\s\d]+)$").unwrap();
+            static ref REF_PAT: Regex = Regex::new(r"(\d+): ([\s\d]+) \| ([\s\d]+)").unwrap();
+            static ref WHITESPACE_PAT: Regex = Regex::new(r"\s+").unwrap();
+        }
+
+        fn split_u8(s: &str) -> Vec<u8> {
+            WHITESPACE_PAT.split(s).flat_map(|w| u8::from_str_radix(w, 10).ok()).collect()
+        }
+
+        if let Some(caps) = LITERAL_PAT.captures(line) {
+            let rule_idx = u8::from_str_radix(&caps[1], 10).unwrap();
+            let c = caps[2].chars().nth(0).unwrap();
+            self.forget(rule_idx);
+            self.rules.insert(rule_idx, Rc::new(Rule::Literal(c)));
+        } else if let Some(caps) = JUST_PAT.captures(line) {
+            let rule_idx = u8::from_str_radix(&caps[1], 10).unwrap();
+            let dependent: Vec<u8> = split_u8(&caps[2]);
+            self.forget(rule_idx);
+            self.just_rules.insert(rule_idx, dependent);
+
+        } else if let Some(caps) = REF_PAT.captures(line) {
+            let rule_idx = u8::from_str_radix(&caps[1], 10).unwrap();
+            self.forget(rule_idx);
+            let alt0: Vec<u8> = split_u8(&caps[2]);
+            let alt1: Vec<u8> = split_u8(&caps[3]);
+
+            self.or_rules.insert(rule_idx, (alt0, alt1));
+        } else {
+            eprintln!("Unexpected line {}", line);
+        }
+    }
+
+    // Drops any previous definition of rule `rule_idx`, so that a new one replaces it
+    fn forget(&mut self, rule_idx: u8) {
+        self.rules.remove(&rule_idx);
+        self.just_rules.remove(&rule_idx);
+        self.or_rules.remove(&rule_idx);
+    }
+
+    fn build(&self) -> Option<Rules> {
+        self.clone().resolve()
+    }
+
+    fn resolve(mut self) -> Option<Rules> {
+        // the only self-references we can resolve are `n: L R | L n R`
+        let mut nests = BTreeMap::new();
+        for (rule_idx, alts) in &self.or_rules {
+            match nest(*rule_idx, alts) {
+                Some(Some(left_right)) => { nests.insert(*rule_idx, left_right); },
+                Some(None) => {
+                    eprintln!("Unsupported self-reference in rule {}: {:?}", rule_idx, alts);
+                    return None
+                },
+                None => ()
+            }
+        }
+        if let Some(rule_idx) = self.just_rules.iter().find(|(idx, rs)| rs.contains(idx)).map(|(idx, _)| *idx) {
+            eprintln!("Unsupported self-reference in rule {}", rule_idx);
+            return None
+        }
+
+        let mut queue = VecDeque::new();
+
+        for rule_idx in self.just_rules.keys() {
+            queue.push_back(*rule_idx);
+        }
+        for rule_idx in self.or_rules.keys() {
+            queue.push_back(*rule_idx);
+        }
+
+        while let Some(rule_idx) = queue.pop_front() {
+
+            enum Resolver {
+                Invalid,
+                Unresolved,
+                Resolved(Vec<Rc<Rule>>)
+            }
+            fn resolve_rules(this: &mut RulesBuilder, queue: &mut VecDeque<u8>, indices: &Vec<u8>) -> Resolver {
+                let mut rules: Vec<Rc<Rule>> = vec!();
+                let mut all_dependencies_resolved = true;
+
+                for idx in indices {
+                    match this.rules.get(idx) {
+                        None => {
+                            all_dependencies_resolved = false;
+                            if this.just_rules.contains_key(idx) || this.or_rules.contains_key(idx) {
+                                queue.push_front(*idx);
+                            } else {
+                                eprintln!("Unknown dependent rules {:?}", indices);
+                                return Resolver::Invalid
+                            }
+                        },
+                        Some(rule) => {
+                            rules.push(Rc::clone(rule));
+                        }
+                    }
+                }
+
+                if all_dependencies_resolved {
+                    return Resolver::Resolved(rules)
+                } else {
+                    return Resolver::Unresolved
+                }
+            }
+
+            if self.rules.contains_key(&rule_idx) {
+                // then we've already handled this rule
+            } else if let Some((left, right)) = nests.get(&rule_idx) {
+                let (left, right) = (left.clone(), right.clone());
+                match (
+                    resolve_rules(&mut self, &mut queue, &left),
+                    resolve_rules(&mut self, &mut queue, &right)
+                ) {
+                    (Resolver::Invalid, _) | (_, Resolver::Invalid) => return None,
+                    (Resolver::Resolved(left), Resolver::Resolved(right)) => {
+                        self.rules.insert(rule_idx, Rc::new(Rule::Nest(left, right)));
+                    },
+                    _ => {
+                        queue.push_back(rule_idx); continue
+                    }
+                }
+            } else if let Some(rs) = self.just_rules.get(&rule_idx) {
+                let rs = rs.clone();
+                match resolve_rules(&mut self, &mut queue, &rs) {
+                    Resolver::Invalid => return None,
+                    Resolver::Unresolved => {
+                        queue.push_back(rule_idx); continue
+                    },
+                    Resolver::Resolved(rules) => self.rules.insert(rule_idx, Rc::new(Rule::Just(rules)))
+                };
+            } else if let Some((alt0, alt1)) = self.or_rules.get(&rule_idx) {
+                let alt0 = alt0.clone();
+                let alt1 = alt1.clone();
+                match (
+                    resolve_rules(&mut self, &mut queue, &alt0),
+                    resolve_rules(&mut self, &mut queue, &alt1)
+                ) {
+                    (Resolver::Invalid, _) => return None,
+                    (_, Resolver::Invalid) => return None,
+                    (Resolver::Unresolved, _) => {
+                        queue.push_back(rule_idx); continue
+                    },
+                    (_, Resolver::Unresolved) => {
+                        queue.push_back(rule_idx); continue
+                    },
+                    (Resolver::Resolved(rules0), Resolver::Resolved(rules1)) => {
+                        self.rules.insert(rule_idx, Rc::new(Rule::Or(rules0, rules1)));
+                    }
+                }
+            }
+        }
+
+        Some(Rules(self.rules))
+    }
+}
+
+// For an Or rule referring to itself, as `n: L R | L n R` (in either order), Some((L, R)); for any
+// other self-reference, Some(None); and None if the rule does not refer to itself.
+fn nest(rule_idx: u8, (alt0, alt1): &(Vec<u8>, Vec<u8>)) -> Option<Option<(Vec<u8>, Vec<u8>)>> {
+    let (base, recursive) = match (alt0.contains(&rule_idx), alt1.contains(&rule_idx)) {
+        (false, false) => return None,
+        (false, true) => (alt0, alt1),
+        (true, false) => (alt1, alt0),
+        (true, true) => return Some(None)
+    };
+    let pos = recursive.iter().position(|idx| *idx == rule_idx).unwrap();
+    let (left, right) = (&recursive[..pos], &recursive[pos + 1..]);
+    if right.contains(&rule_idx) || left.is_empty() || base[..] != [left, right].concat()[..] {
+        return Some(None)
+    }
+    Some(Some((left.to_vec(), right.to_vec())))
+}
+
+// The part 2 replacements for rules 8 and 11
+const RECURSIVE_OVERRIDES: [&str; 2] = ["8: 42 | 42 8", "11: 42 31 | 42 11 31"];
+
+// How many times --fuzz repeats each recursive rule, at most
+const FUZZ_DEPTH: usize = 4;
+
+fn run() -> Result<(), AdventError> {
+    let stdin = std::io::stdin();
+    let mut builder = RulesBuilder::new();
+    for line in stdin.lock().lines().flatten() {
+        if line.is_empty() {
+            break
+        }
+        builder.add_line(&line);
+    }
+
+    let mut rules = builder.build().ok_or_else(|| AdventError::input("rules reference undefined rules"))?;
+    println!("Parsed {} rules.", rules.0.len());
+
+    // must precede simplification, which merges identical nodes
+    if cli::has_flag("--analyze") {
+        let mut table = Table::new(&["Rule", "Min length", "Max length", "Nullable", "Reachable"]);
+        for report in analysis::analyze(&rules) {
+            table.add_row(vec!(
+                report.index.to_string(),
+                report.span.min_len.to_string(),
+                report.span.max_len.map(|n| n.to_string()).unwrap_or_else(|| "∞".to_owned()),
+                report.span.nullable().to_string(),
+                report.reachable.to_string()
+            ));
+        }
+        print!("{}", table);
+    }
+    let unreachable = analysis::unreachable(&rules);
+    if !unreachable.is_empty() {
+        eprintln!("Rules unreachable from rule 0: {:?}", unreachable);
+    }
+    let stats = timing::time("Simplify", || rules.simplify());
+    println!("Simplified rule graph from {} to {} nodes ({} inlined, {} flattened, {} deduplicated).",
+        stats.nodes_before, stats.nodes_after, stats.inlined, stats.flattened, stats.deduplicated);
+    let rule0 = rules.0.get(&0).ok_or_else(|| AdventError::input("rule 0 is not defined"))?;
+
+    for line in RECURSIVE_OVERRIDES.iter() {
+        builder.add_line(line);
+    }
+    let mut recursive_rules = builder.build().ok_or_else(|| AdventError::input("recursive rules reference undefined rules"))?;
+    recursive_rules.simplify();
+    let rule0_recursive = recursive_rules.0.get(&0).ok_or_else(|| AdventError::input("rule 0 is not defined"))?;
+
+    if let Some(samples) = cli::option_value("--fuzz") {
+        let samples = samples.parse::<usize>().map_err(|_| AdventError::input(format!("expected a number of samples, got {}", samples)))?;
+        let mut rng = Lcg::new(2020);
+        for (label, rules) in &[("rule 0", &rules), ("the recursive version of rule 0", &recursive_rules)] {
+            let report = fuzz::fuzz(rules, 0, &mut rng, samples, FUZZ_DEPTH)
+                .map_err(|failure| AdventError::no_solution(format!("fuzzing {}: {}", label, failure)))?;
+            println!("Fuzzed {}: {} sampled messages matched and {} mutations did not.", label, report.samples, report.mutations);
+        }
+    }
+
+    let messages: Vec<String> = stdin.lock().lines().flatten().collect();
+    let m = timing::time("Part 1", || messages.iter().filter(|line| rule0.total_match(line)).count());
+    let m_recursive = timing::time("Part 2", || messages.iter().filter(|line| rule0_recursive.total_match(line)).count());
+
+    println!("{} lines match rule 0", m);
+    println!("{} lines match the recursive version of rule 0", m_recursive);
+    Ok(())
+}
+
+fn main() {
+    exit_on_error(run())
+}
+
+#[cfg(test)]
+mod day19_spec {
+    use super::*;
+
+    mod rules_builder {
+        use super::*;
+
+        #[test]
+        fn add_line_test() {
+            let mut builder = RulesBuilder::new();
+
+            let line = "1: \"a\"";
+            builder.add_line(line);
+
+            assert_eq!(builder.rules.get(&1), Some(&Rc::new(Rule::Literal('a'))));
+
+            let line = "0: 4 1 5";
+            builder.add_line(line);
+            assert_eq!(builder.just_rules.get(&0), Some(&vec!(4, 1, 5)));
+
+            let line = "2: 1 3 | 3 1";
+            builder.add_line(line);
+            assert_eq!(builder.or_rules.get(&2), Some(&(vec!(1, 3), vec!(3, 1))))
+        }
+
+        #[test]
+        fn build_test_negative() {
+            let line0 = "0: 1 2";
+            let line1 = "1: \"a\"";
+            let line2 = "2: 1 3 | 3 1";
+            
+            let mut builder = RulesBuilder::new();
+            builder.add_line(line0);
+
+            assert_eq!(builder.build(), None);
+            
+            let mut builder = RulesBuilder::new();
+            builder.add_line(line0);
+            builder.add_line(line1);
+            assert_eq!(builder.build(), None);
+
+            let mut builder = RulesBuilder::new();
+            builder.add_line(line0);
+            builder.add_line(line1);
+            builder.add_line(line2);
+            assert_eq!(builder.build(), None);
+        }
+
+        #[test]
+        fn build_test() {
+            let line0 = "0: 1 2";
+            let line1 = "1: \"a\"";
+            let line2 = "2: 1 3 | 3 1";
+            let line3 = "3: \"b\"";
+
+            let mut builder = RulesBuilder::new();
+            builder.add_line(line1);
+            let rules = builder.build().unwrap().0;
+            assert_eq!(rules.len(), 1);
+            assert_eq!(rules.get(&1), Some(&Rc::new(Rule::Literal('a'))));
+
+            let mut builder = RulesBuilder::new();
+            builder.add_line(line0);
+            builder.add_line(line1);
+            builder.add_line(line2);
+            builder.add_line(line3);
+            let rules = builder.build().unwrap().0;
+            assert_eq!(rules.len(), 4);
+            let rule1 = Rc::new(Rule::Literal('a'));
+            let rule3 = Rc::new(Rule::Literal('b'));
+            let rule2 = Rc::new(Rule::Or(vec!(Rc::clone(&rule1), Rc::clone(&rule3)), vec!(Rc::clone(&rule3), Rc::clone(&rule1))));
+            let rule0 = Rc::new(Rule::Just(vec!(Rc::clone(&rule1), Rc::clone(&rule2))));
+
+            assert_eq!(rules.get(&1), Some(&rule1));
+            assert_eq!(rules.get(&3), Some(&rule3));
+            assert_eq!(rules.get(&2), Some(&rule2));
+            assert_eq!(rules.get(&0), Some(&rule0));
+
+            let line0 = "0: 4 1 5";
+            let line1 = "1: 2 3 | 3 2";
+            let line2 = "2: 4 4 | 5 5";
+            let line3 = "3: 4 5 | 5 4";
+            let line4 = "4: \"a\"";
+            let line5 = "5: \"b\"";
+
+            let mut builder = RulesBuilder::new();
+            for line in vec!(line0, line1, line2, line3, line4, line5) {
+                builder.add_line(line);
+            }
+            let rules = builder.build().unwrap().0;
+            assert_eq!(rules.len(), 6);
+        }
+
+        #[test]
+        fn override_test() {
+            let mut builder = RulesBuilder::new();
+            for line in vec!("0: 1 2", "1: \"a\"", "2: 1 3 | 3 1", "3: \"b\"") {
+                builder.add_line(line);
+            }
+            let rules = builder.build().unwrap();
+            assert!(rules.0[&0].total_match("aab"));
+            assert!(!rules.0[&0].total_match("bab"));
+
+            // rules depending on an overridden rule see the new definition, whatever its kind
+            builder.add_line("1: \"c\"");
+            let rules = builder.build().unwrap();
+            assert!(rules.0[&0].total_match("ccb"));
+            assert!(!rules.0[&0].total_match("aab"));
+
+            builder.add_line("1: 3 3 | 4");
+            assert_eq!(builder.build(), None);
+            builder.add_line("4: \"c\"");
+            let rules = builder.build().unwrap();
+            assert_eq!(rules.0.len(), 5);
+            assert!(rules.0[&0].total_match("cbc"));
+            assert!(rules.0[&0].total_match("bbbbb"));
+
+            builder.add_line("1: 3 | 3 1");
+            let rules = builder.build().unwrap();
+            assert!(rules.0[&0].total_match("bbbbb"));
+            assert!(rules.0[&0].total_match("bbb"));
+            assert!(!rules.0[&0].total_match("bb"));
+        }
+
+        #[test]
+        fn nest_test() {
+            assert_eq!(nest(8, &(vec!(42), vec!(42, 8))), Some(Some((vec!(42), vec!()))));
+            assert_eq!(nest(8, &(vec!(42, 8), vec!(42))), Some(Some((vec!(42), vec!()))));
+            assert_eq!(nest(11, &(vec!(42, 31), vec!(42, 11, 31))), Some(Some((vec!(42), vec!(31)))));
+            assert_eq!(nest(11, &(vec!(42, 31), vec!(42, 31))), None);
+            assert_eq!(nest(8, &(vec!(42), vec!(8, 42))), Some(None));
+            assert_eq!(nest(8, &(vec!(42), vec!(43, 8))), Some(None));
+            assert_eq!(nest(8, &(vec!(8), vec!(42, 8))), Some(None));
+
+            let mut builder = RulesBuilder::new();
+            builder.add_line("0: \"a\"");
+            builder.add_line("1: 0 1 0 | 0");
+            assert_eq!(builder.build(), None);
+            builder.add_line("1: 0 1");
+            assert_eq!(builder.build(), None);
+        }
+    }
+
+    mod rule {
+        use super::*;
+
+        #[test]
+        fn partial_match_test() {
+            let rule_a = Rc::new(Rule::Literal('a'));
+            assert_eq!(rule_a.partial_match("a"), vec!(""));
+            assert_eq!(rule_a.partial_match("abc"), vec!("bc"));
+            assert!(rule_a.partial_match("bc").is_empty());
+
+            let rule_b = Rc::new(Rule::Literal('b'));
+            let rule_ab = Rule::Just(vec!(Rc::clone(&rule_a), Rc::clone(&rule_b)));
+            assert!(rule_ab.partial_match("a").is_empty());
+            assert_eq!(rule_ab.partial_match("ab"), vec!(""));
+            assert_eq!(rule_ab.partial_match("abc"), vec!("c"));
+            assert!(rule_ab.partial_match("bc").is_empty());
+
+            let rule_a_or_b = Rule::Or(
+                vec!(Rc::clone(&rule_a)),
+                vec!(Rc::clone(&rule_b))
+            );
+            assert_eq!(rule_a_or_b.partial_match("a"), vec!(""));
+            assert_eq!(rule_a_or_b.partial_match("b"), vec!(""));
+            assert_eq!(rule_a_or_b.partial_match("ab"), vec!("b"));
+            assert_eq!(rule_a_or_b.partial_match("ba"), vec!("a"));
+            assert!(rule_a_or_b.partial_match("c").is_empty());
+            assert!(rule_a_or_b.partial_match("cba").is_empty());
+
+            // both alternatives match, leaving different remainders
+            let rule_a_or_ab = Rule::Or(vec!(Rc::clone(&rule_a)), vec!(Rc::clone(&rule_a), Rc::clone(&rule_b)));
+            assert_eq!(rule_a_or_ab.partial_match("abb"), vec!("b", "bb"));
+            let rule_a_or_a = Rule::Or(vec!(Rc::clone(&rule_a)), vec!(Rc::clone(&rule_a)));
+            assert_eq!(rule_a_or_a.partial_match("ab"), vec!("b"));
+        }
+
+        #[test]
+        fn ambiguous_or_test() {
+            // 15 matches "a" or "ab"; a greedy choice of the first alternative cannot match "abb"
+            let mut builder = RulesBuilder::new();
+            for line in &["0: 15 5", "15: 4 | 14", "14: 4 5", "4: \"a\"", "5: \"b\""] {
+                builder.add_line(line);
+            }
+            let rules = builder.build().unwrap();
+            assert!(rules.0[&0].total_match("ab"));
+            assert!(rules.0[&0].total_match("abb"));
+            assert!(!rules.0[&0].total_match("abbb"));
+            assert_eq!(rules.0[&15].partial_match("abb"), vec!("b", "bb"));
+        }
+
+        #[test]
+        fn total_match_test() {
+            let rule5 = Rc::new(Rule::Literal('b'));
+            let rule4 = Rc::new(Rule::Literal('a'));
+            let rule3 = Rc::new(Rule::Or(
+                vec!(Rc::clone(&rule4), Rc::clone(&rule5)),
+                vec!(Rc::clone(&rule5), Rc::clone(&rule4))
+            ));
+            let rule2 = Rc::new(Rule::Or(
+                vec!(Rc::clone(&rule4), Rc::clone(&rule4)),
+                vec!(Rc::clone(&rule5), Rc::clone(&rule5))
+            ));
+            let rule1 = Rc::new(Rule::Or(
+                vec!(Rc::clone(&rule2), Rc::clone(&rule3)),
+                vec!(Rc::clone(&rule3), Rc::clone(&rule2))
+            ));
+            let rule0 = Rc::new(Rule::Just(
+                vec!(Rc::clone(&rule4), Rc::clone(&rule1), Rc::clone(&rule5))
+            ));
+            assert_eq!(rule0.total_match("ababbb"), true);
+            assert_eq!(rule0.total_match("abbbab"), true);
+            assert_eq!(rule0.total_match("bababa"), false);
+            assert_eq!(rule0.total_match("aaabbb"), false);
+            assert_eq!(rule0.total_match("aaaabbb"), false);
+
+        }
+    }
+
+    mod rules {
+        use super::*;
+
+        #[test]
+        fn simplify_test() {
+            let mut builder = RulesBuilder::new();
+            for line in vec!(
+                "0: 4 1 5",
+                "1: 2 3 | 3 2",
+                "2: 4 4 | 5 5",
+                "3: 4 5 | 5 4",
+                "4: \"a\"",
+                "5: \"b\"",
+                "6: 4",
+                "7: 6 6 | 6 6",
+                "8: 0 7",
+                "9: 4 4",
+                "10: 9 | 9"
+            ) {
+                builder.add_line(line);
+            }
+            let mut rules = builder.build().unwrap();
+            let stats = rules.simplify();
+
+            assert!(stats.nodes_after < stats.nodes_before);
+            assert!(Rc::ptr_eq(&rules.0[&6], &rules.0[&4]));
+            assert!(Rc::ptr_eq(&rules.0[&7], &rules.0[&9]));
+            assert!(Rc::ptr_eq(&rules.0[&10], &rules.0[&9]));
+            assert_eq!(rules.0[&7].as_ref(), &Rule::Just(vec!(Rc::clone(&rules.0[&4]), Rc::clone(&rules.0[&4]))));
+            assert_eq!(format!("{}", rules.0[&8]), "(\"a\" ((\"a\" \"a\" | \"b\" \"b\") (\"a\" \"b\" | \"b\" \"a\") | (\"a\" \"b\" | \"b\" \"a\") (\"a\" \"a\" | \"b\" \"b\")) \"b\" \"a\" \"a\")");
+
+            for msg in ["ababbb", "abbbab", "bababa", "aaabbb", "aaaabbb", "ababbbaa", "abbbabaa", "ababbba"] {
+                assert_eq!(rules.0[&8].total_match(msg), msg.len() == 8 && rules.0[&0].total_match(&msg[..6]) && msg.ends_with("aa"));
+            }
+        }
+    }
+
+    #[test]
+    fn recursive_rule_test() {
+        let rule_lines = vec!(
+            "42: 9 14 | 10 1",
+            "9: 14 27 | 1 26",
+            "10: 23 14 | 28 1",
+            "1: \"a\"",
+            "11: 42 31",
+            "5: 1 14 | 15 1",
+            "19: 14 1 | 14 14",
+            "12: 24 14 | 19 1",
+            "16: 15 1 | 14 14",
+            "31: 14 17 | 1 13",
+            "6: 14 14 | 1 14",
+            "2: 1 24 | 14 4",
+            "0: 8 11",
+            "13: 14 3 | 1 12",
+            "15: 1 | 14",
+            "17: 14 2 | 1 7",
+            "23: 25 1 | 22 14",
+            "28: 16 1",
+            "4: 1 1",
+            "20: 14 14 | 1 15",
+            "3: 5 14 | 16 1",
+            "27: 1 6 | 14 18",
+            "14: \"b\"",
+            "21: 14 1 | 1 14",
+            "25: 1 1 | 1 14",
+            "22: 14 14",
+            "8: 42",
+            "26: 14 22 | 1 20",
+            "18: 15 15",
+            "7: 14 5 | 1 21",
+            "24: 14 1",
+        );
+        let mut builder = RulesBuilder::new();
+
+        for line in rule_lines {
+            builder.add_line(line);
+        }
+
+        let mut rules = builder.build().unwrap();
+        let stats = rules.simplify();
+        assert!(stats.nodes_after < stats.nodes_before);
+        let rule0 = rules.0.get(&0).unwrap();
+
+        for line in RECURSIVE_OVERRIDES.iter() {
+            builder.add_line(line);
+        }
+        let recursive_rules = builder.build().unwrap();
+        let rule0_recursive = recursive_rules.0.get(&0).unwrap();
+        assert!(matches!(recursive_rules.0[&8].as_ref(), Rule::Nest(_, right) if right.is_empty()));
+        assert!(matches!(recursive_rules.0[&11].as_ref(), Rule::Nest(_, right) if right.len() == 1));
+
+        let mut m0 = 0;
+        let mut m1 = 0;
+        for msg in vec!(
+            "abbbbbabbbaaaababbaabbbbabababbbabbbbbbabaaaa",
+            "bbabbbbaabaabba",
+            "babbbbaabbbbbabbbbbbaabaaabaaa",
+            "aaabbbbbbaaaabaababaabababbabaaabbababababaaa",
+            "bbbbbbbaaaabbbbaaabbabaaa",
+            "bbbababbbbaaaaaaaabbababaaababaabab",
+            "ababaaaaaabaaab",
+            "ababaaaaabbbaba",
+            "baabbaaaabbaaaababbaababb",
+            "abbbbabbbbaaaababbbbbbaaaababb",
+            "aaaaabbaabaaaaababaa",
+            "aaaabbaaaabbaaa",
+            "aaaabbaabbaaaaaaabbbabbbaaabbaabaaa",
+            "babaaabbbaaabaababbaabababaaab",
+            "aabbbbbaabbbaaaaaabbbbbababaaaaabbaaabba",
+        ) {
+            if rule0.total_match(msg) {
+                m0 += 1;
+            }
+            if rule0_recursive.total_match(msg) {
+                m1 += 1;
+            }
+        }
+        assert_eq!(m0, 3);
+        assert_eq!(m1, 12);
+    }
+}
\ No newline at end of file
@@ -2,9 +2,10 @@ use std::io::prelude::*;
 use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::{Display, Formatter};
 
-#[macro_use]
-extern crate lazy_static;
-use regex::Regex;
+use advent::cli;
+use advent::error::{AdventError, exit_on_error};
+use advent::tiles;
+use advent::timing;
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 enum D4 { // the symmetry group of the square: FIRST, flip the square across the vertical axis if true, THEN rotate CCW
@@ -45,10 +46,10 @@ const RIGHT_PIXEL_COLUMN_MASK: u128 = 0x200802008020080200800; // bits: 81, 71,
 const LEFT_PIXEL_COLUMN_MASK: u128 = RIGHT_PIXEL_COLUMN_MASK << 7; // bits: 88, 78, ..., 18
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
-struct Tile(u128, u16);
+struct Tile(u128, u32);
 
 impl Tile {
-    fn new(pixels: &str, id: u16) -> Option<Tile> {
+    fn new(pixels: &str, id: u32) -> Option<Tile> {
         let mut p = 0;
         for pixel in pixels.chars() {
             match pixel {
@@ -64,7 +65,16 @@ impl Tile {
         Some(Tile(p, id))
     }
 
-    fn id(&self) -> u16 {
+    // A tile read by the library parser, which must be 10 pixels square
+    fn parse(tile: &tiles::Tile) -> Option<Tile> {
+        if tile.rows.len() == 10 && tile.rows.iter().all(|row| row.len() == 10) {
+            Tile::new(&tile.pixels(), tile.id)
+        } else {
+            None
+        }
+    }
+
+    fn id(&self) -> u32 {
         self.1
     }
 
@@ -143,6 +153,27 @@ impl Tile {
             }
         }
     }
+
+    // Like read_pixels, but keeping the border: always 10 rows, each in the low 10 bits (read left to right)
+    fn read_all_pixels(&self, d4: D4) -> Vec<u16> {
+        // the pixel in row r and column c of the unoriented tile
+        let pixel = |r: usize, c: usize| (self.0 >> (99 - 10 * r - c)) & 1 == 1;
+        (0..10).map(|r| {
+            (0..10).fold(0, |row, c| {
+                let on = match d4 {
+                    D4::R0(false) => pixel(r, c),
+                    D4::R0(true) => pixel(r, 9 - c),
+                    D4::R2(false) => pixel(9 - r, 9 - c),
+                    D4::R2(true) => pixel(9 - r, c),
+                    D4::R1(false) => pixel(c, 9 - r),
+                    D4::R1(true) => pixel(c, r),
+                    D4::R3(false) => pixel(9 - c, r),
+                    D4::R3(true) => pixel(9 - c, 9 - r)
+                };
+                (row << 1) | on as u16
+            })
+        }).collect()
+    }
 }
 
 impl Display for Tile {
@@ -191,19 +222,83 @@ const fn reverse_10(x: u16) -> u16 {
     x.reverse_bits() >> 6
 }
 
-fn group_by_edge(tiles: &BTreeSet<Tile>) -> BTreeMap<u16, BTreeSet<(&Tile, D4, Edge)>> {
-    let mut r = BTreeMap::new();
+// An edge and its reversal are the same edge, read from opposite ends; the lesser of the two values
+// stands for both.
+fn canonical(e: u16) -> u16 {
+    e.min(reverse_10(e))
+}
+
+// The tiles bearing each canonical edge, each with the side of the unoriented tile which bears it:
+// four entries per tile, rather than one for each of the 32 (orientation, side) pairs.
+struct EdgeMap<'a> {
+    tiles_by_edge: BTreeMap<u16, Vec<(&'a Tile, Edge)>>
+}
 
-    for tile in tiles {
-        for d4 in D4::items() {
+impl<'a> EdgeMap<'a> {
+    fn new(tiles: &'a BTreeSet<Tile>) -> EdgeMap<'a> {
+        let mut tiles_by_edge: BTreeMap<u16, Vec<(&Tile, Edge)>> = BTreeMap::new();
+        for tile in tiles {
             for edge in Edge::items() {
-                let e = tile.read_edge(d4, edge);
-                r.entry(e).or_insert(BTreeSet::new()).insert((tile, d4, edge));
+                let e = canonical(tile.read_edge(D4::R0(false), edge));
+                tiles_by_edge.entry(e).or_insert(vec!()).push((tile, edge));
             }
         }
+        EdgeMap { tiles_by_edge }
+    }
+
+    fn tiles(&self) -> BTreeSet<&'a Tile> {
+        self.tiles_by_edge.values().flat_map(|ts| ts.iter().map(|(tile, _)| *tile)).collect()
+    }
+
+    // Whether no other tile bears the edge `e`, read either way
+    fn is_unmatched(&self, e: u16) -> bool {
+        self.tiles_by_edge.get(&canonical(e)).map(|ts| ts.len()).unwrap_or(0) <= 1
+    }
+
+    // An available tile, and an orientation of it, whose `side` reads `e`
+    fn find(&self, e: u16, side: Edge, available: &BTreeSet<&Tile>) -> Option<(&'a Tile, D4)> {
+        let candidates = self.tiles_by_edge.get(&canonical(e))?;
+        candidates.iter().filter(|(tile, _)| available.contains(tile)).find_map(|(tile, _)| {
+            D4::items().into_iter().find(|d4| tile.read_edge(*d4, side) == e).map(|d4| (*tile, d4))
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum AnalysisError {
+    // A tile with more than two edges matching no other tile cannot be placed in a rectangular image
+    IsolatedTile { id: u32, unmatched_edges: usize },
+    // An edge (up to reversal) shared by more than two tiles, so that neighbors are not unique
+    AmbiguousEdge { edge: u16, ids: Vec<u32> },
+    // There should be exactly four tiles with two unmatched edges
+    CornerCount(Vec<u32>)
+}
+
+// Identifies the corner tiles from edge matching alone, without assembling the image:
+// a corner tile is one with exactly two edges that match no other tile.
+fn find_corners(edges: &EdgeMap) -> Result<[u32; 4], AnalysisError> {
+    let mut unmatched: BTreeMap<u32, usize> = BTreeMap::new();
+    for (edge, ts) in &edges.tiles_by_edge {
+        match ts[..] {
+            [(tile, _)] => *unmatched.entry(tile.id()).or_insert(0) += 1,
+            [_, _] => (),
+            _ => return Err(AnalysisError::AmbiguousEdge { edge: *edge, ids: ts.iter().map(|(tile, _)| tile.id()).collect() })
+        }
+    }
+
+    let mut corners = vec!();
+    for (id, unmatched_edges) in unmatched {
+        match unmatched_edges {
+            1 => (),
+            2 => corners.push(id),
+            _ => return Err(AnalysisError::IsolatedTile { id, unmatched_edges })
+        }
+    }
+
+    match corners[..] {
+        [c0, c1, c2, c3] => Ok([c0, c1, c2, c3]),
+        _ => Err(AnalysisError::CornerCount(corners))
     }
-    
-    r
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -221,6 +316,24 @@ impl Display for Pixel {
     }
 }
 
+// Whether an assembled image keeps the 1-pixel border of each tile. The borders only serve to line the
+// tiles up, so the search wants them stripped; retaining them shows whether the seams really match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Borders {
+    Stripped,
+    Retained
+}
+
+impl Borders {
+    // The side of each tile's block of pixels in the image
+    fn tile_size(self) -> usize {
+        match self {
+            Borders::Stripped => 8,
+            Borders::Retained => 10
+        }
+    }
+}
+
 struct Image {
     rows: Vec<Vec<Pixel>>
 }
@@ -240,16 +353,21 @@ impl Display for Image {
 }
 
 impl Image {
-    fn new(tiles: &Vec<Vec<(&Tile, D4)>>) -> Image {
+    fn new(tiles: &Vec<Vec<(&Tile, D4)>>, borders: Borders) -> Image {
         let mut rows = vec!();
+        let size = borders.tile_size();
 
         for tile_row in tiles {
-            let mut current_pixel_rows: Vec<Vec<Pixel>> = vec!(vec!(); 8);
+            let mut current_pixel_rows: Vec<Vec<Pixel>> = vec!(vec!(); size);
             for (tile, d4) in tile_row {
-                for (idx, byte) in tile.read_pixels(*d4).iter().enumerate() {
-                    let mut mask = 0x80;
+                let pixel_rows: Vec<u16> = match borders {
+                    Borders::Stripped => tile.read_pixels(*d4).into_iter().map(u16::from).collect(),
+                    Borders::Retained => tile.read_all_pixels(*d4)
+                };
+                for (idx, bits) in pixel_rows.iter().enumerate() {
+                    let mut mask = 1 << (size - 1);
                     while mask > 0 {
-                        if byte & mask > 0 {
+                        if bits & mask > 0 {
                             current_pixel_rows[idx].push(Pixel::On);
                         } else {
                             current_pixel_rows[idx].push(Pixel::Off);
@@ -266,6 +384,36 @@ impl Image {
         Image { rows }
     }
 
+    // The image assembled with Borders::Stripped from the same tiles, given this one was assembled with
+    // Borders::Retained (or rotated or flipped since): drops the outer ring of each 10-pixel block.
+    fn strip_borders(&self) -> Image {
+        let interior = |i: &usize| !matches!(i % 10, 0 | 9);
+        let rows = self.rows.iter().enumerate().filter(|(y, _)| interior(y)).map(|(_, row)| {
+            row.iter().enumerate().filter(|(x, _)| interior(x)).map(|(_, pixel)| *pixel).collect()
+        }).collect();
+
+        Image { rows }
+    }
+
+    // The image split into blocks of `size` pixels square, by a blank column and a blank line between
+    // neighboring blocks, so that the edges of adjoining tiles can be compared by eye.
+    fn render_seams(&self, size: usize) -> String {
+        let mut r = String::new();
+        for (y, row) in self.rows.iter().enumerate() {
+            if y > 0 && y % size == 0 {
+                r.push('\n');
+            }
+            for (x, pixel) in row.iter().enumerate() {
+                if x > 0 && x % size == 0 {
+                    r.push(' ');
+                }
+                r.push_str(&pixel.to_string());
+            }
+            r.push('\n');
+        }
+        r
+    }
+
     // is the pixel at the specified coordinates on or off?
     fn is_on(&self, x: usize, y: usize) -> bool {
         self.rows.get(y).and_then(|row| row.get(x)).map_or(false, |pixel| *pixel == Pixel::On)
@@ -297,36 +445,21 @@ impl Image {
         Image { rows: flipped_rows }
     }
 
-    // returns a dict of sea monsters, keyed by their tail point
-    fn sea_monsters(&self) -> BTreeMap<(usize, usize), BTreeSet<(usize, usize)>> {
+    // returns the occurrences of the pattern, each keyed by the top left corner of its bounding box
+    fn find(&self, pattern: &Pattern) -> BTreeMap<(usize, usize), BTreeSet<(usize, usize)>> {
         let mut r = BTreeMap::new();
+        let height = self.rows.len();
+        let width = self.rows.first().map_or(0, |row| row.len());
+        if pattern.height > height || pattern.width > width {
+            return r
+        }
 
-        for y in 1..(self.rows.len() - 1) {
-            // A sea monster is a subset of a 3x20 window of pixels
-            'x: for x in 0..(self.rows.len() - 20) {
-                let monster: BTreeSet<(usize, usize)> = vec!(
-                    (x    ,  y),
-                    (x + 1,  y + 1),
-                    (x + 4,  y + 1),
-                    (x + 5,  y),
-                    (x + 6,  y),
-                    (x + 7,  y + 1),
-                    (x + 10, y + 1),
-                    (x + 11, y),
-                    (x + 12, y),
-                    (x + 13, y + 1),
-                    (x + 16, y + 1),
-                    (x + 17, y),
-                    (x + 18, y),
-                    (x + 18, y - 1),
-                    (x + 19, y)
-                ).into_iter().collect();
-                for (mx, my) in &monster {
-                    if !self.is_on(*mx, *my) {
-                        continue 'x
-                    }
+        for y in 0..=(height - pattern.height) {
+            for x in 0..=(width - pattern.width) {
+                if pattern.offsets.iter().all(|(dx, dy)| self.is_on(x + dx, y + dy)) {
+                    let cells = pattern.offsets.iter().map(|(dx, dy)| (x + dx, y + dy)).collect();
+                    r.insert((x, y), cells);
                 }
-                r.insert((x,y), monster);
             }
         }
 
@@ -334,51 +467,200 @@ impl Image {
     }
 }
 
-fn assemble_greedy(tiles: &BTreeMap<u16, BTreeSet<(&Tile, D4, Edge)>>) -> Result<Image, String> {
+// The image packed one bit per pixel: pixel (x, y) is bit x % 64 of rows[y][x / 64]
+struct BitImage {
+    width: usize,
+    rows: Vec<Vec<u64>>
+}
+
+impl BitImage {
+    fn new(image: &Image) -> BitImage {
+        let width = image.rows.first().map_or(0, |row| row.len());
+        let rows = image.rows.iter().map(|row| {
+            let mut words = vec!(0u64; width.div_ceil(64));
+            for (x, pixel) in row.iter().enumerate() {
+                if *pixel == Pixel::On {
+                    words[x / 64] |= 1 << (x % 64);
+                }
+            }
+            words
+        }).collect();
+        BitImage { width, rows }
+    }
+
+    // pixels x..x + n (n <= 64) of row y, as the low n bits
+    fn window(&self, x: usize, y: usize, n: usize) -> u64 {
+        let words = &self.rows[y];
+        let (idx, shift) = (x / 64, x % 64);
+        let mut bits = words[idx] >> shift;
+        if shift + n > 64 && idx + 1 < words.len() {
+            bits |= words[idx + 1] << (64 - shift);
+        }
+        if n < 64 {
+            bits & ((1 << n) - 1)
+        } else {
+            bits
+        }
+    }
+
+    // as Image::find
+    fn find(&self, pattern: &BitPattern) -> BTreeMap<(usize, usize), BTreeSet<(usize, usize)>> {
+        let mut r = BTreeMap::new();
+        let height = self.rows.len();
+        if pattern.height > height || pattern.width > self.width {
+            return r
+        }
+
+        for y in 0..=(height - pattern.height) {
+            for x in 0..=(self.width - pattern.width) {
+                let found = pattern.masks.iter().enumerate()
+                    .all(|(dy, mask)| self.window(x, y + dy, pattern.width) & mask == *mask);
+                if found {
+                    r.insert((x, y), pattern.cells(x, y));
+                }
+            }
+        }
+
+        r
+    }
+}
+
+// A pattern at most 64 pixels wide, as one mask per row: bit dx of masks[dy] is set for offset (dx, dy)
+struct BitPattern {
+    masks: Vec<u64>,
+    width: usize,
+    height: usize
+}
+
+impl BitPattern {
+    fn new(pattern: &Pattern) -> Option<BitPattern> {
+        if pattern.width > 64 {
+            return None
+        }
+        let mut masks = vec!(0u64; pattern.height);
+        for (dx, dy) in &pattern.offsets {
+            masks[*dy] |= 1 << dx;
+        }
+        Some(BitPattern { masks, width: pattern.width, height: pattern.height })
+    }
+
+    fn cells(&self, x: usize, y: usize) -> BTreeSet<(usize, usize)> {
+        let mut cells = BTreeSet::new();
+        for (dy, mask) in self.masks.iter().enumerate() {
+            for dx in 0..self.width {
+                if mask & (1 << dx) > 0 {
+                    cells.insert((x + dx, y + dy));
+                }
+            }
+        }
+        cells
+    }
+}
+
+// Times the pixel-by-pixel and the bitwise searches against each other, in every orientation
+fn bench_search(image: &Image, patterns: &[Pattern]) -> Result<(), String> {
+    let bit_patterns = patterns.iter()
+        .map(|pattern| BitPattern::new(pattern).ok_or("patterns wider than 64 pixels are not supported"))
+        .collect::<Result<Vec<BitPattern>, _>>()?;
+    let mut image = Image { rows: image.rows.clone() };
+
+    for i in 0..8 {
+        let pixel_matches: Vec<_> = timing::time("Pixel search", || {
+            patterns.iter().map(|pattern| image.find(pattern)).collect()
+        });
+        let bit_matches: Vec<_> = timing::time("Bit search", || {
+            let bit_image = BitImage::new(&image);
+            bit_patterns.iter().map(|pattern| bit_image.find(pattern)).collect()
+        });
+        if pixel_matches != bit_matches {
+            return Err(format!("searches disagree in orientation {}", i))
+        }
+        if i == 3 {
+            image = image.flip();
+        } else {
+            image = image.rotate();
+        }
+    }
+
+    Ok(())
+}
+
+// The pattern from the puzzle statement
+const SEA_MONSTER: &str = "                  # \n#    ##    ##    ###\n #  #  #  #  #  #   ";
+
+// A shape to look for in the image, as the offsets of its '#' cells from the top left corner
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Pattern {
+    offsets: Vec<(usize, usize)>,
+    width: usize,
+    height: usize
+}
+
+impl Pattern {
+    // Reads ASCII art in which '#' marks the cells of the pattern and ' ' or '.' the cells it ignores
+    fn parse(art: &str) -> Result<Pattern, String> {
+        let mut offsets = vec!();
+        let mut width = 0;
+        let mut height = 0;
+        for (y, line) in art.lines().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                match c {
+                    '#' => {
+                        offsets.push((x, y));
+                        width = width.max(x + 1);
+                        height = y + 1;
+                    },
+                    ' ' | '.' => (),
+                    _ => return Err(format!("unexpected character {:?} in row {}", c, y + 1))
+                }
+            }
+        }
+        if offsets.is_empty() {
+            Err("pattern has no '#' cells".to_owned())
+        } else {
+            Ok(Pattern { offsets, width, height })
+        }
+    }
+
+    // Reads any number of patterns, separated by empty lines
+    fn parse_all(text: &str) -> Result<Vec<Pattern>, String> {
+        let mut patterns = vec!();
+        let mut art = String::new();
+        for line in text.lines().chain(std::iter::once("")) {
+            if line.is_empty() {
+                if !art.is_empty() {
+                    patterns.push(Pattern::parse(&art)?);
+                    art.clear();
+                }
+            } else {
+                art.push_str(line);
+                art.push('\n');
+            }
+        }
+        Ok(patterns)
+    }
+
+    fn sea_monster() -> Pattern {
+        Pattern::parse(SEA_MONSTER).unwrap()
+    }
+}
+
+fn assemble_greedy(edges: &EdgeMap, borders: Borders) -> Result<Image, String> {
     // We must have a perfect square of tiles
-    let mut available_tiles: BTreeSet<&Tile> = tiles.values().flat_map(|ps| ps.iter().map(|p| p.0)).collect();
+    let mut available_tiles: BTreeSet<&Tile> = edges.tiles();
     let side_length: usize = (available_tiles.len() as f32).sqrt() as usize;
     if side_length * side_length != available_tiles.len() {
         let msg = format!("Tile-set has {} entries, which is not a perfect square.", available_tiles.len());
         return Err(msg)
     }
 
-    let mut tile_matrix: Vec<Vec<(&Tile, D4)>> = {        
-        // seed with upper-left tile
-        let upper_left_tile: (&Tile, D4) = {
-            let mut lefts: BTreeSet<(&Tile, D4)> = BTreeSet::new();
-            let mut uppers: BTreeSet<(&Tile, D4)> = BTreeSet::new();
-    
-            let mut ult: Result<(&Tile, D4), String> = Err("No corner tile found".to_owned());
-    
-            'a: for (_, ts) in tiles {
-                let tile_ids: BTreeSet<u16> = ts.iter().map(|p| p.0.id()).collect();
-                if tile_ids.len() == 1 {
-                    for (tile, d4, edge) in ts {
-                        match edge {
-                            Edge::Left if uppers.contains(&(*tile, *d4)) => {
-                                ult = Ok((*tile, *d4)); break 'a
-                            },
-                            Edge::Left => {
-                                lefts.insert((*tile, *d4));
-                            },
-                            Edge::Top if lefts.contains(&(*tile, *d4)) => {
-                                ult = Ok((*tile, *d4)); break 'a
-                            },
-                            Edge::Top => {
-                                uppers.insert((*tile, *d4));
-                            },
-                            _ => ()
-                        }
-                    }
-                }
-            }
-    
-            match ult { // Oh for a for..else construct...
-                Err(msg) => return Err(msg),
-                Ok(t) => t
-            }
-        };
+    let mut tile_matrix: Vec<Vec<(&Tile, D4)>> = {
+        // seed with a corner tile, turned so that its unmatched edges are on top and to the left
+        let upper_left_tile: (&Tile, D4) = available_tiles.iter().find_map(|tile| {
+            D4::items().into_iter().find(|d4| {
+                edges.is_unmatched(tile.read_edge(*d4, Edge::Top)) && edges.is_unmatched(tile.read_edge(*d4, Edge::Left))
+            }).map(|d4| (*tile, d4))
+        }).ok_or_else(|| "No corner tile found".to_owned())?;
 
         available_tiles.remove(upper_left_tile.0);
         vec!(vec!(upper_left_tile))
@@ -400,17 +682,7 @@ fn assemble_greedy(tiles: &BTreeMap<u16, BTreeSet<(&Tile, D4, Edge)>>) -> Result
                         None => return Err("Unreachable error; last_row is always non-empty".to_owned()),
                         Some((tile, d4)) => {
                             let right_border = tile.read_edge(*d4, Edge::Right);
-                            let opt_next_tile = tiles.get(&right_border).and_then(|candidates| {
-                                let mut j = candidates.iter().flat_map(|p| {
-                                    if let (tile, d4, Edge::Left) = p {
-                                        Some((*tile, *d4)).filter(|q| available_tiles.contains(q.0))
-                                    } else {
-                                        None
-                                    }
-                                });
-                                j.next()
-                            });
-                            match opt_next_tile {
+                            match edges.find(right_border, Edge::Left, &available_tiles) {
                                 Some((tile, d4)) => {
                                     last_row.push((tile, d4));
                                     available_tiles.remove(&tile);
@@ -422,17 +694,7 @@ fn assemble_greedy(tiles: &BTreeMap<u16, BTreeSet<(&Tile, D4, Edge)>>) -> Result
                 };
                 let (upper_tile, upper_d4) = last_row[0];
                 let lower_border = upper_tile.read_edge(upper_d4, Edge::Bottom);
-                let opt_next_tile = tiles.get(&lower_border).and_then(|candidates| {
-                    let mut j = candidates.iter().flat_map(|p| {
-                        if let (tile, d4, Edge::Top) = p {
-                            Some((*tile, *d4)).filter(|q| available_tiles.contains(q.0))
-                        } else {
-                            None
-                        }
-                    });
-                    j.next()
-                });
-                match opt_next_tile {
+                match edges.find(lower_border, Edge::Top, &available_tiles) {
                     Some(t) => {
                         tile_matrix.push(vec!(t));
                         available_tiles.remove(t.0);
@@ -444,101 +706,93 @@ fn assemble_greedy(tiles: &BTreeMap<u16, BTreeSet<(&Tile, D4, Edge)>>) -> Result
         }
     }
 
-    let image = Image::new(&tile_matrix);
+    let image = Image::new(&tile_matrix, borders);
 
     Ok(image)
 }
 
-fn main() {
+fn run() -> Result<(), AdventError> {
     let stdin = std::io::stdin();
-    let mut stdin_lines = stdin.lock().lines().flatten();
-
-    lazy_static! {
-        static ref TILE_HEADER: Regex = Regex::new(r"Tile (\d+):").unwrap();
-    }
-
-    let tiles: BTreeSet<Tile> = {
-        let mut tiles = BTreeSet::new();
-
-        enum LoopState {
-            Begin,
-            // We flatten the pixels into a single line
-            Partial(u16, String)
-        }
+    let stdin_lines = stdin.lock().lines().flatten();
 
-        let mut loop_state = LoopState::Begin;
+    let parsed = tiles::parse_tiles(stdin_lines).map_err(|e| AdventError::input(e.to_string()))?;
+    let tiles: BTreeSet<Tile> = parsed.iter().map(|tile| {
+        Tile::parse(tile).ok_or_else(|| AdventError::input(format!("tile {} is not 10 pixels square", tile.id)))
+    }).collect::<Result<_, _>>()?;
 
-        while let Some(line) = stdin_lines.next() {
-            if let Some(caps) = TILE_HEADER.captures(&line) {
-                let tile_id = u16::from_str_radix(&caps[1], 10).unwrap();
-                loop_state = LoopState::Partial(tile_id, String::new());
-            } else if line.is_empty() {
-                if let LoopState::Partial(tile_id, pixels) = &loop_state {
-                    if let Some(tile) = Tile::new(pixels, *tile_id) {
-                        tiles.insert(tile);
-                    } else {
-                        eprintln!("Could not parse tile from pixels {}", pixels);
-                    }
-                } else {
-                    eprintln!("Wrong state!")
-                }
-            } else if let LoopState::Partial(_, pixels) = &mut loop_state {
-                pixels.push_str(&line);
-            }
-        }
+    println!("Parsed {} tiles", tiles.len());
 
-        tiles
+    let patterns = match cli::option_value("--pattern") {
+        Some(path) => {
+            let art = std::fs::read_to_string(&path)?;
+            Pattern::parse_all(&art).map_err(|e| AdventError::input(format!("bad pattern in {}: {}", path, e)))?
+        },
+        None => vec!(Pattern::sea_monster())
     };
 
-    println!("Parsed {} tiles", tiles.len());
-
-    let tiles_by_edge: BTreeMap<u16, BTreeSet<(&Tile, D4, Edge)>> = group_by_edge(&tiles);
-    let mut border_tiles: BTreeMap<u16, u8> = BTreeMap::new();
-    for (_e, ts) in &tiles_by_edge {
-        let tile_ids: BTreeSet<u16> = ts.iter().map(|p| p.0.id()).collect();
-        if tile_ids.len() == 1 {
-            for tile_id in tile_ids {
-                *border_tiles.entry(tile_id).or_insert(0) += 1;
-            }
-        }
-    }
-    let mut c: u128 = 1;
-    for (tile_id, count) in border_tiles {
-        if count > 2 {
-            c *= tile_id as u128;
-        }
+    let png_path = cli::option_value("--png");
+    if png_path.is_some() && !cfg!(feature = "viz") {
+        return Err(AdventError::input("--png needs a build with the viz feature"))
     }
+
+    let edges = timing::time("Edge map", || EdgeMap::new(&tiles));
+
+    let corners = timing::time("Part 1", || find_corners(&edges))
+        .map_err(|e| AdventError::no_solution(format!("tiles do not form a rectangular image: {:?}", e)))?;
+    let c: u128 = corners.iter().map(|id| *id as u128).product();
     println!("Product of corner tile ids: {}", c);
 
-    let mut image = assemble_greedy(&tiles_by_edge).unwrap();
+    let mut image = if cli::has_flag("--borders") {
+        // show the tiles with their borders, spaced apart, before stripping the borders for the search
+        let bordered = timing::time("Assembly", || assemble_greedy(&edges, Borders::Retained)).map_err(AdventError::no_solution)?;
+        println!("{}", bordered.render_seams(Borders::Retained.tile_size()));
+        bordered.strip_borders()
+    } else {
+        timing::time("Assembly", || assemble_greedy(&edges, Borders::Stripped)).map_err(AdventError::no_solution)?
+    };
 
     println!("{}", image);
 
     for i in 0..8 {
-        let sea_monsters = image.sea_monsters();
-
-        if sea_monsters.len() > 0 {
-            println!("{} sea monsters found!", sea_monsters.len());
-            let sea_monster_coordinates: BTreeSet<(usize, usize)> = sea_monsters.values().fold(BTreeSet::new(), |mut acc, m| {
-                for (x,y) in m {
-                    acc.insert((*x, *y));
-                }                
-                acc
-            });
+        let matches: Vec<BTreeMap<(usize, usize), BTreeSet<(usize, usize)>>> = timing::time("Pattern search", || {
+            patterns.iter().map(|pattern| image.find(pattern)).collect()
+        });
+
+        if matches.iter().any(|found| found.len() > 0) {
+            let mut pattern_coordinates: BTreeSet<(usize, usize)> = BTreeSet::new();
+            for (idx, found) in matches.iter().enumerate() {
+                println!("Pattern {}: {} matches found!", idx + 1, found.len());
+                for cells in found.values() {
+                    pattern_coordinates.extend(cells.iter().cloned());
+                }
+            }
 
             let mut t = 0;
 
             for (y, row) in image.rows.iter().enumerate() {
                 for (x, pixel) in row.iter().enumerate() {
                     if let Pixel::On = pixel {
-                        if !sea_monster_coordinates.contains(&(x,y)) {
+                        if !pattern_coordinates.contains(&(x,y)) {
                             t += 1;
                         }
                     }
                 }
             }
 
-            println!("The image contains {} sea monster pixels and {} rough-water pixels.", sea_monster_coordinates.len(), t);
+            println!("The image contains {} pattern pixels and {} rough-water pixels.", pattern_coordinates.len(), t);
+
+            #[cfg(feature = "viz")]
+            if let Some(path) = &png_path {
+                // rough water in white, the patterns in gray
+                let height = image.rows.len();
+                let width = image.rows.first().map_or(0, |row| row.len());
+                advent::viz::write_png(path, width, height, 4, |x, y| match image.rows[y][x] {
+                    Pixel::On if pattern_coordinates.contains(&(x, y)) => 128,
+                    Pixel::On => 255,
+                    Pixel::Off => 0
+                })?;
+                println!("Wrote the image to {}", path);
+            }
         }
         if i == 3 {
             image = image.flip();
@@ -547,11 +801,21 @@ fn main() {
         }
     }
 
+    if cli::has_flag("--bench") {
+        bench_search(&image, &patterns).map_err(AdventError::input)?;
+    }
+
+    Ok(())
+}
+
+fn main() {
+    exit_on_error(run())
 }
 
 #[cfg(test)]
 mod day20_spec {
     use super::*;
+    use advent::random::Lcg;
 
     #[test]
     fn extract_mask_test() {
@@ -759,8 +1023,7 @@ mod day20_spec {
         ));
     }
 
-    #[test]
-    fn sea_monsters_test() {
+    fn sample_tiles() -> BTreeSet<Tile> {
         let pixels = "..##.#..#.\
                       ##..#.....\
                       #...##..#.\
@@ -869,30 +1132,233 @@ mod day20_spec {
                       ..#.###...";
         let tile3079 = Tile::new(pixels, 3079).unwrap();
 
+        vec!(tile2311, tile1951, tile1171, tile1427, tile1489, tile2473, tile2971, tile2729, tile3079).into_iter().collect()
+    }
+
+    #[test]
+    fn sea_monsters_test() {
+        let tiles = sample_tiles();
+        let tile = |id: u32| tiles.iter().find(|t| t.id() == id).unwrap();
+        let tile2311 = tile(2311);
+        let tile1951 = tile(1951);
+        let tile1171 = tile(1171);
+        let tile1427 = tile(1427);
+        let tile1489 = tile(1489);
+        let tile2473 = tile(2473);
+        let tile2971 = tile(2971);
+        let tile2729 = tile(2729);
+        let tile3079 = tile(3079);
+
         let orientations: Vec<Vec<(&Tile, D4)>> = vec!(
-            vec!((&tile1951, D4::R2(true)), (&tile2311, D4::R2(true)), (&tile3079, D4::R0(false))),
-            vec!((&tile2729, D4::R2(true)), (&tile1427, D4::R2(true)), (&tile2473, D4::R3(true))),
-            vec!((&tile2971, D4::R2(true)), (&tile1489, D4::R2(true)), (&tile1171, D4::R0(true)))
+            vec!((tile1951, D4::R2(true)), (tile2311, D4::R2(true)), (tile3079, D4::R0(false))),
+            vec!((tile2729, D4::R2(true)), (tile1427, D4::R2(true)), (tile2473, D4::R3(true))),
+            vec!((tile2971, D4::R2(true)), (tile1489, D4::R2(true)), (tile1171, D4::R0(true)))
         );
-        let image = Image::new(&orientations);
+        let image = Image::new(&orientations, Borders::Stripped);
+        let sea_monster = Pattern::sea_monster();
 
-        assert_eq!(image.sea_monsters().len(), 0);
+        assert_eq!(image.find(&sea_monster).len(), 0);
 
         let orientations2: Vec<Vec<(&Tile, D4)>> = vec!(
-            vec!((&tile1951, D4::R3(false)), (&tile2729, D4::R3(false)), (&tile2971, D4::R3(false))),
-            vec!((&tile2311, D4::R3(false)), (&tile1427, D4::R3(false)), (&tile1489, D4::R3(false))),
-            vec!((&tile3079, D4::R1(true)),  (&tile2473, D4::R2(false)), (&tile1171, D4::R1(false)))
+            vec!((tile1951, D4::R3(false)), (tile2729, D4::R3(false)), (tile2971, D4::R3(false))),
+            vec!((tile2311, D4::R3(false)), (tile1427, D4::R3(false)), (tile1489, D4::R3(false))),
+            vec!((tile3079, D4::R1(true)),  (tile2473, D4::R2(false)), (tile1171, D4::R1(false)))
         );
-        let image2 = Image::new(&orientations2);
+        let image2 = Image::new(&orientations2, Borders::Stripped);
 
-        assert_eq!(image2.sea_monsters().len(), 2);
+        assert_eq!(image2.find(&sea_monster).len(), 2);
 
         let image3 = image.flip();
 
-        assert_eq!(image3.sea_monsters().len(), 2);
+        assert_eq!(image3.find(&sea_monster).len(), 2);
 
         let image4 = image.rotate();
 
-        assert_eq!(image4.sea_monsters().len(), 0);
+        assert_eq!(image4.find(&sea_monster).len(), 0);
+    }
+
+    #[test]
+    fn pattern_parse_test() {
+        let pattern = Pattern::parse("#.\n.##\n").unwrap();
+        assert_eq!(pattern, Pattern { offsets: vec!((0, 0), (1, 1), (2, 1)), width: 3, height: 2 });
+
+        let sea_monster = Pattern::sea_monster();
+        assert_eq!(sea_monster.offsets.len(), 15);
+        assert_eq!((sea_monster.width, sea_monster.height), (20, 3));
+
+        assert!(Pattern::parse("  \n..").is_err());
+        assert!(Pattern::parse("#x").is_err());
+
+        let patterns = Pattern::parse_all("\n#\n\n##\n# \n").unwrap();
+        assert_eq!(patterns.len(), 2);
+        assert_eq!(patterns[1].offsets, vec!((0, 0), (1, 0), (0, 1)));
+    }
+
+    #[test]
+    fn find_test() {
+        let tiles = sample_tiles();
+        let tile = |id: u32| tiles.iter().find(|t| t.id() == id).unwrap();
+        let image = Image::new(&vec!(vec!((tile(1951), D4::R0(false)))), Borders::Stripped);
+        let on = image.rows.iter().flatten().filter(|pixel| **pixel == Pixel::On).count();
+
+        // a single cell matches every on pixel, and a full-width row is at most one match per row
+        assert_eq!(image.find(&Pattern::parse("#").unwrap()).len(), on);
+        let row = Pattern::parse("########").unwrap();
+        for ((x, y), cells) in image.find(&row) {
+            assert_eq!(x, 0);
+            assert!((0..8).all(|x| image.is_on(x, y)));
+            assert_eq!(cells.len(), 8);
+        }
+        assert_eq!(image.find(&Pattern::parse("#########").unwrap()).len(), 0);
+    }
+
+    #[test]
+    fn bit_image_test() {
+        // a wide random image, so that matches straddle word boundaries
+        let mut rng = Lcg::new(2020);
+        let rows = (0..40).map(|_| {
+            (0..150).map(|_| if rng.below(4) > 0 { Pixel::On } else { Pixel::Off }).collect()
+        }).collect();
+        let image = Image { rows };
+        let bit_image = BitImage::new(&image);
+
+        for y in 0..40 {
+            for x in 0..150 {
+                assert_eq!(bit_image.window(x, y, 1) == 1, image.is_on(x, y));
+            }
+        }
+
+        let patterns = vec!(
+            Pattern::sea_monster(),
+            Pattern::parse("#").unwrap(),
+            Pattern::parse("#.#\n.#.\n#.#").unwrap(),
+            Pattern::parse(&"#".repeat(64)).unwrap()
+        );
+        for pattern in &patterns {
+            let bit_pattern = BitPattern::new(pattern).unwrap();
+            assert_eq!(bit_image.find(&bit_pattern), image.find(pattern));
+        }
+        assert!(BitPattern::new(&Pattern::parse(&"#".repeat(65)).unwrap()).is_none());
+    }
+
+    #[test]
+    fn bit_image_sea_monsters_test() {
+        let tiles = sample_tiles();
+        let tile = |id: u32| tiles.iter().find(|t| t.id() == id).unwrap();
+        let orientations: Vec<Vec<(&Tile, D4)>> = vec!(
+            vec!((tile(1951), D4::R3(false)), (tile(2729), D4::R3(false)), (tile(2971), D4::R3(false))),
+            vec!((tile(2311), D4::R3(false)), (tile(1427), D4::R3(false)), (tile(1489), D4::R3(false))),
+            vec!((tile(3079), D4::R1(true)),  (tile(2473), D4::R2(false)), (tile(1171), D4::R1(false)))
+        );
+        let image = Image::new(&orientations, Borders::Stripped);
+        let sea_monster = BitPattern::new(&Pattern::sea_monster()).unwrap();
+
+        assert_eq!(BitImage::new(&image).find(&sea_monster).len(), 2);
+        assert_eq!(bench_search(&image, &[Pattern::sea_monster()]), Ok(()));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn parse_test() {
+        let text = "Tile 3000000000:\n\
+                    ..##.#..#.\n##..#.....\n#...##..#.\n####.#...#\n##.##.###.\n\
+                    ##...#.###\n.#.#.#..##\n..#....#..\n###...#.#.\n..###..###\n\
+                    \n\
+                    Tile 7:\n\
+                    ..##\n##..\n";
+        let parsed = tiles::parse_tiles(text.lines().map(|line| line.to_owned())).unwrap();
+        let tile = Tile::parse(&parsed[0]).unwrap();
+        assert_eq!(tile.id(), 3000000000);
+        assert_eq!(tile.0, sample_tiles().iter().find(|t| t.id() == 2311).unwrap().0);
+        assert_eq!(Tile::parse(&parsed[1]), None);
+    }
+
+    #[test]
+    fn find_corners_test() {
+        let mut tiles = sample_tiles();
+        assert_eq!(find_corners(&EdgeMap::new(&tiles)), Ok([1171, 1951, 2971, 3079]));
+
+        // Without corner 1171, its two neighbors become corners
+        tiles.retain(|t| t.id() != 1171);
+        assert_eq!(find_corners(&EdgeMap::new(&tiles)), Err(AnalysisError::CornerCount(vec!(1489, 1951, 2473, 2971, 3079))));
+
+        // Without corner 1951 and the tile below it, 2971 is left with a single neighbor
+        let tiles: BTreeSet<Tile> = sample_tiles().into_iter().filter(|t| t.id() != 1951 && t.id() != 2729).collect();
+        assert_eq!(find_corners(&EdgeMap::new(&tiles)), Err(AnalysisError::IsolatedTile { id: 2971, unmatched_edges: 3 }));
+    }
+
+    #[test]
+    fn edge_map_test() {
+        let tiles = sample_tiles();
+        let edges = EdgeMap::new(&tiles);
+        assert_eq!(edges.tiles_by_edge.values().map(|ts| ts.len()).sum::<usize>(), 4 * tiles.len());
+        assert_eq!(edges.tiles().len(), tiles.len());
+        // the outer edges of the 3x3 image are unmatched; every inner edge is shared by two tiles
+        assert_eq!(edges.tiles_by_edge.values().filter(|ts| ts.len() == 1).count(), 12);
+        assert_eq!(edges.tiles_by_edge.values().filter(|ts| ts.len() == 2).count(), 12);
+
+        // 2311 lies to the right of 1951 in the image of the puzzle statement
+        let tile = |id: u32| tiles.iter().find(|t| t.id() == id).unwrap();
+        let available: BTreeSet<&Tile> = tiles.iter().filter(|t| t.id() != 1951).collect();
+        let right_border = tile(1951).read_edge(D4::R2(true), Edge::Right);
+        assert!(!edges.is_unmatched(right_border));
+        let (next, d4) = edges.find(right_border, Edge::Left, &available).unwrap();
+        assert_eq!(next.id(), 2311);
+        assert_eq!(next.read_edge(d4, Edge::Left), right_border);
+        assert!(edges.find(right_border, Edge::Left, &BTreeSet::new()).is_none());
+    }
+
+    #[test]
+    fn assemble_test() {
+        let tiles = sample_tiles();
+        let mut image = assemble_greedy(&EdgeMap::new(&tiles), Borders::Stripped).unwrap();
+        let mut monsters = vec!();
+        for i in 0..8 {
+            monsters.push(image.find(&Pattern::sea_monster()).len());
+            image = if i == 3 { image.flip() } else { image.rotate() };
+        }
+        assert_eq!(monsters.iter().filter(|n| **n > 0).collect::<Vec<_>>(), vec!(&2));
+    }
+
+    #[test]
+    fn read_all_pixels_test() {
+        for tile in sample_tiles() {
+            for d4 in D4::items() {
+                let all = tile.read_all_pixels(d4);
+                assert_eq!(all.len(), 10);
+                // the interior is read_pixels, and the border is the four edges
+                let interior: Vec<u8> = all[1..9].iter().map(|row| (row >> 1) as u8).collect();
+                assert_eq!(interior, tile.read_pixels(d4));
+                assert_eq!(all[0], tile.read_edge(d4, Edge::Top));
+                assert_eq!(all[9], tile.read_edge(d4, Edge::Bottom));
+                let column = |shift: usize| all.iter().fold(0, |col, row| (col << 1) | ((row >> shift) & 1));
+                assert_eq!(column(9), tile.read_edge(d4, Edge::Left));
+                assert_eq!(column(0), tile.read_edge(d4, Edge::Right));
+            }
+        }
+    }
+
+    #[test]
+    fn strip_borders_test() {
+        let tiles = sample_tiles();
+        let edges = EdgeMap::new(&tiles);
+        let stripped = assemble_greedy(&edges, Borders::Stripped).unwrap();
+        let bordered = assemble_greedy(&edges, Borders::Retained).unwrap();
+        assert_eq!((bordered.rows.len(), bordered.rows[0].len()), (30, 30));
+        assert_eq!(bordered.strip_borders().rows, stripped.rows);
+        // stripping commutes with rotating and flipping the whole image
+        assert_eq!(bordered.rotate().flip().strip_borders().rows, stripped.rotate().flip().rows);
+
+        // neighboring tiles agree along each seam
+        for seam in [10, 20].iter() {
+            for y in 0..30 {
+                assert_eq!(bordered.rows[y][seam - 1], bordered.rows[y][*seam]);
+                assert_eq!(bordered.rows[seam - 1][y], bordered.rows[*seam][y]);
+            }
+        }
+
+        let rendered = bordered.render_seams(10);
+        assert_eq!(rendered.lines().count(), 32);
+        assert_eq!(rendered.lines().next().unwrap().len(), 32);
+        assert_eq!(rendered.lines().nth(10), Some(""));
+    }
+}
@@ -4,13 +4,43 @@ use std::collections::{BTreeMap, BTreeSet, VecDeque};
 #[macro_use]
 extern crate lazy_static;
 use regex::Regex;
+use unicode_normalization::UnicodeNormalization;
+
+use advent::bitset::BitSet;
+use advent::cli;
+use advent::counter::Counter;
+use advent::error::{AdventError, exit_on_error};
+use advent::intern::{Interner, Symbol};
+use advent::table::Table;
+use advent::timing;
+
+// Names are compared case-insensitively, and without regard to how accented letters are encoded
+fn normalize(s: &str) -> String {
+    s.to_lowercase().nfc().collect()
+}
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
-struct Ingredient(String);
+type IngredientId = Symbol;
+type AllergenId = Symbol;
+
+// The normalized names of every ingredient and allergen read, which everything else refers to by id.
+// An ingredient's id also indexes it in the bitsets of ingredients.
+#[derive(Debug, Default)]
+struct Names {
+    ingredients: Interner,
+    allergens: Interner
+}
+
+impl Names {
+    fn new() -> Names {
+        Names { ingredients: Interner::new(), allergens: Interner::new() }
+    }
+
+    fn resolve_ingredient(&self, ingredient: IngredientId) -> &str {
+        self.ingredients.resolve(ingredient)
+    }
 
-impl Ingredient {
-    fn new(s: &str) -> Ingredient {
-        Ingredient(s.to_string())
+    fn resolve_allergen(&self, allergen: AllergenId) -> &str {
+        self.allergens.resolve(allergen)
     }
 }
 
@@ -19,106 +49,283 @@ struct Allergen(String);
 
 impl Allergen {
     fn new(s: &str) -> Allergen {
-        Allergen(s.to_string())
+        Allergen(normalize(s))
+    }
+}
+
+// Alternative names for allergens, e.g. from labels in other languages. Each line of an alias file
+// gives an allergen's name followed by its aliases: `fish: fisk, poisson`.
+#[derive(Debug, PartialEq, Eq)]
+struct Aliases(BTreeMap<String, Allergen>);
+
+impl Aliases {
+    fn new() -> Aliases {
+        Aliases(BTreeMap::new())
+    }
+
+    fn parse(text: &str) -> Result<Aliases, String> {
+        let mut aliases = Aliases::new();
+        let mut canonical_names = BTreeSet::new();
+        for (idx, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue
+            }
+            let (name, alternatives) = line.split_once(':').ok_or_else(|| format!("expected `<allergen>: <aliases>` on line {}: {}", idx + 1, line))?;
+            let allergen = Allergen::new(name.trim());
+            for alias in alternatives.split(',').map(|alias| normalize(alias.trim())).filter(|alias| !alias.is_empty()) {
+                if alias != allergen.0 && aliases.0.insert(alias.clone(), allergen.clone()).is_some() {
+                    return Err(format!("alias {} is given more than once (line {})", alias, idx + 1))
+                }
+            }
+            canonical_names.insert(allergen.0);
+        }
+        match aliases.0.keys().find(|alias| canonical_names.contains(*alias)) {
+            Some(alias) => Err(format!("{} is both an allergen and an alias", alias)),
+            None => Ok(aliases)
+        }
+    }
+
+    fn resolve(&self, label: &str) -> Allergen {
+        let allergen = Allergen::new(label);
+        self.0.get(&allergen.0).cloned().unwrap_or(allergen)
     }
 }
 
 struct Food {
-    ingredients: BTreeSet<Ingredient>,
-    allergens: BTreeSet<Allergen>
+    // indexed by ingredient id
+    ingredients: BitSet,
+    allergens: BTreeSet<AllergenId>,
+    // the labels read as one of their aliases, normalized, with the allergen each was read as
+    aliased: BTreeSet<(String, AllergenId)>
 }
 
 impl Food {
-    fn parse(line: &str) -> Option<Food> {
+    // Names are only interned once the whole line has parsed.
+    fn parse_with(line: &str, aliases: &Aliases, names: &mut Names) -> Result<Food, String> {
         lazy_static!{
-            static ref FOOD_PAT: Regex = Regex::new(r"(.*) \(contains (.*)\)").unwrap();
+            static ref FOOD_PAT: Regex = Regex::new(r"^(.*?)\s*\(contains (.*)\)$").unwrap();
             static ref WS_PAT: Regex = Regex::new(r",?\s+").unwrap();
         }
 
-        FOOD_PAT.captures(line).map(|caps| {
-            let ingredients = WS_PAT.split(&caps[1]).map(|s| Ingredient::new(s)).collect();
-            let allergens = WS_PAT.split(&caps[2]).map(|s| Allergen::new(s)).collect();
-            Food { ingredients, allergens }
-        })
+        let caps = FOOD_PAT.captures(line.trim()).ok_or("missing `(contains ...)` clause")?;
+        let ingredients: BTreeSet<String> = WS_PAT.split(caps[1].trim()).filter(|s| !s.is_empty()).map(normalize).collect();
+        let mut allergens: BTreeSet<Allergen> = BTreeSet::new();
+        let mut aliased = BTreeSet::new();
+        for label in WS_PAT.split(caps[2].trim()).filter(|s| !s.is_empty()) {
+            let allergen = aliases.resolve(label);
+            if allergen.0 != normalize(label) {
+                aliased.insert((normalize(label), allergen.clone()));
+            }
+            allergens.insert(allergen);
+        }
+        if ingredients.is_empty() {
+            Err("no ingredients listed".to_owned())
+        } else if allergens.is_empty() {
+            Err("no allergens listed".to_owned())
+        } else {
+            let ids: Vec<IngredientId> = ingredients.iter().map(|name| names.ingredients.intern(name)).collect();
+            let mut ingredients = BitSet::new(ids.iter().map(|id| id.index() + 1).max().unwrap_or(0));
+            for id in ids {
+                ingredients.set(id.index());
+            }
+            let allergens = allergens.iter().map(|allergen| names.allergens.intern(&allergen.0)).collect();
+            let aliased = aliased.into_iter().map(|(label, allergen)| (label, names.allergens.intern(&allergen.0))).collect();
+            Ok(Food { ingredients, allergens, aliased })
+        }
+    }
 
+    fn contains(&self, ingredient: IngredientId) -> bool {
+        self.ingredients.get(ingredient.index()) == Some(true)
+    }
+
+    fn ingredients(&self) -> impl Iterator<Item=IngredientId> + '_ {
+        self.ingredients.iter().map(Symbol::from_index)
     }
 }
 
+#[derive(Debug, PartialEq, Eq)]
+struct ParseIssue {
+    line_no: usize, // 1-based
+    reason: String
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParseMode {
+    Strict, // the first malformed line is an error
+    Lenient // malformed lines are skipped and reported
+}
+
+// Blank lines are ignored in either mode.
+fn parse_foods<J>(lines: J, mode: ParseMode, aliases: &Aliases, names: &mut Names) -> Result<(Vec<Food>, Vec<ParseIssue>), ParseIssue>
+where J: Iterator<Item=String> {
+    let mut foods = vec!();
+    let mut issues = vec!();
+
+    for (idx, line) in lines.enumerate() {
+        if line.trim().is_empty() {
+            continue
+        }
+        match Food::parse_with(&line, aliases, names) {
+            Ok(food) => foods.push(food),
+            Err(reason) => {
+                let issue = ParseIssue { line_no: idx + 1, reason };
+                match mode {
+                    ParseMode::Strict => return Err(issue),
+                    ParseMode::Lenient => issues.push(issue)
+                }
+            }
+        }
+    }
+
+    Ok((foods, issues))
+}
+
 struct AllergenCandidates {
-    cs: BTreeMap<Allergen, AllergenSource>
+    cs: BTreeMap<AllergenId, AllergenSource>,
+    // how many foods list each allergen
+    allergen_counts: Counter<AllergenId>,
+    // how many foods list the allergen and contain the ingredient
+    co_occurrences: Counter<(AllergenId, IngredientId)>
+}
+
+// How strongly the foods seen so far implicate `ingredient` as the source of `allergen`:
+// it appears in `together` of the `allergen_foods` foods listing the allergen.
+#[derive(Debug, PartialEq, Eq)]
+struct Suspicion<'a> {
+    ingredient: &'a str,
+    allergen: &'a str,
+    together: usize,
+    allergen_foods: usize
+}
+
+// Why the foods seen so far can't all be right
+#[derive(Debug, PartialEq, Eq)]
+enum Contradiction {
+    // no ingredient is in every food listing the allergen
+    NoCandidates(AllergenId),
+    // a food lists the allergen without its source
+    SourceMissing(AllergenId, IngredientId),
+    // every ingredient which might be the allergen's source is another allergen's
+    NoneLeft(AllergenId)
+}
+
+impl Contradiction {
+    fn describe(&self, names: &Names) -> String {
+        match *self {
+            Contradiction::NoCandidates(allergen) =>
+                format!("No remaining candidates for allergen {}", names.resolve_allergen(allergen)),
+            Contradiction::SourceMissing(allergen, ingredient) =>
+                format!("Allergen {} previously identified as {} not contained in ingredient list",
+                    names.resolve_allergen(allergen), names.resolve_ingredient(ingredient)),
+            Contradiction::NoneLeft(allergen) =>
+                format!("No ingredient left matches allergen {}", names.resolve_allergen(allergen))
+        }
+    }
 }
 
 impl AllergenCandidates {
     fn new() -> AllergenCandidates {
         let cs = BTreeMap::new();
-        AllergenCandidates{ cs }
+        AllergenCandidates{ cs, allergen_counts: Counter::new(), co_occurrences: Counter::new() }
     }
 
-    fn add_food(&mut self, food: &Food) -> Result<(), String> {
-        // newly committed ingredients to be cleared out of other candidates
-        let mut queue: VecDeque<Ingredient> = VecDeque::new();
-        let mut r = Ok(());
-        for allergen in &food.allergens {
-            if let Err(_) = r {
-                return r
+    // Counts the food towards `suspicions`, without narrowing down any candidates.
+    fn record(&mut self, food: &Food) {
+        for &allergen in &food.allergens {
+            self.allergen_counts.add(allergen);
+            for ingredient in food.ingredients() {
+                self.co_occurrences.add((allergen, ingredient));
             }
-            self.cs.entry(allergen.clone())
-            .and_modify(|src| {
-                match src {
+        }
+    }
+
+    fn add_food(&mut self, food: &Food) -> Result<(), Contradiction> {
+        self.record(food);
+        // newly committed ingredients to be cleared out of other candidates
+        let mut queue: VecDeque<IngredientId> = VecDeque::new();
+        for &allergen in &food.allergens {
+            match self.cs.get_mut(&allergen) {
+                None => {
+                    self.cs.insert(allergen, AllergenSource::Maybe(food.ingredients.clone()));
+                },
+                Some(src) => match src {
                     AllergenSource::Maybe(ingrs) => {
-                        let next_ingrs: BTreeSet<Ingredient> = ingrs.intersection(&food.ingredients)
-                            .map(|j| j.clone())
-                            .collect();
-                        match next_ingrs.len() {
-                            0 => {
-                                let msg = format!("No remaining candidates for allergen {:?}", allergen);
-                                r = Err(msg);
-                            },
+                        ingrs.intersect_with(&food.ingredients);
+                        match ingrs.count() {
+                            0 => return Err(Contradiction::NoCandidates(allergen)),
                             1 => {
-                                let tox = next_ingrs.iter().nth(0).unwrap();
-                                *src = AllergenSource::Definitely(tox.clone());
-                                queue.push_back(tox.clone());
+                                let tox = Symbol::from_index(ingrs.min().unwrap());
+                                *src = AllergenSource::Definitely(tox);
+                                queue.push_back(tox);
                             },
-                            _ => *ingrs = next_ingrs
+                            _ => ()
                         }
-
                     },
-                    AllergenSource::Definitely(ingr) if !&food.ingredients.contains(ingr) => {
-                        let msg = format!("Allergen {:?} previously identified as {:?} not contained in ingredient list {:?}",
-                            allergen, ingr, food.ingredients);
-                        r = Err(msg)
-                    }
+                    AllergenSource::Definitely(ingr) if !food.contains(*ingr) => {
+                        return Err(Contradiction::SourceMissing(allergen, *ingr))
+                    },
                     _ => ()
                 }
-            }).or_insert({
-                AllergenSource::Maybe(food.ingredients.clone())
-            });
-        };
+            }
+        }
 
         while let Some(j) = queue.pop_front() {
-            for (allergen, src) in self.cs.iter_mut() {
+            for (&allergen, src) in self.cs.iter_mut() {
                 if let AllergenSource::Maybe(ingrs) = src {
-                    ingrs.remove(&j);
-                    match ingrs.len() {
-                        0 => {
-                            let msg = format!("No ingredient left matches allergen {:?}", allergen);
-                            return Err(msg)
-                        },
+                    ingrs.unset(j.index());
+                    match ingrs.count() {
+                        0 => return Err(Contradiction::NoneLeft(allergen)),
                         1 => {
-                            let tox = ingrs.iter().nth(0).unwrap();
-                            queue.push_back(tox.clone());
-                            *src = AllergenSource::Definitely(tox.clone());
+                            let tox = Symbol::from_index(ingrs.min().unwrap());
+                            queue.push_back(tox);
+                            *src = AllergenSource::Definitely(tox);
                         },
                         _ => ()
                     }
                 }
             }
         }
-        r
+        Ok(())
+    }
+
+    // Every (ingredient, allergen) pair seen together, most suspicious first: ranked by the fraction
+    // of the allergen's foods containing the ingredient, then by the number of those foods.
+    // Unlike the candidate sets, this never rules a pair out, so it remains informative when the
+    // foods are inconsistent or not yet enough to pin every allergen down.
+    fn suspicions<'a>(&self, names: &'a Names) -> Vec<Suspicion<'a>> {
+        let mut suspicions: Vec<Suspicion> = self.co_occurrences.iter().map(|(&(allergen, ingredient), together)| {
+            Suspicion {
+                ingredient: names.resolve_ingredient(ingredient),
+                allergen: names.resolve_allergen(allergen),
+                together,
+                allergen_foods: self.allergen_counts.get(&allergen)
+            }
+        }).collect();
+        suspicions.sort_by(|s, t| {
+            (t.together * s.allergen_foods).cmp(&(s.together * t.allergen_foods))
+                .then(t.together.cmp(&s.together))
+                .then(s.ingredient.cmp(t.ingredient))
+                .then(s.allergen.cmp(t.allergen))
+        });
+        suspicions
+    }
+
+    fn suspicion_table(&self, n: usize, names: &Names) -> Table {
+        let mut table = Table::new(&["Rank", "Ingredient", "Allergen", "Foods", "Score"]);
+        for (rank, suspicion) in self.suspicions(names).into_iter().take(n).enumerate() {
+            table.add_row(vec!(
+                (rank + 1).to_string(),
+                suspicion.ingredient.to_owned(),
+                suspicion.allergen.to_owned(),
+                format!("{}/{}", suspicion.together, suspicion.allergen_foods),
+                format!("{:.2}", suspicion.together as f64 / suspicion.allergen_foods as f64)
+            ));
+        }
+        table
     }
 
     // Returns all ingredients in the input which are neither definitely nor maybe the source of an allergen
-    fn safe_ingredients<'a>(&self, ingredients: &BTreeSet<&'a Ingredient>) -> BTreeSet<&'a Ingredient> {
+    fn safe_ingredients(&self, ingredients: &BTreeSet<IngredientId>) -> BTreeSet<IngredientId> {
         let mut ingredients = ingredients.clone();
         for src in self.cs.values() {
             match src {
@@ -126,8 +333,8 @@ impl AllergenCandidates {
                     ingredients.remove(ingr);
                 },
                 AllergenSource::Maybe(ingrs) => {
-                    for ingr in ingrs {
-                        ingredients.remove(ingr);
+                    for idx in ingrs.iter() {
+                        ingredients.remove(&Symbol::from_index(idx));
                     }
                 }
             }
@@ -138,112 +345,264 @@ impl AllergenCandidates {
 
 #[derive(Debug, PartialEq, Eq)]
 enum AllergenSource {
-    Definitely(Ingredient),
-    Maybe(BTreeSet<Ingredient>)
+    Definitely(IngredientId),
+    // indexed by ingredient id
+    Maybe(BitSet)
 }
 
-fn main() {
+fn run() -> Result<(), AdventError> {
     let stdin = std::io::stdin();
+    let mode = if cli::has_flag("--strict") { ParseMode::Strict } else { ParseMode::Lenient };
+    let aliases = match cli::option_value("--aliases") {
+        Some(path) => {
+            let text = std::fs::read_to_string(&path).map_err(|e| AdventError::input(format!("unable to read {}: {}", path, e)))?;
+            Aliases::parse(&text).map_err(|e| AdventError::input(format!("malformed alias file {}: {}", path, e)))?
+        },
+        None => Aliases::new()
+    };
+
+    let mut names = Names::new();
+    let foods = match parse_foods(stdin.lock().lines().flatten(), mode, &aliases, &mut names) {
+        Ok((foods, issues)) => {
+            for issue in &issues {
+                eprintln!("Skipping line {}: {}", issue.line_no, issue.reason);
+            }
+            println!("Parsed {} foods; skipped {} malformed lines.", foods.len(), issues.len());
+            foods
+        },
+        Err(issue) => return Err(AdventError::input(format!("malformed food on line {}: {}", issue.line_no, issue.reason)))
+    };
+    let mut alias_uses: Counter<&(String, AllergenId)> = Counter::new();
+    for food in &foods {
+        for aliased in &food.aliased {
+            alias_uses.add(aliased);
+        }
+    }
+    for ((label, allergen), n) in alias_uses.iter() {
+        println!("Read {} as {} in {} foods.", label, names.resolve_allergen(*allergen), n);
+    }
+
+    // Scored independently of resolution, which stops at the first contradiction
+    if let Some(n) = cli::option_value("--suspects") {
+        let n = usize::from_str_radix(&n, 10).map_err(|_| AdventError::input(format!("expected a number of suspects, got {}", n)))?;
+        let mut scores = AllergenCandidates::new();
+        for food in &foods {
+            scores.record(food);
+        }
+        print!("{}", scores.suspicion_table(n, &names));
+    }
 
-    let mut occurrences: BTreeMap<Ingredient, usize> = BTreeMap::new();
-    let mut foods: Vec<Food> = vec!();
+    let mut occurrences: Counter<IngredientId> = Counter::new();
     let mut allergen_sources = AllergenCandidates::new();
 
-    for line in stdin.lock().lines().flatten() {
-        let food = Food::parse(&line).unwrap();
-        for ingredient in &food.ingredients {
-            *occurrences.entry(ingredient.clone()).or_insert(0) += 1;
-        }
-        allergen_sources.add_food(&food).unwrap();
-        foods.push(food)
-    }
-
-    let all_ingredients: BTreeSet<&Ingredient> = occurrences.keys().collect();
-    let hypoallergenics = allergen_sources.safe_ingredients(&all_ingredients);
-    let hypoallergenic_count: usize = hypoallergenics.iter().flat_map(|j| occurrences.get(j)).sum();
-    println!("{} hypoallergenic ingredients identified, with {} total usages.", hypoallergenics.len(), hypoallergenic_count);
-
-    let canonical_dangerous_ingredient_list: String = allergen_sources.cs.iter()
-    .fold(String::new(), |mut acc, (allergen, src)| {
-        match src {
-            AllergenSource::Definitely(ingredient) => {
-                if acc.is_empty() {
-                    acc.push_str(&ingredient.0);
-                    acc
-                } else {
-                    acc.push(',');
-                    acc.push_str(&ingredient.0);
-                    acc
-                }
-            },
-            _ => panic!("Unresolved allergen {:?}", allergen)
+    let (hypoallergenics, hypoallergenic_count) = timing::time("Part 1", || {
+        for food in &foods {
+            for ingredient in food.ingredients() {
+                occurrences.add(ingredient);
+            }
+            allergen_sources.add_food(food).map_err(|contradiction| contradiction.describe(&names))?;
         }
-    });
+
+        let all_ingredients: BTreeSet<IngredientId> = occurrences.keys().copied().collect();
+        let hypoallergenics = allergen_sources.safe_ingredients(&all_ingredients);
+        let hypoallergenic_count: usize = hypoallergenics.iter().map(|j| occurrences.get(j)).sum();
+        Ok::<_, String>((hypoallergenics.len(), hypoallergenic_count))
+    }).map_err(AdventError::no_solution)?;
+    println!("{} hypoallergenic ingredients identified, with {} total usages.", hypoallergenics, hypoallergenic_count);
+
+    // ids are in order of appearance, but the list is sorted by allergen name
+    let canonical_dangerous_ingredient_list: String = timing::time("Part 2", || {
+        let mut dangerous = allergen_sources.cs.iter().map(|(&allergen, src)| match src {
+            AllergenSource::Definitely(ingredient) => Ok((names.resolve_allergen(allergen), names.resolve_ingredient(*ingredient))),
+            _ => Err(names.resolve_allergen(allergen))
+        }).collect::<Result<Vec<(&str, &str)>, _>>()?;
+        dangerous.sort_unstable();
+        Ok(dangerous.into_iter().map(|(_, ingredient)| ingredient).collect::<Vec<&str>>())
+    })
+    .map_err(|allergen: &str| AdventError::no_solution(format!("unresolved allergen {}", allergen)))?
+    .join(",");
     println!("Canonical dangerous ingredient list:\n{}", canonical_dangerous_ingredient_list);
+    Ok(())
+}
+
+fn main() {
+    exit_on_error(run())
 }
 
 #[cfg(test)]
 mod day21_spec {
     use super::*;
 
-    fn into_set<T: Ord, F>(ws: Vec<&str>, f: F) -> BTreeSet<T> where F: Fn(&str) -> T {
-        ws.iter().map(|s| f(s)).collect()
+    impl Food {
+        fn parse(line: &str, names: &mut Names) -> Result<Food, String> {
+            Food::parse_with(line, &Aliases::new(), names)
+        }
+    }
+
+    fn ingredient(names: &Names, name: &str) -> IngredientId {
+        names.ingredients.get(name).unwrap()
+    }
+
+    fn allergen(names: &Names, name: &str) -> AllergenId {
+        names.allergens.get(name).unwrap()
+    }
+
+    // The ingredients named, which must all have been read
+    fn ingredient_set(names: &Names, ws: &[&str]) -> BitSet {
+        let ids: Vec<IngredientId> = ws.iter().map(|w| ingredient(names, w)).collect();
+        let mut set = BitSet::new(names.ingredients.len());
+        for id in ids {
+            set.set(id.index());
+        }
+        set
+    }
+
+    // The names of the ingredients in `ingredients`, in order of first appearance
+    fn ingredient_names<'a>(names: &'a Names, ingredients: &BitSet) -> Vec<&'a str> {
+        ingredients.iter().map(|idx| names.resolve_ingredient(Symbol::from_index(idx))).collect()
+    }
+
+    fn allergen_names<'a>(names: &'a Names, food: &Food) -> Vec<&'a str> {
+        food.allergens.iter().map(|&allergen| names.resolve_allergen(allergen)).collect()
     }
 
     #[test]
     fn parse_test() {
+        let mut names = Names::new();
         let line = "mxmxvkd kfcds sqjhc nhms (contains dairy, fish)";
-        let food = Food::parse(line).unwrap();
+        let food = Food::parse(line, &mut names).unwrap();
 
-        assert_eq!(food.ingredients, into_set(vec!(
-            "mxmxvkd", "kfcds", "sqjhc", "nhms"
-        ), Ingredient::new));
-        assert_eq!(food.allergens, into_set(vec!(
-            "dairy", "fish"
-        ), Allergen::new));
+        // sorted by name before interning
+        assert_eq!(ingredient_names(&names, &food.ingredients), vec!(
+            "kfcds", "mxmxvkd", "nhms", "sqjhc"
+        ));
+        assert_eq!(allergen_names(&names, &food), vec!("dairy", "fish"));
 
         let line = "trh fvjkl sbzzf mxmxvkd (contains dairy)";
-        let food = Food::parse(line).unwrap();
+        let food = Food::parse(line, &mut names).unwrap();
+
+        // mxmxvkd keeps its id from the first food
+        assert_eq!(ingredient_names(&names, &food.ingredients), vec!(
+            "mxmxvkd", "fvjkl", "sbzzf", "trh"
+        ));
+        assert_eq!(allergen_names(&names, &food), vec!("dairy"));
+        assert_eq!(names.ingredients.len(), 7);
+        assert!(food.contains(ingredient(&names, "trh")));
+        assert!(!food.contains(ingredient(&names, "kfcds")));
+
+        assert_eq!(Food::parse("trh fvjkl sbzzf mxmxvkd", &mut names).err(), Some("missing `(contains ...)` clause".to_owned()));
+        assert_eq!(Food::parse(" (contains dairy)", &mut names).err(), Some("no ingredients listed".to_owned()));
+        assert_eq!(Food::parse("trh fvjkl qqq (contains )", &mut names).err(), Some("no allergens listed".to_owned()));
+        // nothing is interned from a malformed line
+        assert_eq!(names.ingredients.get("qqq"), None);
+    }
+
+    #[test]
+    fn parse_foods_test() {
+        let input = "mxmxvkd kfcds sqjhc nhms (contains dairy, fish)\n\
+                     trh fvjkl sbzzf mxmxvkd\n\
+                     \n\
+                     sqjhc fvjkl (contains soy)\n\
+                     sqjhc mxmxvkd sbzzf (contains )";
+        let lines = || input.lines().map(|s| s.to_owned());
+
+        let (foods, issues) = parse_foods(lines(), ParseMode::Lenient, &Aliases::new(), &mut Names::new()).unwrap();
+        assert_eq!(foods.len(), 2);
+        assert_eq!(issues, vec!(
+            ParseIssue { line_no: 2, reason: "missing `(contains ...)` clause".to_owned() },
+            ParseIssue { line_no: 5, reason: "no allergens listed".to_owned() }
+        ));
+
+        let err = parse_foods(lines(), ParseMode::Strict, &Aliases::new(), &mut Names::new()).err();
+        assert_eq!(err, Some(ParseIssue { line_no: 2, reason: "missing `(contains ...)` clause".to_owned() }));
+
+        let (foods, issues) = parse_foods(lines().take(1), ParseMode::Strict, &Aliases::new(), &mut Names::new()).unwrap();
+        assert_eq!(foods.len(), 1);
+        assert!(issues.is_empty());
+    }
 
-        assert_eq!(food.ingredients, into_set(vec!(
-            "trh", "fvjkl", "sbzzf", "mxmxvkd"
-        ), Ingredient::new));
-        assert_eq!(food.allergens, into_set(vec!("dairy"), Allergen::new));
+    #[test]
+    fn normalize_test() {
+        assert_eq!(normalize("Dairy"), "dairy");
+        // a precomposed é, and an e followed by a combining acute accent
+        assert_eq!(normalize("N\u{e9}ctar"), normalize("Ne\u{301}ctar"));
+        assert_eq!(normalize("Ne\u{301}ctar"), "n\u{e9}ctar");
+
+        let mut names = Names::new();
+        let food = Food::parse("MXMXVKD kfcds Mxmxvkd (contains Dairy, FISH)", &mut names).unwrap();
+        assert_eq!(ingredient_names(&names, &food.ingredients), vec!("kfcds", "mxmxvkd"));
+        assert_eq!(allergen_names(&names, &food), vec!("dairy", "fish"));
+        assert!(food.aliased.is_empty());
+    }
+
+    #[test]
+    fn aliases_test() {
+        let aliases = Aliases::parse("fish: fisk, Poisson\n\nDairy: mejeri\n").unwrap();
+        assert_eq!(aliases.resolve("FISK"), Allergen::new("fish"));
+        assert_eq!(aliases.resolve("mejeri"), Allergen::new("dairy"));
+        assert_eq!(aliases.resolve("soy"), Allergen::new("soy"));
+
+        let mut names = Names::new();
+        let food = Food::parse_with("mxmxvkd kfcds (contains mejeri, fisk, fish)", &aliases, &mut names).unwrap();
+        assert_eq!(allergen_names(&names, &food), vec!("dairy", "fish"));
+        assert_eq!(food.aliased, vec!(
+            ("fisk".to_owned(), allergen(&names, "fish")),
+            ("mejeri".to_owned(), allergen(&names, "dairy"))
+        ).into_iter().collect());
+
+        let input = "mxmxvkd kfcds sqjhc nhms (contains mejeri, fisk)\n\
+                     trh fvjkl sbzzf mxmxvkd (contains dairy)";
+        let (foods, _) = parse_foods(input.lines().map(|s| s.to_owned()), ParseMode::Strict, &aliases, &mut names).unwrap();
+        let mut allergen_sources = AllergenCandidates::new();
+        for food in &foods {
+            allergen_sources.add_food(food).unwrap();
+        }
+        let dairy = allergen(&names, "dairy");
+        assert_eq!(allergen_sources.cs.get(&dairy), Some(&AllergenSource::Definitely(ingredient(&names, "mxmxvkd"))));
+
+        assert!(Aliases::parse("fish fisk").is_err());
+        assert!(Aliases::parse("fish: fisk\ndairy: FISK").is_err());
+        assert!(Aliases::parse("fish: fisk\nfisk: poisson").is_err());
+        // an allergen may list its own name among its aliases
+        assert_eq!(Aliases::parse("fish: Fish, fisk"), Aliases::parse("fish: fisk"));
     }
 
     #[test]
     fn add_food_test() {
+        let mut names = Names::new();
         let mut allergen_sources = AllergenCandidates::new();
-        let food = Food::parse("mxmxvkd kfcds sqjhc nhms (contains dairy, fish)").unwrap();
-        let dairy = Allergen::new("dairy");
-        let fish = Allergen::new("fish");
+        let food = Food::parse("mxmxvkd kfcds sqjhc nhms (contains dairy, fish)", &mut names).unwrap();
+        let dairy = allergen(&names, "dairy");
+        let fish = allergen(&names, "fish");
         allergen_sources.add_food(&food).unwrap();
 
         assert_eq!(allergen_sources.cs.len(), 2);
-        let expected_src = {
-            let ingredients = into_set(vec!("mxmxvkd", "kfcds", "sqjhc", "nhms"), Ingredient::new);
-            AllergenSource::Maybe(ingredients)
-        };
+        let expected_src = AllergenSource::Maybe(ingredient_set(&names, &["mxmxvkd", "kfcds", "sqjhc", "nhms"]));
         assert_eq!(allergen_sources.cs.get(&dairy), Some(&expected_src));
-        assert_eq!(allergen_sources.cs.get(&&fish), Some(&expected_src));
+        assert_eq!(allergen_sources.cs.get(&fish), Some(&expected_src));
 
-        let food = Food::parse("trh fvjkl sbzzf mxmxvkd (contains dairy)").unwrap();
+        let food = Food::parse("trh fvjkl sbzzf mxmxvkd (contains dairy)", &mut names).unwrap();
         allergen_sources.add_food(&food).unwrap();
-        assert_eq!(allergen_sources.cs.get(&dairy), Some(&AllergenSource::Definitely(Ingredient::new("mxmxvkd"))));
-        let expected_src = AllergenSource::Maybe(into_set(vec!("kfcds", "sqjhc", "nhms"), Ingredient::new));
+        assert_eq!(allergen_sources.cs.get(&dairy), Some(&AllergenSource::Definitely(ingredient(&names, "mxmxvkd"))));
+        let expected_src = AllergenSource::Maybe(ingredient_set(&names, &["kfcds", "sqjhc", "nhms"]));
         assert_eq!(allergen_sources.cs.get(&fish), Some(&expected_src));
 
-        let food = Food::parse("sqjhc fvjkl (contains soy)").unwrap();
+        let food = Food::parse("sqjhc fvjkl (contains soy)", &mut names).unwrap();
         allergen_sources.add_food(&food).unwrap();
-        let soy = Allergen::new("soy");
+        let soy = allergen(&names, "soy");
         assert_eq!(allergen_sources.cs.len(), 3);
-        let expected_src = AllergenSource::Maybe(into_set(vec!("sqjhc", "fvjkl"), Ingredient::new));
+        let expected_src = AllergenSource::Maybe(ingredient_set(&names, &["sqjhc", "fvjkl"]));
         assert_eq!(allergen_sources.cs.get(&soy), Some(&expected_src));
 
-        let food = Food::parse("sqjhc mxmxvkd sbzzf (contains fish)").unwrap();
+        let food = Food::parse("sqjhc mxmxvkd sbzzf (contains fish)", &mut names).unwrap();
         allergen_sources.add_food(&food).unwrap();
-        assert_eq!(allergen_sources.cs.get(&soy), Some(&AllergenSource::Definitely(Ingredient::new("fvjkl"))));
-        assert_eq!(allergen_sources.cs.get(&fish), Some(&AllergenSource::Definitely(Ingredient::new("sqjhc"))));
+        assert_eq!(allergen_sources.cs.get(&soy), Some(&AllergenSource::Definitely(ingredient(&names, "fvjkl"))));
+        assert_eq!(allergen_sources.cs.get(&fish), Some(&AllergenSource::Definitely(ingredient(&names, "sqjhc"))));
+
+        let food = Food::parse("kfcds nhms (contains dairy)", &mut names).unwrap();
+        let contradiction = allergen_sources.add_food(&food).unwrap_err();
+        assert_eq!(contradiction, Contradiction::SourceMissing(dairy, ingredient(&names, "mxmxvkd")));
+        assert_eq!(contradiction.describe(&names), "Allergen dairy previously identified as mxmxvkd not contained in ingredient list");
 
         // TODO: there is a subtle bug in this implementation, where if an ingredient has already
         // been committed, new allergens will not eliminate it from their candidate list
@@ -251,27 +610,59 @@ mod day21_spec {
 
     #[test]
     fn safe_ingredients_test() {
+        let mut names = Names::new();
+        let input = "mxmxvkd kfcds sqjhc nhms (contains dairy)\n\
+                     trh fvjkl sbzzf mxmxvkd (contains dairy)\n\
+                     sqjhc fvjkl (contains dairy)\n\
+                     sqjhc mxmxvkd sbzzf aaa bbb (contains dairy)";
+        parse_foods(input.lines().map(|s| s.to_owned()), ParseMode::Strict, &Aliases::new(), &mut names).unwrap();
         let allergen_sources = {
             let mut cs = BTreeMap::new();
-            cs.insert(Allergen::new("peanut"), AllergenSource::Definitely(Ingredient::new("sqjhc")));
-            cs.insert(Allergen::new("gluten"), AllergenSource::Definitely(Ingredient::new("fvjkl")));
-            let maybe_garlic = into_set(vec!("aaa", "bbb"), Ingredient::new);
-            cs.insert(Allergen::new("garlic"), AllergenSource::Maybe(maybe_garlic));
-            AllergenCandidates { cs }
+            let mut allergen = |name: &str| names.allergens.intern(name);
+            let (peanut, gluten, garlic) = (allergen("peanut"), allergen("gluten"), allergen("garlic"));
+            cs.insert(peanut, AllergenSource::Definitely(ingredient(&names, "sqjhc")));
+            cs.insert(gluten, AllergenSource::Definitely(ingredient(&names, "fvjkl")));
+            cs.insert(garlic, AllergenSource::Maybe(ingredient_set(&names, &["aaa", "bbb"])));
+            AllergenCandidates { cs, ..AllergenCandidates::new() }
         };
-        let all_ingredients = into_set(vec!(
-            "mxmxvkd", "kfcds", "sqjhc", "nhms",
-            "trh", "fvjkl", "sbzzf", "mxmxvkd",
-            "sqjhc", "fvjkl",
-            "sqjhc", "mxmxvkd", "sbzzf"
-        ), Ingredient::new);
-        let safe_ingredients = all_ingredients.iter().filter(|j| {
-            j.0 != "sqjhc" && j.0 != "fvjkl"
+        let all_ingredients: BTreeSet<IngredientId> = ["mxmxvkd", "kfcds", "sqjhc", "nhms", "trh", "fvjkl", "sbzzf"].iter()
+            .map(|name| ingredient(&names, name))
+            .collect();
+        let safe_ingredients = all_ingredients.iter().copied().filter(|&j| {
+            names.resolve_ingredient(j) != "sqjhc" && names.resolve_ingredient(j) != "fvjkl"
         }).collect();
 
-        assert_eq!(
-            allergen_sources.safe_ingredients(&all_ingredients.iter().collect()),
-            safe_ingredients
-        );
+        assert_eq!(allergen_sources.safe_ingredients(&all_ingredients), safe_ingredients);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn suspicions_test() {
+        let mut names = Names::new();
+        let mut allergen_sources = AllergenCandidates::new();
+        for line in &[
+            "mxmxvkd kfcds sqjhc nhms (contains dairy, fish)",
+            "trh fvjkl sbzzf mxmxvkd (contains dairy)",
+            "sqjhc fvjkl (contains soy)",
+            "sqjhc mxmxvkd sbzzf (contains fish)"
+        ] {
+            allergen_sources.add_food(&Food::parse(line, &mut names).unwrap()).unwrap();
+        }
+        // ties are broken by name, not by id
+        let ranked: Vec<(&str, &str, usize, usize)> = allergen_sources.suspicions(&names).iter()
+            .map(|s| (s.ingredient, s.allergen, s.together, s.allergen_foods))
+            .collect();
+        assert_eq!(ranked[..6], [
+            ("mxmxvkd", "dairy", 2, 2),
+            ("mxmxvkd", "fish", 2, 2),
+            ("sqjhc", "fish", 2, 2),
+            ("fvjkl", "soy", 1, 1),
+            ("sqjhc", "soy", 1, 1),
+            ("fvjkl", "dairy", 1, 2)
+        ]);
+        assert_eq!(ranked.len(), 7 + 5 + 2);
+
+        let table = allergen_sources.suspicion_table(2, &names).to_string();
+        assert_eq!(table.lines().nth(2), Some("1    | mxmxvkd    | dairy    | 2/2   | 1.00"));
+        assert_eq!(table.lines().count(), 4);
+    }
+}
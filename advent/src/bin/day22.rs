@@ -3,6 +3,12 @@ use std::collections::{VecDeque, HashSet};
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
 
+use serde::{Serialize, Deserialize};
+
+use advent::cli;
+use advent::error::{AdventError, exit_on_error};
+use advent::timing;
+
 fn eat_line<J>(j: &mut J, expected: &str) where J: Iterator<Item=String> {
     if let Some(line) = j.next() {
         if line.as_str() != expected {
@@ -33,22 +39,90 @@ fn read_deck<J>(j: &mut J) -> Result<Vec<usize>, String> where J: Iterator<Item=
     Ok(deck)
 }
 
-fn score(deck: &VecDeque<usize>) -> usize {
-    deck.iter().rev().enumerate().fold(0, |acc, (idx, card)| {
-        acc + (card * (1 + idx))
-    })
+// How a deck is scored at the end of a game
+trait Scoring {
+    fn score(&self, deck: &VecDeque<usize>) -> usize;
 }
 
-#[derive(Debug, PartialEq, Eq)]
+// The puzzle's score: each card times its position counted from the bottom of the deck
+struct Weighted;
+
+impl Scoring for Weighted {
+    fn score(&self, deck: &VecDeque<usize>) -> usize {
+        deck.iter().rev().enumerate().fold(0, |acc, (idx, card)| {
+            acc + (card * (1 + idx))
+        })
+    }
+}
+
+// One point for each card held
+struct CardCount;
+
+impl Scoring for CardCount {
+    fn score(&self, deck: &VecDeque<usize>) -> usize {
+        deck.len()
+    }
+}
+
+fn scoring_named(name: &str) -> Option<&'static dyn Scoring> {
+    match name {
+        "weighted" => Some(&Weighted),
+        "cards" => Some(&CardCount),
+        _ => None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 enum Player {
     Player1, Player2
 }
 
-fn play_combat(deck_1: &Vec<usize>, deck_2: &Vec<usize>) -> (Player, usize) {
+// How a game ended: the winner, the number of rounds played and the decks left. A game cut short by
+// repetition scores nothing for either player.
+#[derive(Debug, PartialEq, Eq)]
+struct Outcome {
+    winner: Player,
+    rounds: usize,
+    dq_1: VecDeque<usize>,
+    dq_2: VecDeque<usize>,
+    repeated: bool
+}
+
+// The result of a game under some scoring. `margin` is the winner's score less the loser's.
+// Transcripts from before margins and rounds were reported lack them, and read them as 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct GameReport {
+    winner: Player,
+    score: usize,
+    #[serde(default)]
+    margin: usize,
+    #[serde(default)]
+    rounds: usize
+}
+
+impl Outcome {
+    fn report(&self, scoring: &dyn Scoring) -> GameReport {
+        let (winner_deck, loser_deck) = match self.winner {
+            Player::Player1 => (&self.dq_1, &self.dq_2),
+            Player::Player2 => (&self.dq_2, &self.dq_1)
+        };
+        let (score, margin) = if self.repeated {
+            (0, 0)
+        } else {
+            let score = scoring.score(winner_deck);
+            (score, score.saturating_sub(scoring.score(loser_deck)))
+        };
+        GameReport { winner: self.winner, score, margin, rounds: self.rounds }
+    }
+}
+
+fn play_combat(deck_1: &[usize], deck_2: &[usize]) -> Outcome {
     let mut deck_1: VecDeque<usize> = deck_1.iter().map(|p| *p).collect();
     let mut deck_2: VecDeque<usize> = deck_2.iter().map(|p| *p).collect();
+    let mut rounds = 0;
 
     while !deck_1.is_empty() && !deck_2.is_empty() {
+        rounds += 1;
         let card_1 = deck_1.pop_front().unwrap();
         let card_2 = deck_2.pop_front().unwrap();
 
@@ -61,11 +135,8 @@ fn play_combat(deck_1: &Vec<usize>, deck_2: &Vec<usize>) -> (Player, usize) {
         }
     }
 
-    if deck_1.is_empty() {
-        (Player::Player2, score(&deck_2))
-    } else {
-        (Player::Player1, score(&deck_1))
-    }
+    let winner = if deck_1.is_empty() { Player::Player2 } else { Player::Player1 };
+    Outcome { winner, rounds, dq_1: deck_1, dq_2: deck_2, repeated: false }
 }
 
 fn hash(dq_1: &VecDeque<usize>, dq_2: &VecDeque<usize>) -> u64 {
@@ -75,11 +146,181 @@ fn hash(dq_1: &VecDeque<usize>, dq_2: &VecDeque<usize>) -> u64 {
     hasher.finish()
 }
 
-fn play_recursive_combat(deck_1: &Vec<usize>, deck_2: &Vec<usize>) -> (Player, usize) {
+// One step of a recursive combat game, as recorded in a transcript
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event")]
+enum Event {
+    GameStart { game: usize, deck_1: Vec<usize>, deck_2: Vec<usize> },
+    // A deck configuration seen before in this game; player 1 wins the game
+    Repetition { game: usize, round: usize },
+    // `subgame` is the number of the game spawned to decide the round, if any
+    Round { game: usize, round: usize, card_1: usize, card_2: usize, subgame: Option<usize>, winner: Player },
+    GameEnd { game: usize, winner: Player, score: usize }
+}
+
+// Version 0 transcripts (with no version recorded) report only the winner and score
+const TRANSCRIPT_VERSION: u32 = 1;
+
+// The result is scored the puzzle's way, as are the GameEnd events
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct Transcript {
+    #[serde(default)]
+    version: u32,
+    deck_1: Vec<usize>,
+    deck_2: Vec<usize>,
+    events: Vec<Event>,
+    #[serde(flatten)]
+    report: GameReport
+}
+
+// The first point at which a replayed game departs from a transcript. The events are boxed to
+// keep the error small.
+#[derive(Debug, PartialEq, Eq)]
+struct TranscriptMismatch {
+    index: usize,
+    recorded: Option<Box<Event>>,
+    replayed: Option<Box<Event>>
+}
+
+// How recursive combat is played: `Recursive` plays each subgame in a recursive call, while
+// `Iterative` keeps its games on an explicit stack, so that deep inputs cannot overflow the call
+// stack. Either way, a game nested more than `max_depth` deep (the first game being at depth 1)
+// is an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Engine {
+    Recursive,
+    Iterative
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CombatConfig {
+    engine: Engine,
+    max_depth: Option<usize>
+}
+
+impl Default for CombatConfig {
+    fn default() -> CombatConfig {
+        CombatConfig { engine: Engine::Recursive, max_depth: None }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum CombatError {
+    // Game number `game` would have been played at depth `depth`
+    TooDeep { game: usize, depth: usize }
+}
+
+fn play_recursive_combat(deck_1: &[usize], deck_2: &[usize], config: &CombatConfig) -> Result<Outcome, CombatError> {
+    recursive_combat(deck_1, deck_2, None, config)
+}
+
+fn record_recursive_combat(deck_1: &[usize], deck_2: &[usize], config: &CombatConfig) -> Result<(Outcome, Transcript), CombatError> {
+    let mut events = vec!();
+    let outcome = recursive_combat(deck_1, deck_2, Some(&mut events), config)?;
+    let report = outcome.report(&Weighted);
+    Ok((outcome, Transcript { version: TRANSCRIPT_VERSION, deck_1: deck_1.to_vec(), deck_2: deck_2.to_vec(), events, report }))
+}
+
+// Replays the game from the transcript's starting decks and checks that every event, and the result, agree.
+// The replay is iterative and unlimited, so it cannot fail.
+fn verify_transcript(transcript: &Transcript) -> Result<(), TranscriptMismatch> {
+    let config = CombatConfig { engine: Engine::Iterative, max_depth: None };
+    let (_, replay) = record_recursive_combat(&transcript.deck_1, &transcript.deck_2, &config)
+        .expect("a game without a depth limit cannot be too deep");
+    let n = transcript.events.len().max(replay.events.len());
+    for index in 0..n {
+        let recorded = transcript.events.get(index);
+        let replayed = replay.events.get(index);
+        if recorded != replayed {
+            return Err(TranscriptMismatch { index, recorded: recorded.cloned().map(Box::new), replayed: replayed.cloned().map(Box::new) })
+        }
+    }
+    let agrees = if transcript.version == 0 {
+        (transcript.report.winner, transcript.report.score) == (replay.report.winner, replay.report.score)
+    } else {
+        transcript.report == replay.report
+    };
+    if !agrees {
+        return Err(TranscriptMismatch { index: n, recorded: None, replayed: None })
+    }
+    Ok(())
+}
+
+fn recursive_combat(deck_1: &[usize], deck_2: &[usize], mut events: Option<&mut Vec<Event>>, config: &CombatConfig) -> Result<Outcome, CombatError> {
+    match config.engine {
+        Engine::Recursive => recursive_engine(deck_1, deck_2, &mut events, config.max_depth),
+        Engine::Iterative => iterative_engine(deck_1, deck_2, &mut events, config.max_depth)
+    }
+}
+
+// The checks and bookkeeping common to both engines
+
+fn check_depth(game: usize, depth: usize, max_depth: Option<usize>) -> Result<(), CombatError> {
+    match max_depth {
+        Some(max_depth) if depth > max_depth => Err(CombatError::TooDeep { game, depth }),
+        _ => Ok(())
+    }
+}
+
+fn start_game(deck_1: &[usize], deck_2: &[usize], game_number: &mut usize, events: &mut Option<&mut Vec<Event>>) -> usize {
+    *game_number += 1;
+    if let Some(es) = events {
+        es.push(Event::GameStart { game: *game_number, deck_1: deck_1.to_vec(), deck_2: deck_2.to_vec() });
+    }
+    *game_number
+}
+
+// Round `rn` would repeat an earlier one, so it is not played
+fn repetition(gn: usize, rn: usize, dq_1: VecDeque<usize>, dq_2: VecDeque<usize>, events: &mut Option<&mut Vec<Event>>) -> Outcome {
+    println!("Game {} has encountered a hash collision", gn);
+    if let Some(es) = events {
+        es.push(Event::Repetition { game: gn, round: rn });
+        es.push(Event::GameEnd { game: gn, winner: Player::Player1, score: 0 });
+    }
+    Outcome { winner: Player::Player1, rounds: rn - 1, dq_1, dq_2, repeated: true }
+}
+
+// The cards go to the winner of the round, theirs first
+#[allow(clippy::too_many_arguments)]
+fn finish_round(gn: usize, rn: usize, card_1: usize, card_2: usize, subgame: Option<usize>, winner: Player,
+    dq_1: &mut VecDeque<usize>, dq_2: &mut VecDeque<usize>, events: &mut Option<&mut Vec<Event>>) {
+    if let Some(es) = events {
+        es.push(Event::Round { game: gn, round: rn, card_1, card_2, subgame, winner });
+    }
+    match winner {
+        Player::Player1 => {
+            println!("Player 1 wins round {} of game {}", rn, gn);
+            dq_1.push_back(card_1);
+            dq_1.push_back(card_2);
+        },
+        Player::Player2 => {
+            println!("Player 2 wins round {} of game {}", rn, gn);
+            dq_2.push_back(card_2);
+            dq_2.push_back(card_1);
+        }
+    }
+}
+
+fn finish_game(gn: usize, rounds: usize, dq_1: VecDeque<usize>, dq_2: VecDeque<usize>, events: &mut Option<&mut Vec<Event>>) -> Outcome {
+    let (winner, score) = if dq_2.is_empty() {
+        println!("Player 1 wins game {}", gn);
+        println!("Winning deck: {:?}", dq_1);
+        (Player::Player1, Weighted.score(&dq_1))
+    } else {
+        println!("Player 2 wins game {}", gn);
+        println!("Winning deck: {:?}", dq_2);
+        (Player::Player2, Weighted.score(&dq_2))
+    };
+    if let Some(es) = events {
+        es.push(Event::GameEnd { game: gn, winner, score });
+    }
+    Outcome { winner, rounds, dq_1, dq_2, repeated: false }
+}
+
+fn recursive_engine(deck_1: &[usize], deck_2: &[usize], events: &mut Option<&mut Vec<Event>>, max_depth: Option<usize>) -> Result<Outcome, CombatError> {
     let mut game_number = 0;
-    fn rc(deck_1: &[usize], deck_2: &[usize], game_number: &mut usize) -> (Player, usize) {
-        *game_number += 1;
-        let gn = *game_number;
+    fn rc(deck_1: &[usize], deck_2: &[usize], depth: usize, game_number: &mut usize, events: &mut Option<&mut Vec<Event>>, max_depth: Option<usize>) -> Result<Outcome, CombatError> {
+        let gn = start_game(deck_1, deck_2, game_number, events);
         let mut rn = 0;
         let mut previous_hashes = HashSet::new();
         let mut dq_1: VecDeque<usize> = deck_1.iter().map(|p| *p).collect();
@@ -88,66 +329,177 @@ fn play_recursive_combat(deck_1: &Vec<usize>, deck_2: &Vec<usize>) -> (Player, u
             rn += 1;
             if !previous_hashes.insert(hash(&dq_1, &dq_2)) {
                 // then we have already played this game
-                println!("Game {} has encountered a hash collision", gn);
-                return (Player::Player1, 0)
+                return Ok(repetition(gn, rn, dq_1, dq_2, events))
             }
             let card_1 = dq_1.pop_front().unwrap() as usize;
             let card_2 = dq_2.pop_front().unwrap() as usize;
             let winner: Player;
+            let mut subgame = None;
             if dq_1.len() >= card_1 && dq_2.len() >= card_2 {
+                check_depth(*game_number + 1, depth + 1, max_depth)?;
                 let dq_1_slice = dq_1.make_contiguous();
                 let dq_2_slice = dq_2.make_contiguous();
                 println!("Game {} spawning a recursive game to determine winner of round {}",
                     gn, rn);
-                let w = rc(&dq_1_slice[..card_1], &dq_2_slice[..card_2], game_number);
-                winner = w.0;
+                subgame = Some(*game_number + 1);
+                winner = rc(&dq_1_slice[..card_1], &dq_2_slice[..card_2], depth + 1, game_number, events, max_depth)?.winner;
             } else if card_1 > card_2 {
                 winner = Player::Player1;
             } else {
                 winner = Player::Player2;
             }
-            match winner {
-                Player::Player1 => {
-                    println!("Player 1 wins round {} of game {}", rn, gn);
-                    dq_1.push_back(card_1);
-                    dq_1.push_back(card_2);
-                },
-                Player::Player2 => {
-                    println!("Player 2 wins round {} of game {}", rn, gn);
-                    dq_2.push_back(card_2);
-                    dq_2.push_back(card_1);
-                }
-            }
+            finish_round(gn, rn, card_1, card_2, subgame, winner, &mut dq_1, &mut dq_2, events);
         }
-        if dq_2.is_empty() {
-            println!("Player 1 wins game {}", gn);
-            println!("Winning deck: {:?}", dq_1);
-            (Player::Player1, score(&dq_1))
+        Ok(finish_game(gn, rn, dq_1, dq_2, events))
+    }
+
+    rc(deck_1, deck_2, 1, &mut game_number, events, max_depth)
+}
+
+// A game in progress on the iterative engine's stack
+struct Frame {
+    game: usize,
+    round: usize,
+    dq_1: VecDeque<usize>,
+    dq_2: VecDeque<usize>,
+    seen: HashSet<u64>,
+    // the cards of the round waiting on the subgame above this frame, and that subgame's number
+    pending: Option<(usize, usize, usize)>
+}
+
+impl Frame {
+    fn start(deck_1: &[usize], deck_2: &[usize], game_number: &mut usize, events: &mut Option<&mut Vec<Event>>) -> Frame {
+        let game = start_game(deck_1, deck_2, game_number, events);
+        Frame {
+            game,
+            round: 0,
+            dq_1: deck_1.iter().copied().collect(),
+            dq_2: deck_2.iter().copied().collect(),
+            seen: HashSet::new(),
+            pending: None
+        }
+    }
+}
+
+fn iterative_engine(deck_1: &[usize], deck_2: &[usize], events: &mut Option<&mut Vec<Event>>, max_depth: Option<usize>) -> Result<Outcome, CombatError> {
+    // What to do with the stack after a step of the game on top of it
+    enum Step {
+        Continue,
+        Finish(Outcome),
+        Spawn(Vec<usize>, Vec<usize>)
+    }
+
+    let mut game_number = 0;
+    let mut stack = vec!(Frame::start(deck_1, deck_2, &mut game_number, events));
+    // the result of the game most recently finished, for the game which spawned it
+    let mut finished = None;
+
+    while !stack.is_empty() {
+        let depth = stack.len();
+        let frame = stack.last_mut().unwrap();
+        let step = if let Some((card_1, card_2, subgame)) = frame.pending.take() {
+            let winner = finished.take().map(|outcome: Outcome| outcome.winner).expect("a subgame finishes before its round resumes");
+            finish_round(frame.game, frame.round, card_1, card_2, Some(subgame), winner, &mut frame.dq_1, &mut frame.dq_2, events);
+            Step::Continue
+        } else if frame.dq_1.is_empty() || frame.dq_2.is_empty() {
+            Step::Finish(finish_game(frame.game, frame.round, std::mem::take(&mut frame.dq_1), std::mem::take(&mut frame.dq_2), events))
         } else {
-            println!("Player 2 wins game {}", gn);
-            println!("Winning deck: {:?}", dq_2);
-            (Player::Player2, score(&dq_2))
+            frame.round += 1;
+            if !frame.seen.insert(hash(&frame.dq_1, &frame.dq_2)) {
+                Step::Finish(repetition(frame.game, frame.round, std::mem::take(&mut frame.dq_1), std::mem::take(&mut frame.dq_2), events))
+            } else {
+                let card_1 = frame.dq_1.pop_front().unwrap();
+                let card_2 = frame.dq_2.pop_front().unwrap();
+                if frame.dq_1.len() >= card_1 && frame.dq_2.len() >= card_2 {
+                    check_depth(game_number + 1, depth + 1, max_depth)?;
+                    println!("Game {} spawning a recursive game to determine winner of round {}",
+                        frame.game, frame.round);
+                    frame.pending = Some((card_1, card_2, game_number + 1));
+                    Step::Spawn(frame.dq_1.iter().take(card_1).copied().collect(), frame.dq_2.iter().take(card_2).copied().collect())
+                } else {
+                    let winner = if card_1 > card_2 { Player::Player1 } else { Player::Player2 };
+                    finish_round(frame.game, frame.round, card_1, card_2, None, winner, &mut frame.dq_1, &mut frame.dq_2, events);
+                    Step::Continue
+                }
+            }
+        };
+        match step {
+            Step::Continue => (),
+            Step::Finish(result) => {
+                stack.pop();
+                finished = Some(result);
+            },
+            Step::Spawn(sub_1, sub_2) => stack.push(Frame::start(&sub_1, &sub_2, &mut game_number, events))
         }
     }
 
-    rc(&deck_1[..], &deck_2[..], &mut game_number)
+    Ok(finished.expect("the first game finishes last"))
 }
 
-fn main() {
+fn run() -> Result<(), AdventError> {
     let stdin = std::io::stdin();
     let mut stdin_lines = stdin.lock().lines().flatten();
     eat_line(&mut stdin_lines, "Player 1:");
 
-    let deck_1: Vec<usize> = read_deck(&mut stdin_lines).unwrap();
+    let deck_1: Vec<usize> = read_deck(&mut stdin_lines).map_err(AdventError::input)?;
 
     eat_line(&mut stdin_lines, "Player 2:");
-    let deck_2: Vec<usize> = read_deck(&mut stdin_lines).unwrap();
+    let deck_2: Vec<usize> = read_deck(&mut stdin_lines).map_err(AdventError::input)?;
+
+    let scoring = match cli::option_value("--scoring") {
+        None => &Weighted,
+        Some(name) => scoring_named(&name)
+            .ok_or_else(|| AdventError::input(format!("unknown scoring `{}`; expected weighted or cards", name)))?
+    };
+    let report = timing::time("Part 1", || play_combat(&deck_1, &deck_2)).report(scoring);
+    println!("Player {:?} wins Combat with a score of {}", report.winner, report.score);
+    println!("Margin of victory: {} after {} rounds", report.margin, report.rounds);
+
+    if let Some(path) = cli::option_value("--replay") {
+        let transcript: Transcript = std::fs::read_to_string(&path).map_err(|e| e.to_string())
+            .and_then(|json| serde_json::from_str(&json).map_err(|e| e.to_string()))
+            .map_err(|e| AdventError::input(format!("unable to read transcript {}: {}", path, e)))?;
+        verify_transcript(&transcript).map_err(|mismatch| AdventError::no_solution(format!(
+            "transcript {} diverges at event {}: recorded {:?}, replayed {:?}",
+            path, mismatch.index, mismatch.recorded, mismatch.replayed
+        )))?;
+        println!("Transcript {} verified: {} events replayed.", path, transcript.events.len());
+    }
 
-    let winner = play_combat(&deck_1, &deck_2);
-    println!("Player {:?} wins Combat with a score of {}", winner.0, winner.1);
+    let engine = match cli::option_value("--engine").as_deref() {
+        None | Some("recursive") => Engine::Recursive,
+        Some("iterative") => Engine::Iterative,
+        Some(other) => return Err(AdventError::input(format!("unknown engine `{}`; expected recursive or iterative", other)))
+    };
+    let max_depth = match cli::option_value("--max-depth") {
+        Some(s) => Some(s.parse::<usize>().map_err(|_| AdventError::input(format!("expected a maximum depth, got {}", s)))?),
+        None => None
+    };
+    let config = CombatConfig { engine, max_depth };
+    let too_deep = |e: CombatError| match e {
+        CombatError::TooDeep { game, depth } =>
+            AdventError::no_solution(format!("game {} would be nested {} deep, beyond --max-depth", game, depth))
+    };
 
-    let winner = play_recursive_combat(&deck_1, &deck_2);
-    println!("Player {:?} wins Recursive Combat with a score of {}", winner.0, winner.1);
+    let outcome = match cli::option_value("--transcript") {
+        None => timing::time("Part 2", || play_recursive_combat(&deck_1, &deck_2, &config)).map_err(too_deep)?,
+        Some(path) => {
+            let (outcome, transcript) = timing::time("Part 2", || record_recursive_combat(&deck_1, &deck_2, &config)).map_err(too_deep)?;
+            let json = serde_json::to_string_pretty(&transcript).unwrap();
+            if let Err(e) = std::fs::write(&path, json) {
+                eprintln!("Unable to write transcript {}: {}", path, e);
+            }
+            outcome
+        }
+    };
+    let report = outcome.report(scoring);
+    println!("Player {:?} wins Recursive Combat with a score of {}", report.winner, report.score);
+    println!("Margin of victory: {} after {} rounds of the first game", report.margin, report.rounds);
+    Ok(())
+}
+
+fn main() {
+    exit_on_error(run())
 }
 
 #[cfg(test)]
@@ -159,18 +511,36 @@ mod day22_spec {
         let deck_1 = vec!(9, 2, 6, 3, 1);
         let deck_2 = vec!(5, 8, 4, 7, 10);
 
-        let winner = play_combat(&deck_1, &deck_2);
-        assert_eq!(winner.0, Player::Player2);
-        assert_eq!(winner.1, 306)
+        let report = play_combat(&deck_1, &deck_2).report(&Weighted);
+        assert_eq!(report.winner, Player::Player2);
+        assert_eq!(report.score, 306);
+        assert_eq!((report.margin, report.rounds), (306, 29));
+    }
+
+    #[test]
+    fn scoring_test() {
+        let deck: VecDeque<usize> = vec!(3, 2, 10, 6, 8, 5, 9, 4, 7, 1).into_iter().collect();
+        assert_eq!(Weighted.score(&deck), 306);
+        assert_eq!(CardCount.score(&deck), 10);
+        assert_eq!(scoring_named("cards").map(|scoring| scoring.score(&deck)), Some(10));
+        assert!(scoring_named("points").is_none());
+
+        let outcome = play_combat(&[9, 2, 6, 3, 1], &[5, 8, 4, 7, 10]);
+        assert_eq!(outcome.report(&CardCount), GameReport { winner: Player::Player2, score: 10, margin: 10, rounds: 29 });
+
+        // the game ends on round 7, which is never played
+        let outcome = play_recursive_combat(&[43, 19], &[2, 29, 14], &CombatConfig::default()).unwrap();
+        assert!(outcome.repeated);
+        assert_eq!(outcome.report(&CardCount), GameReport { winner: Player::Player1, score: 0, margin: 0, rounds: 6 });
     }
 
     #[test]
     fn recursive_combat_loop_test() {
         let deck_1 = vec!(43, 19);
         let deck_2 = vec!(2, 29, 14);
-        let winner = play_recursive_combat(&deck_1, &deck_2);
-        assert_eq!(winner.0, Player::Player1);
-        assert_eq!(winner.1, 0);
+        let report = play_recursive_combat(&deck_1, &deck_2, &CombatConfig::default()).unwrap().report(&Weighted);
+        assert_eq!(report.winner, Player::Player1);
+        assert_eq!(report.score, 0);
     }
 
     #[test]
@@ -178,8 +548,78 @@ mod day22_spec {
         let deck_1 = vec!(9, 2, 6, 3, 1);
         let deck_2 = vec!(5, 8, 4, 7, 10);
 
-        let winner = play_recursive_combat(&deck_1, &deck_2);
-        assert_eq!(winner.0, Player::Player2);
-        assert_eq!(winner.1, 291);
+        let report = play_recursive_combat(&deck_1, &deck_2, &CombatConfig::default()).unwrap().report(&Weighted);
+        assert_eq!(report, GameReport { winner: Player::Player2, score: 291, margin: 291, rounds: 17 });
+    }
+
+    #[test]
+    fn transcript_test() {
+        let deck_1 = vec!(43, 19);
+        let deck_2 = vec!(2, 29, 14);
+        let (_, transcript) = record_recursive_combat(&deck_1, &deck_2, &CombatConfig::default()).unwrap();
+        assert_eq!((transcript.report.winner, transcript.report.score), (Player::Player1, 0));
+        assert_eq!(transcript.events.first(), Some(&Event::GameStart { game: 1, deck_1: deck_1.clone(), deck_2: deck_2.clone() }));
+        assert!(transcript.events.contains(&Event::Repetition { game: 1, round: 7 }));
+
+        let json = serde_json::to_string(&transcript).unwrap();
+        assert!(json.ends_with(r#""winner":"Player1","score":0,"margin":0,"rounds":6}"#), "{}", json);
+        let parsed: Transcript = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, transcript);
+        assert_eq!(verify_transcript(&parsed), Ok(()));
+
+        // a transcript written before versions, margins and rounds still replays
+        let legacy = json.replace(r#","margin":0,"rounds":6"#, "").replacen(r#""version":1,"#, "", 1);
+        assert!(!legacy.contains("rounds") && !legacy.contains("version"), "{}", legacy);
+        let parsed: Transcript = serde_json::from_str(&legacy).unwrap();
+        assert_eq!((parsed.version, parsed.report.margin, parsed.report.rounds), (0, 0, 0));
+        assert_eq!(verify_transcript(&parsed), Ok(()));
+        let wrong_score = legacy.replace(r#""score":0"#, r#""score":1"#);
+        assert!(verify_transcript(&serde_json::from_str(&wrong_score).unwrap()).is_err());
+    }
+
+    #[test]
+    fn transcript_subgame_test() {
+        let deck_1 = vec!(9, 2, 6, 3, 1);
+        let deck_2 = vec!(5, 8, 4, 7, 10);
+        let (_, mut transcript) = record_recursive_combat(&deck_1, &deck_2, &CombatConfig::default()).unwrap();
+        assert_eq!((transcript.report.winner, transcript.report.score), (Player::Player2, 291));
+        assert_eq!(transcript.events.iter().filter(|e| matches!(e, Event::GameStart { .. })).count(), 5);
+        // a round decided by a subgame is logged after that subgame ends
+        let round_idx = transcript.events.iter().position(|e| matches!(e, Event::Round { subgame: Some(2), .. })).unwrap();
+        assert!(matches!(transcript.events[round_idx - 1], Event::GameEnd { game: 2, .. }));
+        assert_eq!(verify_transcript(&transcript), Ok(()));
+
+        let tampered = Event::Round { game: 1, round: 1, card_1: 9, card_2: 5, subgame: None, winner: Player::Player2 };
+        let original = std::mem::replace(&mut transcript.events[1], tampered.clone());
+        assert_eq!(verify_transcript(&transcript), Err(TranscriptMismatch {
+            index: 1, recorded: Some(Box::new(tampered)), replayed: Some(Box::new(original))
+        }));
+    }
+
+    #[test]
+    fn iterative_engine_test() {
+        let iterative = CombatConfig { engine: Engine::Iterative, max_depth: None };
+        for (deck_1, deck_2) in [(vec!(9, 2, 6, 3, 1), vec!(5, 8, 4, 7, 10)), (vec!(43, 19), vec!(2, 29, 14))].iter() {
+            let recorded = record_recursive_combat(deck_1, deck_2, &CombatConfig::default()).unwrap();
+            assert_eq!(record_recursive_combat(deck_1, deck_2, &iterative), Ok(recorded));
+        }
+        let report = play_recursive_combat(&[9, 2, 6, 3, 1], &[5, 8, 4, 7, 10], &iterative).map(|outcome| outcome.report(&Weighted));
+        assert_eq!(report, Ok(GameReport { winner: Player::Player2, score: 291, margin: 291, rounds: 17 }));
+    }
+
+    #[test]
+    fn max_depth_test() {
+        // the example nests games three deep
+        let (deck_1, deck_2) = (vec!(9, 2, 6, 3, 1), vec!(5, 8, 4, 7, 10));
+        for engine in [Engine::Recursive, Engine::Iterative].iter() {
+            let config = |max_depth| CombatConfig { engine: *engine, max_depth: Some(max_depth) };
+            let result = |deck_1, deck_2, max_depth| play_recursive_combat(deck_1, deck_2, &config(max_depth))
+                .map(|outcome| (outcome.winner, outcome.report(&Weighted).score));
+            assert_eq!(result(&deck_1, &deck_2, 3), Ok((Player::Player2, 291)));
+            assert_eq!(play_recursive_combat(&deck_1, &deck_2, &config(2)), Err(CombatError::TooDeep { game: 4, depth: 3 }));
+            assert_eq!(play_recursive_combat(&deck_1, &deck_2, &config(1)), Err(CombatError::TooDeep { game: 2, depth: 2 }));
+            // games without subgames need no depth at all beyond the first
+            assert_eq!(result(&[43, 19], &[2, 29, 14], 1), Ok((Player::Player1, 0)));
+        }
     }
-}
\ No newline at end of file
+}
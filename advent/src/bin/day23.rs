@@ -1,31 +1,42 @@
 use std::io::prelude::*;
 use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::num::NonZeroU64;
 
+use serde::{Serialize, Deserialize};
+
+use advent::cli;
+use advent::error::{AdventError, exit_on_error};
 use advent::make_string::MakeString;
+use advent::timing;
+
+// Anything that can label a cup: the crab game itself uses u32, but letters work just as well.
+trait Label: Copy + Ord + Display {}
+
+impl<T: Copy + Ord + Display> Label for T {}
 
 #[derive(Debug, PartialEq, Eq)]
-struct RingNode {
-    prev: u32,
-    next: u32
+struct RingNode<T> {
+    prev: T,
+    next: T
 }
 
 
-/*  A nonempty circular arrangement of items, in this case u32. It would be relatively simple to make this generic over any Copy + Ord type,
-    but that's not worth the trouble here.
+/*  A nonempty circular arrangement of labels, u32 unless otherwise specified.
 
     `point` is guaranteed to be a key in the map; similarly, it is an invariant of the map that at the end of any method body, hopping `next` pointers
     and hopping `prev` pointers will traverse the entire keyset in the same cycle (in reverse order).
 */
-struct Ring {
-    nodes: BTreeMap<u32, RingNode>,
-    point: u32
+struct Ring<T = u32> {
+    nodes: BTreeMap<T, RingNode<T>>,
+    point: T
 }
 
-impl Ring {
+impl<T: Label> Ring<T> {
 
-    fn new<J>(mut j: J) -> Result<Ring, String> where J: Iterator<Item=u32> {
+    fn new<J>(mut j: J) -> Result<Ring<T>, String> where J: Iterator<Item=T> {
         let mut nodes = BTreeMap::new();
-        let first_node: u32;
+        let first_node: T;
         match j.next() {
             None => return Err("Ring must be non-empty".to_owned()),
             Some(c) => {
@@ -33,7 +44,7 @@ impl Ring {
                 nodes.insert(c, RingNode { prev: c, next: c });
             }
         }
-        let mut last_node: u32 = first_node;
+        let mut last_node: T = first_node;
         while let Some(c) = j.next() {
             if nodes.contains_key(&c) {
                 let msg = format!("Duplicate entry {} in interator", c);
@@ -56,7 +67,7 @@ impl Ring {
         self.nodes.len()
     }
 
-    fn max(&self) -> &u32 {
+    fn max(&self) -> &T {
         self.nodes.keys().rev().nth(0).unwrap()
     }
 
@@ -66,7 +77,7 @@ impl Ring {
         }
     }
 
-    fn insert_after(&mut self, existing_node: u32, new_node: u32) -> Result<(), String> {
+    fn insert_after(&mut self, existing_node: T, new_node: T) -> Result<(), String> {
         match self.nodes.get(&existing_node).map(|node| node.next) {
             None => {
                 let msg = format!("Node {} not found in ring", existing_node);
@@ -87,15 +98,15 @@ impl Ring {
 
     // Removes and returns, in order, the `n` entries clockwise from (but not including) `self.point`. If the starting
     // size of the ring is less than or equal to `n`, no modification is performed and an Err is returned.
-    fn remove_after_point(&mut self, n: usize) -> Result<Vec<u32>, String> {
+    fn remove_after_point(&mut self, n: usize) -> Result<Vec<T>, String> {
         if self.len() > n {
-            let mut r: Vec<u32> = vec!();
+            let mut r: Vec<T> = vec!();
 
             while let Some(RingNode { next, .. }) = self.nodes.get(&self.point) {
                 if r.len() == n {
                     break
                 }
-                let next_node: u32 = *next;
+                let next_node: T = *next;
                 r.push(next_node);
                 match self.nodes.remove(&next_node) {
                     Some(RingNode { next: next2, .. }) => {
@@ -120,55 +131,457 @@ impl Ring {
         }
     }
 
-    fn iter(&self) -> RingIterator {
-        RingIterator { ring: self, start: self.point, last_yielded: None}
+    // The label `n` hops clockwise from `label`, or None if `label` is not in the ring.
+    fn nth_after(&self, label: T, n: usize) -> Option<T> {
+        let mut current = label;
+        if !self.nodes.contains_key(&current) {
+            return None
+        }
+        for _ in 0..(n % self.len()) {
+            current = self.nodes.get(&current)?.next;
+        }
+        Some(current)
+    }
+
+    fn iter(&self) -> RingIterator<T> {
+        self.iter_from(self.point)
     }
 
-    fn iter_from(&self, start: u32) -> RingIterator {
-        RingIterator { ring: self, start, last_yielded: None }
+    // Iterates clockwise over the whole ring starting just after `start` and ending with `start` itself.
+    // Iterating from the back yields `start` first and then proceeds counterclockwise.
+    fn iter_from(&self, start: T) -> RingIterator<T> {
+        let remaining = if self.nodes.contains_key(&start) { self.len() } else { 0 };
+        RingIterator { ring: self, front: start, back: start, remaining }
     }
 }
 
+// The successor array of the ring of `seed` followed by the labels from just above the seed's
+// largest through `upto`, with its offset, as in RingSnapshot. Only the seed is checked, for being a
+// contiguous range of labels without repeats; the labels after it are contiguous by construction.
+fn contiguous_successors(seed: &[u32], upto: u32) -> Result<(u32, Vec<u32>), String> {
+    let (offset, seed_max) = match (seed.iter().min(), seed.iter().max()) {
+        (Some(min), Some(max)) => (*min, *max),
+        _ => return Err("Ring must be non-empty".to_owned())
+    };
+    let mut seen = vec!(false; (seed_max - offset) as usize + 1);
+    for label in seed {
+        let idx = (label - offset) as usize;
+        if seen[idx] {
+            return Err(format!("Duplicate entry {} in interator", label))
+        }
+        seen[idx] = true;
+    }
+    if seed.len() != seen.len() {
+        return Err(format!("Ring labels {}..={} are not contiguous", offset, seed_max))
+    }
 
-struct RingIterator<'a> {
-    ring: &'a Ring,
-    start: u32,
-    last_yielded: Option<&'a u32>
+    let max = seed_max.max(upto);
+    // every label past the seed is followed by the next one, and the last by the first of the seed
+    let mut successors: Vec<u32> = (offset + 1..=max + 1).collect();
+    for pair in seed.windows(2) {
+        successors[(pair[0] - offset) as usize] = pair[1];
+    }
+    let last = seed[seed.len() - 1];
+    if max > seed_max {
+        successors[(last - offset) as usize] = seed_max + 1;
+        successors[(max - offset) as usize] = seed[0];
+    } else {
+        successors[(last - offset) as usize] = seed[0];
+    }
+    Ok((offset, successors))
 }
 
-impl<'a> Iterator for RingIterator<'a> {
-    type Item = &'a u32;
+impl Ring {
+    // The ring of `seed` followed by the labels from just above the seed's largest through `upto`,
+    // as in the million-cup game. Unlike Ring::new, this checks only the seed, and builds the map in
+    // a single pass over the successor array.
+    fn from_contiguous(seed: &[u32], upto: u32) -> Result<Ring, String> {
+        let (offset, successors) = contiguous_successors(seed, upto)?;
+        let mut predecessors = vec!(0; successors.len());
+        for (idx, next) in successors.iter().enumerate() {
+            predecessors[(next - offset) as usize] = offset + idx as u32;
+        }
+        // in increasing order of label, which a BTreeMap is built from in bulk
+        let nodes = successors.into_iter().zip(predecessors).enumerate()
+            .map(|(idx, (next, prev))| (offset + idx as u32, RingNode { prev, next }))
+            .collect();
+        Ok(Ring { nodes, point: seed[0] })
+    }
 
-    fn next(&mut self) -> Option<&'a u32> {
-        match self.last_yielded {
-            None => {
-                let y = self.ring.nodes.get(&self.start).map(|node| &node.next);
-                self.last_yielded = y;
-                y
-            },
-            Some(y_prev) if *y_prev == self.start => None,
-            Some(y_prev) => {
-                let y = self.ring.nodes.get(y_prev).map(|node| &node.next);
-                self.last_yielded = y;
-                y
+    // Plays `moves` moves, recording each. This only reads the ring between steps, so the
+    // untraced game pays nothing for it.
+    fn simulate_with_trace(&mut self, moves: usize) -> Result<Vec<Move>, String> {
+        let mut trace = Vec::with_capacity(moves);
+        // the cup the puzzle statement puts first: the first move's current cup
+        let mut first = self.point;
+        for number in 1..=moves {
+            let current = self.point;
+            let pick_up: Vec<u32> = (1..=3).flat_map(|n| self.nth_after(current, n)).collect();
+            crab_step(self)?;
+            // the picked-up cups were put back just clockwise of the destination
+            let destination = self.nodes[&pick_up[0]].prev;
+            // the statement moves each current cup one place clockwise of the last
+            let len = self.len();
+            first = self.nth_after(self.point, len - number % len).unwrap_or(first);
+            let cups = std::iter::once(first).chain(self.iter_from(first).take(len - 1).cloned()).collect();
+            trace.push(Move { number, current, pick_up, destination, cups });
+        }
+        Ok(trace)
+    }
+
+    // Only rings whose labels form a contiguous range (as in the crab game) can be captured.
+    fn snapshot(&self, steps: u64) -> Result<RingSnapshot, String> {
+        let offset = *self.nodes.keys().next().unwrap();
+        if (*self.max() - offset) as usize + 1 != self.len() {
+            return Err(format!("Ring labels {}..={} are not contiguous", offset, self.max()))
+        }
+        let successors = self.nodes.values().map(|node| node.next).collect();
+        Ok(RingSnapshot { steps, point: self.point, offset, successors })
+    }
+
+    fn restore(snapshot: &RingSnapshot) -> Result<Ring, String> {
+        let len = snapshot.successors.len();
+        let successor = |label: u32| {
+            label.checked_sub(snapshot.offset)
+                .and_then(|idx| snapshot.successors.get(idx as usize).cloned())
+                .ok_or_else(|| format!("Label {} is outside the snapshot", label))
+        };
+
+        let mut labels = Vec::with_capacity(len);
+        let mut label = snapshot.point;
+        for _ in 0..len {
+            labels.push(label);
+            label = successor(label)?;
+        }
+        if label != snapshot.point {
+            return Err(format!("Successors do not form a cycle through {}", snapshot.point))
+        }
+        // Ring::new rejects duplicates, so the cycle visits each label exactly once
+        Ring::new(labels.into_iter())
+    }
+}
+
+// One move of the crab game, after the worked example in the puzzle statement. `cups` is the ring
+// after the move, placed as the statement places it: the current cup of move n + 1 is at index n,
+// modulo the number of cups.
+#[derive(Debug, PartialEq, Eq)]
+struct Move {
+    number: usize,
+    current: u32,
+    pick_up: Vec<u32>,
+    destination: u32,
+    cups: Vec<u32>
+}
+
+impl Move {
+    // The cups, with the next current cup in parentheses
+    fn cups_line(&self) -> String {
+        let next_current = self.number % self.cups.len();
+        self.cups.iter().enumerate().map(|(idx, cup)| {
+            if idx == next_current { format!("({})", cup) } else { cup.to_string() }
+        }).mk_string(" ")
+    }
+}
+
+impl Display for Move {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        writeln!(f, "-- move {} --", self.number)?;
+        writeln!(f, "current: {}", self.current)?;
+        writeln!(f, "pick up: {}", self.pick_up.iter().mk_string(", "))?;
+        writeln!(f, "destination: {}", self.destination)?;
+        writeln!(f, "cups after: {}", self.cups_line())
+    }
+}
+
+// The example from the puzzle statement, and the cups after each of its first ten moves
+const SAMPLE: &str = "389125467";
+const SAMPLE_TRACE: [&str; 10] = [
+    "3 (2) 8 9 1 5 4 6 7",
+    "3 2 (5) 4 6 7 8 9 1",
+    "7 2 5 (8) 9 1 3 4 6",
+    "3 2 5 8 (4) 6 7 9 1",
+    "9 2 5 8 4 (1) 3 6 7",
+    "7 2 5 8 4 1 (9) 3 6",
+    "8 3 6 7 4 1 9 (2) 5",
+    "7 4 1 5 8 3 9 2 (6)",
+    "(5) 7 4 1 8 3 9 2 6",
+    "5 (8) 3 7 4 1 9 2 6"
+];
+
+// Checks a trace of the sample against the statement, as far as both go
+fn verify_sample(trace: &[Move]) -> Result<(), String> {
+    for (m, expected) in trace.iter().zip(SAMPLE_TRACE.iter()) {
+        if m.cups_line() != *expected {
+            return Err(format!("after move {}, expected cups {} but found {}", m.number, expected, m.cups_line()))
+        }
+    }
+    Ok(())
+}
+
+// A compact image of a Ring with contiguous labels, after `steps` steps of the crab game:
+// `successors[i]` is the label clockwise from `offset + i`.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct RingSnapshot {
+    steps: u64,
+    point: u32,
+    offset: u32,
+    successors: Vec<u32>
+}
+
+impl RingSnapshot {
+    fn save(&self, path: &str) -> Result<(), String> {
+        let json = serde_json::to_string(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, json).map_err(|e| e.to_string())
+    }
+
+    fn load(path: &str) -> Result<RingSnapshot, String> {
+        let json = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        serde_json::from_str(&json).map_err(|e| e.to_string())
+    }
+}
+
+/*  The crab game only ever hops `next` pointers, so a ring of contiguous u32 labels can drop the
+    `prev` pointers and keep only a successor array, as in RingSnapshot: `successors[i]` is the label
+    clockwise from `offset + i`. This takes half the memory of a doubly-linked array (and far less
+    than Ring's map) for the million-cup game. Ring remains the general-purpose structure.
+*/
+struct SinglyLinkedRing {
+    offset: u32,
+    successors: Vec<u32>,
+    point: u32
+}
+
+impl SinglyLinkedRing {
+    fn new<J>(j: J) -> Result<SinglyLinkedRing, String> where J: Iterator<Item=u32> {
+        let labels: Vec<u32> = j.collect();
+        let (offset, max) = match (labels.iter().min(), labels.iter().max()) {
+            (Some(min), Some(max)) => (*min, *max),
+            _ => return Err("Ring must be non-empty".to_owned())
+        };
+        if (max - offset) as usize + 1 != labels.len() {
+            return Err(format!("Ring labels {}..={} are not contiguous", offset, max))
+        }
+
+        let mut successors = vec!(None; labels.len());
+        for (idx, label) in labels.iter().enumerate() {
+            let successor = &mut successors[(label - offset) as usize];
+            if successor.is_some() {
+                return Err(format!("Duplicate entry {} in interator", label))
             }
+            *successor = Some(labels[(idx + 1) % labels.len()]);
+        }
+        // contiguous and free of duplicates, so every label has its successor
+        let successors = successors.into_iter().flatten().collect();
+
+        Ok(SinglyLinkedRing { offset, successors, point: labels[0] })
+    }
+
+    // As Ring::from_contiguous
+    fn from_contiguous(seed: &[u32], upto: u32) -> Result<SinglyLinkedRing, String> {
+        let (offset, successors) = contiguous_successors(seed, upto)?;
+        Ok(SinglyLinkedRing { offset, successors, point: seed[0] })
+    }
+
+    fn len(&self) -> usize {
+        self.successors.len()
+    }
+
+    fn max(&self) -> u32 {
+        self.offset + self.successors.len() as u32 - 1
+    }
+
+    fn next(&self, label: u32) -> u32 {
+        self.successors[(label - self.offset) as usize]
+    }
+
+    fn set_next(&mut self, label: u32, next: u32) {
+        self.successors[(label - self.offset) as usize] = next;
+    }
+
+    fn contains(&self, label: u32) -> bool {
+        label >= self.offset && label <= self.max()
+    }
+
+    // One step of the crab game, as crab_step does for Ring
+    fn crab_step(&mut self) -> Result<(), String> {
+        if self.len() <= 3 {
+            return Err(format!("Ring is too small for the crab game; it has only {} elements.", self.len()))
+        }
+        let first = self.next(self.point);
+        let second = self.next(first);
+        let third = self.next(second);
+
+        let mut destination = self.point;
+        loop {
+            destination = if destination == self.offset { self.max() } else { destination - 1 };
+            if destination != first && destination != second && destination != third {
+                break
+            }
+        }
+
+        self.set_next(self.point, self.next(third));
+        self.set_next(third, self.next(destination));
+        self.set_next(destination, first);
+        self.point = self.next(self.point);
+        Ok(())
+    }
+
+    fn nth_after(&self, label: u32, n: usize) -> Option<u32> {
+        if !self.contains(label) {
+            return None
+        }
+        Some((0..(n % self.len())).fold(label, |current, _| self.next(current)))
+    }
+
+    // As Ring::iter_from, clockwise from just after `start` through `start` itself
+    fn iter_from(&self, start: u32) -> impl Iterator<Item=u32> + '_ {
+        let len = if self.contains(start) { self.len() } else { 0 };
+        let mut current = start;
+        (0..len).map(move |_| {
+            current = self.next(current);
+            current
+        })
+    }
+
+    fn snapshot(&self, steps: u64) -> RingSnapshot {
+        RingSnapshot { steps, point: self.point, offset: self.offset, successors: self.successors.clone() }
+    }
+
+    fn restore(snapshot: &RingSnapshot) -> Result<SinglyLinkedRing, String> {
+        if snapshot.successors.is_empty() {
+            return Err("Ring must be non-empty".to_owned())
+        }
+        let ring = SinglyLinkedRing { offset: snapshot.offset, successors: snapshot.successors.clone(), point: snapshot.point };
+
+        // the cycle through the point must visit every label exactly once
+        let mut seen = vec!(false; ring.len());
+        let mut label = ring.point;
+        for _ in 0..ring.len() {
+            if !ring.contains(label) {
+                return Err(format!("Label {} is outside the snapshot", label))
+            }
+            let idx = (label - ring.offset) as usize;
+            if seen[idx] {
+                break
+            }
+            seen[idx] = true;
+            label = ring.next(label);
+        }
+        if label != ring.point || seen.contains(&false) {
+            return Err(format!("Successors do not form a cycle through {}", ring.point))
+        }
+        Ok(ring)
+    }
+}
+
+// The operations the million-cup game needs, so either ring can play it
+trait CrabGame: Sized {
+    fn from_contiguous(seed: &[u32], upto: u32) -> Result<Self, String>;
+    fn resume(snapshot: &RingSnapshot) -> Result<Self, String>;
+    fn step(&mut self) -> Result<(), String>;
+    fn snapshot(&self, steps: u64) -> Result<RingSnapshot, String>;
+    fn nth_after(&self, label: u32, n: usize) -> Option<u32>;
+}
+
+impl CrabGame for Ring {
+    fn from_contiguous(seed: &[u32], upto: u32) -> Result<Ring, String> {
+        Ring::from_contiguous(seed, upto)
+    }
+
+    fn resume(snapshot: &RingSnapshot) -> Result<Ring, String> {
+        Ring::restore(snapshot)
+    }
+
+    fn step(&mut self) -> Result<(), String> {
+        crab_step(self)
+    }
+
+    fn snapshot(&self, steps: u64) -> Result<RingSnapshot, String> {
+        Ring::snapshot(self, steps)
+    }
+
+    fn nth_after(&self, label: u32, n: usize) -> Option<u32> {
+        Ring::nth_after(self, label, n)
+    }
+}
+
+impl CrabGame for SinglyLinkedRing {
+    fn from_contiguous(seed: &[u32], upto: u32) -> Result<SinglyLinkedRing, String> {
+        SinglyLinkedRing::from_contiguous(seed, upto)
+    }
+
+    fn resume(snapshot: &RingSnapshot) -> Result<SinglyLinkedRing, String> {
+        SinglyLinkedRing::restore(snapshot)
+    }
+
+    fn step(&mut self) -> Result<(), String> {
+        self.crab_step()
+    }
+
+    fn snapshot(&self, steps: u64) -> Result<RingSnapshot, String> {
+        Ok(SinglyLinkedRing::snapshot(self, steps))
+    }
+
+    fn nth_after(&self, label: u32, n: usize) -> Option<u32> {
+        SinglyLinkedRing::nth_after(self, label, n)
+    }
+}
+
+struct RingIterator<'a, T> {
+    ring: &'a Ring<T>,
+    front: T, // last label yielded from the front
+    back: T, // next label to be yielded from the back
+    remaining: usize
+}
+
+impl<'a, T: Label> Iterator for RingIterator<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if self.remaining == 0 {
+            return None
         }
+        let y = self.ring.nodes.get(&self.front).map(|node| &node.next)?;
+        self.front = *y;
+        self.remaining -= 1;
+        Some(y)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
     }
+}
+
+impl<'a, T: Label> ExactSizeIterator for RingIterator<'a, T> {}
 
+impl<'a, T: Label> DoubleEndedIterator for RingIterator<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        if self.remaining == 0 {
+            return None
+        }
+        let (y, node) = self.ring.nodes.get_key_value(&self.back)?;
+        self.back = node.prev;
+        self.remaining -= 1;
+        Some(y)
+    }
 }
 
 // One step of the crab game
 fn crab_step(ring: &mut Ring) -> Result<(), String> {
+    crab_step_with(ring, |d| d.checked_sub(1))
+}
+
+// One step of the crab game, with the destination found by stepping down from the current label with
+// `predecessor`, skipping labels not in the ring and wrapping around to the largest label whenever
+// `predecessor` returns None.
+fn crab_step_with<T, F>(ring: &mut Ring<T>, predecessor: F) -> Result<(), String> where T: Label, F: Fn(T) -> Option<T> {
     let mut removed = ring.remove_after_point(3)?;
 
-    let destination: u32 = {
+    let destination: T = {
         let mut d = ring.point;
 
         loop {
-            match d {
-                0 => d = *ring.max(),
-                _ => d -= 1
-            };
+            d = predecessor(d).unwrap_or(*ring.max());
             if ring.nodes.contains_key(&d) {
                 break
             }
@@ -184,39 +597,148 @@ fn crab_step(ring: &mut Ring) -> Result<(), String> {
     Ok(ring.advance_clockwise())
 }
 
-fn main() {
+fn letter_before(c: char) -> Option<char> {
+    (c as u32).checked_sub(1).and_then(std::char::from_u32)
+}
+
+// Part 1 with cups labeled by letters, read from the alphabetically first cup
+fn play_letters(line: &str) -> Result<(), AdventError> {
+    let mut ring: Ring<char> = Ring::new(line.chars().filter(|c| c.is_alphabetic())).map_err(AdventError::input)?;
+    let first = *ring.nodes.keys().next().unwrap();
+
+    timing::time("Part 1", || {
+        for _ in 0..100 {
+            crab_step_with(&mut ring, letter_before)?;
+        }
+        Ok::<_, String>(())
+    }).map_err(AdventError::no_solution)?;
+
+    let label: String = ring.iter_from(first).take(ring.len() - 1).mk_string("");
+    println!("Ring label after 100 steps, starting from {}: {}", first, label);
+    Ok(())
+}
+
+// Part 2: plays the crab game through ten million steps, from `seed` followed by the labels up to
+// a million or from the snapshot at `resume`, saving a snapshot to `checkpoint` (if given) every
+// `every` steps. Returns the two labels following 1.
+fn play_million<G: CrabGame>(seed: &[u32], resume: Option<String>, checkpoint: Option<String>, every: NonZeroU64) -> Result<Vec<u64>, AdventError> {
+    let (mut ring, steps_done) = match resume {
+        Some(path) => {
+            match RingSnapshot::load(&path).and_then(|snapshot| G::resume(&snapshot).map(|ring| (ring, snapshot.steps))) {
+                Ok((ring, steps)) => {
+                    println!("Resuming from {} after {} steps", path, steps);
+                    (ring, steps)
+                },
+                Err(e) => return Err(AdventError::input(format!("unable to resume from {}: {}", path, e)))
+            }
+        },
+        None => (G::from_contiguous(seed, 1_000_000).map_err(AdventError::input)?, 0)
+    };
+
+    timing::time("Part 2", || {
+        for step in steps_done..10_000_000 {
+            ring.step()?;
+            if let Some(path) = &checkpoint {
+                if (step + 1) % every.get() == 0 {
+                    if let Err(e) = ring.snapshot(step + 1).and_then(|snapshot| snapshot.save(path)) {
+                        eprintln!("Unable to write checkpoint {}: {}", path, e);
+                    }
+                }
+            }
+        }
+        Ok::<_, String>(())
+    }).map_err(AdventError::no_solution)?;
+
+    Ok((1..=2).flat_map(|n| ring.nth_after(1, n)).map(|x| x as u64).collect())
+}
+
+// Times building the million-cup ring both ways, and a million steps of the game on each ring,
+// failing if they end up differently
+fn bench_rings(seed: &[u32]) -> Result<(), String> {
+    const STEPS: usize = 1_000_000;
+    let start = || seed.iter().copied().chain(seed.iter().max().map_or(1, |max| max + 1)..=1_000_000);
+    let chained = timing::time("Ring::new", || Ring::new(start()))?;
+    let mut ring = timing::time("Ring::from_contiguous", || Ring::from_contiguous(seed, 1_000_000))?;
+    if ring.nodes != chained.nodes {
+        return Err("Ring::new and Ring::from_contiguous build different rings".to_owned())
+    }
+    let mut singly = SinglyLinkedRing::new(start())?;
+    timing::time("Ring", || (0..STEPS).try_for_each(|_| crab_step(&mut ring)))?;
+    timing::time("SinglyLinkedRing", || (0..STEPS).try_for_each(|_| singly.crab_step()))?;
+    println!("SinglyLinkedRing successor array: {} bytes", singly.len() * std::mem::size_of::<u32>());
+
+    let labels: Vec<u32> = ring.iter_from(1).take(8).cloned().collect();
+    let singly_labels: Vec<u32> = singly.iter_from(1).take(8).collect();
+    if ring.point == singly.point && labels == singly_labels {
+        Ok(())
+    } else {
+        Err(format!("rings disagree after {} steps: {:?} vs {:?}", STEPS, labels, singly_labels))
+    }
+}
+
+fn run() -> Result<(), AdventError> {
     let stdin = std::io::stdin();
-    let line = stdin.lock().lines().flatten().next().unwrap();
-    let mut ring = Ring::new(line.chars().flat_map(|c| c.to_digit(10))).unwrap();
+    let line = stdin.lock().lines().flatten().next().ok_or_else(|| AdventError::input("expected a line of cup labels"))?;
+    if cli::has_flag("--letters") {
+        return play_letters(&line)
+    }
+    let mut ring = Ring::new(line.chars().flat_map(|c| c.to_digit(10))).map_err(AdventError::input)?;
 
     let label: String = ring.iter().take(8).mk_string("");
     println!("Initial ring label: {}", label);
     let label: String = ring.iter_from(1).take(8).mk_string("");
     println!("Initial ring label, starting from 1: {}", label);
-    
-    for _ in 0..100 {
-        crab_step(&mut ring).unwrap();
+
+    if let Some(n) = cli::option_value("--trace") {
+        let moves: usize = n.parse().map_err(|_| AdventError::input(format!("expected a number of moves, got {}", n)))?;
+        let mut traced = Ring::new(line.chars().flat_map(|c| c.to_digit(10))).map_err(AdventError::input)?;
+        let trace = traced.simulate_with_trace(moves).map_err(AdventError::no_solution)?;
+        for m in &trace {
+            print!("{}", m);
+        }
+        if line.trim() == SAMPLE {
+            verify_sample(&trace).map_err(AdventError::no_solution)?;
+            println!("The first {} moves agree with the puzzle statement.", trace.len().min(SAMPLE_TRACE.len()));
+        }
     }
+    
+    timing::time("Part 1", || {
+        for _ in 0..100 {
+            crab_step(&mut ring)?;
+        }
+        Ok::<_, String>(())
+    }).map_err(AdventError::no_solution)?;
 
     let label: String = ring.iter_from(1).take(8).mk_string("");
     println!("Ring label after 100 steps: {}", label);
 
-    let mut ring1m = {
-        let nodes = Iterator::chain(
-            line.chars().flat_map(|c| c.to_digit(10)),
-            (*ring.max() + 1)..=1_000_000
-        );
-        Ring::new(nodes).unwrap()
+    let checkpoint = cli::option_value("--checkpoint");
+    let every = match cli::option_value("--every") {
+        Some(s) => s.parse::<NonZeroU64>().map_err(|_| AdventError::input(format!("expected a positive number of steps between checkpoints, got {}", s)))?,
+        None => NonZeroU64::new(1_000_000).unwrap()
     };
 
-    for _i in 0..10_000_000 {
-        crab_step(&mut ring1m).unwrap();
+    let seed: Vec<u32> = line.chars().flat_map(|c| c.to_digit(10)).collect();
+
+    if cli::has_flag("--bench") {
+        bench_rings(&seed).map_err(AdventError::no_solution)?;
     }
 
-    let labels: Vec<u64> = ring1m.iter_from(1).take(2).map(|x| *x as u64).collect();
+    let resume = cli::option_value("--resume");
+    let labels = if cli::has_flag("--singly") {
+        play_million::<SinglyLinkedRing>(&seed, resume, checkpoint, every)?
+    } else {
+        play_million::<Ring>(&seed, resume, checkpoint, every)?
+    };
+
     println!("After 10M steps, {:?} follows 1", labels);
-    let p: u64 = 1u64 * labels[0] * labels[1];
+    let p: u64 = labels.iter().product();
     println!("Product of labels: {}", p);
+    Ok(())
+}
+
+fn main() {
+    exit_on_error(run())
 }
 
 #[cfg(test)]
@@ -250,6 +772,20 @@ mod day23_spec {
     }
 
 
+    #[test]
+    fn crab_step_with_letters_test() {
+        let mut ring: Ring<char> = Ring::new("chiabedfg".chars()).unwrap();
+        let mut numbers = Ring::new(vec!(3, 8, 9, 1, 2, 5, 4, 6, 7).into_iter()).unwrap();
+        let letter = |n: &u32| (b'a' + *n as u8 - 1) as char;
+        for _ in 0..10 {
+            crab_step_with(&mut ring, letter_before).unwrap();
+            crab_step(&mut numbers).unwrap();
+            assert_eq!(ring.point, letter(&numbers.point));
+            assert_eq!(ring.iter().mk_string(""), numbers.iter().map(letter).mk_string(""));
+        }
+        assert_eq!(ring.iter_from('a').take(8).mk_string(""), "ibfehcgd");
+    }
+
     mod ring {
         use super::*;
 
@@ -326,5 +862,211 @@ mod day23_spec {
             let removed = ring.remove_after_point(2).unwrap();
             assert_eq!(removed, vec!(6, 5));
         }
+
+        #[test]
+        fn nth_after_test() {
+            let ring = Ring::new(vec!(3, 8, 9, 1, 2, 5, 4, 6, 7).into_iter()).unwrap();
+            assert_eq!(ring.nth_after(1, 0), Some(1));
+            assert_eq!(ring.nth_after(1, 1), Some(2));
+            assert_eq!(ring.nth_after(1, 2), Some(5));
+            assert_eq!(ring.nth_after(7, 1), Some(3));
+            assert_eq!(ring.nth_after(7, 10), Some(3));
+            assert_eq!(ring.nth_after(10, 1), None);
+        }
+
+        #[test]
+        fn iter_test() {
+            let ring = Ring::new(vec!(3, 8, 9, 1, 2, 5, 4, 6, 7).into_iter()).unwrap();
+            assert_eq!(ring.iter().mk_string(""), "891254673");
+            assert_eq!(ring.iter_from(1).mk_string(""), "254673891");
+            assert_eq!(ring.iter_from(1).rev().mk_string(""), "198376452");
+            assert_eq!(ring.iter_from(10).count(), 0);
+
+            let mut it = ring.iter_from(1);
+            assert_eq!(it.next(), Some(&2));
+            assert_eq!(it.next_back(), Some(&1));
+            assert_eq!(it.next_back(), Some(&9));
+            assert_eq!(it.len(), 6);
+            assert_eq!(it.mk_string(""), "546738");
+
+            let single = Ring::new(vec!(4).into_iter()).unwrap();
+            assert_eq!(single.iter().rev().mk_string(""), "4");
+        }
+    }
+
+    #[test]
+    fn snapshot_test() {
+        let mut ring = Ring::new(vec!(3, 8, 9, 1, 2, 5, 4, 6, 7).into_iter()).unwrap();
+        for _ in 0..5 {
+            crab_step(&mut ring).unwrap();
+        }
+        let snapshot = ring.snapshot(5).unwrap();
+        assert_eq!(snapshot.offset, 1);
+        assert_eq!(snapshot.successors.len(), 9);
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let mut restored = Ring::restore(&serde_json::from_str(&json).unwrap()).unwrap();
+        assert_eq!(restored.point, ring.point);
+        assert_eq!(restored.nodes, ring.nodes);
+
+        for _ in 5..10 {
+            crab_step(&mut restored).unwrap();
+        }
+        assert_eq!(restored.iter_from(1).take(8).mk_string(""), "92658374");
+    }
+
+    #[test]
+    fn trace_test() {
+        let mut ring = Ring::new(SAMPLE.chars().flat_map(|c| c.to_digit(10))).unwrap();
+        let trace = ring.simulate_with_trace(10).unwrap();
+        assert_eq!(trace.iter().map(|m| m.cups_line()).collect::<Vec<_>>(), SAMPLE_TRACE.to_vec());
+        assert_eq!(verify_sample(&trace), Ok(()));
+
+        let moves: Vec<(u32, Vec<u32>, u32)> = trace.iter().map(|m| (m.current, m.pick_up.clone(), m.destination)).collect();
+        assert_eq!(moves, vec!(
+            (3, vec!(8, 9, 1), 2),
+            (2, vec!(8, 9, 1), 7),
+            (5, vec!(4, 6, 7), 3),
+            (8, vec!(9, 1, 3), 7),
+            (4, vec!(6, 7, 9), 3),
+            (1, vec!(3, 6, 7), 9),
+            (9, vec!(3, 6, 7), 8),
+            (2, vec!(5, 8, 3), 1),
+            (6, vec!(7, 4, 1), 5),
+            (5, vec!(7, 4, 1), 3)
+        ));
+        assert_eq!(trace[0].to_string(), "-- move 1 --\ncurrent: 3\npick up: 8, 9, 1\ndestination: 2\ncups after: 3 (2) 8 9 1 5 4 6 7\n");
+
+        // tracing plays the same game
+        assert_eq!(ring.iter_from(1).take(8).mk_string(""), "92658374");
+        let mut untraced = Ring::new(SAMPLE.chars().flat_map(|c| c.to_digit(10))).unwrap();
+        for _ in 0..10 {
+            crab_step(&mut untraced).unwrap();
+        }
+        assert_eq!(untraced.nodes, ring.nodes);
+        assert_eq!(untraced.point, ring.point);
+
+        let mut wrong = Ring::new(SAMPLE.chars().flat_map(|c| c.to_digit(10))).unwrap();
+        crab_step(&mut wrong).unwrap();
+        assert!(verify_sample(&wrong.simulate_with_trace(1).unwrap()).is_err());
+    }
+
+    mod singly_linked_ring {
+        use super::*;
+
+        #[test]
+        fn new_test() {
+            let ring = SinglyLinkedRing::new(vec!(3, 8, 9, 1, 2, 5, 4, 6, 7).into_iter()).unwrap();
+            assert_eq!((ring.offset, ring.point, ring.max()), (1, 3, 9));
+            assert_eq!(ring.successors, vec!(2, 5, 8, 6, 4, 7, 3, 9, 1));
+            assert_eq!(ring.iter_from(1).map(|label| label.to_string()).mk_string(""), "254673891");
+            assert_eq!(ring.iter_from(10).count(), 0);
+
+            assert_eq!(SinglyLinkedRing::new(vec!(3, 4, 3, 5).into_iter()).err(), Some("Ring labels 3..=5 are not contiguous".to_owned()));
+            assert_eq!(SinglyLinkedRing::new(vec!(3, 3, 5).into_iter()).err(), Some("Duplicate entry 3 in interator".to_owned()));
+            assert!(SinglyLinkedRing::new(vec!(1, 3, 4).into_iter()).is_err());
+            assert!(SinglyLinkedRing::new(std::iter::empty()).is_err());
+        }
+
+        #[test]
+        fn crab_step_test() {
+            let mut ring = Ring::new(vec!(3, 8, 9, 1, 2, 5, 4, 6, 7).into_iter()).unwrap();
+            let mut singly = SinglyLinkedRing::new(vec!(3, 8, 9, 1, 2, 5, 4, 6, 7).into_iter()).unwrap();
+            for _ in 0..100 {
+                crab_step(&mut ring).unwrap();
+                singly.crab_step().unwrap();
+                assert_eq!(singly.point, ring.point);
+                assert_eq!(singly.iter_from(singly.point).collect::<Vec<_>>(), ring.iter().cloned().collect::<Vec<_>>());
+            }
+            assert_eq!(singly.iter_from(1).take(8).map(|label| label.to_string()).mk_string(""), "67384529");
+            assert_eq!(singly.nth_after(1, 1), ring.nth_after(1, 1));
+            assert_eq!(singly.nth_after(1, 9), Some(1));
+            assert_eq!(singly.nth_after(0, 1), None);
+
+            // with four cups, the three picked up go straight back after the current cup
+            let mut ring = Ring::new(vec!(2, 1, 3, 4).into_iter()).unwrap();
+            let mut singly = SinglyLinkedRing::new(vec!(2, 1, 3, 4).into_iter()).unwrap();
+            for _ in 0..8 {
+                crab_step(&mut ring).unwrap();
+                singly.crab_step().unwrap();
+                assert_eq!(singly.point, ring.point);
+                assert_eq!(singly.iter_from(singly.point).collect::<Vec<_>>(), ring.iter().cloned().collect::<Vec<_>>());
+            }
+
+            let mut small = SinglyLinkedRing::new(vec!(2, 1, 3).into_iter()).unwrap();
+            assert!(small.crab_step().is_err());
+            assert_eq!(small.successors, vec!(3, 1, 2));
+            assert!(crab_step(&mut Ring::new(vec!(2, 1, 3).into_iter()).unwrap()).is_err());
+        }
+
+        #[test]
+        fn snapshot_test() {
+            let mut ring = Ring::new(vec!(3, 8, 9, 1, 2, 5, 4, 6, 7).into_iter()).unwrap();
+            let mut singly = SinglyLinkedRing::new(vec!(3, 8, 9, 1, 2, 5, 4, 6, 7).into_iter()).unwrap();
+            for _ in 0..5 {
+                crab_step(&mut ring).unwrap();
+                singly.crab_step().unwrap();
+            }
+            // the two rings take the same snapshots, so either can resume from the other's
+            assert_eq!(SinglyLinkedRing::snapshot(&singly, 5), ring.snapshot(5).unwrap());
+            let mut restored = SinglyLinkedRing::restore(&ring.snapshot(5).unwrap()).unwrap();
+            for _ in 5..10 {
+                restored.crab_step().unwrap();
+            }
+            assert_eq!(restored.iter_from(1).take(8).map(|label| label.to_string()).mk_string(""), "92658374");
+        }
+
+        #[test]
+        fn restore_invalid_test() {
+            let snapshot = RingSnapshot { steps: 0, point: 1, offset: 1, successors: vec!(2, 1, 1) };
+            assert_eq!(SinglyLinkedRing::restore(&snapshot).err(), Some("Successors do not form a cycle through 1".to_owned()));
+
+            let snapshot = RingSnapshot { steps: 0, point: 1, offset: 1, successors: vec!(2, 3, 2) };
+            assert_eq!(SinglyLinkedRing::restore(&snapshot).err(), Some("Successors do not form a cycle through 1".to_owned()));
+
+            let snapshot = RingSnapshot { steps: 0, point: 1, offset: 1, successors: vec!(2, 5, 1) };
+            assert_eq!(SinglyLinkedRing::restore(&snapshot).err(), Some("Label 5 is outside the snapshot".to_owned()));
+
+            let snapshot = RingSnapshot { steps: 0, point: 1, offset: 1, successors: vec!() };
+            assert!(SinglyLinkedRing::restore(&snapshot).is_err());
+        }
+    }
+
+    #[test]
+    fn from_contiguous_test() {
+        let seed = [3, 8, 9, 1, 2, 5, 4, 6, 7];
+        for upto in [0, 9, 10, 20].iter() {
+            let chained = Ring::new(seed.iter().copied().chain(10..=*upto)).unwrap();
+            let ring = Ring::from_contiguous(&seed, *upto).unwrap();
+            assert_eq!(ring.nodes, chained.nodes);
+            assert_eq!(ring.point, 3);
+
+            let singly = SinglyLinkedRing::from_contiguous(&seed, *upto).unwrap();
+            assert_eq!(singly.snapshot(0), ring.snapshot(0).unwrap());
+        }
+        // a seed need not start from 1
+        let ring = Ring::from_contiguous(&[4, 3], 6).unwrap();
+        assert_eq!(ring.iter().mk_string(""), "3564");
+
+        assert_eq!(Ring::from_contiguous(&[], 10).err(), Some("Ring must be non-empty".to_owned()));
+        assert_eq!(Ring::from_contiguous(&[3, 1, 3], 10).err(), Some("Duplicate entry 3 in interator".to_owned()));
+        assert_eq!(Ring::from_contiguous(&[1, 3], 10).err(), Some("Ring labels 1..=3 are not contiguous".to_owned()));
+        assert!(SinglyLinkedRing::from_contiguous(&[2, 4], 10).is_err());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn restore_invalid_test() {
+        let ring = Ring::new(vec!(1, 3, 4).into_iter()).unwrap();
+        assert!(ring.snapshot(0).is_err());
+
+        // 1 -> 2 -> 1, leaving 3 out of the cycle
+        let snapshot = RingSnapshot { steps: 0, point: 1, offset: 1, successors: vec!(2, 1, 1) };
+        assert!(Ring::restore(&snapshot).is_err());
+
+        let snapshot = RingSnapshot { steps: 0, point: 1, offset: 1, successors: vec!(2, 3, 4) };
+        assert_eq!(Ring::restore(&snapshot).err(), Some("Successors do not form a cycle through 1".to_owned()));
+
+        let snapshot = RingSnapshot { steps: 0, point: 1, offset: 1, successors: vec!(2, 5, 1) };
+        assert_eq!(Ring::restore(&snapshot).err(), Some("Label 5 is outside the snapshot".to_owned()));
+    }
+}
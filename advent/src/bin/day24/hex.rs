@@ -0,0 +1,262 @@
+// Paths on the hexagonal floor as sequences of directions, independent of where they lead.
+//
+// Every tile is reached from the reference tile by a*E + b*NE for unique integers a, b, since
+// NW = NE - E and SE = E - NE (and W, SW are the opposites of E, NE). These are the tile's axial
+// coordinates. Normalizing a path means finding them and spelling out a shortest path with the
+// same endpoint.
+
+use std::fmt::{Display, Formatter};
+
+use super::Tile;
+
+// The offsets (a, b) of the six neighbors, in the order of Direction
+pub const NEIGHBOR_OFFSETS: [(i32, i32); 6] = [(1, 0), (0, 1), (-1, 1), (-1, 0), (0, -1), (1, -1)];
+
+// A tile in axial coordinates: half the size of a Tile, and with simpler distances
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Axial {
+    pub a: i32,
+    pub b: i32
+}
+
+impl Axial {
+    pub fn new(a: i32, b: i32) -> Axial {
+        Axial { a, b }
+    }
+
+    pub fn neighbors(self) -> [Axial; 6] {
+        let mut neighbors = [self; 6];
+        for (neighbor, (da, db)) in neighbors.iter_mut().zip(NEIGHBOR_OFFSETS.iter()) {
+            neighbor.a += da;
+            neighbor.b += db;
+        }
+        neighbors
+    }
+
+    // The number of steps from the reference tile
+    pub fn distance(self) -> usize {
+        ((self.a.abs() + self.b.abs() + (self.a + self.b).abs()) / 2) as usize
+    }
+}
+
+impl From<Tile> for Axial {
+    fn from(tile: Tile) -> Axial {
+        let b = tile.y / 3;
+        Axial::new(((tile.x - b) / 2) as i32, b as i32)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Direction {
+    East,
+    Northeast,
+    Northwest,
+    West,
+    Southwest,
+    Southeast
+}
+
+impl Direction {
+    pub fn step(self, tile: &Tile) -> Tile {
+        match self {
+            Direction::East => tile.east(),
+            Direction::Northeast => tile.northeast(),
+            Direction::Northwest => tile.northwest(),
+            Direction::West => tile.west(),
+            Direction::Southwest => tile.southwest(),
+            Direction::Southeast => tile.southeast()
+        }
+    }
+
+    fn abbreviation(self) -> &'static str {
+        match self {
+            Direction::East => "e",
+            Direction::Northeast => "ne",
+            Direction::Northwest => "nw",
+            Direction::West => "w",
+            Direction::Southwest => "sw",
+            Direction::Southeast => "se"
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HexPath(pub Vec<Direction>);
+
+impl HexPath {
+    // Reads directions in the puzzle's alphabet {e, se, sw, w, nw, ne}, with no delimiters.
+    pub fn parse(line: &str) -> Option<HexPath> {
+        let mut directions = vec!();
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            let direction = match c {
+                'e' => Direction::East,
+                'w' => Direction::West,
+                'n' | 's' => match (c, chars.next()) {
+                    ('n', Some('e')) => Direction::Northeast,
+                    ('n', Some('w')) => Direction::Northwest,
+                    ('s', Some('e')) => Direction::Southeast,
+                    ('s', Some('w')) => Direction::Southwest,
+                    _ => return None
+                },
+                _ => return None
+            };
+            directions.push(direction);
+        }
+        Some(HexPath(directions))
+    }
+
+    // The tile this path leads to from the reference tile
+    pub fn end(&self) -> Tile {
+        self.0.iter().fold(Tile::new(0, 0), |tile, direction| direction.step(&tile))
+    }
+
+    // The coefficients (a, b) with end() = a*E + b*NE
+    fn coordinates(&self) -> (isize, isize) {
+        let Axial { a, b } = Axial::from(self.end());
+        (a as isize, b as isize)
+    }
+
+    // A shortest path to the same tile, with its directions in the canonical order
+    // E, NE, NW, W, SW, SE. Two paths lead to the same tile exactly when they normalize alike.
+    pub fn normalize(&self) -> HexPath {
+        let (a, b) = self.coordinates();
+        let mut directions = vec!();
+        let mut push = |direction, n: isize| directions.extend(std::iter::repeat(direction).take(n as usize));
+        if a.signum() * b.signum() >= 0 {
+            // both coefficients pull the same way (or one vanishes)
+            let (e, ne) = if a >= 0 && b >= 0 { (Direction::East, Direction::Northeast) } else { (Direction::West, Direction::Southwest) };
+            push(e, a.abs());
+            push(ne, b.abs());
+        } else {
+            // NW (or SE) cancels one E against one NE at the cost of a single step
+            let (diagonal, e, ne) = if a < 0 {
+                (Direction::Northwest, Direction::West, Direction::Northeast)
+            } else {
+                (Direction::Southeast, Direction::East, Direction::Southwest)
+            };
+            let shared = a.abs().min(b.abs());
+            push(diagonal, shared);
+            push(e, a.abs() - shared);
+            push(ne, b.abs() - shared);
+        }
+        directions.sort();
+        HexPath(directions)
+    }
+
+    // The number of steps between the reference tile and the end of this path
+    pub fn distance(&self) -> usize {
+        Axial::from(self.end()).distance()
+    }
+}
+
+impl Display for HexPath {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        for direction in &self.0 {
+            write!(f, "{}", direction.abbreviation())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod hex_spec {
+    use super::*;
+    use super::super::traverse;
+    use advent::random::Lcg;
+
+    #[test]
+    fn parse_test() {
+        assert_eq!(HexPath::parse("esenee"), Some(HexPath(vec!(
+            Direction::East, Direction::Southeast, Direction::Northeast, Direction::East
+        ))));
+        assert_eq!(HexPath::parse(""), Some(HexPath(vec!())));
+        assert_eq!(HexPath::parse("nee"), Some(HexPath(vec!(Direction::Northeast, Direction::East))));
+        assert_eq!(HexPath::parse("n"), None);
+        assert_eq!(HexPath::parse("ex"), None);
+    }
+
+    #[test]
+    fn normalize_test() {
+        // e/w cancel, and ne + se = e
+        assert_eq!(HexPath::parse("ew").unwrap().normalize(), HexPath(vec!()));
+        assert_eq!(HexPath::parse("nese").unwrap().normalize(), HexPath(vec!(Direction::East)));
+        assert_eq!(HexPath::parse("nwwswee").unwrap().normalize(), HexPath(vec!()));
+        assert_eq!(HexPath::parse("sesenwnenenewseeswwswswwnenewsewsw").unwrap().normalize().to_string(), "wswsw");
+        assert_eq!(HexPath::parse("swneewse").unwrap().normalize().to_string(), "se");
+    }
+
+    #[test]
+    fn axial_test() {
+        let all = [
+            Direction::East, Direction::Northeast, Direction::Northwest,
+            Direction::West, Direction::Southwest, Direction::Southeast
+        ];
+        let origin = Axial::new(0, 0);
+        for (direction, neighbor) in all.iter().zip(origin.neighbors().iter()) {
+            assert_eq!(Axial::from(direction.step(&Tile::new(0, 0))), *neighbor);
+            assert_eq!(neighbor.distance(), 1);
+        }
+        assert_eq!(Axial::from(Tile::new(5, -3)), Axial::new(3, -1));
+        assert_eq!(Axial::new(3, -1).distance(), 3);
+        assert_eq!(Axial::new(-2, -2).distance(), 4);
+        assert_eq!(std::mem::size_of::<Axial>() * 2, std::mem::size_of::<Tile>());
+    }
+
+    #[test]
+    fn distance_test() {
+        assert_eq!(HexPath::parse("").unwrap().distance(), 0);
+        assert_eq!(HexPath::parse("nwse").unwrap().distance(), 0);
+        assert_eq!(HexPath::parse("nenene").unwrap().distance(), 3);
+        assert_eq!(HexPath::parse("nenenww").unwrap().distance(), 3);
+        assert_eq!(HexPath::parse("esesw").unwrap().distance(), 2);
+    }
+
+    // Random paths, from a small linear congruential generator
+    fn sample_paths() -> Vec<HexPath> {
+        let all = [
+            Direction::East, Direction::Northeast, Direction::Northwest,
+            Direction::West, Direction::Southwest, Direction::Southeast
+        ];
+        let mut rng = Lcg::new(2020);
+        (0..500).map(|_| {
+            let len = rng.below(40);
+            HexPath((0..len).map(|_| all[rng.below(6) as usize]).collect())
+        }).collect()
+    }
+
+    #[test]
+    fn normalize_agrees_with_traverse() {
+        for path in sample_paths() {
+            let normal = path.normalize();
+            assert_eq!(traverse(&path.to_string()), Some(path.end()));
+            assert_eq!(traverse(&normal.to_string()), Some(path.end()), "{} => {}", path, normal);
+            assert_eq!(normal.0.len(), path.distance(), "{}", path);
+            assert!(normal.0.len() <= path.0.len());
+            assert_eq!(normal.normalize(), normal);
+        }
+    }
+
+    #[test]
+    fn distance_is_shortest() {
+        // breadth-first search from the reference tile first reaches each tile by a shortest path
+        let mut frontier = vec!(HexPath(vec!()));
+        let mut seen = std::collections::BTreeSet::new();
+        seen.insert(Tile::new(0, 0));
+        for steps in 1..=4 {
+            let mut next_frontier = vec!();
+            for path in &frontier {
+                for direction in [Direction::East, Direction::Northeast, Direction::Northwest, Direction::West, Direction::Southwest, Direction::Southeast].iter() {
+                    let mut extended = path.clone();
+                    extended.0.push(*direction);
+                    if seen.insert(extended.end()) {
+                        assert_eq!(extended.distance(), steps, "{}", extended);
+                        next_frontier.push(extended);
+                    }
+                }
+            }
+            frontier = next_frontier;
+        }
+        assert_eq!(seen.len(), 1 + 6 + 12 + 18 + 24);
+    }
+}
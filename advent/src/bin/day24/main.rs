@@ -0,0 +1,372 @@
+use std::io::prelude::*;
+use std::collections::{BTreeMap, BTreeSet};
+
+#[macro_use]
+extern crate lazy_static;
+use regex::Regex;
+
+use advent::cli;
+use advent::error::{AdventError, exit_on_error};
+use advent::parallel;
+use advent::table::Table;
+use advent::timing;
+
+mod hex;
+use hex::{Axial, HexPath};
+
+// Consider a tiling by regular hexagons whose sides are 2 units in length, and such that the origin
+// (0,0) is the center of one tile. Then each tile's center will be at (k * sqrt(3), m) where k,m are integers.
+// (Not all such points are centers of a tile, of course.)
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord)]
+struct Tile {
+    x: isize,
+    y: isize
+}
+
+impl Tile {
+    // Constructs the tile centered at (x*sqrt(3), y)
+    fn new(x: isize, y: isize) -> Tile {
+        Tile { x, y }
+    }
+
+    fn east(&self) -> Tile {
+        Tile::new(self.x + 2, self.y)
+    }
+
+    fn west(&self) -> Tile {
+        Tile::new(self.x - 2, self.y)
+    }
+
+    fn northeast(&self) -> Tile {
+        Tile::new(self.x + 1, self.y + 3)
+    }
+
+    fn northwest(&self) -> Tile {
+        Tile::new(self.x - 1, self.y + 3)
+    }
+
+    fn southeast(&self) -> Tile {
+        Tile::new(self.x + 1, self.y - 3)
+    }
+
+    fn southwest(&self) -> Tile {
+        Tile::new(self.x - 1, self.y - 3)
+    }
+}
+
+// Start from the reference tile (0,0) and read directions
+fn traverse(line: &str) -> Option<Tile> {
+    lazy_static! {
+        static ref EAST_PAT: Regex = Regex::new(r"^e([news]*)$").unwrap();
+        static ref WEST_PAT: Regex = Regex::new(r"^w([news]*)$").unwrap();
+        static ref NORTHEAST_PAT: Regex = Regex::new(r"^ne([news]*)$").unwrap();
+        static ref NORTHWEST_PAT: Regex = Regex::new(r"^nw([news]*)$").unwrap();
+        static ref SOUTHWEST_PAT: Regex = Regex::new(r"^sw([news]*)$").unwrap();
+        static ref SOUTHEAST_PAT: Regex = Regex::new(r"^se([news]*)$").unwrap();
+    }
+
+    let mut tail: &str = line;
+    let mut tile = Tile::new(0,0);
+
+    while !tail.is_empty() {
+        
+        if let Some(caps) = NORTHEAST_PAT.captures(tail) {
+            tile = tile.northeast();
+            for m in caps.get(1) {
+                tail = m.as_str();
+            }
+        } else if let Some(caps) = NORTHWEST_PAT.captures(tail) {
+            tile = tile.northwest();
+            for m in caps.get(1) {
+                tail = m.as_str();
+            }
+        } else if let Some(caps) = SOUTHEAST_PAT.captures(tail) {
+            tile = tile.southeast();
+            for m in caps.get(1) {
+                tail = m.as_str();
+            }
+        } else if let Some(caps) = SOUTHWEST_PAT.captures(tail) {
+            tile = tile.southwest();
+            for m in caps.get(1) {
+                tail = m.as_str()
+            }
+        } else if let Some(caps) = EAST_PAT.captures(tail) {
+            tile = tile.east();
+            for m in caps.get(1) {
+                tail = m.as_str();
+            }
+        } else if let Some(caps) = WEST_PAT.captures(tail) {
+            tile = tile.west();
+            for m in caps.get(1) {
+                tail= m.as_str();
+            }
+            // TODO: Can this by DRYed out?
+        } else {
+            eprintln!("Could not match text {}", tail);
+            return None
+        }
+    }
+
+    return Some(tile);
+}
+
+fn collect_keys<K: Copy + Ord, V, F>(m: &BTreeMap<K, V>, f: F) -> BTreeSet<K>
+    where F: Fn(&K, &V) -> bool {
+
+    m.iter().flat_map(|(k,v)| {
+        Some(*k).filter(|_| f(k, v))
+    }).collect()
+}
+
+// Whether `tile` will be black on the day after `black_tiles`
+fn next_state(tile: &Axial, black_tiles: &BTreeSet<Axial>) -> bool {
+    let borders = tile.neighbors().iter().filter(|n| black_tiles.contains(n)).count();
+    if black_tiles.contains(tile) {
+        borders == 1 || borders == 2
+    } else {
+        borders == 2
+    }
+}
+
+fn evolve(black_tiles: &BTreeSet<Axial>) -> BTreeSet<Axial> {
+    let mut visited: BTreeMap<Axial, bool> = BTreeMap::new();
+
+    for tile in black_tiles {
+        for neighbor in tile.neighbors().iter() { // decide if `neighbor` should be black or white in the next iteration
+            visited.entry(*neighbor).or_insert_with(|| next_state(neighbor, black_tiles));
+        }
+    }
+
+    collect_keys(&visited, |_,v| *v)
+}
+
+// As evolve, but on rayon's thread pool in a build with the parallel feature. Only the neighbors
+// of black tiles can be black tomorrow, and each one's fate depends only on today, so rayon may
+// split these candidates among its threads however it likes; collecting the survivors merges them.
+fn evolve_parallel(black_tiles: &BTreeSet<Axial>) -> BTreeSet<Axial> {
+    let mut candidates = BTreeSet::new();
+    for tile in black_tiles {
+        candidates.extend(tile.neighbors().iter());
+    }
+    let candidates: Vec<Axial> = candidates.into_iter().collect();
+    parallel::filter(&candidates, |tile| next_state(tile, black_tiles)).into_iter().collect()
+}
+
+// Maps each flip count to the number of tiles flipped exactly that many times
+fn flip_distribution(tiles: &BTreeMap<Tile, usize>) -> BTreeMap<usize, usize> {
+    let mut distribution = BTreeMap::new();
+    for flips in tiles.values() {
+        *distribution.entry(*flips).or_insert(0) += 1;
+    }
+    distribution
+}
+
+// One day's evolution: evolve or evolve_parallel
+type Step = fn(&BTreeSet<Axial>) -> BTreeSet<Axial>;
+
+// The black tiles on each day evolved so far, starting from day 0
+struct Floor {
+    states: Vec<BTreeSet<Axial>>,
+    step: Step
+}
+
+impl Floor {
+    fn new(black_tiles: BTreeSet<Axial>, step: Step) -> Floor {
+        Floor { states: vec!(black_tiles), step }
+    }
+
+    // Evolves (and remembers) any days up to `day` not already seen
+    fn state_at(&mut self, day: usize) -> &BTreeSet<Axial> {
+        while self.states.len() <= day {
+            let next = (self.step)(&self.states[self.states.len() - 1]);
+            self.states.push(next);
+        }
+        &self.states[day]
+    }
+
+    // The black tile counts for days 0 through `days`, so `days + 1` of them
+    fn counts(&mut self, days: usize) -> Vec<usize> {
+        self.state_at(days);
+        self.states[..=days].iter().map(|black_tiles| black_tiles.len()).collect()
+    }
+}
+
+fn run() -> Result<(), AdventError> {
+    let step: Step = if cli::has_flag("--parallel") {
+        if !parallel::ENABLED {
+            return Err(AdventError::input("--parallel needs a build with the parallel feature"))
+        }
+        evolve_parallel
+    } else {
+        evolve
+    };
+
+    let stdin = std::io::stdin();
+    let mut tiles: BTreeMap<Tile, usize> = BTreeMap::new();
+
+    let lines: Vec<String> = stdin.lock().lines().flatten().collect();
+    for line in &lines {
+        let tile = traverse(line).ok_or_else(|| AdventError::input(format!("unrecognized directions: {}", line)))?;
+        *tiles.entry(tile).or_insert(0) += 1;
+    }
+
+    println!("{} distinct tiles parsed.", tiles.len());
+
+    let black_tiles: BTreeSet<Axial> = collect_keys(&tiles, |_,v| v % 2 == 1).into_iter().map(Axial::from).collect();
+
+    println!("{} tiles are black on day 0", black_tiles.len());
+
+    // --days queries are answered from the same floor, so days up to 100 are not evolved again
+    let mut floor = Floor::new(black_tiles, step);
+    let counts = timing::time("Part 2", || floor.counts(100));
+    println!("After 100 evolutions, {} tiles are black.", counts[100]);
+
+    if let Some(days) = cli::option_value("--days") {
+        let days = days.split(',').map(|day| day.trim().parse::<usize>())
+            .collect::<Result<Vec<usize>, _>>()
+            .map_err(|_| AdventError::input(format!("expected a comma-separated list of days, got {}", days)))?;
+        for day in days {
+            println!("On day {}, {} tiles are black.", day, floor.state_at(day).len());
+        }
+    }
+
+    if cli::has_flag("--distances") {
+        let mut distances = Table::new(&["Shortest path", "Distance"]);
+        for line in &lines {
+            if let Some(path) = HexPath::parse(line) {
+                distances.add_row(vec!(path.normalize().to_string(), path.distance().to_string()));
+            }
+        }
+        print!("{}", distances);
+    }
+
+    if cli::has_flag("--stats") {
+        let mut flips = Table::new(&["Flips", "Tiles"]);
+        for (n, count) in flip_distribution(&tiles) {
+            flips.add_row(vec!(n.to_string(), count.to_string()));
+        }
+        print!("{}", flips);
+
+        let mut series = Table::new(&["Day", "Black tiles"]);
+        for (day, count) in counts.iter().enumerate() {
+            series.add_row(vec!(day.to_string(), count.to_string()));
+        }
+        print!("{}", series);
+    }
+    Ok(())
+}
+
+fn main() {
+    exit_on_error(run())
+}
+
+#[cfg(test)]
+mod day24_spec {
+    use super::*;
+
+    #[test]
+    fn traverse_test() {
+        let line = "esenee";
+        let tile = traverse(line).unwrap();
+        assert_eq!(tile, Tile::new(6, 0));
+
+        let line = "nwwswee";
+        let tile = traverse(line).unwrap();
+        assert_eq!(tile, Tile::new(0, 0));
+    }
+
+    // The black tiles on day 0 of the example
+    fn sample_black_tiles() -> BTreeSet<Axial> {
+        let lines = vec!(
+            "sesenwnenenewseeswwswswwnenewsewsw",
+            "neeenesenwnwwswnenewnwwsewnenwseswesw",
+            "seswneswswsenwwnwse",
+            "nwnwneseeswswnenewneswwnewseswneseene",
+            "swweswneswnenwsewnwneneseenw",
+            "eesenwseswswnenwswnwnwsewwnwsene",
+            "sewnenenenesenwsewnenwwwse",
+            "wenwwweseeeweswwwnwwe",
+            "wsweesenenewnwwnwsenewsenwwsesesenwne",
+            "neeswseenwwswnwswswnw",
+            "nenwswwsewswnenenewsenwsenwnesesenew",
+            "enewnwewneswsewnwswenweswnenwsenwsw",
+            "sweneswneswneneenwnewenewwneswswnese",
+            "swwesenesewenwneswnwwneseswwne",
+            "enesenwswwswneneswsenwnewswseenwsese",
+            "wnwnesenesenenwwnenwsewesewsesesew",
+            "nenewswnwewswnenesenwnesewesw",
+            "eneswnwswnwsenenwnwnwwseeswneewsenese",
+            "neswnwewnwnwseenwseesewsenwsweewe",
+            "wseweeenwnesenwwwswnew"
+        );
+        lines.iter().fold(BTreeSet::new(), |mut acc, line| {
+            let tile = Axial::from(traverse(line).unwrap());
+            if acc.contains(&tile) {
+                acc.remove(&tile);
+            } else {
+                acc.insert(tile);
+            }
+            acc
+        })
+    }
+
+    #[test]
+    fn evolve_test() {
+        let black_tiles_0 = sample_black_tiles();
+        assert_eq!(black_tiles_0.len(), 10);
+
+        let black_tiles_1 = evolve(&black_tiles_0);
+        assert_eq!(black_tiles_1.len(), 15);
+
+        let black_tiles_2 = evolve(&black_tiles_1);
+        assert_eq!(black_tiles_2.len(), 12);
+
+        let counts = Floor::new(black_tiles_0.clone(), evolve).counts(100);
+        assert_eq!(counts.len(), 101);
+        // the day-by-day counts in the puzzle statement
+        assert_eq!(&counts[..11], &[10, 15, 12, 25, 14, 23, 28, 41, 37, 49, 37]);
+        assert_eq!(&counts[20..=100].iter().step_by(10).cloned().collect::<Vec<_>>(),
+            &[132, 259, 406, 566, 788, 1106, 1373, 1844, 2208]);
+        assert_eq!(Floor::new(black_tiles_0.clone(), evolve).counts(0), vec!(10));
+
+        let mut floor = Floor::new(black_tiles_0, evolve);
+        assert_eq!(floor.state_at(2), &black_tiles_2);
+        assert_eq!(floor.states.len(), 3);
+        // earlier days are remembered rather than evolved again
+        assert_eq!(floor.state_at(1), &black_tiles_1);
+        assert_eq!(floor.states.len(), 3);
+        assert_eq!(floor.state_at(100).len(), 2208);
+        assert_eq!(floor.counts(100), counts);
+    }
+
+    #[test]
+    fn evolve_parallel_test() {
+        let black_tiles_0 = sample_black_tiles();
+        let mut sequential = Floor::new(black_tiles_0.clone(), evolve);
+        let mut parallel = Floor::new(black_tiles_0, evolve_parallel);
+        sequential.state_at(100);
+        parallel.state_at(100);
+        // every day's tiles, not just their number, agree
+        for (day, (s, p)) in sequential.states.iter().zip(parallel.states.iter()).enumerate() {
+            assert_eq!(s, p, "day {}", day);
+        }
+        assert_eq!(parallel.states.len(), 101);
+        assert_eq!(parallel.states[100].len(), 2208);
+
+        assert!(evolve_parallel(&BTreeSet::new()).is_empty());
+        // a lone black tile turns white, and nothing is born
+        assert!(evolve_parallel(&vec!(Axial::new(4, -7)).into_iter().collect()).is_empty());
+    }
+
+    #[test]
+    fn flip_distribution_test() {
+        let mut tiles = BTreeMap::new();
+        for line in &["esew", "nwwswee", "e", "nwwswee", "ee", "esew", "nwwswee"] {
+            *tiles.entry(traverse(line).unwrap()).or_insert(0) += 1;
+        }
+        let distribution = flip_distribution(&tiles);
+        let expected: BTreeMap<usize, usize> = vec!((1, 2), (2, 1), (3, 1)).into_iter().collect();
+        assert_eq!(distribution, expected);
+    }
+}
\ No newline at end of file
@@ -1,6 +1,10 @@
 use std::io::prelude::*;
+use std::collections::BTreeMap;
 
-use mod_exp::mod_exp;
+use advent::cli;
+use advent::error::{AdventError, exit_on_error};
+use advent::numbers::mod_pow;
+use advent::timing;
 
 const Q: u64 = 2020_1227;
 
@@ -29,28 +33,112 @@ const fn log_q(b: u64, x: u64) -> Option<u64> {
     None
 }
 
-fn main() {
-    let stdin = std::io::stdin();
-    let mut stdin_lines = stdin.lock().lines();
+// Loop sizes found in earlier runs, by public key, so that they need not be brute-forced again
+#[derive(Debug, Default, PartialEq, Eq)]
+struct LoopSizeCache(BTreeMap<u64, u64>);
+
+impl LoopSizeCache {
+    // A missing file is an empty cache
+    fn load(path: &str) -> Result<LoopSizeCache, AdventError> {
+        match std::fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str(&json).map(LoopSizeCache)
+                .map_err(|e| AdventError::input(format!("malformed loop size cache {}: {}", path, e))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(LoopSizeCache::default()),
+            Err(e) => Err(e.into())
+        }
+    }
+
+    fn save(&self, path: &str) -> Result<(), AdventError> {
+        std::fs::write(path, serde_json::to_string_pretty(&self.0).unwrap())?;
+        Ok(())
+    }
+
+    // The loop size transforming the subject number into `public_key`. A cached value is checked
+    // before it is trusted, and replaced if wrong.
+    fn loop_size(&mut self, public_key: u64) -> Option<u64> {
+        match self.0.get(&public_key) {
+            Some(n) if mod_pow(Handshake::SUBJECT, *n, Q) == public_key => Some(*n),
+            _ => {
+                let n = log_q(Handshake::SUBJECT, public_key)?;
+                self.0.insert(public_key, n);
+                Some(n)
+            }
+        }
+    }
+}
 
-    let public_key_1: u64 = stdin_lines.next().and_then(|result| {
-        result.ok().and_then(|line| u64::from_str_radix(&line, 10).ok())
-    }).unwrap();
-    let public_key_2: u64 = stdin_lines.next().and_then(|result| {
-        result.ok().and_then(|line| u64::from_str_radix(&line, 10).ok())
-    }).unwrap();
+#[derive(Debug, PartialEq, Eq)]
+enum HandshakeError {
+    // No loop size transforms the subject number 7 into this public key
+    NoLoopSize { public_key: u64 },
+    // The card and the door derived different encryption keys
+    KeyMismatch { card: u64, door: u64 }
+}
 
-    println!("Card public key: {}\nDoor public key: {}", public_key_1, public_key_2);
+#[derive(Debug, PartialEq, Eq)]
+struct Handshake {
+    card_public_key: u64,
+    door_public_key: u64,
+    card_loop_size: u64,
+    door_loop_size: u64,
+    encryption_key: u64
+}
 
-    let loop_size_1: u64 = log_q(7, public_key_1).unwrap();
-    let loop_size_2: u64 = log_q(7, public_key_2).unwrap();
+impl Handshake {
+    const SUBJECT: u64 = 7;
 
-    println!("Card secret key: {}\nDoor secret key: {}", loop_size_1, loop_size_2);
+    // Recovers both loop sizes from the public keys, and checks that each party, transforming
+    // the other's public key with its own loop size, arrives at the same encryption key.
+    fn new(card_public_key: u64, door_public_key: u64) -> Result<Handshake, HandshakeError> {
+        Handshake::with_cache(card_public_key, door_public_key, &mut LoopSizeCache::default())
+    }
 
-    let handshake_1: u64 = mod_exp(public_key_1, loop_size_2, Q);
-    let handshake_2: u64 = mod_exp(public_key_2, loop_size_1, Q);
+    // As new, looking up the loop sizes in `cache` and adding any it lacks
+    fn with_cache(card_public_key: u64, door_public_key: u64, cache: &mut LoopSizeCache) -> Result<Handshake, HandshakeError> {
+        let card_loop_size = cache.loop_size(card_public_key)
+            .ok_or(HandshakeError::NoLoopSize { public_key: card_public_key })?;
+        let door_loop_size = cache.loop_size(door_public_key)
+            .ok_or(HandshakeError::NoLoopSize { public_key: door_public_key })?;
+
+        let card = mod_pow(door_public_key, card_loop_size, Q);
+        let door = mod_pow(card_public_key, door_loop_size, Q);
+        if card != door {
+            return Err(HandshakeError::KeyMismatch { card, door })
+        }
 
-    println!("Handshake 1: {}\nHandshake 2: {}", handshake_1, handshake_2);
+        Ok(Handshake { card_public_key, door_public_key, card_loop_size, door_loop_size, encryption_key: card })
+    }
+}
+
+fn run() -> Result<(), AdventError> {
+    let stdin = std::io::stdin();
+    let mut stdin_lines = stdin.lock().lines();
+
+    let public_key_1: u64 = stdin_lines.next().transpose()?.and_then(|line| u64::from_str_radix(&line, 10).ok())
+        .ok_or_else(|| AdventError::input("expected the card's public key on the first line"))?;
+    let public_key_2: u64 = stdin_lines.next().transpose()?.and_then(|line| u64::from_str_radix(&line, 10).ok())
+        .ok_or_else(|| AdventError::input("expected the door's public key on the second line"))?;
+
+    let handshake = match cli::option_value("--cache") {
+        Some(path) => {
+            let mut cache = LoopSizeCache::load(&path)?;
+            let handshake = timing::time("Handshake", || Handshake::with_cache(public_key_1, public_key_2, &mut cache));
+            cache.save(&path)?;
+            handshake
+        },
+        None => timing::time("Handshake", || Handshake::new(public_key_1, public_key_2))
+    }.map_err(|e| AdventError::no_solution(format!("handshake failed: {:?}", e)))?;
+
+    if cli::has_flag("--verbose") {
+        println!("Card public key: {}\nDoor public key: {}", handshake.card_public_key, handshake.door_public_key);
+        println!("Card loop size: {}\nDoor loop size: {}", handshake.card_loop_size, handshake.door_loop_size);
+    }
+    println!("Encryption key: {}", handshake.encryption_key);
+    Ok(())
+}
+
+fn main() {
+    exit_on_error(run())
 }
 
 #[cfg(test)]
@@ -65,4 +153,45 @@ mod day25_spec {
         let k = 17807724;
         assert_eq!(log_q(7, k), Some(11));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn handshake_test() {
+        let handshake = Handshake::new(5764801, 17807724).unwrap();
+        assert_eq!(handshake.card_loop_size, 8);
+        assert_eq!(handshake.door_loop_size, 11);
+        assert_eq!(handshake.encryption_key, 14897079);
+
+        // 0 is not a power of 7 mod Q
+        assert_eq!(Handshake::new(5764801, 0), Err(HandshakeError::NoLoopSize { public_key: 0 }));
+    }
+
+    #[test]
+    fn loop_size_cache_test() {
+        let mut cache = LoopSizeCache::default();
+        let handshake = Handshake::with_cache(5764801, 17807724, &mut cache).unwrap();
+        assert_eq!(handshake.encryption_key, 14897079);
+        assert_eq!(cache.0.get(&5764801), Some(&8));
+        assert_eq!(cache.0.get(&17807724), Some(&11));
+
+        // a wrong entry is recomputed rather than trusted
+        cache.0.insert(5764801, 9);
+        assert_eq!(Handshake::with_cache(5764801, 17807724, &mut cache), Ok(handshake));
+        assert_eq!(cache.0.get(&5764801), Some(&8));
+    }
+
+    #[test]
+    fn loop_size_cache_file_test() {
+        let path = std::env::temp_dir().join(format!("day25_cache_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        assert_eq!(LoopSizeCache::load(path).unwrap(), LoopSizeCache::default());
+
+        let mut cache = LoopSizeCache::default();
+        cache.loop_size(5764801);
+        cache.save(path).unwrap();
+        assert_eq!(LoopSizeCache::load(path).unwrap(), cache);
+
+        std::fs::write(path, "[1, 2]").unwrap();
+        assert!(LoopSizeCache::load(path).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,23 @@
+// Prints the puzzle input for a day: `fetch 7 > input/day07.txt`. The session cookie is read from
+// the file given by --session, or else from the AOC_SESSION environment variable.
+// Needs the `net` feature: `cargo run --features net --bin fetch -- 7`.
+
+use advent::cli;
+use advent::error::{AdventError, exit_on_error};
+use advent::fetch;
+
+fn run() -> Result<(), AdventError> {
+    let day = std::env::args().nth(1).and_then(|arg| arg.parse::<u32>().ok())
+        .ok_or_else(|| AdventError::input("expected a day as the first argument"))?;
+    let session = match cli::option_value("--session") {
+        Some(path) => std::fs::read_to_string(path)?,
+        None => std::env::var("AOC_SESSION")
+            .map_err(|_| AdventError::input("expected a session cookie in AOC_SESSION, or a file containing one after --session"))?
+    };
+    print!("{}", fetch::input(day, &session)?);
+    Ok(())
+}
+
+fn main() {
+    exit_on_error(run())
+}
@@ -1,5 +1,7 @@
 use std::boxed::Box;
+use std::fmt::{Debug, Formatter};
 
+/// Two bitsets are equal when the same bits are set, whatever their sizes.
 #[derive(Clone)]
 pub struct BitSet {
     n: usize,
@@ -68,6 +70,54 @@ impl BitSet {
         }
         return None
     }
+
+    /// The number of set bits.
+    pub fn count(&self) -> usize {
+        self.bytes.iter().map(|byte| byte.count_ones() as usize).sum()
+    }
+
+    /// The set indices, in increasing order.
+    pub fn iter(&self) -> impl Iterator<Item=usize> + '_ {
+        self.bytes.iter().enumerate().filter(|(_, &byte)| byte > 0).flat_map(|(byte_idx, &byte)| {
+            (0..8).filter(move |bit_idx| byte & (0x80 >> bit_idx) > 0).map(move |bit_idx| 8*byte_idx + bit_idx)
+        })
+    }
+
+    /// The bitset of the same size with bit `i` set exactly when bit `n - 1 - i` is set in this one.
+    pub fn reversed(&self) -> BitSet {
+        let mut bytes: Vec<u8> = self.bytes.iter().rev().map(|byte| byte.reverse_bits()).collect();
+        // reversing the bytes puts bit i at n - 1 - i + pad, where pad is the number of unused trailing bits
+        let pad = bytes.len() * 8 - self.n;
+        if pad > 0 {
+            for byte_idx in 0..bytes.len() {
+                let next = bytes.get(byte_idx + 1).map_or(0, |byte| *byte);
+                bytes[byte_idx] = (bytes[byte_idx] << pad) | (next >> (8 - pad));
+            }
+        }
+        BitSet { n: self.n, bytes: bytes.into_boxed_slice() }
+    }
+
+    /// Unsets every bit which is not also set in `other`; bits beyond the end of `other` are unset.
+    pub fn intersect_with(&mut self, other: &BitSet) {
+        for (byte_idx, byte) in self.bytes.iter_mut().enumerate() {
+            // bits past the end of `other` are never set in its last byte
+            *byte &= other.bytes.get(byte_idx).map_or(0, |other_byte| *other_byte);
+        }
+    }
+}
+
+impl PartialEq for BitSet {
+    fn eq(&self, other: &BitSet) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl Eq for BitSet {}
+
+impl Debug for BitSet {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
 }
 
 #[cfg(test)]
@@ -170,4 +220,60 @@ mod bitset_spec {
         bitset.unset(1);
         assert_eq!(bitset.min(), Some(13));
     }
+
+    fn bitset_of(n: usize, bits: &[usize]) -> BitSet {
+        let mut bitset = BitSet::new(n);
+        for bit in bits {
+            bitset.set(*bit);
+        }
+        bitset
+    }
+
+    #[test]
+    fn iter_test() {
+        assert_eq!(BitSet::new(20).iter().count(), 0);
+        assert_eq!(bitset_of(20, &[0, 7, 8, 19]).iter().collect::<Vec<_>>(), vec!(0, 7, 8, 19));
+    }
+
+    #[test]
+    fn count_test() {
+        assert_eq!(BitSet::new(0).count(), 0);
+        assert_eq!(bitset_of(20, &[0, 7, 8, 19]).count(), 4);
+        assert_eq!(bitset_of(9, &[8]).count(), 1);
+    }
+
+    #[test]
+    fn eq_test() {
+        assert_eq!(bitset_of(12, &[1, 9]), bitset_of(30, &[1, 9]));
+        assert_ne!(bitset_of(12, &[1, 9]), bitset_of(12, &[1]));
+        assert_eq!(BitSet::new(3), BitSet::new(0));
+        assert_eq!(format!("{:?}", bitset_of(20, &[0, 7, 19])), "{0, 7, 19}");
+    }
+
+    #[test]
+    fn reversed_test() {
+        for n in 1..=24 {
+            let bits: Vec<usize> = (0..n).filter(|bit| bit % 3 == 0 || bit % 5 == 1).collect();
+            let reversed = bitset_of(n, &bits).reversed();
+            let mut expected: Vec<usize> = bits.iter().map(|bit| n - 1 - bit).collect();
+            expected.sort();
+            assert_eq!(reversed.iter().collect::<Vec<_>>(), expected, "n = {}", n);
+            assert_eq!(reversed.n, n);
+        }
+    }
+
+    #[test]
+    fn intersect_with_test() {
+        let mut bitset = bitset_of(12, &[1, 2, 9, 10, 11]);
+        bitset.intersect_with(&bitset_of(16, &[2, 3, 9, 15]));
+        assert_eq!(bitset.iter().collect::<Vec<_>>(), vec!(2, 9));
+
+        let mut bitset = bitset_of(12, &[1, 2, 9, 10, 11]);
+        bitset.intersect_with(&bitset_of(10, &[1, 9]));
+        assert_eq!(bitset.iter().collect::<Vec<_>>(), vec!(1, 9));
+
+        let mut bitset = bitset_of(12, &[1, 9, 10]);
+        bitset.intersect_with(&bitset_of(10, &[1, 9, 10]));
+        assert_eq!(bitset.iter().collect::<Vec<_>>(), vec!(1, 9));
+    }
 }
\ No newline at end of file
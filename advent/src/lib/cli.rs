@@ -0,0 +1,41 @@
+// Tiny helpers for reading command-line switches; the binaries take at most a handful
+// of options, so a full argument parser would be overkill.
+
+/// True if `name` (e.g. "--stats") appears anywhere on the command line.
+pub fn has_flag(name: &str) -> bool {
+    std::env::args().skip(1).any(|arg| arg == name)
+}
+
+/// The argument following `name`, if present. Also accepts `name=value`.
+pub fn option_value(name: &str) -> Option<String> {
+    find_value(std::env::args().skip(1), name)
+}
+
+fn find_value<J>(mut args: J, name: &str) -> Option<String> where J: Iterator<Item=String> {
+    let prefix = format!("{}=", name);
+    while let Some(arg) = args.next() {
+        if arg == name {
+            return args.next()
+        } else if let Some(value) = arg.strip_prefix(&prefix) {
+            return Some(value.to_owned())
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod cli_spec {
+    use super::*;
+
+    fn args(xs: &[&str]) -> impl Iterator<Item=String> {
+        xs.iter().map(|x| x.to_string()).collect::<Vec<_>>().into_iter()
+    }
+
+    #[test]
+    fn find_value_test() {
+        assert_eq!(find_value(args(&["--k", "3"]), "--k"), Some("3".to_owned()));
+        assert_eq!(find_value(args(&["--stats", "--k=3"]), "--k"), Some("3".to_owned()));
+        assert_eq!(find_value(args(&["--k"]), "--k"), None);
+        assert_eq!(find_value(args(&["--kk", "3"]), "--k"), None);
+    }
+}
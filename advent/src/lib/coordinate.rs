@@ -1,5 +1,7 @@
 // Utility for dealing with two-dimensional coordinate systems
 
+use crate::direction::Dir4;
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub struct XY {
     pub x: usize,
@@ -12,11 +14,13 @@ impl XY {
         XY { x, y}
     }
 
+    /// The cell one step away in direction `dir`, unless that is north of row 0 or west of column 0.
+    pub fn step(&self, dir: Dir4) -> Option<XY> {
+        dir.step((self.x, self.y)).map(|(x, y)| XY { x, y })
+    }
+
     pub fn north(&self) -> Option<XY> {
-        match self.y {
-            0 => None,
-            _ => Some(XY { x: self.x, y: self.y - 1 })
-        }
+        self.step(Dir4::North)
     }
 
     pub fn south(&self) -> XY {
@@ -24,10 +28,7 @@ impl XY {
     }
 
     pub fn west(&self) -> Option<XY> {
-        match self.x {
-            0 => None,
-            _ => Some(XY { x: self.x - 1, y: self.y })
-        }
+        self.step(Dir4::West)
     }
 
     pub fn east(&self) -> XY {
@@ -75,6 +76,18 @@ mod coordinate_spec {
         assert_eq!(xy.west(), None);
     }
 
+    #[test]
+    fn step_spec() {
+        let xy: XY = XY { x: 5, y: 7 };
+        for dir in Dir4::ALL.iter() {
+            let back = xy.step(*dir).and_then(|next| next.step(dir.reverse()));
+            assert_eq!(back, Some(xy.clone()));
+        }
+        assert_eq!(xy.step(Dir4::South), Some(xy.south()));
+        assert_eq!(xy.step(Dir4::East), Some(xy.east()));
+        assert_eq!(XY { x: 0, y: 0 }.step(Dir4::North), None);
+    }
+
     #[test]
     fn east_spec() {
         let xy: XY = XY { x: 5, y: 7 };
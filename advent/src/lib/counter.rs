@@ -0,0 +1,109 @@
+use std::collections::BTreeMap;
+use std::iter::FromIterator;
+
+/// A tally of how many times each item has been seen.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Counter<T> {
+    counts: BTreeMap<T, usize>
+}
+
+impl<T: Ord> Counter<T> {
+    pub fn new() -> Counter<T> {
+        Counter { counts: BTreeMap::new() }
+    }
+
+    pub fn add(&mut self, item: T) {
+        self.add_n(item, 1);
+    }
+
+    pub fn add_n(&mut self, item: T, n: usize) {
+        *self.counts.entry(item).or_insert(0) += n;
+    }
+
+    /// The number of times `item` has been seen, possibly 0.
+    pub fn get(&self, item: &T) -> usize {
+        self.counts.get(item).copied().unwrap_or(0)
+    }
+
+    /// The distinct items seen, in their natural order.
+    pub fn keys(&self) -> impl Iterator<Item=&T> {
+        self.counts.keys()
+    }
+
+    /// Items with their counts, most common first. Equally common items appear in their natural order.
+    pub fn iter(&self) -> impl Iterator<Item=(&T, usize)> {
+        let mut entries: Vec<(&T, usize)> = self.counts.iter().map(|(item, count)| (item, *count)).collect();
+        entries.sort_by(|(s, m), (t, n)| n.cmp(m).then(s.cmp(t)));
+        entries.into_iter()
+    }
+
+    /// The `n` most common items, as by `iter`.
+    pub fn most_common(&self, n: usize) -> Vec<(&T, usize)> {
+        self.iter().take(n).collect()
+    }
+
+    /// The number of distinct items seen.
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    /// The number of items seen, counting repeats.
+    pub fn total(&self) -> usize {
+        self.counts.values().sum()
+    }
+}
+
+impl<T: Ord> Default for Counter<T> {
+    fn default() -> Counter<T> {
+        Counter::new()
+    }
+}
+
+impl<T: Ord> Extend<T> for Counter<T> {
+    fn extend<J: IntoIterator<Item=T>>(&mut self, items: J) {
+        for item in items {
+            self.add(item);
+        }
+    }
+}
+
+impl<T: Ord> FromIterator<T> for Counter<T> {
+    fn from_iter<J: IntoIterator<Item=T>>(items: J) -> Counter<T> {
+        let mut counter = Counter::new();
+        counter.extend(items);
+        counter
+    }
+}
+
+#[cfg(test)]
+mod counter_spec {
+    use super::*;
+
+    #[test]
+    fn add_test() {
+        let mut counter = Counter::new();
+        assert!(counter.is_empty());
+        counter.add("a");
+        counter.add("b");
+        counter.add("a");
+        counter.add_n("c", 3);
+        assert_eq!(counter.get(&"a"), 2);
+        assert_eq!(counter.get(&"c"), 3);
+        assert_eq!(counter.get(&"z"), 0);
+        assert_eq!(counter.len(), 3);
+        assert_eq!(counter.total(), 6);
+    }
+
+    #[test]
+    fn count_order_test() {
+        let counter: Counter<char> = "mississippi".chars().collect();
+        assert_eq!(counter.iter().collect::<Vec<_>>(), vec!((&'i', 4), (&'s', 4), (&'p', 2), (&'m', 1)));
+        assert_eq!(counter.most_common(1), vec!((&'i', 4)));
+        assert_eq!(counter.most_common(10).len(), 4);
+        assert_eq!(counter.keys().collect::<String>(), "imps");
+    }
+}
@@ -0,0 +1,223 @@
+// Day 6: customs declaration forms. Each line is one person's "yes" answers; groups are
+// separated by blank lines.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::Serialize;
+
+use crate::counter::Counter;
+use crate::input::{blocks, Span};
+
+/// One group's answers, a line per member, and the lines it was read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Group {
+    pub span: Span,
+    pub members: Vec<String>
+}
+
+impl Group {
+    /// The questions answered yes by ANY member.
+    pub fn any(&self) -> BTreeSet<char> {
+        self.members.iter().flat_map(|member| member.chars()).collect()
+    }
+
+    /// The questions answered yes by ALL members.
+    pub fn all(&self) -> BTreeSet<char> {
+        let mut members = self.members.iter().map(|member| member.chars().collect::<BTreeSet<char>>());
+        let first = members.next().unwrap_or_default();
+        members.fold(first, |all, qs| all.intersection(&qs).copied().collect())
+    }
+
+    /// As (any, all), for questions 'a'..='z' as bits of a u32. Fails on the first member
+    /// answering anything else.
+    pub fn masks(&self) -> Result<(u32, u32), InvalidAnswer> {
+        let mut masks = self.members.iter().enumerate()
+            .map(|(idx, member)| answer_mask(member, self.span.first + idx));
+        let first = masks.next().unwrap_or(Ok(0))?;
+        masks.try_fold((first, first), |(any, all), mask| mask.map(|mask| (any | mask, all & mask)))
+    }
+}
+
+/// The groups in the input. Any number of blank (or whitespace-only) lines may separate groups,
+/// precede the first or follow the last; no group is empty.
+pub fn parse_groups<J>(lines: J) -> impl Iterator<Item=Group> where J: IntoIterator<Item=String> {
+    blocks(lines).map(|block| Group { span: block.span, members: block.lines })
+}
+
+/// Returns the total, over all groups, of the number of questions answered yes by ANY
+/// group member and by ALL group members. Any char counts as a question.
+pub fn count_group_questions<J>(lines: &mut J) -> (usize, usize)
+where J: Iterator<Item=String> {
+    parse_groups(lines).fold((0, 0), |(t_any, t_all), group| {
+        (t_any + group.any().len(), t_all + group.all().len())
+    })
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct InvalidAnswer {
+    pub line_no: usize, // 1-based
+    pub c: char
+}
+
+/// The first character outside 'a'..='z' on each offending line.
+pub fn invalid_answers<'a, J>(lines: J) -> Vec<InvalidAnswer>
+where J: Iterator<Item=&'a String> {
+    lines.enumerate().flat_map(|(idx, line)| {
+        line.chars().find(|c| !c.is_ascii_lowercase()).map(|c| InvalidAnswer { line_no: idx + 1, c })
+    }).collect()
+}
+
+fn answer_mask(line: &str, line_no: usize) -> Result<u32, InvalidAnswer> {
+    line.chars().try_fold(0, |mask, c| {
+        if c.is_ascii_lowercase() {
+            Ok(mask | 1 << (c as u32 - 'a' as u32))
+        } else {
+            Err(InvalidAnswer { line_no, c })
+        }
+    })
+}
+
+/// As count_group_questions, but restricted to questions 'a'..='z', which lets each person's
+/// answers be a u32 bitmask. Fails on the first line containing any other character.
+pub fn count_group_questions_bitmask<J>(lines: &mut J) -> Result<(usize, usize), InvalidAnswer>
+where J: Iterator<Item=String> {
+    parse_groups(lines).try_fold((0, 0), |(t_any, t_all), group| {
+        let (any, all) = group.masks()?;
+        Ok((t_any + any.count_ones() as usize, t_all + all.count_ones() as usize))
+    })
+}
+
+/// How answers overlap across all groups.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct OverlapReport {
+    pub groups: usize,
+    /// The number of groups of each size
+    pub group_sizes: BTreeMap<usize, usize>,
+    /// The question answered yes by the most people overall; ties go to the earliest question.
+    pub most_common: Option<QuestionCount>,
+    /// For each question, the number of groups in which everyone answered it yes
+    pub unanimous: BTreeMap<char, usize>
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct QuestionCount {
+    pub question: char,
+    pub people: usize
+}
+
+/// Collects an OverlapReport. As with count_group_questions, any char counts as a question.
+pub fn overlap_report<J>(lines: J) -> OverlapReport
+where J: Iterator<Item=String> {
+    let mut sizes: Counter<usize> = Counter::new();
+    let mut answers: Counter<char> = Counter::new();
+    let mut unanimous: Counter<char> = Counter::new();
+
+    for group in parse_groups(lines) {
+        sizes.add(group.members.len());
+        for member in &group.members {
+            answers.extend(member.chars().collect::<BTreeSet<char>>());
+        }
+        unanimous.extend(group.all());
+    }
+
+    OverlapReport {
+        groups: sizes.total(),
+        group_sizes: sizes.keys().map(|size| (*size, sizes.get(size))).collect(),
+        most_common: answers.most_common(1).first().map(|(q, n)| QuestionCount { question: **q, people: *n }),
+        unanimous: unanimous.keys().map(|q| (*q, unanimous.get(q))).collect()
+    }
+}
+
+#[cfg(test)]
+mod customs_spec {
+    use super::*;
+
+    const TEST_INPUT: &str =
+    "abc\n\
+    \n\
+    a\n\
+    b\n\
+    c\n\
+    \n\
+    ab\n\
+    ac\n\
+    \n\
+    a\n\
+    a\n\
+    a\n\
+    a\n\
+    \n\
+    b\n";
+
+    fn lines(s: &str) -> impl Iterator<Item=String> + '_ {
+        s.lines().map(|s| s.to_owned())
+    }
+
+    #[test]
+    fn bitmask_count_test() {
+        assert_eq!(count_group_questions_bitmask(&mut lines(TEST_INPUT)), Ok((11, 6)));
+        assert_eq!(count_group_questions_bitmask(&mut lines("az\nza\n\n\nz")), Ok((3, 3)));
+        assert_eq!(count_group_questions_bitmask(&mut lines("")), Ok((0, 0)));
+        assert_eq!(count_group_questions_bitmask(&mut lines("ab\n\nbC")), Err(InvalidAnswer { line_no: 3, c: 'C' }));
+    }
+
+    #[test]
+    fn overlap_report_test() {
+        let report = overlap_report(lines(TEST_INPUT));
+        assert_eq!(report.groups, 5);
+        assert_eq!(report.group_sizes, vec!((1, 2), (2, 1), (3, 1), (4, 1)).into_iter().collect());
+        assert_eq!(report.most_common, Some(QuestionCount { question: 'a', people: 8 }));
+        assert_eq!(report.unanimous, vec!(('a', 3), ('b', 2), ('c', 1)).into_iter().collect());
+        assert_eq!(report.unanimous.values().sum::<usize>(), 6);
+
+        // blank lines between groups don't make empty groups, and repeats on a line count once
+        let report = overlap_report(lines("\n\nxx\n\n\nyx\nyz\n"));
+        assert_eq!(report.group_sizes, vec!((1, 1), (2, 1)).into_iter().collect());
+        assert_eq!(report.most_common, Some(QuestionCount { question: 'x', people: 2 }));
+        assert_eq!(report.unanimous, vec!(('x', 1), ('y', 1)).into_iter().collect());
+
+        let report = overlap_report(lines(""));
+        assert_eq!(report.groups, 0);
+        assert_eq!(report.most_common, None);
+        assert_eq!(serde_json::to_string(&report).unwrap(),
+            r#"{"groups":0,"group_sizes":{},"most_common":null,"unanimous":{}}"#);
+    }
+
+    #[test]
+    fn parse_groups_test() {
+        let groups: Vec<Group> = parse_groups(lines(TEST_INPUT)).collect();
+        assert_eq!(groups.len(), 5);
+        assert_eq!(groups[1], Group { span: Span { first: 3, last: 5 }, members: lines("a\nb\nc").collect() });
+        assert_eq!(groups[4].span, Span { first: 15, last: 15 });
+        assert_eq!(groups[2].any(), "abc".chars().collect());
+        assert_eq!(groups[2].all(), "a".chars().collect());
+        assert_eq!(groups[2].masks(), Ok((0b111, 0b1)));
+
+        let spans = |s: &str| parse_groups(lines(s)).map(|group| (group.span.first, group.span.last)).collect::<Vec<_>>();
+        // trailing blank lines
+        assert_eq!(spans("ab\nb\n\n\n"), vec!((1, 2)));
+        // consecutive blank lines, including whitespace-only ones
+        assert_eq!(spans("ab\n\n \n\nc\n\n\n\nd"), vec!((1, 1), (5, 5), (9, 9)));
+        // a final group without a trailing newline, and leading blank lines
+        assert_eq!(spans("\n\nab\nb"), vec!((3, 4)));
+        assert_eq!(spans(""), vec!());
+
+        for &(input, expected) in &[("ab\nb\n\n\n", (2, 1)), ("\n\nab\n\n\n\nb", (3, 3)), ("ab\nb", (2, 1))] {
+            assert_eq!(count_group_questions(&mut lines(input)), expected, "{:?}", input);
+            assert_eq!(count_group_questions_bitmask(&mut lines(input)), Ok(expected), "{:?}", input);
+            assert_eq!(overlap_report(lines(input)).unanimous.values().sum::<usize>(), expected.1, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn invalid_answers_test() {
+        let input: Vec<String> = lines("abc\n\na b\nxyz\né").collect();
+        assert_eq!(invalid_answers(input.iter()), vec!(
+            InvalidAnswer { line_no: 3, c: ' ' },
+            InvalidAnswer { line_no: 5, c: 'é' }
+        ));
+
+        // the general implementation still counts these as questions
+        assert_eq!(count_group_questions(&mut input.into_iter()), (3 + 7, 3));
+    }
+}
@@ -0,0 +1,151 @@
+// Compass directions on a grid of (x, y) cells, with x growing eastward and y southward, so that
+// north is the previous row.
+
+/// The four orthogonal directions, clockwise from north.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Dir4 {
+    North,
+    East,
+    South,
+    West
+}
+
+/// The eight orthogonal and diagonal directions, clockwise from north.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Dir8 {
+    North,
+    NorthEast,
+    East,
+    SouthEast,
+    South,
+    SouthWest,
+    West,
+    NorthWest
+}
+
+// The cell `delta` away from (x, y), unless it would have a negative coordinate
+fn offset((x, y): (usize, usize), (dx, dy): (isize, isize)) -> Option<(usize, usize)> {
+    Some((x.checked_add_signed(dx)?, y.checked_add_signed(dy)?))
+}
+
+impl Dir4 {
+    pub const ALL: [Dir4; 4] = [Dir4::North, Dir4::East, Dir4::South, Dir4::West];
+
+    /// The change (dx, dy) in moving one cell this way.
+    pub fn delta(self) -> (isize, isize) {
+        match self {
+            Dir4::North => (0, -1),
+            Dir4::East => (1, 0),
+            Dir4::South => (0, 1),
+            Dir4::West => (-1, 0)
+        }
+    }
+
+    /// This direction turned `quarter_turns` times clockwise, or counterclockwise if negative.
+    pub fn rotate(self, quarter_turns: isize) -> Dir4 {
+        Dir4::ALL[(self as isize + quarter_turns).rem_euclid(4) as usize]
+    }
+
+    pub fn reverse(self) -> Dir4 {
+        self.rotate(2)
+    }
+
+    /// The cell one step this way from (x, y), unless it would have a negative coordinate.
+    pub fn step(self, xy: (usize, usize)) -> Option<(usize, usize)> {
+        offset(xy, self.delta())
+    }
+
+    /// N, E, S or W.
+    pub fn letter(self) -> char {
+        match self {
+            Dir4::North => 'N',
+            Dir4::East => 'E',
+            Dir4::South => 'S',
+            Dir4::West => 'W'
+        }
+    }
+}
+
+impl Dir8 {
+    pub const ALL: [Dir8; 8] = [
+        Dir8::North, Dir8::NorthEast, Dir8::East, Dir8::SouthEast,
+        Dir8::South, Dir8::SouthWest, Dir8::West, Dir8::NorthWest
+    ];
+
+    /// The change (dx, dy) in moving one cell this way; diagonal moves change both coordinates.
+    pub fn delta(self) -> (isize, isize) {
+        match self {
+            Dir8::North => (0, -1),
+            Dir8::NorthEast => (1, -1),
+            Dir8::East => (1, 0),
+            Dir8::SouthEast => (1, 1),
+            Dir8::South => (0, 1),
+            Dir8::SouthWest => (-1, 1),
+            Dir8::West => (-1, 0),
+            Dir8::NorthWest => (-1, -1)
+        }
+    }
+
+    /// This direction turned `eighth_turns` times 45° clockwise, or counterclockwise if negative.
+    pub fn rotate(self, eighth_turns: isize) -> Dir8 {
+        Dir8::ALL[(self as isize + eighth_turns).rem_euclid(8) as usize]
+    }
+
+    pub fn reverse(self) -> Dir8 {
+        self.rotate(4)
+    }
+
+    /// The cell one step this way from (x, y), unless it would have a negative coordinate.
+    pub fn step(self, xy: (usize, usize)) -> Option<(usize, usize)> {
+        offset(xy, self.delta())
+    }
+}
+
+impl From<Dir4> for Dir8 {
+    fn from(dir: Dir4) -> Dir8 {
+        Dir8::ALL[2 * dir as usize]
+    }
+}
+
+#[cfg(test)]
+mod direction_spec {
+    use super::*;
+
+    #[test]
+    fn dir4_test() {
+        let deltas: Vec<(isize, isize)> = Dir4::ALL.iter().map(|dir| dir.delta()).collect();
+        assert_eq!(deltas, vec!((0, -1), (1, 0), (0, 1), (-1, 0)));
+        assert_eq!(Dir4::North.rotate(1), Dir4::East);
+        assert_eq!(Dir4::North.rotate(-1), Dir4::West);
+        assert_eq!(Dir4::West.rotate(7), Dir4::South);
+        for dir in Dir4::ALL.iter() {
+            let (dx, dy) = dir.delta();
+            assert_eq!(dir.reverse().delta(), (-dx, -dy));
+            assert_eq!(dir.rotate(4), *dir);
+        }
+        assert_eq!(Dir4::North.step((5, 0)), None);
+        assert_eq!(Dir4::West.step((5, 0)), Some((4, 0)));
+        assert_eq!(Dir4::ALL.iter().map(|dir| dir.letter()).collect::<String>(), "NESW");
+    }
+
+    #[test]
+    fn dir8_test() {
+        assert_eq!(Dir8::ALL.iter().map(|dir| dir.delta()).fold((0, 0), |(x, y), (dx, dy)| (x + dx, y + dy)), (0, 0));
+        assert_eq!(Dir8::North.rotate(1), Dir8::NorthEast);
+        assert_eq!(Dir8::North.rotate(-3), Dir8::SouthWest);
+        assert_eq!(Dir8::SouthEast.reverse(), Dir8::NorthWest);
+        for dir in Dir8::ALL.iter() {
+            let (dx, dy) = dir.delta();
+            assert_eq!(dir.reverse().delta(), (-dx, -dy));
+            // a quarter turn clockwise takes (dx, dy) to (-dy, dx)
+            assert_eq!(dir.rotate(2).delta(), (-dy, dx));
+        }
+        assert_eq!(Dir8::NorthWest.step((3, 0)), None);
+        assert_eq!(Dir8::SouthWest.step((3, 0)), Some((2, 1)));
+
+        for dir in Dir4::ALL.iter() {
+            assert_eq!(Dir8::from(*dir).delta(), dir.delta());
+            assert_eq!(Dir8::from(dir.rotate(1)), Dir8::from(*dir).rotate(2));
+        }
+    }
+}
@@ -0,0 +1,60 @@
+// A common error type for the binaries' top-level `run` functions, so that bad input is reported
+// with a message and a non-zero exit status rather than a panic and a backtrace.
+
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug)]
+pub enum AdventError {
+    /// The puzzle input is missing or malformed.
+    Input(String),
+    /// The input parsed, but the puzzle has no answer for it.
+    NoSolution(String),
+    Io(std::io::Error)
+}
+
+impl AdventError {
+    pub fn input<S: Into<String>>(msg: S) -> AdventError {
+        AdventError::Input(msg.into())
+    }
+
+    pub fn no_solution<S: Into<String>>(msg: S) -> AdventError {
+        AdventError::NoSolution(msg.into())
+    }
+}
+
+impl Display for AdventError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            AdventError::Input(msg) => write!(f, "Invalid input: {}", msg),
+            AdventError::NoSolution(msg) => write!(f, "No solution: {}", msg),
+            AdventError::Io(e) => write!(f, "I/O error: {}", e)
+        }
+    }
+}
+
+impl From<std::io::Error> for AdventError {
+    fn from(e: std::io::Error) -> AdventError {
+        AdventError::Io(e)
+    }
+}
+
+/// For use as the body of `main`: reports an error from `run` on stderr and exits with status 1.
+pub fn exit_on_error(result: Result<(), AdventError>) {
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1)
+    }
+}
+
+#[cfg(test)]
+mod error_spec {
+    use super::*;
+
+    #[test]
+    fn display_test() {
+        assert_eq!(AdventError::input("empty deck").to_string(), "Invalid input: empty deck");
+        assert_eq!(AdventError::no_solution(format!("no {} sum to {}", 2, 2020)).to_string(), "No solution: no 2 sum to 2020");
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "missing");
+        assert_eq!(AdventError::from(io).to_string(), "I/O error: missing");
+    }
+}
@@ -0,0 +1,39 @@
+// Downloads puzzle input from adventofcode.com. Inputs differ from user to user, so the site asks
+// for the session cookie of a logged-in user. Only built with the `net` feature.
+
+use crate::error::AdventError;
+
+pub const YEAR: u32 = 2020;
+
+/// The URL of the input for `day`.
+pub fn input_url(day: u32) -> String {
+    format!("https://adventofcode.com/{}/day/{}/input", YEAR, day)
+}
+
+/// The input for `day`, fetched with the given session cookie.
+pub fn input(day: u32, session: &str) -> Result<String, AdventError> {
+    if !(1..=25).contains(&day) {
+        return Err(AdventError::input(format!("there is no day {}; puzzles run from 1 to 25", day)))
+    }
+    let response = ureq::get(&input_url(day))
+        .set("Cookie", &format!("session={}", session.trim()))
+        .call()
+        .map_err(|e| match e {
+            ureq::Error::Status(status, _) => AdventError::input(format!(
+                "adventofcode.com refused the request for day {} with status {}; is the session cookie current?", day, status
+            )),
+            other => AdventError::Io(std::io::Error::other(other.to_string()))
+        })?;
+    Ok(response.into_string()?)
+}
+
+#[cfg(test)]
+mod fetch_spec {
+    use super::*;
+
+    #[test]
+    fn input_url_test() {
+        assert_eq!(input_url(7), "https://adventofcode.com/2020/day/7/input");
+        assert!(input(26, "").is_err());
+    }
+}
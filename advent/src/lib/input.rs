@@ -0,0 +1,104 @@
+// Adapters over the lines of puzzle input, for inputs made of blank-line separated blocks
+// (passports, customs groups, ...) whose errors should point back at the offending lines.
+
+use std::fmt::{Display, Formatter};
+
+/// An inclusive range of 1-based line numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Span {
+    pub first: usize,
+    pub last: usize
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        if self.first == self.last {
+            write!(f, "line {}", self.first)
+        } else {
+            write!(f, "lines {}-{}", self.first, self.last)
+        }
+    }
+}
+
+/// Lines paired with their 1-based line numbers.
+pub struct Numbered<J> {
+    lines: J,
+    line_no: usize
+}
+
+impl<J: Iterator<Item=String>> Iterator for Numbered<J> {
+    type Item = (usize, String);
+
+    fn next(&mut self) -> Option<(usize, String)> {
+        let line = self.lines.next()?;
+        self.line_no += 1;
+        Some((self.line_no, line))
+    }
+}
+
+pub fn numbered<J>(lines: J) -> Numbered<J::IntoIter> where J: IntoIterator<Item=String> {
+    Numbered { lines: lines.into_iter(), line_no: 0 }
+}
+
+/// A maximal run of non-blank lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub span: Span,
+    pub lines: Vec<String>
+}
+
+/// The blocks of the input, in order. Any number of blank (or whitespace-only) lines may
+/// separate blocks, precede the first or follow the last.
+pub struct Blocks<J> {
+    lines: Numbered<J>
+}
+
+impl<J: Iterator<Item=String>> Iterator for Blocks<J> {
+    type Item = Block;
+
+    fn next(&mut self) -> Option<Block> {
+        let (first, line) = self.lines.by_ref().find(|(_, line)| !line.trim().is_empty())?;
+        let mut block = Block { span: Span { first, last: first }, lines: vec!(line) };
+        for (line_no, line) in self.lines.by_ref() {
+            if line.trim().is_empty() {
+                break
+            }
+            block.span.last = line_no;
+            block.lines.push(line);
+        }
+        Some(block)
+    }
+}
+
+pub fn blocks<J>(lines: J) -> Blocks<J::IntoIter> where J: IntoIterator<Item=String> {
+    Blocks { lines: numbered(lines) }
+}
+
+#[cfg(test)]
+mod input_spec {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(|line| line.to_owned()).collect()
+    }
+
+    #[test]
+    fn numbered_test() {
+        let numbered: Vec<(usize, String)> = numbered(lines("a\n\nb")).collect();
+        assert_eq!(numbered, vec!((1, "a".to_owned()), (2, "".to_owned()), (3, "b".to_owned())));
+    }
+
+    #[test]
+    fn blocks_test() {
+        let found: Vec<Block> = blocks(lines("\nab\nc\n\n  \n\nd\n")).collect();
+        assert_eq!(found, vec!(
+            Block { span: Span { first: 2, last: 3 }, lines: lines("ab\nc") },
+            Block { span: Span { first: 7, last: 7 }, lines: lines("d") }
+        ));
+        assert_eq!(found[0].span.to_string(), "lines 2-3");
+        assert_eq!(found[1].span.to_string(), "line 7");
+
+        assert_eq!(blocks(lines("")).count(), 0);
+        assert_eq!(blocks(lines("\n\n")).count(), 0);
+    }
+}
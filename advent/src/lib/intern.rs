@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+/// A stand-in for an interned string. Symbols from the same `Interner` are equal exactly when
+/// their strings are, and order by when their strings were first interned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    /// The position of this symbol's string among those interned, from 0.
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    /// The symbol at position `index`, as when symbols are kept as indices into a bitset. It
+    /// belongs to whichever interner the index came from.
+    ///
+    /// Panics if `index` could not be the position of a symbol.
+    pub fn from_index(index: usize) -> Symbol {
+        Symbol(u32::try_from(index).expect("symbol index out of range"))
+    }
+}
+
+/// Hands out one `Symbol` per distinct string, keeping a single copy of each string.
+#[derive(Debug, Default)]
+pub struct Interner {
+    symbols: HashMap<Rc<str>, Symbol>,
+    names: Vec<Rc<str>>
+}
+
+impl Interner {
+    pub fn new() -> Interner {
+        Interner { symbols: HashMap::new(), names: vec!() }
+    }
+
+    /// The symbol for `name`, interning it if it is new.
+    ///
+    /// Panics if more than `u32::MAX` distinct strings are interned.
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(symbol) = self.symbols.get(name) {
+            return *symbol
+        }
+        let symbol = Symbol(u32::try_from(self.names.len()).expect("too many interned strings"));
+        let name: Rc<str> = Rc::from(name);
+        self.names.push(name.clone());
+        self.symbols.insert(name, symbol);
+        symbol
+    }
+
+    /// The symbol for `name`, if it has been interned.
+    pub fn get(&self, name: &str) -> Option<Symbol> {
+        self.symbols.get(name).copied()
+    }
+
+    /// The string behind `symbol`, which must come from this interner.
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        &self.names[symbol.index()]
+    }
+
+    /// The number of distinct strings interned.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod intern_spec {
+    use super::*;
+
+    #[test]
+    fn intern_test() {
+        let mut interner = Interner::new();
+        assert!(interner.is_empty());
+        let gold = interner.intern("shiny gold");
+        let red = interner.intern("light red");
+        assert_ne!(gold, red);
+        assert_eq!(interner.intern("shiny gold"), gold);
+        assert_eq!(interner.len(), 2);
+        // symbols order by first appearance, not alphabetically
+        assert!(gold < red);
+        assert_eq!((gold.index(), red.index()), (0, 1));
+
+        assert_eq!(interner.resolve(red), "light red");
+        assert_eq!(interner.get("light red"), Some(red));
+        assert_eq!(interner.get("dark olive"), None);
+        assert_eq!(interner.len(), 2);
+        assert_eq!(interner.intern(""), Symbol(2));
+        assert_eq!(Symbol::from_index(red.index()), red);
+    }
+}
@@ -1,3 +1,24 @@
 pub mod coordinate;
+pub mod direction;
 pub mod bitset;
 pub mod make_string;
+pub mod table;
+pub mod cli;
+pub mod customs;
+pub mod seats;
+pub mod timing;
+pub mod memo;
+pub mod error;
+pub mod search;
+pub mod counter;
+pub mod stats;
+pub mod numbers;
+pub mod tiles;
+pub mod intern;
+pub mod input;
+pub mod random;
+pub mod parallel;
+#[cfg(feature = "viz")]
+pub mod viz;
+#[cfg(feature = "net")]
+pub mod fetch;
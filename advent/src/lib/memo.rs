@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// A HashMap-backed cache of computed values, optionally bounded in size.
+///
+/// The compute closure passed to `get_or_compute` receives the memo itself, so that
+/// recursive computations can consult (and fill) the cache for their subproblems.
+pub struct Memo<K, V> {
+    values: HashMap<K, V>,
+    capacity: Option<usize>
+}
+
+impl<K: Hash + Eq, V: Clone> Memo<K, V> {
+    pub fn new() -> Memo<K, V> {
+        Memo { values: HashMap::new(), capacity: None }
+    }
+
+    /// A memo holding at most `capacity` values. Once full, further values are still
+    /// computed, but no longer stored.
+    pub fn bounded(capacity: usize) -> Memo<K, V> {
+        Memo { values: HashMap::new(), capacity: Some(capacity) }
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.values.get(key)
+    }
+
+    /// Stores `value` under `key`, unless the memo is full. Returns true if the value was stored.
+    pub fn insert(&mut self, key: K, value: V) -> bool {
+        match self.capacity {
+            Some(capacity) if self.values.len() >= capacity && !self.values.contains_key(&key) => false,
+            _ => {
+                self.values.insert(key, value);
+                true
+            }
+        }
+    }
+
+    /// The value stored under `key`, if any; otherwise computes it with `f`, stores it, and returns it.
+    pub fn get_or_compute<F>(&mut self, key: K, f: F) -> V where F: FnOnce(&mut Memo<K, V>) -> V {
+        if let Some(value) = self.values.get(&key) {
+            return value.clone()
+        }
+        let value = f(self);
+        self.insert(key, value.clone());
+        value
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+impl<K: Hash + Eq, V: Clone> Default for Memo<K, V> {
+    fn default() -> Memo<K, V> {
+        Memo::new()
+    }
+}
+
+#[cfg(test)]
+mod memo_spec {
+    use super::*;
+
+    fn fib(n: u64, memo: &mut Memo<u64, u64>) -> u64 {
+        memo.get_or_compute(n, |memo| {
+            if n < 2 {
+                n
+            } else {
+                fib(n - 1, memo) + fib(n - 2, memo)
+            }
+        })
+    }
+
+    #[test]
+    fn get_or_compute_test() {
+        let mut memo = Memo::new();
+        assert_eq!(fib(90, &mut memo), 2880067194370816120);
+        assert_eq!(memo.len(), 91);
+        assert_eq!(memo.get(&10), Some(&55));
+
+        let mut calls = 0;
+        assert_eq!(memo.get_or_compute(10, |_| { calls += 1; 0 }), 55);
+        assert_eq!(calls, 0);
+    }
+
+    #[test]
+    fn bounded_test() {
+        let mut memo = Memo::bounded(2);
+        assert!(memo.insert("a", 1));
+        assert!(memo.insert("b", 2));
+        assert!(!memo.insert("c", 3));
+        assert!(memo.insert("a", 4));
+        assert_eq!(memo.get(&"a"), Some(&4));
+        assert_eq!(memo.get(&"c"), None);
+
+        assert_eq!(memo.get_or_compute("d", |_| 5), 5);
+        assert_eq!(memo.len(), 2);
+
+        let mut memo = Memo::bounded(10);
+        assert_eq!(fib(30, &mut memo), 832040);
+        assert_eq!(memo.len(), 10);
+    }
+}
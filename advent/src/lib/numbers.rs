@@ -0,0 +1,127 @@
+/// Computes `base^exp mod modulus` by square-and-multiply. Every one of the 64 exponent bits
+/// costs the same squaring and multiplication, whether or not it is set, so the running time
+/// does not depend on the exponent. Intermediate products are taken in u128, so any u64
+/// modulus is safe. Panics if `modulus` is 0.
+pub fn mod_pow(base: u64, exp: u64, modulus: u64) -> u64 {
+    let m = modulus as u128;
+    let mut result = 1 % m;
+    let mut square = base as u128 % m;
+    for bit in 0..64 {
+        let product = result * square % m;
+        result = if (exp >> bit) & 1 == 1 { product } else { result };
+        square = square * square % m;
+    }
+    result as u64
+}
+
+/// The inverse of `a` modulo `modulus`, if they are coprime, by the extended Euclidean algorithm.
+pub fn mod_inverse(a: u64, modulus: u64) -> Option<u64> {
+    let (mut r0, mut r1) = (modulus as i128, (a % modulus.max(1)) as i128);
+    let (mut t0, mut t1) = (0i128, 1i128);
+    while r1 != 0 {
+        let q = r0 / r1;
+        let (r, t) = (r0 - q * r1, t0 - q * t1);
+        r0 = r1;
+        r1 = r;
+        t0 = t1;
+        t1 = t;
+    }
+    if r0 == 1 {
+        Some(t0.rem_euclid(modulus as i128) as u64)
+    } else {
+        None
+    }
+}
+
+/// The least non-negative x with x ≡ r (mod n) for every (n, r) in `constraints`, or None if some
+/// modulus is 0 or two of them share a factor. Computed in arbitrary precision, so that any number
+/// of moduli may be combined; needs the `bigint` feature.
+#[cfg(feature = "bigint")]
+pub fn chinese_remainder_big(constraints: &[(u64, u64)]) -> Option<num_bigint::BigUint> {
+    use num_bigint::BigUint;
+
+    let mut x = BigUint::from(0u32);
+    let mut product = BigUint::from(1u32);
+    for &(n, r) in constraints {
+        if n == 0 {
+            return None
+        }
+        // x + k * product satisfies this constraint when k ≡ (r - x) / product (mod n)
+        let x_mod_n = (&x % n).to_u64_digits().first().copied().unwrap_or(0);
+        let product_mod_n = (&product % n).to_u64_digits().first().copied().unwrap_or(0);
+        let difference = ((r % n) as u128 + n as u128 - x_mod_n as u128) % n as u128;
+        let k = difference * mod_inverse(product_mod_n, n)? as u128 % n as u128;
+        x += &product * BigUint::from(k);
+        product *= n;
+    }
+    Some(x)
+}
+
+#[cfg(test)]
+mod numbers_spec {
+    use super::*;
+    use crate::random::Lcg;
+
+    // Naive repeated multiplication, for small exponents
+    fn mod_pow_naive(base: u64, exp: u64, modulus: u64) -> u64 {
+        (0..exp).fold(1 % modulus as u128, |acc, _| acc * base as u128 % modulus as u128) as u64
+    }
+
+    #[test]
+    fn mod_pow_test() {
+        assert_eq!(mod_pow(7, 8, 2020_1227), 5764801);
+        assert_eq!(mod_pow(17807724, 8, 2020_1227), 14897079);
+        assert_eq!(mod_pow(5764801, 11, 2020_1227), 14897079);
+        assert_eq!(mod_pow(3, 0, 1), 0);
+        assert_eq!(mod_pow(0, 0, 5), 1);
+        assert_eq!(mod_pow(0, 3, 5), 0);
+    }
+
+    // Values the mod_exp crate, which this replaces, must also produce; some of them overflow
+    // u64 intermediates.
+    #[test]
+    fn mod_pow_reference_test() {
+        assert_eq!(mod_pow(2, 1_000_000_000_000_000_000, 1_000_000_007), 719476260);
+        assert_eq!(mod_pow(123456789, 987654321, (1 << 61) - 1), 50357601586279104);
+        assert_eq!(mod_pow(u64::MAX, u64::MAX, u64::MAX - 58), 4959809447704153900);
+    }
+
+    #[test]
+    fn mod_pow_agrees_with_naive() {
+        let mut rng = Lcg::new(2020);
+        for _ in 0..500 {
+            let base = rng.next().unwrap();
+            let (exp, modulus) = (rng.below(200), rng.below(100_000) + 1);
+            assert_eq!(mod_pow(base, exp, modulus), mod_pow_naive(base, exp, modulus), "{}^{} mod {}", base, exp, modulus);
+        }
+    }
+
+    #[test]
+    fn mod_inverse_test() {
+        assert_eq!(mod_inverse(3, 7), Some(5));
+        assert_eq!(mod_inverse(10, 7), Some(5));
+        assert_eq!(mod_inverse(6, 9), None);
+        assert_eq!(mod_inverse(0, 1), Some(0));
+        assert_eq!(mod_inverse(u64::MAX - 1, u64::MAX), Some(u64::MAX - 1));
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn chinese_remainder_big_test() {
+        use num_bigint::BigUint;
+
+        assert_eq!(chinese_remainder_big(&[(7, 0), (13, 12), (59, 55), (31, 25), (19, 12)]), Some(BigUint::from(1068781u32)));
+        assert_eq!(chinese_remainder_big(&[]), Some(BigUint::from(0u32)));
+        assert_eq!(chinese_remainder_big(&[(6, 1), (4, 1)]), None);
+        assert_eq!(chinese_remainder_big(&[(0, 1)]), None);
+
+        // the product of these primes overflows u128
+        let primes = [4294967291u64, 4294967279, 4294967231, 4294967197, 4294967189];
+        let constraints: Vec<(u64, u64)> = primes.iter().enumerate().map(|(idx, p)| (*p, idx as u64)).collect();
+        let x = chinese_remainder_big(&constraints).unwrap();
+        assert!(x.bits() > 128);
+        for (n, r) in constraints {
+            assert_eq!(&x % n, BigUint::from(r));
+        }
+    }
+}
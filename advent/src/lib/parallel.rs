@@ -0,0 +1,49 @@
+// Data-parallel helpers. Built with the `parallel` feature they run on rayon's thread pool;
+// otherwise they are plain iterator chains. Either way the results are the same, in the same
+// order, so callers need no cfg of their own.
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// Whether the helpers run in parallel in this build.
+pub const ENABLED: bool = cfg!(feature = "parallel");
+
+/// Applies `f` to every item, keeping the order of `items`.
+pub fn map<T, U, F>(items: &[T], f: F) -> Vec<U>
+where T: Sync, U: Send, F: Fn(&T) -> U + Sync + Send {
+    #[cfg(feature = "parallel")]
+    let mapped = items.par_iter().map(f).collect();
+    #[cfg(not(feature = "parallel"))]
+    let mapped = items.iter().map(f).collect();
+    mapped
+}
+
+/// The items satisfying `predicate`, in the order of `items`.
+pub fn filter<T, F>(items: &[T], predicate: F) -> Vec<T>
+where T: Copy + Send + Sync, F: Fn(&T) -> bool + Sync + Send {
+    #[cfg(feature = "parallel")]
+    let kept = items.par_iter().filter(|item| predicate(item)).copied().collect();
+    #[cfg(not(feature = "parallel"))]
+    let kept = items.iter().filter(|item| predicate(item)).copied().collect();
+    kept
+}
+
+#[cfg(test)]
+mod parallel_spec {
+    use super::*;
+
+    #[test]
+    fn map_test() {
+        let items: Vec<u64> = (0..10_000).collect();
+        let squares = map(&items, |x| x * x);
+        assert_eq!(squares.len(), items.len());
+        assert!(squares.iter().enumerate().all(|(idx, square)| *square == (idx * idx) as u64));
+        assert!(map(&[] as &[u64], |x| x + 1).is_empty());
+    }
+
+    #[test]
+    fn filter_test() {
+        let items: Vec<u32> = (0..10_000).collect();
+        assert_eq!(filter(&items, |x| x % 1000 == 7), vec!(7, 1007, 2007, 3007, 4007, 5007, 6007, 7007, 8007, 9007));
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::{BTreeMap, VecDeque};
+
+/// A route through a state space: `states` runs from the start to the goal, inclusive, and
+/// `moves[i]` is the move leading from `states[i]` to `states[i + 1]`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Path<S, M> {
+    pub states: Vec<S>,
+    pub moves: Vec<M>
+}
+
+impl<S: Ord, M> Path<S, M> {
+    fn trace(mut predecessors: BTreeMap<S, Option<(S, M)>>, end: S) -> Path<S, M> {
+        let mut states = vec!(end);
+        let mut moves = vec!();
+        while let Some(Some((prev, m))) = states.last().and_then(|s| predecessors.remove(s)) {
+            moves.push(m);
+            states.push(prev);
+        }
+        states.reverse();
+        moves.reverse();
+        Path { states, moves }
+    }
+}
+
+/// Breadth-first search from `start` to the nearest state satisfying `is_goal`.
+///
+/// `successors` lists the states reachable in one move, together with a label for that move.
+/// Returns a path with the fewest possible moves, or None if no goal state is reachable.
+pub fn bfs<S, M, F, G>(start: S, mut successors: F, mut is_goal: G) -> Option<Path<S, M>>
+where S: Ord + Clone, F: FnMut(&S) -> Vec<(S, M)>, G: FnMut(&S) -> bool {
+    let mut predecessors: BTreeMap<S, Option<(S, M)>> = BTreeMap::new();
+    let mut to_visit: VecDeque<S> = VecDeque::new();
+
+    predecessors.insert(start.clone(), None);
+    to_visit.push_back(start);
+
+    while let Some(state) = to_visit.pop_front() {
+        if is_goal(&state) {
+            return Some(Path::trace(predecessors, state))
+        }
+        for (next, m) in successors(&state) {
+            if !predecessors.contains_key(&next) {
+                predecessors.insert(next.clone(), Some((state.clone(), m)));
+                to_visit.push_back(next);
+            }
+        }
+    }
+
+    None
+}
+
+/// The fewest moves from the nearest of `starts` to every reachable state, by breadth-first search.
+///
+/// `successors` is as for `bfs`. The state space reachable from `starts` must be finite.
+pub fn distances<S, M, J, F>(starts: J, mut successors: F) -> BTreeMap<S, usize>
+where S: Ord + Clone, J: IntoIterator<Item=S>, F: FnMut(&S) -> Vec<(S, M)> {
+    let mut distances: BTreeMap<S, usize> = BTreeMap::new();
+    let mut to_visit: VecDeque<S> = VecDeque::new();
+
+    for start in starts {
+        if !distances.contains_key(&start) {
+            distances.insert(start.clone(), 0);
+            to_visit.push_back(start);
+        }
+    }
+
+    while let Some(state) = to_visit.pop_front() {
+        let d = distances[&state];
+        for (next, _) in successors(&state) {
+            if !distances.contains_key(&next) {
+                distances.insert(next.clone(), d + 1);
+                to_visit.push_back(next);
+            }
+        }
+    }
+
+    distances
+}
+
+#[cfg(test)]
+mod search_spec {
+    use super::*;
+
+    // Reach `target` from 1 by doubling or incrementing.
+    fn double_or_increment(n: &u32) -> Vec<(u32, char)> {
+        vec!((n * 2, 'D'), (n + 1, 'I'))
+    }
+
+    #[test]
+    fn bfs_should_find_shortest_path() {
+        let path = bfs(1, double_or_increment, |n| *n == 10).unwrap();
+        assert_eq!(path.states, vec!(1, 2, 4, 5, 10));
+        assert_eq!(path.moves, vec!('D', 'D', 'I', 'D'));
+    }
+
+    #[test]
+    fn bfs_should_accept_start_as_goal() {
+        let path = bfs(7, double_or_increment, |n| *n == 7).unwrap();
+        assert_eq!(path, Path { states: vec!(7), moves: vec!() });
+    }
+
+    #[test]
+    fn distances_should_agree_with_bfs() {
+        let bounded = |n: &u32| if *n < 40 { double_or_increment(n) } else { vec!() };
+        let ds = distances(vec!(1), bounded);
+        // 1..=40, and the doubles of 21..40
+        assert_eq!(ds.len(), 40 + 19);
+        for (&n, &d) in &ds {
+            assert_eq!(bfs(1, bounded, |m| *m == n).unwrap().moves.len(), d, "{}", n);
+        }
+
+        // the nearest start counts
+        let ds = distances(vec!(1, 9, 9), bounded);
+        assert_eq!((ds[&1], ds[&9], ds[&10], ds[&18]), (0, 0, 1, 1));
+        assert!(distances(Vec::<u32>::new(), bounded).is_empty());
+    }
+
+    #[test]
+    fn bfs_should_fail_when_goal_unreachable() {
+        let path = bfs(1, |n: &u32| if *n < 20 { double_or_increment(n) } else { vec!() }, |n| *n == 0);
+        assert_eq!(path, None);
+    }
+}
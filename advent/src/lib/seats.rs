@@ -0,0 +1,71 @@
+// Day 5: finding my seat among the boarding passes' seat ids.
+
+use std::ops::RangeInclusive;
+
+/// The range of seat ids taken, and the gaps within it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeatSummary {
+    pub min: usize,
+    pub max: usize,
+    /// The ids strictly between min and max which nobody has, in increasing order
+    pub missing: Vec<usize>
+}
+
+impl SeatSummary {
+    /// The maximal runs of missing ids, in increasing order.
+    pub fn gaps(&self) -> Vec<RangeInclusive<usize>> {
+        let mut gaps: Vec<RangeInclusive<usize>> = vec!();
+        for &id in &self.missing {
+            match gaps.last_mut() {
+                Some(gap) if *gap.end() + 1 == id => *gap = *gap.start()..=id,
+                _ => gaps.push(id..=id)
+            }
+        }
+        gaps
+    }
+
+    /// The first missing id whose neighbors are both taken, as mine is.
+    pub fn open_seat(&self) -> Option<usize> {
+        self.gaps().into_iter().find(|gap| gap.start() == gap.end()).map(|gap| *gap.start())
+    }
+}
+
+/// Summarizes the ids in one pass, or None if there are none. Repeated ids are allowed.
+pub fn seat_summary<J>(ids: J) -> Option<SeatSummary> where J: IntoIterator<Item=usize> {
+    let mut taken: Vec<bool> = vec!();
+    let mut bounds: Option<(usize, usize)> = None;
+    for id in ids {
+        if id >= taken.len() {
+            taken.resize(id + 1, false);
+        }
+        taken[id] = true;
+        bounds = Some(match bounds {
+            None => (id, id),
+            Some((min, max)) => (min.min(id), max.max(id))
+        });
+    }
+    let (min, max) = bounds?;
+    let missing = (min..=max).filter(|&id| !taken[id]).collect();
+    Some(SeatSummary { min, max, missing })
+}
+
+#[cfg(test)]
+mod seats_spec {
+    use super::*;
+
+    #[test]
+    fn seat_summary_test() {
+        assert_eq!(seat_summary(vec!()), None);
+        assert_eq!(seat_summary(vec!(7)), Some(SeatSummary { min: 7, max: 7, missing: vec!() }));
+
+        let summary = seat_summary(vec!(12, 5, 9, 6, 12, 10)).unwrap();
+        assert_eq!(summary, SeatSummary { min: 5, max: 12, missing: vec!(7, 8, 11) });
+        assert_eq!(summary.gaps(), vec!(7..=8, 11..=11));
+        assert_eq!(summary.open_seat(), Some(11));
+
+        let summary = seat_summary(vec!(3, 5, 8, 4)).unwrap();
+        assert_eq!(summary.missing, vec!(6, 7));
+        assert_eq!(summary.gaps(), vec!(6..=7));
+        assert_eq!(summary.open_seat(), None);
+    }
+}
@@ -0,0 +1,101 @@
+use std::iter::FromIterator;
+
+/// The count, mean and (population) variance of a stream of observations, accumulated in one
+/// pass by Welford's method.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Summary {
+    count: usize,
+    mean: f64,
+    m2: f64 // sum of squared deviations from the current mean
+}
+
+impl Summary {
+    pub fn new() -> Summary {
+        Summary::default()
+    }
+
+    pub fn add(&mut self, x: f64) {
+        self.count += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (x - self.mean);
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// None until something has been observed.
+    pub fn mean(&self) -> Option<f64> {
+        if self.count > 0 { Some(self.mean) } else { None }
+    }
+
+    pub fn variance(&self) -> Option<f64> {
+        if self.count > 0 { Some(self.m2 / self.count as f64) } else { None }
+    }
+
+    pub fn std_dev(&self) -> Option<f64> {
+        self.variance().map(f64::sqrt)
+    }
+}
+
+impl Extend<f64> for Summary {
+    fn extend<J: IntoIterator<Item=f64>>(&mut self, xs: J) {
+        for x in xs {
+            self.add(x);
+        }
+    }
+}
+
+impl FromIterator<f64> for Summary {
+    fn from_iter<J: IntoIterator<Item=f64>>(xs: J) -> Summary {
+        let mut summary = Summary::new();
+        summary.extend(xs);
+        summary
+    }
+}
+
+/// The mean and variance of the number of successes among independent trials, each of which
+/// succeeds with the given probability (a Poisson binomial distribution).
+pub fn successes<J: IntoIterator<Item=f64>>(probabilities: J) -> (f64, f64) {
+    probabilities.into_iter().fold((0.0, 0.0), |(mean, variance), p| (mean + p, variance + p * (1.0 - p)))
+}
+
+#[cfg(test)]
+mod stats_spec {
+    use super::*;
+
+    fn assert_close(x: f64, y: f64) {
+        assert!((x - y).abs() < 1e-9, "{} != {}", x, y);
+    }
+
+    #[test]
+    fn summary_test() {
+        let empty = Summary::new();
+        assert_eq!(empty.count(), 0);
+        assert_eq!(empty.mean(), None);
+        assert_eq!(empty.variance(), None);
+
+        let summary: Summary = vec!(2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0).into_iter().collect();
+        assert_eq!(summary.count(), 8);
+        assert_close(summary.mean().unwrap(), 5.0);
+        assert_close(summary.variance().unwrap(), 4.0);
+        assert_close(summary.std_dev().unwrap(), 2.0);
+
+        let constant: Summary = std::iter::repeat(0.25).take(1000).collect();
+        assert_close(constant.mean().unwrap(), 0.25);
+        assert_close(constant.variance().unwrap(), 0.0);
+    }
+
+    #[test]
+    fn successes_test() {
+        assert_eq!(successes(vec!()), (0.0, 0.0));
+        let (mean, variance) = successes(vec!(0.5; 10));
+        assert_close(mean, 5.0);
+        assert_close(variance, 2.5);
+        // certain outcomes add nothing to the variance
+        let (mean, variance) = successes(vec!(1.0, 0.0, 1.0, 0.5));
+        assert_close(mean, 2.5);
+        assert_close(variance, 0.25);
+    }
+}
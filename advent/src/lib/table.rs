@@ -0,0 +1,79 @@
+// A minimal left-aligned text table, for binaries that want to print structured results.
+
+pub struct Table {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>
+}
+
+impl Table {
+    pub fn new(headers: &[&str]) -> Table {
+        let headers = headers.iter().map(|h| h.to_string()).collect();
+        Table { headers, rows: vec!() }
+    }
+
+    /// Rows shorter than the header are padded with empty cells; extra cells are dropped.
+    pub fn add_row(&mut self, mut row: Vec<String>) {
+        row.resize(self.headers.len(), String::new());
+        self.rows.push(row);
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    fn widths(&self) -> Vec<usize> {
+        let mut widths: Vec<usize> = self.headers.iter().map(|h| h.chars().count()).collect();
+        for row in &self.rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        widths
+    }
+}
+
+impl std::fmt::Display for Table {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let widths = self.widths();
+
+        let write_row = |f: &mut std::fmt::Formatter<'_>, row: &Vec<String>| -> std::fmt::Result {
+            let cells: Vec<String> = row.iter().zip(&widths).map(|(cell, width)| {
+                format!("{:<width$}", cell, width = width)
+            }).collect();
+            writeln!(f, "{}", cells.join(" | ").trim_end())
+        };
+
+        write_row(f, &self.headers)?;
+        let rule: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+        writeln!(f, "{}", rule.join("-+-"))?;
+        for row in &self.rows {
+            write_row(f, row)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod table_spec {
+    use super::*;
+
+    #[test]
+    fn display_test() {
+        let mut table = Table::new(&["idx", "name", "value"]);
+        table.add_row(vec!("0".to_owned(), "row".to_owned(), "11".to_owned()));
+        table.add_row(vec!("10".to_owned(), "departure time".to_owned()));
+        assert_eq!(table.len(), 2);
+
+        let expected = concat!(
+            "idx | name           | value\n",
+            "----+----------------+------\n",
+            "0   | row            | 11\n",
+            "10  | departure time |\n"
+        );
+        assert_eq!(table.to_string(), expected);
+    }
+}
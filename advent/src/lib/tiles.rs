@@ -0,0 +1,143 @@
+// Day 20: camera tiles. Each tile is a header line `Tile <id>:` followed by rows of pixels, '.'
+// (off) or '#' (on); tiles are separated by blank lines.
+
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+/// A tile as it appears in the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tile {
+    pub id: u32,
+    pub rows: Vec<String>
+}
+
+impl Tile {
+    /// The pixels, row by row, as a single string.
+    pub fn pixels(&self) -> String {
+        self.rows.concat()
+    }
+}
+
+/// Each variant carries the 1-based line number and text of the header of the offending tile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TileParseError {
+    /// A tile begins with something other than `Tile <id>:`, or with an id too large for a u32.
+    Header { line_no: usize, header: String },
+    /// A second tile with the id of an earlier one, which began on line `first_line_no`.
+    DuplicateId { id: u32, line_no: usize, header: String, first_line_no: usize },
+    /// A tile with no rows, a row of a different width from the first, or a pixel other than '.' or '#'.
+    Pixels { line_no: usize, header: String }
+}
+
+impl Display for TileParseError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            TileParseError::Header { line_no, header } =>
+                write!(f, "bad tile header on line {}: {}", line_no, header),
+            TileParseError::DuplicateId { id, line_no, header, first_line_no } =>
+                write!(f, "tile id {} on line {} ({}) was already used on line {}", id, line_no, header, first_line_no),
+            TileParseError::Pixels { line_no, header } =>
+                write!(f, "malformed pixels in the tile beginning on line {}: {}", line_no, header)
+        }
+    }
+}
+
+/// Reads every tile, in input order, failing on the first malformed one.
+pub fn parse_tiles<J>(lines: J) -> Result<Vec<Tile>, TileParseError>
+where J: Iterator<Item=String> {
+    lazy_static! {
+        static ref TILE_HEADER: Regex = Regex::new(r"^Tile (\d+):$").unwrap();
+    }
+
+    let mut tiles = vec!();
+    // the line on which each id was first seen
+    let mut first_lines: BTreeMap<u32, usize> = BTreeMap::new();
+    // the header of the tile being read, with its line number and id, and its rows so far
+    let mut current: Option<(usize, String, u32, Vec<String>)> = None;
+
+    let mut close = |current: Option<(usize, String, u32, Vec<String>)>, tiles: &mut Vec<Tile>| {
+        if let Some((line_no, header, id, rows)) = current {
+            let width = rows.first().map_or(0, |row| row.len());
+            let well_formed = width > 0 && rows.iter().all(|row| {
+                row.len() == width && row.chars().all(|c| c == '.' || c == '#')
+            });
+            if !well_formed {
+                return Err(TileParseError::Pixels { line_no, header })
+            }
+            if let Some(first_line_no) = first_lines.insert(id, line_no) {
+                return Err(TileParseError::DuplicateId { id, line_no, header, first_line_no })
+            }
+            tiles.push(Tile { id, rows });
+        }
+        Ok(())
+    };
+
+    for (idx, line) in lines.enumerate() {
+        let line_no = idx + 1;
+        if line.trim().is_empty() {
+            close(current.take(), &mut tiles)?;
+        } else if let Some((_, _, _, rows)) = &mut current {
+            rows.push(line.trim().to_owned());
+        } else {
+            let id = TILE_HEADER.captures(line.trim())
+                .and_then(|caps| caps[1].parse::<u32>().ok())
+                .ok_or_else(|| TileParseError::Header { line_no, header: line.clone() })?;
+            current = Some((line_no, line, id, vec!()));
+        }
+    }
+    close(current, &mut tiles)?;
+
+    Ok(tiles)
+}
+
+#[cfg(test)]
+mod tiles_spec {
+    use super::*;
+
+    fn lines(text: &str) -> impl Iterator<Item=String> + '_ {
+        text.lines().map(|line| line.to_owned())
+    }
+
+    #[test]
+    fn parse_test() {
+        let text = "Tile 2311:\n..#\n#.#\n\nTile 4000000000:\n##.\n...\n";
+        assert_eq!(parse_tiles(lines(text)), Ok(vec!(
+            Tile { id: 2311, rows: vec!("..#".to_owned(), "#.#".to_owned()) },
+            Tile { id: 4000000000, rows: vec!("##.".to_owned(), "...".to_owned()) }
+        )));
+        // neither a trailing blank line nor extra blank lines between tiles are needed
+        let text = "Tile 1:\n#\n\n\n\nTile 2:\n.";
+        assert_eq!(parse_tiles(lines(text)).unwrap().iter().map(|tile| tile.pixels()).collect::<Vec<_>>(), vec!("#", "."));
+        assert_eq!(parse_tiles(lines("")), Ok(vec!()));
+    }
+
+    #[test]
+    fn duplicate_id_test() {
+        let text = "Tile 17:\n#.\n\nTile 18:\n..\n\nTile 17:\n.#\n";
+        assert_eq!(parse_tiles(lines(text)), Err(TileParseError::DuplicateId {
+            id: 17, line_no: 7, header: "Tile 17:".to_owned(), first_line_no: 1
+        }));
+        // tiles with the same pixels are fine
+        let text = "Tile 17:\n#.\n\nTile 18:\n#.\n";
+        assert_eq!(parse_tiles(lines(text)).map(|tiles| tiles.len()), Ok(2));
+    }
+
+    #[test]
+    fn error_test() {
+        assert_eq!(parse_tiles(lines("Tile 4294967296:\n#\n")),
+            Err(TileParseError::Header { line_no: 1, header: "Tile 4294967296:".to_owned() }));
+        assert_eq!(parse_tiles(lines("Tile 1:\n#\n\n#.#\n")),
+            Err(TileParseError::Header { line_no: 4, header: "#.#".to_owned() }));
+        assert_eq!(parse_tiles(lines("Tile 1:\n#\n\nTile 2:\n#.\n#\n")),
+            Err(TileParseError::Pixels { line_no: 4, header: "Tile 2:".to_owned() }));
+        assert_eq!(parse_tiles(lines("Tile 1:\n#o\n")),
+            Err(TileParseError::Pixels { line_no: 1, header: "Tile 1:".to_owned() }));
+        assert_eq!(parse_tiles(lines("Tile 1:\n\nTile 2:\n#\n")),
+            Err(TileParseError::Pixels { line_no: 1, header: "Tile 1:".to_owned() }));
+        assert_eq!(TileParseError::DuplicateId { id: 3, line_no: 9, header: "Tile 3:".to_owned(), first_line_no: 1 }.to_string(),
+            "tile id 3 on line 9 (Tile 3:) was already used on line 1");
+    }
+}
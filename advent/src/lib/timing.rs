@@ -0,0 +1,46 @@
+// Wall-clock timing for the binaries' part 1 / part 2 computations. Reports go to stderr so
+// that the answers on stdout are unaffected; set ADVENT_NO_TIMING to silence them entirely.
+
+use std::time::{Duration, Instant};
+
+/// Name of the environment variable which, when set (to anything), suppresses timing output.
+pub const SUPPRESS_VAR: &str = "ADVENT_NO_TIMING";
+
+/// Runs `f`, reports how long it took under `label`, and returns its result.
+pub fn time<T, F>(label: &str, f: F) -> T where F: FnOnce() -> T {
+    let start = Instant::now();
+    let result = f();
+    let elapsed = start.elapsed();
+    if std::env::var_os(SUPPRESS_VAR).is_none() {
+        eprintln!("[{}] {}", label, format_duration(elapsed));
+    }
+    result
+}
+
+fn format_duration(d: Duration) -> String {
+    let micros = d.as_micros();
+    if micros < 1_000 {
+        format!("{}µs", micros)
+    } else if micros < 1_000_000 {
+        format!("{:.3}ms", micros as f64 / 1_000.0)
+    } else {
+        format!("{:.3}s", d.as_secs_f64())
+    }
+}
+
+#[cfg(test)]
+mod timing_spec {
+    use super::*;
+
+    #[test]
+    fn time_test() {
+        assert_eq!(time("test", || 6 * 7), 42);
+    }
+
+    #[test]
+    fn format_duration_test() {
+        assert_eq!(format_duration(Duration::from_micros(17)), "17µs");
+        assert_eq!(format_duration(Duration::from_micros(2_500)), "2.500ms");
+        assert_eq!(format_duration(Duration::from_millis(3_250)), "3.250s");
+    }
+}
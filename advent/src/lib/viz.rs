@@ -0,0 +1,51 @@
+// PNG output for the puzzles whose answers are pictures. Only built with the `viz` feature.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Encodes a `width` × `height` grayscale picture as a PNG, in which the cell at (x, y),
+/// counted from the top left, has luminance `shade(x, y)` (0 for black, 255 for white) and is
+/// drawn as a `scale` × `scale` block of pixels.
+pub fn encode<W, F>(w: W, width: usize, height: usize, scale: usize, shade: F) -> std::io::Result<()>
+where W: Write, F: Fn(usize, usize) -> u8 {
+    let scale = scale.max(1);
+    let mut encoder = png::Encoder::new(w, (width * scale) as u32, (height * scale) as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut data = Vec::with_capacity(width * height * scale * scale);
+    for y in 0..height * scale {
+        data.extend((0..width * scale).map(|x| shade(x / scale, y / scale)));
+    }
+    encoder.write_header()
+        .and_then(|mut writer| writer.write_image_data(&data))
+        .map_err(std::io::Error::other)
+}
+
+/// As `encode`, to a file.
+pub fn write_png<P, F>(path: P, width: usize, height: usize, scale: usize, shade: F) -> std::io::Result<()>
+where P: AsRef<Path>, F: Fn(usize, usize) -> u8 {
+    encode(BufWriter::new(File::create(path)?), width, height, scale, shade)
+}
+
+#[cfg(test)]
+mod viz_spec {
+    use super::*;
+
+    #[test]
+    fn encode_test() {
+        let mut bytes = vec!();
+        encode(&mut bytes, 3, 2, 2, |x, y| if (x + y) % 2 == 0 { 255 } else { 0 }).unwrap();
+
+        let mut reader = png::Decoder::new(bytes.as_slice()).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!((info.width, info.height), (6, 4));
+        assert_eq!(&pixels[..info.buffer_size()], &[
+            255, 255, 0, 0, 255, 255,
+            255, 255, 0, 0, 255, 255,
+            0, 0, 255, 255, 0, 0,
+            0, 0, 255, 255, 0, 0
+        ]);
+    }
+}
@@ -0,0 +1,237 @@
+// Runs each day's binary on the example input from its puzzle statement (examples/dayNN.txt)
+// and checks the answers it prints against the published ones.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+struct Run {
+    success: bool,
+    stdout: String
+}
+
+impl Run {
+    // Asserts a successful run printing each of `lines` verbatim
+    fn expect(&self, lines: &[&str]) {
+        assert!(self.success, "unsuccessful run; stdout was\n{}", self.stdout);
+        self.expect_partial(lines);
+    }
+
+    // As expect, for binaries which give up on part 2 of an example
+    fn expect_partial(&self, lines: &[&str]) {
+        for line in lines {
+            assert!(self.stdout.lines().any(|l| l == *line), "missing line {:?}; stdout was\n{}", line, self.stdout);
+        }
+    }
+}
+
+fn run(exe: &str, day: &str, args: &[&str]) -> Run {
+    let path = format!("{}/examples/{}.txt", env!("CARGO_MANIFEST_DIR"), day);
+    let input = std::fs::read(&path).unwrap_or_else(|e| panic!("unable to read {}: {}", path, e));
+    let mut child = Command::new(exe)
+        .args(args)
+        .env("ADVENT_NO_TIMING", "1")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(&input).unwrap();
+    let output = child.wait_with_output().unwrap();
+    Run { success: output.status.success(), stdout: String::from_utf8_lossy(&output.stdout).into_owned() }
+}
+
+macro_rules! example {
+    ($day:ident $(, $arg:expr)*) => {
+        run(env!(concat!("CARGO_BIN_EXE_", stringify!($day))), stringify!($day), &[$($arg),*])
+    };
+}
+
+#[test]
+fn day01() {
+    example!(day01).expect(&[
+        "Found expenses 299, 1721. Product: 514579",
+        "Found expenses 366, 675, 979. Product: 241861950"
+    ]);
+}
+
+#[test]
+fn day02() {
+    example!(day02).expect(&[
+        "2 passwords are valid.",
+        "1 passwords are valid in the second sense."
+    ]);
+}
+
+#[test]
+fn day03() {
+    example!(day03).expect(&[
+        "With dy/dx = 1/3, I hit 7 trees.",
+        "Product: 336"
+    ]);
+}
+
+#[test]
+fn day04() {
+    example!(day04).expect(&["Total passports: 4. Correct keys: 2; valid values: 2"]);
+}
+
+#[test]
+fn day05() {
+    // the four example passes leave no gap for my seat
+    example!(day05).expect_partial(&["Max seat id: 820 (row 102, column 4)"]);
+}
+
+#[test]
+fn day06() {
+    example!(day06).expect(&[
+        "Total questions answered yes by ANY group member: 11",
+        "Total questions answered yes by ALL group members: 6"
+    ]);
+}
+
+#[test]
+fn day07() {
+    example!(day07).expect(&[
+        "4 bags can contain my shiny gold bag.",
+        "My bag must contain 32 other bags."
+    ]);
+}
+
+#[test]
+fn day08() {
+    example!(day08).expect(&[
+        "Entering infinite loop: accumulator = 5",
+        "Output of fixed program: 8"
+    ]);
+}
+
+#[test]
+fn day09() {
+    example!(day09, "--preamble", "5").expect(&[
+        "Indecomposable XMAS value: 127 at index 14.",
+        "Bounds of sum slice: 15, 47. Min/Max Sum: 62"
+    ]);
+}
+
+#[test]
+fn day10() {
+    example!(day10).expect(&[
+        "Challenge 1: 22 * 10 = 220",
+        "There are 19208 paths."
+    ]);
+}
+
+#[test]
+fn day11() {
+    example!(day11).expect(&[
+        "Part 1: 37 seats are occupied",
+        "Part 2: 26 seats are occupied"
+    ]);
+}
+
+#[test]
+fn day12() {
+    example!(day12).expect(&[
+        "Ship's position: x=17, y=-8. Manhattan displacement: 25",
+        "Waypoint method: x=214, y=-72. Manhattan displacement: 286"
+    ]);
+}
+
+#[test]
+fn day13() {
+    example!(day13).expect(&[
+        "The first bus to leave will be #59 in 5 minutes. Part 1: 295",
+        "Earliest timestamp: 1068781"
+    ]);
+}
+
+#[test]
+fn day14() {
+    // the part 1 example has too many floating bits for part 2, so this is the part 2 example
+    example!(day14).expect(&["Part 2: Sum of set values = 208"]);
+}
+
+#[test]
+fn day15() {
+    example!(day15, "--table", "dense").expect(&[
+        "2020th number in the game: 436",
+        "30000000th number in the game: 175594"
+    ]);
+}
+
+#[test]
+fn day16() {
+    example!(day16).expect(&["1 valid nearby tickets parsed; scanning error rate: 71"]);
+}
+
+#[test]
+fn day17() {
+    example!(day17).expect(&[
+        "3D active cells after 6 generations: 112",
+        "4D Active cells after 6 generations: 848"
+    ]);
+}
+
+#[test]
+fn day18() {
+    example!(day18).expect(&[
+        "Left-to-right sum of provided expressions: 26457",
+        "Add-before-multiply sum of expressions: 694173"
+    ]);
+}
+
+#[test]
+fn day19() {
+    example!(day19).expect(&[
+        "3 lines match rule 0",
+        "12 lines match the recursive version of rule 0"
+    ]);
+}
+
+#[test]
+fn day20() {
+    example!(day20).expect(&[
+        "Product of corner tile ids: 20899048083289",
+        "The image contains 30 pattern pixels and 273 rough-water pixels."
+    ]);
+}
+
+#[test]
+fn day21() {
+    example!(day21).expect(&[
+        "4 hypoallergenic ingredients identified, with 5 total usages.",
+        "mxmxvkd,sqjhc,fvjkl"
+    ]);
+}
+
+#[test]
+fn day22() {
+    example!(day22).expect(&[
+        "Player Player2 wins Combat with a score of 306",
+        "Margin of victory: 306 after 29 rounds",
+        "Player Player2 wins Recursive Combat with a score of 291",
+        "Margin of victory: 291 after 17 rounds of the first game"
+    ]);
+}
+
+#[test]
+#[ignore] // ten million moves on a million cups; run with --ignored, preferably in release mode
+fn day23() {
+    example!(day23).expect(&[
+        "Ring label after 100 steps: 67384529",
+        "Product of labels: 149245887792"
+    ]);
+}
+
+#[test]
+fn day24() {
+    example!(day24).expect(&[
+        "10 tiles are black on day 0",
+        "After 100 evolutions, 2208 tiles are black."
+    ]);
+}
+
+#[test]
+fn day25() {
+    example!(day25).expect(&["Encryption key: 14897079"]);
+}